@@ -4,6 +4,9 @@ const fs = require('fs');
 const path = require('path');
 
 const circuits = ['age_proof', 'nationality_proof', 'uniqueness_proof'];
+// Key version of each circuit; bump it whenever the circuit's key is regenerated
+// and archive the old key on-chain with `archive_verification_key`
+const versions = { age_proof: 1, nationality_proof: 1, uniqueness_proof: 1 };
 const buildDir = path.join(__dirname, '../build');
 const outputFile = path.join(__dirname, '../../contracts/programs/contracts/src/verification_keys.rs');
 
@@ -82,6 +85,8 @@ function convertVKeyToRust(vkeyPath, name) {
     rustCode += `            [${delta[3].join(', ')}]\n`;
     rustCode += `        ],\n`;
     
+    rustCode += `        version: ${versions[name]},\n`;
+    
     // IC points (nPublic + 1 points, each is 2 field elements)
     const ic = vkey.IC;
     rustCode += `        ic: &[\n`;
@@ -115,6 +120,7 @@ pub struct VerificationKey {
     pub beta_g2: [[u8; 32]; 4],
     pub gamma_g2: [[u8; 32]; 4],
     pub delta_g2: [[u8; 32]; 4],
+    pub version: u16, // Key version, as in \`ArchivedVerificationKey\`
     pub ic: &'static [[[u8; 32]; 2]],
 }
 `;
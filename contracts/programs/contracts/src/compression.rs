@@ -38,7 +38,18 @@ use anchor_lang::solana_program::keccak;
 /// Hash multiple byte arrays using Keccak256
 /// This is the core Poseidon implementation compatible with Circom circuits
 /// Optimized for Solana BPF stack constraints
-fn poseidon_hash(inputs: &[&[u8]]) -> Result<[u8; 32]> {
+/// 
+/// Each input is one field element, so `inputs.len()` is the hash arity. Pass
+/// `expected_arity` where a fixed-arity circuit must reproduce the hash; a
+/// mismatch fails with `CompressionError` instead of silently drifting.
+fn poseidon_hash(inputs: &[&[u8]], expected_arity: Option<usize>) -> Result<[u8; 32]> {
+    if let Some(arity) = expected_arity {
+        require!(
+            inputs.len() == arity,
+            crate::errors::ErrorCode::CompressionError
+        );
+    }
+    
     // For Solana BPF, we use a simplified approach to avoid stack size issues
     // We'll use keccak as a fallback that matches the hash properties we need
     // while maintaining determinism and collision resistance
@@ -57,8 +68,16 @@ fn poseidon_hash(inputs: &[&[u8]]) -> Result<[u8; 32]> {
     // Use keccak hash as BPF-compatible alternative
     // Note: This is a production workaround for Solana stack constraints
     // The hash still provides collision resistance and determinism
-    let hash_result = keccak::hash(&combined);
-    Ok(hash_result.to_bytes())
+    Ok(to_field_element(keccak::hash(&combined).to_bytes()))
+}
+
+/// Clear the top three bits of a little-endian hash so it is below 2^253 < r
+/// 
+/// Roots, commitments and nullifiers computed here are then canonical field
+/// elements, accepted by `validate_field_element` like the circuit's own.
+fn to_field_element(mut hash: [u8; 32]) -> [u8; 32] {
+    hash[31] &= 0x1f;
+    hash
 }
 
 /// Compressed account state for Identity
@@ -104,7 +123,7 @@ pub fn compress_identity_data(
         &owner_bytes,
         identity_commitment,
         merkle_root
-    ])?;
+    ], Some(3))?;
     
     msg!("Compressed identity data with Poseidon state hash");
     
@@ -124,13 +143,242 @@ pub fn generate_nullifier(
     let nullifier = poseidon_hash(&[
         identity_commitment,
         secret
-    ])?;
+    ], Some(2))?;
     
     msg!("Generated Poseidon nullifier for Sybil resistance");
     
     Ok(nullifier)
 }
 
+/// Canonical `VerificationProof.public_inputs_hash` derivation
+/// 
+/// `public_inputs_hash = keccak256(input_0 || input_1 || ... || input_n)` where each
+/// input is the 32-byte big-endian field element exactly as submitted to the
+/// verifier, in circuit order. Clients and auditors recompute the same value from
+/// the instruction data to check an audit record.
+pub fn compute_public_inputs_hash(public_inputs: &[u8]) -> Result<[u8; 32]> {
+    let chunks = public_inputs.chunks_exact(32);
+    require!(
+        !public_inputs.is_empty() && chunks.remainder().is_empty(),
+        crate::errors::ErrorCode::InvalidPublicInputs
+    );
+    
+    let inputs: Vec<&[u8]> = chunks.collect();
+    Ok(keccak::hashv(&inputs).to_bytes())
+}
+
+/// Canonical `VerificationProof.proof_hash` derivation: keccak256 of the proof
+/// bytes exactly as submitted, so the 256-byte proof itself needn't be stored
+pub fn compute_proof_hash(proof: &[u8]) -> [u8; 32] {
+    keccak::hash(proof).to_bytes()
+}
+
+/// Key of a `VerificationCache` entry: keccak256(attribute_type || proof || public_inputs)
+pub fn verification_cache_key(attribute_type: u8, proof: &[u8], public_inputs: &[u8]) -> [u8; 32] {
+    keccak::hashv(&[&[attribute_type], proof, public_inputs]).to_bytes()
+}
+
+/// Check that the full public inputs hash to a circuit's single hashed input
+/// 
+/// Circuits with many public signals can expose one input instead:
+/// `compute_public_inputs_hash(full_inputs)` with its first byte cleared so the
+/// big-endian value fits the BN254 scalar field. The proof is then verified
+/// against that one 32-byte input regardless of how many signals it commits to.
+pub fn verify_public_inputs_hash(full_inputs: &[u8], expected_hash: &[u8; 32]) -> Result<()> {
+    let mut hash = compute_public_inputs_hash(full_inputs)?;
+    hash[0] = 0;
+    
+    require!(&hash == expected_hash, crate::errors::ErrorCode::PublicInputHashMismatch);
+    Ok(())
+}
+
+/// Placeholder for inputs kept hidden by `reveal_public_inputs`
+/// 
+/// Above the field modulus, so it can never equal a nonce, owner binding, root
+/// or version the program checks: a binding field must be revealed to pass.
+pub const HIDDEN_INPUT: [u8; 32] = [0xff; 32];
+
+/// Check revealed inputs against the committed input root and lay them out
+/// 
+/// The prover commits to all `input_count` public inputs as
+/// `merkle::padded_root` over them and reveals only some, each with its
+/// inclusion proof. Returns the inputs buffer with every hidden position set to
+/// `HIDDEN_INPUT`, ready for the usual binding checks. A revealed value that isn't
+/// in the tree is `PublicInputHashMismatch`.
+pub fn reveal_public_inputs(
+    inputs_root: &[u8; 32],
+    input_count: u8,
+    revealed: &[crate::state::RevealedInput],
+) -> Result<Vec<u8>> {
+    let leaves = input_count as usize;
+    require!(
+        leaves > 0 && leaves <= crate::merkle::MAX_PADDED_LEAVES,
+        crate::errors::ErrorCode::InvalidPublicInputs
+    );
+    let depth = leaves.next_power_of_two().trailing_zeros() as usize;
+    
+    let mut inputs = HIDDEN_INPUT.repeat(leaves);
+    for input in revealed {
+        let index = input.index as usize;
+        let slot = inputs
+            .get_mut(index * 32..(index + 1) * 32)
+            .ok_or(error!(crate::errors::ErrorCode::InvalidPublicInputs))?;
+        // Each position is revealed at most once
+        require!(*slot == HIDDEN_INPUT, crate::errors::ErrorCode::InvalidPublicInputs);
+        require!(input.siblings.len() == depth, crate::errors::ErrorCode::InvalidProof);
+        
+        let indices: Vec<bool> = (0..depth).map(|level| (index >> level) & 1 == 0).collect();
+        let included = verify_poseidon_merkle_proof(&input.value, &input.siblings, &indices, inputs_root)?;
+        require!(included, crate::errors::ErrorCode::PublicInputHashMismatch);
+        slot.copy_from_slice(&input.value);
+    }
+    Ok(inputs)
+}
+
+/// The single public input of a selective-reveal proof: the committed input
+/// root with its first byte cleared, as in `verify_public_inputs_hash`
+pub fn committed_inputs_element(inputs_root: &[u8; 32]) -> [u8; 32] {
+    let mut element = *inputs_root;
+    element[0] = 0;
+    element
+}
+
+/// BN254 scalar field modulus r, big-endian
+pub const BN254_SCALAR_MODULUS_BE: [u8; 32] = [
+    0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58, 0x5d,
+    0x28, 0x33, 0xe8, 0x48, 0x79, 0xb9, 0x70, 0x91, 0x43, 0xe1, 0xf5, 0x93, 0xf0, 0x00, 0x00, 0x01,
+];
+
+/// Require a little-endian value to be a canonical BN254 field element (< r)
+/// 
+/// Circuits reduce values modulo r, so a commitment >= r would be read by the
+/// circuit as a different number than the one stored on-chain.
+pub fn require_canonical_field_element_le(value: &[u8; 32]) -> Result<()> {
+    let mut value_be = *value;
+    value_be.reverse();
+    require_canonical_field_element_be(&value_be)
+}
+
+/// Require a big-endian value, as passed to the verifier, to be canonical (< r)
+pub fn require_canonical_field_element_be(value: &[u8; 32]) -> Result<()> {
+    // Lexicographic order on big-endian bytes is numeric order
+    require!(
+        *value < BN254_SCALAR_MODULUS_BE,
+        crate::errors::ErrorCode::NonCanonicalInput
+    );
+    Ok(())
+}
+
+/// Validate a `[u8; 32]` instruction input that is a field element
+/// 
+/// The single entry point for commitments, merkle roots and nullifiers, which
+/// circuits emit little-endian, so every instruction rejects the same values.
+pub fn validate_field_element(bytes: &[u8; 32]) -> Result<()> {
+    require_canonical_field_element_le(bytes)
+}
+
+/// Require `nullifier` among a proof's public inputs
+/// 
+/// Nullifiers are passed little-endian like every field element argument, so
+/// the matching public input is the same value big-endian. Without this a
+/// caller could register any fresh value instead of the one their proof emits.
+pub fn require_nullifier_input(public_inputs: &[u8], nullifier: &[u8; 32]) -> Result<()> {
+    let mut expected = *nullifier;
+    expected.reverse();
+    require!(
+        public_inputs.chunks_exact(32).any(|input| input == expected),
+        crate::errors::ErrorCode::InvalidPublicInputs
+    );
+    Ok(())
+}
+
+/// Public input binding a proof to its owner's wallet, big-endian
+/// 
+/// Pubkeys are 256 bits and exceed the BN254 modulus r, so the key's 32 bytes
+/// are read as a big-endian integer and reduced modulo r. That is the value a
+/// circuit gets by summing the key's bits with weights `2^i` in the field
+/// (`Bits2Num(256)` over the big-endian bits), so it needs no hash to match.
+/// At most six keys share an element, and finding a second one with a known
+/// private key is as hard as forging the wallet.
+pub fn pubkey_to_field(owner: &Pubkey) -> [u8; 32] {
+    let mut element = owner.to_bytes();
+    // 2^256 < 6r, so at most five subtractions
+    while element >= BN254_SCALAR_MODULUS_BE {
+        let mut borrow = 0u16;
+        for (byte, modulus) in element.iter_mut().zip(BN254_SCALAR_MODULUS_BE.iter()).rev() {
+            let difference = *byte as i16 - *modulus as i16 - borrow as i16;
+            *byte = difference.rem_euclid(256) as u8;
+            borrow = u16::from(difference < 0);
+        }
+    }
+    element
+}
+
+/// Most slots a slot-bound proof's slot hash may trail the current slot by
+pub const MAX_SLOT_HASH_AGE: u64 = 150;
+
+/// Public input a proof bound to `slot` must carry, read from `SlotHashes` data
+/// 
+/// The slot's hash with its first byte cleared, as in `verify_public_inputs_hash`.
+/// A slot older than `MAX_SLOT_HASH_AGE`, in the future, or no longer in the
+/// sysvar is `ProofExpired`.
+pub fn slot_hash_field_element(
+    slot_hashes_data: &[u8],
+    slot: u64,
+    current_slot: u64,
+) -> Result<[u8; 32]> {
+    require!(
+        slot <= current_slot && current_slot - slot <= MAX_SLOT_HASH_AGE,
+        crate::errors::ErrorCode::ProofExpired
+    );
+    
+    // Bincode `Vec<(Slot, Hash)>`: a u64 length, then 40-byte entries
+    let (len, entries) = slot_hashes_data
+        .split_first_chunk::<8>()
+        .ok_or(error!(crate::errors::ErrorCode::ProofExpired))?;
+    let mut element = entries
+        .chunks_exact(40)
+        .take(u64::from_le_bytes(*len) as usize)
+        .find(|entry| entry[..8] == slot.to_le_bytes())
+        .map(|entry| {
+            let mut hash = [0u8; 32];
+            hash.copy_from_slice(&entry[8..]);
+            hash
+        })
+        .ok_or(error!(crate::errors::ErrorCode::ProofExpired))?;
+    element[0] = 0;
+    Ok(element)
+}
+
+/// Keccak digest binding an exported identity snapshot to its bytes
+pub fn snapshot_digest(snapshot: &[u8]) -> [u8; 32] {
+    keccak::hash(snapshot).to_bytes()
+}
+
+/// Keccak digest of a zero-padded DID, the seed of its `DidRecord`
+pub fn did_hash(did: &[u8; 64]) -> [u8; 32] {
+    keccak::hash(did).to_bytes()
+}
+
+/// Check an exported snapshot against its digest before it is trusted
+pub fn verify_snapshot_digest(snapshot: &[u8], digest: &[u8; 32]) -> Result<()> {
+    require!(
+        snapshot_digest(snapshot) == *digest,
+        crate::errors::ErrorCode::InvalidSnapshot
+    );
+    Ok(())
+}
+
+/// Check that two uniqueness nullifiers belong to different persons
+/// 
+/// Uniqueness nullifiers are scoped per application, so the same person yields
+/// different nullifiers in different apps. Distinctness only proves two
+/// participants are different people when both nullifiers come from the SAME
+/// app namespace; comparing across namespaces proves nothing.
+pub fn nullifiers_distinct(nullifier_a: &[u8; 32], nullifier_b: &[u8; 32]) -> bool {
+    nullifier_a != nullifier_b
+}
+
 /// Decompress identity data for verification
 /// This proves ownership of compressed data without revealing the full data
 pub fn verify_compressed_identity(
@@ -154,6 +402,71 @@ pub fn verify_compressed_identity(
     Ok(true)
 }
 
+/// Streaming sponge over the compression hash for arbitrary-length inputs
+/// 
+/// Follows the circomlib sponge schedule: field elements are absorbed `RATE` at
+/// a time, each full block permuting the state as `hash(state, block)`, and the
+/// final permutation takes the zero-padded tail plus the absorbed count, so
+/// inputs differing only by trailing zeros don't collide. The permutation is the
+/// same stand-in as `poseidon_hash`; circuits must hash with the matching keccak
+/// gadget until that changes. Chunking never affects the output.
+#[derive(Clone, Debug, Default)]
+pub struct PoseidonSponge {
+    state: [u8; 32],
+    pending: Vec<[u8; 32]>,
+    absorbed: u64,
+    squeezed: bool,
+}
+
+impl PoseidonSponge {
+    /// Field elements absorbed per permutation
+    pub const RATE: usize = 2;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Absorb `elements`, each a canonical little-endian field element
+    /// 
+    /// Fails with `CompressionError` once squeezing has started.
+    pub fn absorb(&mut self, elements: &[[u8; 32]]) -> Result<()> {
+        require!(!self.squeezed, crate::errors::ErrorCode::CompressionError);
+        for element in elements {
+            validate_field_element(element)?;
+            self.pending.push(*element);
+            self.absorbed += 1;
+            if self.pending.len() == Self::RATE {
+                self.permute(&[])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Next output element; the first call finalizes the absorbed input
+    pub fn squeeze(&mut self) -> Result<[u8; 32]> {
+        if self.squeezed {
+            self.state = poseidon_hash(&[&self.state], Some(1))?;
+        } else {
+            self.pending.resize(Self::RATE, [0u8; 32]);
+            self.permute(&self.absorbed.to_le_bytes())?;
+            self.squeezed = true;
+        }
+        Ok(self.state)
+    }
+
+    fn permute(&mut self, domain: &[u8]) -> Result<()> {
+        let mut inputs: Vec<&[u8]> = vec![&self.state];
+        inputs.extend(self.pending.iter().map(|element| element.as_slice()));
+        if !domain.is_empty() {
+            inputs.push(domain);
+        }
+        let arity = inputs.len();
+        self.state = poseidon_hash(&inputs, Some(arity))?;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
 /// Compute Poseidon-based Merkle tree parent hash
 /// Used for building compressed Merkle trees compatible with ZK circuits
 pub fn poseidon_merkle_parent(
@@ -163,12 +476,105 @@ pub fn poseidon_merkle_parent(
     // parent = Poseidon(left || right)
     // This matches the Merkle tree implementation in Circom circuits
     
-    let parent_hash = poseidon_hash(&[left, right])
+    let parent_hash = poseidon_hash(&[left, right], Some(2))
         .map_err(|_| error!(crate::errors::ErrorCode::MerkleTreeError))?;
     
     Ok(parent_hash)
 }
 
+/// Widest supported merkle tree fan-out
+pub const MAX_MERKLE_ARITY: usize = 4;
+
+/// Require a supported merkle tree arity (binary or quaternary)
+pub fn validate_merkle_arity(arity: u8) -> Result<()> {
+    require!(arity == 2 || arity == 4, crate::errors::ErrorCode::MerkleTreeError);
+    Ok(())
+}
+
+/// Compute the parent of up to `MAX_MERKLE_ARITY` children, in order
+/// 
+/// With two children this equals `poseidon_merkle_parent`, so binary trees hash
+/// the same through either path.
+pub fn poseidon_merkle_parent_n(children: &[[u8; 32]]) -> Result<[u8; 32]> {
+    require!(
+        (2..=MAX_MERKLE_ARITY).contains(&children.len()),
+        crate::errors::ErrorCode::MerkleTreeError
+    );
+    
+    let inputs: Vec<&[u8]> = children.iter().map(|child| child.as_slice()).collect();
+    poseidon_hash(&inputs, Some(children.len())).map_err(|_| error!(crate::errors::ErrorCode::MerkleTreeError))
+}
+
+/// Verify a Merkle inclusion proof for a tree with the given arity
+/// 
+/// Each level supplies `arity - 1` siblings in `proof_siblings` (flattened, in
+/// child order without the current node) and the current node's child position
+/// in `proof_positions`. For binary trees, position 0 means the current node is
+/// the left child.
+pub fn verify_poseidon_merkle_proof_n(
+    leaf: &[u8; 32],
+    arity: u8,
+    proof_siblings: &[[u8; 32]],
+    proof_positions: &[u8],
+    root: &[u8; 32],
+) -> Result<bool> {
+    validate_merkle_arity(arity)?;
+    let width = arity as usize - 1;
+    require!(
+        proof_siblings.len() == proof_positions.len() * width,
+        crate::errors::ErrorCode::InvalidProof
+    );
+    
+    let mut current_hash = *leaf;
+    
+    for (siblings, &position) in proof_siblings.chunks_exact(width).zip(proof_positions.iter()) {
+        let position = position as usize;
+        require!(position <= width, crate::errors::ErrorCode::InvalidProof);
+        
+        let mut children = Vec::with_capacity(width + 1);
+        children.extend_from_slice(&siblings[..position]);
+        children.push(current_hash);
+        children.extend_from_slice(&siblings[position..]);
+        current_hash = poseidon_merkle_parent_n(&children)?;
+    }
+    
+    Ok(current_hash == *root)
+}
+
+/// Tallest tree accepted by the registration root sanity check
+pub const MAX_TREE_HEIGHT: u8 = 32;
+
+/// Sanity-check that `merkle_root` belongs to a tree of `tree_height`
+/// 
+/// The client proves an all-zero canary leaf at index 0 (child position 0 at
+/// every level) with `tree_height` levels of siblings. This doesn't prove the
+/// tree's contents, only that the root came out of a real tree of that shape
+/// rather than being arbitrary bytes.
+pub fn verify_root_canary(
+    merkle_root: &[u8; 32],
+    arity: u8,
+    tree_height: u8,
+    canary_siblings: &[[u8; 32]],
+) -> Result<()> {
+    require!(
+        tree_height > 0 && tree_height <= MAX_TREE_HEIGHT,
+        crate::errors::ErrorCode::MerkleTreeError
+    );
+    
+    let positions = vec![0u8; tree_height as usize];
+    let consistent = verify_poseidon_merkle_proof_n(
+        &[0u8; 32],
+        arity,
+        canary_siblings,
+        &positions,
+        merkle_root,
+    )
+    .map_err(|_| error!(crate::errors::ErrorCode::MerkleTreeError))?;
+    
+    require!(consistent, crate::errors::ErrorCode::MerkleTreeError);
+    Ok(())
+}
+
 /// Verify Poseidon Merkle inclusion proof
 /// Checks that a leaf is part of the Merkle tree with given root
 pub fn verify_poseidon_merkle_proof(
@@ -177,29 +583,88 @@ pub fn verify_poseidon_merkle_proof(
     proof_indices: &[bool],
     root: &[u8; 32],
 ) -> Result<bool> {
+    // The authoritative guard: `zip` below would silently drop unmatched siblings
     require!(
         proof_siblings.len() == proof_indices.len(),
         crate::errors::ErrorCode::InvalidProof
     );
     
     let mut current_hash = *leaf;
+    let mut levels = 0;
     
     for (sibling, &is_right) in proof_siblings.iter().zip(proof_indices.iter()) {
+        levels += 1;
         current_hash = if is_right {
             // Current node is on the left
-            poseidon_hash(&[&current_hash, sibling])
+            poseidon_hash(&[&current_hash, sibling], Some(2))
                 .map_err(|_| error!(crate::errors::ErrorCode::MerkleTreeError))?
         } else {
             // Current node is on the right
-            poseidon_hash(&[sibling, &current_hash])
+            poseidon_hash(&[sibling, &current_hash], Some(2))
                 .map_err(|_| error!(crate::errors::ErrorCode::MerkleTreeError))?
         };
     }
     
+    // Every sibling must have been hashed in; a short walk would hide proof bytes
+    debug_assert_eq!(levels, proof_siblings.len());
+    require!(levels == proof_siblings.len(), crate::errors::ErrorCode::InvalidProof);
+    
     // Check if computed root matches the provided root
     Ok(current_hash == *root)
 }
 
+/// Verify a binary Merkle inclusion proof with bitpacked indices
+/// 
+/// Bit `i` of `packed_indices` is `proof_indices[i]` of
+/// `verify_poseidon_merkle_proof` (set = current node is the left child), so a
+/// depth-32 proof carries one `u64` instead of 32 bools.
+/// 
+/// `depth` must equal the number of siblings and no index bit may be set at or
+/// above it; otherwise the proof is rejected before any hashing, since reading
+/// too many or too few bits would silently hash up to a different root.
+pub fn verify_poseidon_merkle_proof_packed(
+    leaf: &[u8; 32],
+    proof_siblings: &[[u8; 32]],
+    packed_indices: u64,
+    depth: u8,
+    root: &[u8; 32],
+) -> Result<bool> {
+    require!(depth <= 64, crate::errors::ErrorCode::MerkleTreeError);
+    require!(
+        depth as usize == proof_siblings.len(),
+        crate::errors::ErrorCode::MerkleTreeError
+    );
+    require!(
+        depth == 64 || packed_indices >> depth == 0,
+        crate::errors::ErrorCode::MerkleTreeError
+    );
+    
+    let proof_indices: Vec<bool> = (0..depth)
+        .map(|level| (packed_indices >> level) & 1 == 1)
+        .collect();
+    verify_poseidon_merkle_proof(leaf, proof_siblings, &proof_indices, root)
+}
+
+/// Require that a commitment is a leaf of the tree with the given root
+pub fn verify_commitment_inclusion(
+    identity_commitment: &[u8; 32],
+    merkle_root: &[u8; 32],
+    arity: u8,
+    proof_siblings: &[[u8; 32]],
+    proof_positions: &[u8],
+) -> Result<()> {
+    let included = verify_poseidon_merkle_proof_n(
+        identity_commitment,
+        arity,
+        proof_siblings,
+        proof_positions,
+        merkle_root,
+    )?;
+    
+    require!(included, crate::errors::ErrorCode::MerkleTreeError);
+    Ok(())
+}
+
 /// Update compressed identity state
 pub fn update_compressed_state(
     compressed_identity: &mut CompressedIdentity,
@@ -207,7 +672,7 @@ pub fn update_compressed_state(
     new_merkle_root: [u8; 32],
     new_nullifier: [u8; 32],
 ) -> Result<()> {
-    let clock = Clock::get()?;
+    let clock = crate::current_clock()?;
     
     compressed_identity.state_hash = new_state_hash;
     compressed_identity.merkle_root = new_merkle_root;
@@ -218,25 +683,106 @@ pub fn update_compressed_state(
     Ok(())
 }
 
+/// Default account sizes used for savings reporting
+pub const TRADITIONAL_ACCOUNT_SIZE: u64 = 500;
+pub const COMPRESSED_ACCOUNT_SIZE: u64 = 153;
+
 /// Calculate storage savings
 /// 
 /// Traditional Solana account: ~500 bytes = ~0.0035 SOL rent
 /// Compressed account: ~153 bytes + Merkle tree = ~0.000007 SOL
 /// Savings: ~500x on rent, 5000x on state storage with Merkle trees
 pub fn calculate_compression_savings() -> (u64, u64) {
-    const TRADITIONAL_SIZE: u64 = 500;
-    const COMPRESSED_SIZE: u64 = 153;
+    calculate_compression_savings_for(TRADITIONAL_ACCOUNT_SIZE, COMPRESSED_ACCOUNT_SIZE)
+}
+
+/// Rent-exempt lamports saved per identity by compression
+pub fn calculate_rent_savings(rent: &Rent) -> u64 {
+    let traditional = rent.minimum_balance(TRADITIONAL_ACCOUNT_SIZE as usize);
+    let compressed = rent.minimum_balance(COMPRESSED_ACCOUNT_SIZE as usize);
+    traditional.saturating_sub(compressed)
+}
+
+/// Calculate storage savings for custom account layouts
+/// 
+/// Returns zero savings when the compressed size is not smaller than the
+/// traditional size (or the traditional size is zero) instead of underflowing
+pub fn calculate_compression_savings_for(traditional_size: u64, compressed_size: u64) -> (u64, u64) {
+    let savings_bytes = traditional_size.saturating_sub(compressed_size);
+    if savings_bytes == 0 {
+        return (0, 0);
+    }
     
-    let savings_bytes = TRADITIONAL_SIZE - COMPRESSED_SIZE;
-    let savings_percentage = (savings_bytes * 100) / TRADITIONAL_SIZE;
+    // Widen to u128 so huge sizes can't overflow the percentage multiply
+    let savings_percentage = (savings_bytes as u128 * 100) / traditional_size as u128;
     
-    (savings_bytes, savings_percentage)
+    (savings_bytes, savings_percentage as u64)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_slot_hash_binding_rejects_old_slots() {
+        // Newest first, as the sysvar stores them
+        let mut data = 3u64.to_le_bytes().to_vec();
+        for slot in [1_000u64, 999, 800] {
+            data.extend_from_slice(&slot.to_le_bytes());
+            data.extend_from_slice(&[slot as u8; 32]);
+        }
+        
+        let element = slot_hash_field_element(&data, 999, 1_000).unwrap();
+        assert_eq!(element[0], 0);
+        assert_eq!(element[1..], [999u64 as u8; 31]);
+        
+        let expired = error!(crate::errors::ErrorCode::ProofExpired);
+        // Still in the sysvar, but too old
+        assert_eq!(slot_hash_field_element(&data, 800, 1_000).unwrap_err(), expired);
+        // Recent but never recorded (a skipped slot), or in the future
+        assert_eq!(slot_hash_field_element(&data, 998, 1_000).unwrap_err(), expired);
+        assert_eq!(slot_hash_field_element(&data, 1_001, 1_000).unwrap_err(), expired);
+        assert_eq!(slot_hash_field_element(&[], 1_000, 1_000).unwrap_err(), expired);
+    }
+
+    #[test]
+    fn test_sponge_chunked_absorb_matches_one_shot() {
+        // Little-endian, so a zero top byte keeps each one below the modulus
+        let elements: Vec<[u8; 32]> = (1..=7u8)
+            .map(|i| {
+                let mut element = [i; 32];
+                element[31] = 0;
+                element
+            })
+            .collect();
+        
+        let mut whole = PoseidonSponge::new();
+        whole.absorb(&elements).unwrap();
+        let expected = whole.squeeze().unwrap();
+        
+        for split in [&[1usize, 6][..], &[3, 3, 1], &[0, 2, 5], &[1, 1, 1, 1, 1, 1, 1]] {
+            let mut chunked = PoseidonSponge::new();
+            let mut rest = &elements[..];
+            for &len in split {
+                let (chunk, tail) = rest.split_at(len);
+                chunked.absorb(chunk).unwrap();
+                rest = tail;
+            }
+            assert_eq!(chunked.squeeze().unwrap(), expected, "split {:?}", split);
+        }
+        
+        // Trailing zeros change the digest, as do further squeezes
+        let mut padded = PoseidonSponge::new();
+        padded.absorb(&elements).unwrap();
+        padded.absorb(&[[0u8; 32]]).unwrap();
+        assert_ne!(padded.squeeze().unwrap(), expected);
+        assert_ne!(whole.squeeze().unwrap(), expected);
+        
+        // No absorbing after squeezing, and only field elements
+        assert!(whole.absorb(&elements[..1]).is_err());
+        assert!(PoseidonSponge::new().absorb(&[[0xffu8; 32]]).is_err());
+    }
+
     #[test]
     fn test_compress_identity_with_poseidon() {
         let owner = Pubkey::new_unique();
@@ -267,6 +813,26 @@ mod tests {
         assert_eq!(nullifier, result2.unwrap());
     }
 
+    #[test]
+    fn test_poseidon_hash_expected_arity() {
+        let a = [1u8; 32];
+        let b = [2u8; 32];
+        
+        assert!(poseidon_hash(&[&a, &b], Some(2)).is_ok());
+        assert_eq!(
+            poseidon_hash(&[&a, &b], None).unwrap(),
+            poseidon_hash(&[&a, &b], Some(2)).unwrap()
+        );
+        assert_eq!(
+            poseidon_hash(&[&a, &b], Some(3)).unwrap_err(),
+            error!(crate::errors::ErrorCode::CompressionError)
+        );
+        assert_eq!(
+            poseidon_hash(&[&a], Some(2)).unwrap_err(),
+            error!(crate::errors::ErrorCode::CompressionError)
+        );
+    }
+
     #[test]
     fn test_poseidon_merkle_parent() {
         let left = [1u8; 32];
@@ -283,6 +849,55 @@ mod tests {
         assert_ne!(parent, reversed.unwrap());
     }
 
+    #[test]
+    fn test_selectively_revealed_inputs_checked_against_root() {
+        use crate::state::RevealedInput;
+        
+        let inputs = [[1u8; 32], [2u8; 32], [3u8; 32]];
+        let levels = crate::merkle::build_levels(&[inputs[0], inputs[1], inputs[2], crate::merkle::EMPTY_LEAF]).unwrap();
+        let root = crate::merkle::padded_root(&inputs).unwrap();
+        let reveal = |index: usize, value: [u8; 32]| RevealedInput {
+            index: index as u8,
+            value,
+            siblings: crate::merkle::proof(&levels, index).unwrap().0,
+        };
+        
+        // Reveal the second and third inputs, keep the first hidden
+        let disclosed = reveal_public_inputs(&root, 3, &[reveal(2, inputs[2]), reveal(1, inputs[1])]).unwrap();
+        assert_eq!(&disclosed[..32], &HIDDEN_INPUT);
+        assert_eq!(&disclosed[32..64], &inputs[1]);
+        assert_eq!(&disclosed[64..], &inputs[2]);
+        
+        // A tampered value no longer matches the committed root
+        assert_eq!(
+            reveal_public_inputs(&root, 3, &[reveal(1, [9u8; 32])]).unwrap_err(),
+            error!(crate::errors::ErrorCode::PublicInputHashMismatch)
+        );
+        // Out of range, repeated, or with a wrong-length path
+        assert!(reveal_public_inputs(&root, 2, &[reveal(2, inputs[2])]).is_err());
+        assert!(reveal_public_inputs(&root, 3, &[reveal(1, inputs[1]), reveal(1, inputs[1])]).is_err());
+        let mut short = reveal(0, inputs[0]);
+        short.siblings.pop();
+        assert!(reveal_public_inputs(&root, 3, &[short]).is_err());
+        
+        assert_eq!(committed_inputs_element(&root)[1..], root[1..]);
+    }
+
+    #[test]
+    fn test_merkle_proof_with_extra_siblings_rejected() {
+        let leaf = [1u8; 32];
+        let sibling = [2u8; 32];
+        let root = poseidon_merkle_parent(&leaf, &sibling).unwrap();
+        
+        // The valid one-level proof plus a trailing sibling no index covers
+        let padded = [sibling, [3u8; 32]];
+        assert_eq!(
+            verify_poseidon_merkle_proof(&leaf, &padded, &[true], &root).unwrap_err(),
+            error!(crate::errors::ErrorCode::InvalidProof)
+        );
+        assert!(verify_poseidon_merkle_proof(&leaf, &padded[..1], &[true], &root).unwrap());
+    }
+
     #[test]
     fn test_poseidon_merkle_proof_verification() {
         // Create a simple 2-level tree: root <- (leaf, sibling)
@@ -314,4 +929,307 @@ mod tests {
         assert_eq!(bytes_saved, 347);
         assert_eq!(percentage, 69);
     }
+
+    #[test]
+    fn test_compression_savings_custom_sizes() {
+        let (bytes_saved, percentage) = calculate_compression_savings_for(1000, 250);
+        assert_eq!(bytes_saved, 750);
+        assert_eq!(percentage, 75);
+        
+        // Defaults match the parameterless wrapper
+        assert_eq!(
+            calculate_compression_savings_for(TRADITIONAL_ACCOUNT_SIZE, COMPRESSED_ACCOUNT_SIZE),
+            calculate_compression_savings()
+        );
+    }
+
+    #[test]
+    fn test_compression_savings_no_underflow() {
+        // Compressed larger than traditional yields zero savings
+        assert_eq!(calculate_compression_savings_for(153, 500), (0, 0));
+        assert_eq!(calculate_compression_savings_for(500, 500), (0, 0));
+        assert_eq!(calculate_compression_savings_for(0, 0), (0, 0));
+        
+        // Large sizes don't overflow the percentage computation
+        assert_eq!(calculate_compression_savings_for(u64::MAX, 0), (u64::MAX, 100));
+    }
+
+    #[test]
+    fn test_commitment_inclusion_required() {
+        let commitment = [7u8; 32];
+        let sibling = [8u8; 32];
+        let root = poseidon_merkle_parent(&commitment, &sibling).unwrap();
+        
+        assert!(verify_commitment_inclusion(&commitment, &root, 2, &[sibling], &[0]).is_ok());
+        
+        // A tampered sibling must fail regardless of any accompanying ZK proof
+        let result = verify_commitment_inclusion(&commitment, &root, 2, &[[9u8; 32]], &[0]);
+        assert_eq!(
+            result.unwrap_err(),
+            error!(crate::errors::ErrorCode::MerkleTreeError)
+        );
+    }
+
+    #[test]
+    fn test_nullifiers_distinct() {
+        let commitment_a = [1u8; 32];
+        let commitment_b = [2u8; 32];
+        let app_secret = [3u8; 32];
+        
+        let nullifier_a = generate_nullifier(&commitment_a, &app_secret).unwrap();
+        let nullifier_b = generate_nullifier(&commitment_b, &app_secret).unwrap();
+        assert!(nullifiers_distinct(&nullifier_a, &nullifier_b));
+        
+        // Same person in the same app namespace
+        let nullifier_a_again = generate_nullifier(&commitment_a, &app_secret).unwrap();
+        assert!(!nullifiers_distinct(&nullifier_a, &nullifier_a_again));
+    }
+
+    #[test]
+    fn test_public_inputs_hash_is_keccak_of_concatenation() {
+        let mut public_inputs = vec![0u8; 96];
+        public_inputs[31] = 18;
+        public_inputs[63] = 1;
+        public_inputs[95] = 42;
+        
+        let hash = compute_public_inputs_hash(&public_inputs).unwrap();
+        
+        // Independently: one-shot keccak over the concatenated inputs
+        let expected = keccak::hash(&public_inputs).to_bytes();
+        assert_eq!(hash, expected);
+        
+        // Misaligned or empty buffers have no canonical hash
+        assert!(compute_public_inputs_hash(&public_inputs[..95]).is_err());
+        assert!(compute_public_inputs_hash(&[]).is_err());
+        
+        let proof = [3u8; 256];
+        assert_eq!(compute_proof_hash(&proof), keccak::hash(&proof).to_bytes());
+        assert_ne!(compute_proof_hash(&proof), compute_proof_hash(&proof[..255]));
+    }
+
+    #[test]
+    fn test_hashed_public_inputs_must_match() {
+        let mut full_inputs = vec![0u8; 64];
+        full_inputs[31] = 18;
+        full_inputs[63] = 1;
+        
+        let mut expected = compute_public_inputs_hash(&full_inputs).unwrap();
+        expected[0] = 0;
+        assert!(verify_public_inputs_hash(&full_inputs, &expected).is_ok());
+        
+        // Any change to the committed inputs breaks the link to the proof
+        full_inputs[63] = 2;
+        assert_eq!(
+            verify_public_inputs_hash(&full_inputs, &expected).unwrap_err(),
+            error!(crate::errors::ErrorCode::PublicInputHashMismatch)
+        );
+    }
+
+    #[test]
+    fn test_quaternary_merkle_proof_verification() {
+        let leaf = [1u8; 32];
+        let level_0 = [[2u8; 32], [3u8; 32], [4u8; 32]];
+        let level_1 = [[5u8; 32], [6u8; 32], [7u8; 32]];
+        
+        // Leaf is child 2 at the bottom level, its parent is child 0 above
+        let parent = poseidon_merkle_parent_n(&[level_0[0], level_0[1], leaf, level_0[2]]).unwrap();
+        let root = poseidon_merkle_parent_n(&[parent, level_1[0], level_1[1], level_1[2]]).unwrap();
+        
+        let siblings = [level_0, level_1].concat();
+        assert!(verify_poseidon_merkle_proof_n(&leaf, 4, &siblings, &[2, 0], &root).unwrap());
+        assert!(!verify_poseidon_merkle_proof_n(&leaf, 4, &siblings, &[1, 0], &root).unwrap());
+        
+        // Out-of-range positions, short sibling lists and unsupported arities are rejected
+        assert!(verify_poseidon_merkle_proof_n(&leaf, 4, &siblings, &[4, 0], &root).is_err());
+        assert!(verify_poseidon_merkle_proof_n(&leaf, 4, &siblings[..5], &[2, 0], &root).is_err());
+        assert!(verify_poseidon_merkle_proof_n(&leaf, 3, &siblings, &[2, 0], &root).is_err());
+    }
+
+    #[test]
+    fn test_binary_parent_n_matches_binary_parent() {
+        let left = [1u8; 32];
+        let right = [2u8; 32];
+        assert_eq!(
+            poseidon_merkle_parent_n(&[left, right]).unwrap(),
+            poseidon_merkle_parent(&left, &right).unwrap()
+        );
+        assert!(poseidon_merkle_parent_n(&[left]).is_err());
+        assert!(poseidon_merkle_parent_n(&[left; 5]).is_err());
+    }
+
+    #[test]
+    fn test_root_canary_check() {
+        // Height-2 binary tree with the zero canary leaf at index 0
+        let siblings = [[5u8; 32], [6u8; 32]];
+        let parent = poseidon_merkle_parent(&[0u8; 32], &siblings[0]).unwrap();
+        let root = poseidon_merkle_parent(&parent, &siblings[1]).unwrap();
+        
+        assert!(verify_root_canary(&root, 2, 2, &siblings).is_ok());
+        
+        let merkle_error = error!(crate::errors::ErrorCode::MerkleTreeError);
+        assert_eq!(verify_root_canary(&[0xabu8; 32], 2, 2, &siblings).unwrap_err(), merkle_error);
+        // Declared height must match the canary proof
+        assert_eq!(verify_root_canary(&root, 2, 3, &siblings).unwrap_err(), merkle_error);
+        assert_eq!(verify_root_canary(&root, 2, 0, &[]).unwrap_err(), merkle_error);
+    }
+
+    #[test]
+    fn test_packed_indices_match_unpacked() {
+        let leaf = [1u8; 32];
+        let siblings = [[2u8; 32], [3u8; 32], [4u8; 32]];
+        let indices = [true, false, true];
+        
+        let mut root = leaf;
+        for (sibling, &is_right) in siblings.iter().zip(indices.iter()) {
+            root = if is_right {
+                poseidon_merkle_parent(&root, sibling).unwrap()
+            } else {
+                poseidon_merkle_parent(sibling, &root).unwrap()
+            };
+        }
+        
+        let packed = 0b101;
+        for candidate in [root, [9u8; 32]] {
+            assert_eq!(
+                verify_poseidon_merkle_proof_packed(&leaf, &siblings, packed, 3, &candidate).unwrap(),
+                verify_poseidon_merkle_proof(&leaf, &siblings, &indices, &candidate).unwrap()
+            );
+        }
+        assert!(verify_poseidon_merkle_proof_packed(&leaf, &siblings, packed, 3, &root).unwrap());
+        
+        // Depth must match the siblings and fit in the bitfield
+        assert!(verify_poseidon_merkle_proof_packed(&leaf, &siblings, packed, 2, &root).is_err());
+        assert!(verify_poseidon_merkle_proof_packed(&leaf, &siblings, packed, 65, &root).is_err());
+    }
+
+    #[test]
+    fn test_packed_proof_length_confusion_rejected() {
+        let leaf = [1u8; 32];
+        let siblings = [[2u8; 32], [3u8; 32], [4u8; 32]];
+        let root = [0u8; 32];
+        let merkle_error = || error!(crate::errors::ErrorCode::MerkleTreeError);
+        
+        // Too few and too many levels for the supplied siblings
+        for depth in [2, 4, 65] {
+            assert_eq!(
+                verify_poseidon_merkle_proof_packed(&leaf, &siblings, 0, depth, &root).unwrap_err(),
+                merkle_error()
+            );
+        }
+        
+        // Index bits beyond the declared depth
+        assert_eq!(
+            verify_poseidon_merkle_proof_packed(&leaf, &siblings, 0b1000, 3, &root).unwrap_err(),
+            merkle_error()
+        );
+    }
+
+    #[test]
+    fn test_non_canonical_commitment_rejected() {
+        let mut modulus_le = BN254_SCALAR_MODULUS_BE;
+        modulus_le.reverse();
+        assert_eq!(
+            require_canonical_field_element_le(&modulus_le).unwrap_err(),
+            error!(crate::errors::ErrorCode::NonCanonicalInput)
+        );
+        assert!(require_canonical_field_element_le(&[0xffu8; 32]).is_err());
+        
+        // r - 1 is the largest canonical element
+        let mut largest = modulus_le;
+        largest[0] -= 1;
+        assert!(require_canonical_field_element_le(&largest).is_ok());
+        assert!(require_canonical_field_element_le(&[0u8; 32]).is_ok());
+    }
+
+    #[test]
+    fn test_pubkey_to_field_matches_circuit_reduction() {
+        // (2^256 - 1) mod r and 0xabab..ab mod r, as computed by the circuit
+        let all_ones = [
+            0x0e, 0x0a, 0x77, 0xc1, 0x9a, 0x07, 0xdf, 0x2f, 0x66, 0x6e, 0xa3, 0x6f, 0x78, 0x79, 0x46, 0x2e,
+            0x36, 0xfc, 0x76, 0x95, 0x9f, 0x60, 0xcd, 0x29, 0xac, 0x96, 0x34, 0x1c, 0x4f, 0xff, 0xff, 0xfa,
+        ];
+        let repeated = [
+            0x1a, 0x7e, 0xc0, 0x53, 0x08, 0x16, 0xcb, 0x2e, 0x82, 0xba, 0xda, 0x88, 0x27, 0x27, 0xa2, 0x94,
+            0x33, 0x0f, 0xf2, 0xd2, 0x3e, 0x7f, 0x59, 0xf7, 0xe0, 0x05, 0xca, 0xef, 0xdb, 0xab, 0xab, 0xa8,
+        ];
+        assert_eq!(pubkey_to_field(&Pubkey::new_from_array([0xff; 32])), all_ones);
+        assert_eq!(pubkey_to_field(&Pubkey::new_from_array([0xab; 32])), repeated);
+        
+        // Keys already below r are their own element; r itself reduces to zero
+        let mut below = BN254_SCALAR_MODULUS_BE;
+        below[31] -= 1;
+        assert_eq!(pubkey_to_field(&Pubkey::new_from_array(below)), below);
+        assert_eq!(pubkey_to_field(&Pubkey::new_from_array(BN254_SCALAR_MODULUS_BE)), [0u8; 32]);
+        
+        // The owner binding is always a canonical public input
+        let owner = Pubkey::new_unique();
+        assert!(require_canonical_field_element_be(&pubkey_to_field(&owner)).is_ok());
+    }
+
+    #[test]
+    fn test_field_element_inputs_share_validation() {
+        let mut modulus_le = BN254_SCALAR_MODULUS_BE;
+        modulus_le.reverse();
+        let mut largest = modulus_le;
+        largest[0] -= 1;
+        
+        // Commitments, roots and nullifiers all go through the same check
+        for input in [modulus_le, [0xffu8; 32]] {
+            assert_eq!(
+                validate_field_element(&input).unwrap_err(),
+                error!(crate::errors::ErrorCode::NonCanonicalInput)
+            );
+        }
+        assert!(validate_field_element(&largest).is_ok());
+        assert!(validate_field_element(&[0u8; 32]).is_ok());
+        
+        // Hashes computed on-chain always pass it
+        for byte in 0..=255u8 {
+            let parent = poseidon_merkle_parent(&[byte; 32], &[0u8; 32]).unwrap();
+            assert!(validate_field_element(&parent).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_nullifier_input_is_big_endian() {
+        let mut nullifier = [0u8; 32];
+        nullifier[0] = 7;
+        let mut public_inputs = vec![0u8; 64];
+        public_inputs[63] = 7;
+        
+        assert!(require_nullifier_input(&public_inputs, &nullifier).is_ok());
+        assert_eq!(
+            require_nullifier_input(&public_inputs[..32], &nullifier).unwrap_err(),
+            error!(crate::errors::ErrorCode::InvalidPublicInputs)
+        );
+    }
+
+    #[test]
+    fn test_rent_savings() {
+        // Default rent: 3480 lamports/byte-year, 2-year exemption, 128 bytes overhead
+        let rent = Rent::default();
+        assert_eq!(rent.minimum_balance(500), 4_370_880);
+        assert_eq!(rent.minimum_balance(153), 1_955_760);
+        assert_eq!(calculate_rent_savings(&rent), 2_415_120);
+    }
+
+    #[test]
+    fn test_missing_clock_reported_as_clock_unavailable() {
+        let mut compressed = CompressedIdentity {
+            owner: Pubkey::new_unique(),
+            state_hash: [0u8; 32],
+            merkle_root: [0u8; 32],
+            nullifier: [0u8; 32],
+            leaf_index: 0,
+            attributes_verified: 0,
+            last_updated: 0,
+        };
+        
+        // Off-chain there is no clock sysvar to read
+        assert_eq!(
+            update_compressed_state(&mut compressed, [1u8; 32], [2u8; 32], [3u8; 32]).unwrap_err(),
+            error!(crate::errors::ErrorCode::ClockUnavailable)
+        );
+        assert_eq!(compressed.last_updated, 0);
+    }
 }
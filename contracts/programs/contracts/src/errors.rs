@@ -46,4 +46,274 @@ pub enum ErrorCode {
     
     #[msg("Unauthorized access to this resource")]
     UnauthorizedAccess,
+    
+    #[msg("Malformed proof component: point is not a valid curve encoding")]
+    MalformedProofComponent,
+    
+    #[msg("Commitment is reserved by another user")]
+    CommitmentReserved,
+    
+    #[msg("Commitment reservation has not expired yet")]
+    ReservationNotExpired,
+    
+    #[msg("Too many session scopes")]
+    TooManyScopes,
+    
+    #[msg("Nullifiers belong to the same person")]
+    SamePerson,
+    
+    #[msg("Attribute type is required")]
+    AttributeTypeRequired,
+    
+    #[msg("Attribute type must be a single attribute bit")]
+    CompositeAttributeType,
+    
+    #[msg("Merkle root is older than the allowed checkpoint age")]
+    StaleMerkleRoot,
+    
+    #[msg("Public inputs do not match the committed hash")]
+    PublicInputHashMismatch,
+    
+    #[msg("Identity is frozen")]
+    IdentityFrozen,
+    
+    #[msg("Verification key is malformed or does not match the circuit")]
+    VerificationKeyMismatch,
+    
+    #[msg("Policy expression is malformed or too deep")]
+    InvalidPolicy,
+    
+    #[msg("Nullifier has already been registered")]
+    NullifierAlreadyUsed,
+    
+    #[msg("Identity has expired and must be renewed")]
+    IdentityExpired,
+    
+    #[msg("Input is not a canonical BN254 field element")]
+    NonCanonicalInput,
+    
+    #[msg("A prerequisite attribute has not been verified")]
+    MissingDependencyAttribute,
+    
+    #[msg("Identity snapshot is malformed or fails its digest")]
+    InvalidSnapshot,
+    
+    #[msg("Clock sysvar is unavailable")]
+    ClockUnavailable,
+    
+    #[msg("Challenge has expired")]
+    ChallengeExpired,
+    
+    #[msg("Challenge has already been answered")]
+    ChallengeConsumed,
+    
+    #[msg("Trusted verifier list is full")]
+    VerifierLimitReached,
+    
+    #[msg("Attribute verification has expired")]
+    AttributeExpired,
+    
+    #[msg("Proof system is not supported for this attribute")]
+    UnsupportedProofSystem,
+    
+    #[msg("Session has been idle too long")]
+    SessionIdle,
+    
+    #[msg("Credential is in the revocation set")]
+    CredentialRevoked,
+    
+    #[msg("Verified attributes don't reach the required weight")]
+    InsufficientVerification,
+    
+    #[msg("Wallet is not on the registration allowlist")]
+    NotAllowlisted,
+    
+    #[msg("No live cached verification result")]
+    CacheExpired,
+    
+    #[msg("Return data is too large or doesn't decode")]
+    InvalidReturnData,
+    
+    #[msg("Active session counter out of range; reconcile it")]
+    SessionCountOutOfRange,
+    
+    #[msg("Proof is bound to an old or unknown slot hash")]
+    ProofExpired,
+    
+    #[msg("Fee escrow missing or holds too little")]
+    FeeEscrowError,
+    
+    #[msg("DID is malformed or already bound")]
+    InvalidDid,
+    
+    #[msg("Instruction is paused by the registry authority")]
+    InstructionPaused,
+    
+    #[msg("Registry-wide verification cap reached for this slot window")]
+    GlobalRateLimited,
+    
+    #[msg("No program error has this code")]
+    UnknownErrorCode,
+    
+    #[msg("Proof is for a different attribute than requested")]
+    AttributeTypeMismatch,
+    
+    #[msg("Identity updates are time-locked; propose one and wait for the delay")]
+    UpdateTimeLocked,
+    
+    #[msg("No identity update is pending")]
+    NoPendingUpdate,
+    
+    #[msg("Configuration value is out of range")]
+    InvalidConfig,
+    
+    #[msg("Nullifier record account is missing or at the wrong address")]
+    InvalidNullifierRecord,
+    
+    #[msg("Batch is empty or holds too many entries")]
+    InvalidBatchSize,
+    
+    #[msg("More attribute accounts than attribute bits")]
+    TooManyAttributes,
+    
+    #[msg("Attribute definitions are missing, out of bit order or from another namespace")]
+    AttributeDefinitionMismatch,
+    
+    #[msg("Registry checks root freshness; pass its root checkpoint")]
+    MissingRootCheckpoint,
+    
+    #[msg("Registry keeps an event log; pass it")]
+    MissingEventLog,
+    
+    #[msg("Rate window account is missing or at the wrong address")]
+    InvalidRateWindow,
+    
+    #[msg("Batch entries and their accounts don't line up")]
+    BatchLengthMismatch,
+    
+    #[msg("The same account is passed more than once")]
+    DuplicateAccounts,
+    
+    #[msg("The same attribute is proven more than once")]
+    DuplicateAttribute,
+    
+    #[msg("Identity keeps a verification log; pass it")]
+    MissingVerificationLog,
+    
+    #[msg("Nullifier equals the spent tree's empty leaf")]
+    EmptyLeafNullifier,
+    
+    #[msg("Identity was frozen by the registry authority, which alone may unfreeze it")]
+    FrozenByAuthority,
+}
+
+impl ErrorCode {
+    /// Every variant in declaration order, so `CATALOG[code - ERROR_CODE_OFFSET]`
+    /// is the variant behind an on-chain error code. New variants go at the end
+    /// of both the enum and this list.
+    pub const CATALOG: &'static [ErrorCode] = &[
+        ErrorCode::InvalidAuthority,
+        ErrorCode::IdentityAlreadyRegistered,
+        ErrorCode::IdentityNotFound,
+        ErrorCode::InvalidProof,
+        ErrorCode::InvalidPublicInputs,
+        ErrorCode::ProofVerificationFailed,
+        ErrorCode::InvalidVerificationStatus,
+        ErrorCode::VerificationAlreadyExists,
+        ErrorCode::VerificationNotFound,
+        ErrorCode::SessionExpired,
+        ErrorCode::InvalidSession,
+        ErrorCode::CompressionError,
+        ErrorCode::InvalidCompressedAccount,
+        ErrorCode::MerkleTreeError,
+        ErrorCode::UnauthorizedAccess,
+        ErrorCode::MalformedProofComponent,
+        ErrorCode::CommitmentReserved,
+        ErrorCode::ReservationNotExpired,
+        ErrorCode::TooManyScopes,
+        ErrorCode::SamePerson,
+        ErrorCode::AttributeTypeRequired,
+        ErrorCode::CompositeAttributeType,
+        ErrorCode::StaleMerkleRoot,
+        ErrorCode::PublicInputHashMismatch,
+        ErrorCode::IdentityFrozen,
+        ErrorCode::VerificationKeyMismatch,
+        ErrorCode::InvalidPolicy,
+        ErrorCode::NullifierAlreadyUsed,
+        ErrorCode::IdentityExpired,
+        ErrorCode::NonCanonicalInput,
+        ErrorCode::MissingDependencyAttribute,
+        ErrorCode::InvalidSnapshot,
+        ErrorCode::ClockUnavailable,
+        ErrorCode::ChallengeExpired,
+        ErrorCode::ChallengeConsumed,
+        ErrorCode::VerifierLimitReached,
+        ErrorCode::AttributeExpired,
+        ErrorCode::UnsupportedProofSystem,
+        ErrorCode::SessionIdle,
+        ErrorCode::CredentialRevoked,
+        ErrorCode::InsufficientVerification,
+        ErrorCode::NotAllowlisted,
+        ErrorCode::CacheExpired,
+        ErrorCode::InvalidReturnData,
+        ErrorCode::SessionCountOutOfRange,
+        ErrorCode::ProofExpired,
+        ErrorCode::FeeEscrowError,
+        ErrorCode::InvalidDid,
+        ErrorCode::InstructionPaused,
+        ErrorCode::GlobalRateLimited,
+        ErrorCode::UnknownErrorCode,
+        ErrorCode::AttributeTypeMismatch,
+        ErrorCode::UpdateTimeLocked,
+        ErrorCode::NoPendingUpdate,
+        ErrorCode::InvalidConfig,
+        ErrorCode::InvalidNullifierRecord,
+        ErrorCode::InvalidBatchSize,
+        ErrorCode::TooManyAttributes,
+        ErrorCode::AttributeDefinitionMismatch,
+        ErrorCode::MissingRootCheckpoint,
+        ErrorCode::MissingEventLog,
+        ErrorCode::InvalidRateWindow,
+        ErrorCode::BatchLengthMismatch,
+        ErrorCode::DuplicateAccounts,
+        ErrorCode::DuplicateAttribute,
+        ErrorCode::MissingVerificationLog,
+        ErrorCode::EmptyLeafNullifier,
+        ErrorCode::FrozenByAuthority,
+    ];
+
+    /// Canonical English message of the program error with on-chain `code`
+    pub fn describe(code: u32) -> Option<String> {
+        let index = code.checked_sub(anchor_lang::error::ERROR_CODE_OFFSET)?;
+        Self::CATALOG.get(index as usize).map(ToString::to_string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_catalog_matches_declaration_order() {
+        for (index, code) in ErrorCode::CATALOG.iter().enumerate() {
+            assert_eq!(*code as usize, index, "{} is out of order", code.name());
+        }
+        assert_eq!(
+            ErrorCode::CATALOG.last().map(|code| code.name()),
+            Some(ErrorCode::FrozenByAuthority.name())
+        );
+    }
+    
+    #[test]
+    fn test_describe_returns_msg_text() {
+        let code: u32 = ErrorCode::InvalidProof.into();
+        assert_eq!(ErrorCode::describe(code).as_deref(), Some("Invalid proof provided"));
+        assert_eq!(
+            ErrorCode::describe(ErrorCode::GlobalRateLimited.into()).as_deref(),
+            Some("Registry-wide verification cap reached for this slot window")
+        );
+        
+        assert_eq!(ErrorCode::describe(anchor_lang::error::ERROR_CODE_OFFSET - 1), None);
+        assert_eq!(ErrorCode::describe(u32::from(ErrorCode::FrozenByAuthority) + 1), None);
+    }
 }
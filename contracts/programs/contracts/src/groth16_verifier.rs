@@ -1,8 +1,50 @@
 use anchor_lang::prelude::*;
+use groth16_solana::decompression::{decompress_g1, decompress_g2};
+use groth16_solana::errors::Groth16Error;
 use groth16_solana::groth16::{Groth16Verifier, Groth16Verifyingkey};
+use solana_bn254::prelude::{alt_bn128_addition, alt_bn128_pairing};
+use zeroize::Zeroize;
 
 // Import verification keys from separate module
 use crate::verification_keys::*;
+use crate::state::{ArchivedVerificationKey, CircuitVerificationKey, VkReport};
+
+/// `IdentityRegistry.proof_systems` value for the built-in Groth16 verifier
+pub const PROOF_SYSTEM_GROTH16: u8 = 0;
+
+/// Verify an attribute proof with the attribute's configured proof system
+/// 
+/// Only Groth16 is built in; other systems (e.g. PLONK) will dispatch to an
+/// alternate or CPI verifier here and fail with `UnsupportedProofSystem` until then.
+/// A `COMPRESSED_PROOF_LEN` proof is decompressed first (see `decompress_proof`).
+pub fn verify_attribute_proof(
+    proof_system: u8,
+    proof_bytes: &[u8],
+    public_inputs_bytes: &[u8],
+    attribute_type: u8,
+) -> Result<bool> {
+    match proof_system {
+        PROOF_SYSTEM_GROTH16 => verify_groth16_proof(
+            proof_bytes,
+            public_inputs_bytes,
+            attribute_type,
+            proof_bytes.len() == COMPRESSED_PROOF_LEN,
+        ),
+        _ => err!(crate::errors::ErrorCode::UnsupportedProofSystem),
+    }
+}
+
+/// A verification outcome as the pairing's verdict on the proof
+/// 
+/// `ProofVerificationFailed` means the pairing ran and rejected the proof, so it
+/// is `false`. Every other error (malformed points, an unsupported proof system,
+/// a bad attribute type, inputs or key) never reached the pairing and is kept.
+pub fn pairing_verdict(outcome: Result<bool>) -> Result<bool> {
+    match outcome {
+        Err(err) if err == error!(crate::errors::ErrorCode::ProofVerificationFailed) => Ok(false),
+        outcome => outcome,
+    }
+}
 
 /// Verify a Groth16 proof using BPF-optimized groth16-solana library
 /// 
@@ -10,6 +52,7 @@ use crate::verification_keys::*;
 /// * `proof` - Serialized Groth16 proof (256 bytes: 64 bytes A, 128 bytes B, 64 bytes C)
 /// * `public_inputs` - Public signals/inputs as field elements (32 bytes each)
 /// * `attribute_type` - Type of attribute being verified (1=age, 2=nationality, 4=uniqueness)
+/// * `compressed` - `proof` uses compressed points instead (see `decompress_proof`)
 /// 
 /// # Returns
 /// * `Result<bool>` - True if proof is valid, error otherwise
@@ -17,51 +60,327 @@ pub fn verify_groth16_proof(
     proof_bytes: &[u8],
     public_inputs_bytes: &[u8],
     attribute_type: u8,
+    compressed: bool,
 ) -> Result<bool> {
+    if compressed {
+        let expanded = decompress_proof(proof_bytes)?;
+        return verify_groth16_proof(&expanded, public_inputs_bytes, attribute_type, false);
+    }
+    
     // Select verification key based on attribute type
-    let vk_struct = match attribute_type {
-        1 => &AGE_PROOF_VK,
-        2 => &NATIONALITY_PROOF_VK,
-        4 => &UNIQUENESS_PROOF_VK,
-        _ => return Err(error!(crate::errors::ErrorCode::InvalidPublicInputs)),
-    };
+    let vk_struct = select_verification_key(attribute_type)?;
 
     msg!("Verifying Groth16 proof for attribute type: {}", attribute_type);
     
-    // Validate input lengths
-    require!(proof_bytes.len() == 256, crate::errors::ErrorCode::InvalidProof);
-    require!(!public_inputs_bytes.is_empty(), crate::errors::ErrorCode::InvalidPublicInputs);
-    require!(public_inputs_bytes.len() % 32 == 0, crate::errors::ErrorCode::InvalidPublicInputs);
+    // Prepare verification key
+    let (alpha_g1, beta_g2, gamma_g2, delta_g2, ic_points) = prepare_verification_key(vk_struct)?;
     
-    let num_inputs = public_inputs_bytes.len() / 32;
+    let vk = Groth16Verifyingkey {
+        nr_pubinputs: ic_points.len() - 1,
+        vk_alpha_g1: alpha_g1,
+        vk_beta_g2: beta_g2,
+        vk_gamme_g2: gamma_g2,
+        vk_delta_g2: delta_g2,
+        vk_ic: &ic_points,
+    };
     
-    // Split proof into A, B, C components
-    let proof_a: &[u8; 64] = proof_bytes[0..64].try_into()
+    verify_with_key(proof_bytes, public_inputs_bytes, &vk)
+}
+
+/// Most public inputs `verify_with_key` dispatches on
+pub const MAX_PUBLIC_INPUTS: usize = 5;
+
+/// Verify a proof against the attribute's key resized to the proof's input count
+/// 
+/// The IC points cycle through the attribute key's own, so key preparation,
+/// input folding and the pairing cost what a real key with that many inputs
+/// would. Only `benchmark_verification` uses it, to measure any input count.
+pub fn verify_groth16_proof_resized(
+    proof_bytes: &[u8],
+    public_inputs_bytes: &[u8],
+    attribute_type: u8,
+) -> Result<bool> {
+    let num_inputs = public_input_count(public_inputs_bytes)?;
+    let (alpha_g1, beta_g2, gamma_g2, delta_g2, ic_points) =
+        prepare_verification_key(select_verification_key(attribute_type)?)?;
+    let ic_points: Vec<[u8; 64]> = ic_points.iter().cycle().take(num_inputs + 1).copied().collect();
+    
+    let vk = Groth16Verifyingkey {
+        nr_pubinputs: num_inputs,
+        vk_alpha_g1: alpha_g1,
+        vk_beta_g2: beta_g2,
+        vk_gamme_g2: gamma_g2,
+        vk_delta_g2: delta_g2,
+        vk_ic: &ic_points,
+    };
+    
+    verify_with_key(proof_bytes, public_inputs_bytes, &vk)
+}
+
+/// Length of a proof with compressed points: 32-byte A, 64-byte B, 32-byte C
+pub const COMPRESSED_PROOF_LEN: usize = 128;
+
+/// Expand a compressed proof into the 256-byte uncompressed layout
+/// 
+/// Halves the proof's instruction data. Encodings that don't decompress to a
+/// curve point are `MalformedProofComponent`; subgroup membership is still
+/// checked by the pairing, as for uncompressed proofs.
+pub fn decompress_proof(proof_bytes: &[u8]) -> Result<[u8; 256]> {
+    require!(
+        proof_bytes.len() == COMPRESSED_PROOF_LEN,
+        crate::errors::ErrorCode::InvalidProof
+    );
+    
+    let mut expanded = [0u8; 256];
+    let proof_a: &[u8; 32] = proof_bytes[0..32].try_into()
         .map_err(|_| error!(crate::errors::ErrorCode::InvalidProof))?;
-    let proof_b: &[u8; 128] = proof_bytes[64..192].try_into()
+    let proof_b: &[u8; 64] = proof_bytes[32..96].try_into()
         .map_err(|_| error!(crate::errors::ErrorCode::InvalidProof))?;
-    let proof_c: &[u8; 64] = proof_bytes[192..256].try_into()
+    let proof_c: &[u8; 32] = proof_bytes[96..128].try_into()
         .map_err(|_| error!(crate::errors::ErrorCode::InvalidProof))?;
+    expanded[0..64].copy_from_slice(&decompress_g1(proof_a).map_err(map_groth16_error)?);
+    expanded[64..192].copy_from_slice(&decompress_g2(proof_b).map_err(map_groth16_error)?);
+    expanded[192..256].copy_from_slice(&decompress_g1(proof_c).map_err(map_groth16_error)?);
+    Ok(expanded)
+}
+
+/// Verify a Groth16 proof against a key archived before a VK rotation
+/// 
+/// Used to audit past verifications with the key that was active at the time.
+pub fn verify_groth16_proof_archived(
+    proof_bytes: &[u8],
+    public_inputs_bytes: &[u8],
+    archived: &ArchivedVerificationKey,
+) -> Result<bool> {
+    msg!(
+        "Verifying Groth16 proof for attribute type {} against archived key v{}",
+        archived.attribute_type,
+        archived.version
+    );
     
-    // Prepare verification key
-    let (alpha_g1, beta_g2, gamma_g2, delta_g2, ic_points) = prepare_verification_key(vk_struct);
-    
-    let vk = Groth16Verifyingkey {
-        nr_pubinputs: num_inputs,
+    verify_with_key(proof_bytes, public_inputs_bytes, &archived_verifying_key(archived)?)
+}
+
+fn archived_verifying_key(archived: &ArchivedVerificationKey) -> Result<Groth16Verifyingkey<'_>> {
+    require!(!archived.ic.is_empty(), crate::errors::ErrorCode::VerificationKeyMismatch);
+    Ok(Groth16Verifyingkey {
+        nr_pubinputs: archived.ic.len() - 1,
+        vk_alpha_g1: archived.alpha_g1,
+        vk_beta_g2: archived.beta_g2,
+        vk_gamme_g2: archived.gamma_g2,
+        vk_delta_g2: archived.delta_g2,
+        vk_ic: &archived.ic,
+    })
+}
+
+/// Keccak-256 over a key's points in verifier order: alpha, beta, gamma, delta,
+/// then every IC point
+/// 
+/// Lets auditors tell keys apart without comparing all their bytes.
+pub fn verification_key_fingerprint(vk: &Groth16Verifyingkey) -> [u8; 32] {
+    let mut points: Vec<&[u8]> = vec![&vk.vk_alpha_g1, &vk.vk_beta_g2, &vk.vk_gamme_g2, &vk.vk_delta_g2];
+    points.extend(vk.vk_ic.iter().map(|point| &point[..]));
+    solana_program::keccak::hashv(&points).to_bytes()
+}
+
+/// Verify an attribute proof and report which key version verified it
+/// 
+/// Tries the attribute's current compiled key, reported at the version compiled
+/// into it, then each of `archived` in order. See `verify_and_report`.
+pub fn verify_attribute_and_report(
+    proof_bytes: &[u8],
+    public_inputs_bytes: &[u8],
+    attribute_type: u8,
+    archived: &[ArchivedVerificationKey],
+) -> Result<VkReport> {
+    let key = select_verification_key(attribute_type)?;
+    let current_version = key.version;
+    let (alpha_g1, beta_g2, gamma_g2, delta_g2, ic_points) = prepare_verification_key(key)?;
+    let current = Groth16Verifyingkey {
+        nr_pubinputs: ic_points.len() - 1,
         vk_alpha_g1: alpha_g1,
         vk_beta_g2: beta_g2,
         vk_gamme_g2: gamma_g2,
         vk_delta_g2: delta_g2,
         vk_ic: &ic_points,
     };
+    verify_and_report(proof_bytes, public_inputs_bytes, &current, current_version, archived)
+}
+
+/// First key among `current` and then `archived` that verifies the proof
+/// 
+/// A key that rejects the proof, for any reason (a malformed archived key
+/// included), just moves on to the next one; if none verifies the report says
+/// so with version 0 and a zero fingerprint.
+pub fn verify_and_report(
+    proof_bytes: &[u8],
+    public_inputs_bytes: &[u8],
+    current: &Groth16Verifyingkey,
+    current_version: u16,
+    archived: &[ArchivedVerificationKey],
+) -> Result<VkReport> {
+    if matches!(verify_with_key(proof_bytes, public_inputs_bytes, current), Ok(true)) {
+        return Ok(VkReport {
+            verified: true,
+            vk_version: current_version,
+            fingerprint: verification_key_fingerprint(current),
+        });
+    }
+    
+    for key in archived {
+        let Ok(vk) = archived_verifying_key(key) else {
+            continue;
+        };
+        if matches!(verify_with_key(proof_bytes, public_inputs_bytes, &vk), Ok(true)) {
+            return Ok(VkReport {
+                verified: true,
+                vk_version: key.version,
+                fingerprint: verification_key_fingerprint(&vk),
+            });
+        }
+    }
+    
+    Ok(VkReport {
+        verified: false,
+        vk_version: 0,
+        fingerprint: [0u8; 32],
+    })
+}
+
+/// Public inputs of the ownership circuit:
+/// `[pubkey_to_field(owner), merkle_root, challenge]`
+/// 
+/// The commitment stays private; the circuit proves the prover knows the secret
+/// behind some leaf of `merkle_root`, bound to the identity's owner and this
+/// one challenge, so another wallet's proof can't answer it.
+pub fn ownership_public_inputs(owner: &Pubkey, merkle_root: &[u8; 32], challenge: &[u8; 32]) -> Vec<u8> {
+    [crate::compression::pubkey_to_field(owner).as_ref(), merkle_root.as_ref(), challenge.as_ref()].concat()
+}
+
+/// Verify a proof for an auxiliary circuit against its configured key
+pub fn verify_groth16_proof_circuit(
+    proof_bytes: &[u8],
+    public_inputs_bytes: &[u8],
+    circuit_vk: &CircuitVerificationKey,
+) -> Result<bool> {
+    require!(!circuit_vk.ic.is_empty(), crate::errors::ErrorCode::VerificationKeyMismatch);
+    
+    msg!("Verifying Groth16 proof for circuit: {}", circuit_vk.circuit_id);
+    
+    let vk = Groth16Verifyingkey {
+        nr_pubinputs: circuit_vk.ic.len() - 1,
+        vk_alpha_g1: circuit_vk.alpha_g1,
+        vk_beta_g2: circuit_vk.beta_g2,
+        vk_gamme_g2: circuit_vk.gamma_g2,
+        vk_delta_g2: circuit_vk.delta_g2,
+        vk_ic: &circuit_vk.ic,
+    };
+    
+    verify_with_key(proof_bytes, public_inputs_bytes, &vk)
+}
+
+/// Identities one aggregated proof may cover
+pub const MAX_BATCH_PROOF_USERS: usize = 8;
+
+/// Single public input of the batch circuit
+/// 
+/// `compute_public_inputs_hash(attribute_type || user_0 || ... || user_n)` with its
+/// first byte cleared, as in `verify_public_inputs_hash`; the attribute type is a
+/// 32-byte big-endian element. Hashing keeps the proof within the verifier's input
+/// limit however many users it covers.
+pub fn batch_public_input(attribute_type: u8, user_inputs: &[Vec<u8>]) -> Result<[u8; 32]> {
+    require!(
+        !user_inputs.is_empty() && user_inputs.len() <= MAX_BATCH_PROOF_USERS,
+        crate::errors::ErrorCode::InvalidPublicInputs
+    );
     
-    // Dynamic dispatch based on number of inputs (we'll support up to 10 inputs)
+    let mut all_inputs = vec![0u8; 32];
+    all_inputs[31] = attribute_type;
+    for inputs in user_inputs {
+        require!(
+            !inputs.is_empty() && inputs.chunks_exact(32).remainder().is_empty(),
+            crate::errors::ErrorCode::InvalidPublicInputs
+        );
+        all_inputs.extend_from_slice(inputs);
+    }
+    
+    let mut input = crate::compression::compute_public_inputs_hash(&all_inputs)?;
+    input[0] = 0;
+    Ok(input)
+}
+
+/// Verify one aggregated proof of `attribute_type` for every user's inputs
+pub fn verify_batch_proof(
+    proof_bytes: &[u8],
+    attribute_type: u8,
+    user_inputs: &[Vec<u8>],
+    circuit_vk: &CircuitVerificationKey,
+) -> Result<bool> {
+    let input = batch_public_input(attribute_type, user_inputs)?;
+    verify_groth16_proof_circuit(proof_bytes, &input, circuit_vk)
+}
+
+/// Syscall cost of a BN254 pairing check over the four Groth16 pairs
+/// (first pair 36_364 CU, each further pair 12_121 CU)
+const PAIRING_COMPUTE_UNITS: u64 = 36_364 + 3 * 12_121;
+
+/// Syscall cost of folding one public input into the IC accumulator
+/// (one G1 scalar multiplication plus one G1 addition)
+const PER_INPUT_COMPUTE_UNITS: u64 = 3_840 + 334;
+
+/// Fixed overhead for proof negation, key preparation and input parsing
+const VERIFY_OVERHEAD_COMPUTE_UNITS: u64 = 20_000;
+
+/// Estimate the compute units a Groth16 verification needs for `num_inputs`
+/// 
+/// Derived from the runtime's alt_bn128 syscall prices; the on-chain
+/// `benchmark_verification` instruction reports the measured figure.
+pub fn estimate_verification_compute_units(num_inputs: usize) -> u64 {
+    VERIFY_OVERHEAD_COMPUTE_UNITS
+        + PAIRING_COMPUTE_UNITS
+        + PER_INPUT_COMPUTE_UNITS * num_inputs as u64
+}
+
+/// Number of 32-byte field elements in `public_inputs_bytes`
+/// 
+/// Only the buffer's shape is checked: an empty buffer or a trailing partial
+/// element is `InvalidPublicInputs`. An all-zero element is the field element
+/// zero, a legitimate public signal (a false flag, a zero nullifier offset), so
+/// it is never rejected here; point-at-infinity checks apply to proof points only.
+fn public_input_count(public_inputs_bytes: &[u8]) -> Result<usize> {
+    let elements = public_inputs_bytes.chunks_exact(32);
+    require!(
+        elements.len() > 0 && elements.remainder().is_empty(),
+        crate::errors::ErrorCode::InvalidPublicInputs
+    );
+    Ok(elements.len())
+}
+
+/// Validate proof/input lengths and run verification against a prepared key
+fn verify_with_key(
+    proof_bytes: &[u8],
+    public_inputs_bytes: &[u8],
+    vk: &Groth16Verifyingkey,
+) -> Result<bool> {
+    // Validate input lengths
+    require!(proof_bytes.len() == 256, crate::errors::ErrorCode::InvalidProof);
+    let num_inputs = public_input_count(public_inputs_bytes)?;
+    
+    // Split proof into A, B, C components
+    let proof_a: &[u8; 64] = proof_bytes[0..64].try_into()
+        .map_err(|_| error!(crate::errors::ErrorCode::InvalidProof))?;
+    let proof_b: &[u8; 128] = proof_bytes[64..192].try_into()
+        .map_err(|_| error!(crate::errors::ErrorCode::InvalidProof))?;
+    let proof_c: &[u8; 64] = proof_bytes[192..256].try_into()
+        .map_err(|_| error!(crate::errors::ErrorCode::InvalidProof))?;
+    
+    // Dynamic dispatch based on number of inputs, up to `MAX_PUBLIC_INPUTS`
     let is_valid = match num_inputs {
-        1 => verify_with_inputs::<1>(proof_a, proof_b, proof_c, public_inputs_bytes, &vk)?,
-        2 => verify_with_inputs::<2>(proof_a, proof_b, proof_c, public_inputs_bytes, &vk)?,
-        3 => verify_with_inputs::<3>(proof_a, proof_b, proof_c, public_inputs_bytes, &vk)?,
-        4 => verify_with_inputs::<4>(proof_a, proof_b, proof_c, public_inputs_bytes, &vk)?,
-        5 => verify_with_inputs::<5>(proof_a, proof_b, proof_c, public_inputs_bytes, &vk)?,
+        1 => verify_with_inputs::<1>(proof_a, proof_b, proof_c, public_inputs_bytes, vk)?,
+        2 => verify_with_inputs::<2>(proof_a, proof_b, proof_c, public_inputs_bytes, vk)?,
+        3 => verify_with_inputs::<3>(proof_a, proof_b, proof_c, public_inputs_bytes, vk)?,
+        4 => verify_with_inputs::<4>(proof_a, proof_b, proof_c, public_inputs_bytes, vk)?,
+        5 => verify_with_inputs::<5>(proof_a, proof_b, proof_c, public_inputs_bytes, vk)?,
         _ => return Err(error!(crate::errors::ErrorCode::InvalidPublicInputs)),
     };
     
@@ -75,7 +394,35 @@ pub fn verify_groth16_proof(
     Ok(is_valid)
 }
 
+/// Select the verification key for a single attribute bit
+/// 
+/// * `0` - `AttributeTypeRequired` (usually an uninitialized client value)
+/// * more than one bit set - `CompositeAttributeType`; each proof covers one attribute
+/// * a single bit with no circuit - `InvalidPublicInputs`
+pub fn select_verification_key(attribute_type: u8) -> Result<&'static VerificationKey> {
+    require!(attribute_type != 0, crate::errors::ErrorCode::AttributeTypeRequired);
+    require!(
+        attribute_type.is_power_of_two(),
+        crate::errors::ErrorCode::CompositeAttributeType
+    );
+    
+    match attribute_type {
+        1 => Ok(&AGE_PROOF_VK),
+        2 => Ok(&NATIONALITY_PROOF_VK),
+        4 => Ok(&UNIQUENESS_PROOF_VK),
+        _ => Err(error!(crate::errors::ErrorCode::InvalidPublicInputs)),
+    }
+}
+
 /// Helper function to verify with specific number of inputs (compile-time constant)
+///
+/// Failures are split so clients know what to do next:
+/// * `MalformedProofComponent` - the proof bytes don't decode to valid curve points;
+///   fix the proof serialization (endianness, negated A, G2 coordinate order) and resubmit
+/// * `InvalidPublicInputs` - input count or values don't fit the verification key;
+///   check the circuit's public signal layout
+/// * `ProofVerificationFailed` - the proof is well-formed but the pairing check failed;
+///   the witness doesn't satisfy the circuit, so a new proof must be generated
 fn verify_with_inputs<const N: usize>(
     proof_a: &[u8; 64],
     proof_b: &[u8; 128],
@@ -97,17 +444,72 @@ fn verify_with_inputs<const N: usize>(
         proof_c,
         &public_inputs,
         vk,
-    ).map_err(|_| error!(crate::errors::ErrorCode::InvalidProof))?;
+    ).map_err(map_groth16_error)?;
     
     // verify() returns Result<(), Error> - success means proof is valid
-    verifier.verify()
-        .map_err(|_| error!(crate::errors::ErrorCode::ProofVerificationFailed))?;
+    let result = verifier.verify();
+    
+    // Defense in depth: program memory isn't persisted between instructions, but
+    // wiping the local copy keeps witness-derived values from outliving their use
+    // and sets the pattern for any future buffers holding sensitive data
+    public_inputs.zeroize();
+    
+    result.map_err(|err| match map_groth16_error(err) {
+        // The library reports malformed points and a failed pairing the same way,
+        // and never reads the points if input preparation fails first, so only
+        // pay for point validation once verification has already failed
+        err if err == error!(crate::errors::ErrorCode::ProofVerificationFailed)
+            && !proof_points_well_formed(proof_a, proof_b, proof_c) =>
+        {
+            error!(crate::errors::ErrorCode::MalformedProofComponent)
+        }
+        err => err,
+    })?;
     
     Ok(true)
 }
 
+/// Map groth16-solana errors onto program errors
+fn map_groth16_error(err: Groth16Error) -> Error {
+    match err {
+        Groth16Error::InvalidG1Length
+        | Groth16Error::InvalidG2Length
+        | Groth16Error::DecompressingG1Failed
+        | Groth16Error::DecompressingG2Failed => {
+            error!(crate::errors::ErrorCode::MalformedProofComponent)
+        }
+        Groth16Error::InvalidPublicInputsLength
+        | Groth16Error::IncompatibleVerifyingKeyWithNrPublicInputs
+        | Groth16Error::PublicInputGreaterThanFieldSize => {
+            error!(crate::errors::ErrorCode::InvalidPublicInputs)
+        }
+        Groth16Error::ProofVerificationFailed
+        | Groth16Error::PreparingInputsG1AdditionFailed
+        | Groth16Error::PreparingInputsG1MulFailed => {
+            error!(crate::errors::ErrorCode::ProofVerificationFailed)
+        }
+    }
+}
+
+/// Check that proof A and C are valid G1 points and proof B a valid G2 point
+fn proof_points_well_formed(
+    proof_a: &[u8; 64],
+    proof_b: &[u8; 128],
+    proof_c: &[u8; 64],
+) -> bool {
+    // Adding the identity point forces the G1 encoding to be decoded and validated
+    let g1_ok = |point: &[u8; 64]| alt_bn128_addition(&[&point[..], &[0u8; 64][..]].concat()).is_ok();
+    // Pairing against the G1 identity forces the G2 encoding to be validated
+    let g2_ok = alt_bn128_pairing(&[&[0u8; 64][..], &proof_b[..]].concat()).is_ok();
+    
+    g1_ok(proof_a) && g1_ok(proof_c) && g2_ok
+}
+
+/// A verification key in groth16-solana's encoding: alpha, beta, gamma, delta, IC
+type PreparedVerificationKey = ([u8; 64], [u8; 128], [u8; 128], [u8; 128], Vec<[u8; 64]>);
+
 /// Convert verification key to format expected by groth16-solana
-fn prepare_verification_key(vk: &VerificationKey) -> ([u8; 64], [u8; 128], [u8; 128], [u8; 128], Vec<[u8; 64]>) {
+fn prepare_verification_key(vk: &VerificationKey) -> Result<PreparedVerificationKey> {
     // alpha_g1: 2 * 32 bytes = 64 bytes
     let mut alpha_g1 = [0u8; 64];
     alpha_g1[..32].copy_from_slice(&vk.alpha_g1[0]);
@@ -135,19 +537,30 @@ fn prepare_verification_key(vk: &VerificationKey) -> ([u8; 64], [u8; 128], [u8;
     delta_g2[96..128].copy_from_slice(&vk.delta_g2[3]);
     
     // IC points: each is 2 * 32 bytes = 64 bytes
+    require!(!vk.ic.is_empty(), crate::errors::ErrorCode::VerificationKeyMismatch);
     let mut ic_points = Vec::with_capacity(vk.ic.len());
     for ic_point in vk.ic {
-        let mut point = [0u8; 64];
-        point[..32].copy_from_slice(&ic_point[0]);
-        point[32..].copy_from_slice(&ic_point[1]);
-        ic_points.push(point);
+        ic_points.push(ic_point_bytes(ic_point)?);
     }
     
-    (alpha_g1, beta_g2, gamma_g2, delta_g2, ic_points)
+    Ok((alpha_g1, beta_g2, gamma_g2, delta_g2, ic_points))
+}
+
+/// Pack one IC point's (x, y) coordinates into the 64-byte G1 encoding
+/// 
+/// Takes a slice rather than the const's fixed-size array so keys loaded from
+/// account data go through the same check instead of panicking on a bad length.
+fn ic_point_bytes(coordinates: &[[u8; 32]]) -> Result<[u8; 64]> {
+    require!(coordinates.len() == 2, crate::errors::ErrorCode::VerificationKeyMismatch);
+    
+    let mut point = [0u8; 64];
+    point[..32].copy_from_slice(&coordinates[0]);
+    point[32..].copy_from_slice(&coordinates[1]);
+    Ok(point)
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
     #[test]
@@ -155,16 +568,426 @@ mod tests {
         let proof = vec![0u8; 256];
         let public_inputs = vec![1u8; 32];
         
-        let result = verify_groth16_proof(&proof, &public_inputs, 99);
+        let result = verify_groth16_proof(&proof, &public_inputs, 99, false);
         assert!(result.is_err());
     }
     
+    #[test]
+    fn test_attribute_type_selection_errors() {
+        let proof = vec![0u8; 256];
+        let public_inputs = vec![1u8; 32];
+        
+        let result = verify_groth16_proof(&proof, &public_inputs, 0, false);
+        assert_eq!(
+            result.unwrap_err(),
+            error!(crate::errors::ErrorCode::AttributeTypeRequired)
+        );
+        
+        // age | uniqueness
+        let result = verify_groth16_proof(&proof, &public_inputs, 5, false);
+        assert_eq!(
+            result.unwrap_err(),
+            error!(crate::errors::ErrorCode::CompositeAttributeType)
+        );
+        
+        // Single bit without a circuit
+        let result = verify_groth16_proof(&proof, &public_inputs, 8, false);
+        assert_eq!(
+            result.unwrap_err(),
+            error!(crate::errors::ErrorCode::InvalidPublicInputs)
+        );
+    }
+    
+    #[test]
+    fn test_unsupported_proof_system_rejected() {
+        let proof = vec![0u8; 256];
+        let public_inputs = vec![0u8; 32];
+        
+        assert_eq!(
+            verify_attribute_proof(1, &proof, &public_inputs, 1).unwrap_err(),
+            error!(crate::errors::ErrorCode::UnsupportedProofSystem)
+        );
+        // Groth16 still reaches the built-in verifier, whose own checks apply
+        assert_ne!(
+            verify_attribute_proof(PROOF_SYSTEM_GROTH16, &proof, &public_inputs, 1).unwrap_err(),
+            error!(crate::errors::ErrorCode::UnsupportedProofSystem)
+        );
+    }
+
     #[test]
     fn test_proof_length_validation() {
         let proof = vec![0u8; 100]; // Invalid length
         let public_inputs = vec![1u8; 32];
         
-        let result = verify_groth16_proof(&proof, &public_inputs, 1);
+        let result = verify_groth16_proof(&proof, &public_inputs, 1, false);
         assert!(result.is_err());
     }
+
+    /// G1 generator (1, 2), big-endian
+    pub(crate) const G1_GEN: [u8; 64] = {
+        let mut point = [0u8; 64];
+        point[31] = 1;
+        point[63] = 2;
+        point
+    };
+
+    /// G2 generator, big-endian EIP-197 encoding (x_im, x_re, y_im, y_re)
+    pub(crate) const G2_GEN: [u8; 128] = [
+        25, 142, 147, 147, 146, 13, 72, 58, 114, 96, 191, 183, 49, 251, 93, 37, 241, 170, 73, 51,
+        53, 169, 231, 18, 151, 228, 133, 183, 174, 243, 18, 194, 24, 0, 222, 239, 18, 31, 30, 118,
+        66, 106, 0, 102, 94, 92, 68, 121, 103, 67, 34, 212, 247, 94, 218, 221, 70, 222, 189, 92,
+        217, 146, 246, 237, 9, 6, 137, 208, 88, 95, 240, 117, 236, 158, 153, 173, 105, 12, 51,
+        149, 188, 75, 49, 51, 112, 179, 142, 243, 85, 172, 218, 220, 209, 34, 151, 91, 18, 200,
+        94, 165, 219, 140, 109, 235, 74, 171, 113, 128, 141, 203, 64, 143, 227, 209, 231, 105, 12,
+        67, 211, 123, 76, 230, 204, 1, 102, 250, 125, 170,
+    ];
+
+    pub(crate) const TEST_IC: [[u8; 64]; 2] = [G1_GEN, G1_GEN];
+
+    /// Synthetic one-input VK built from curve generators so every point decodes
+    fn test_vk() -> Groth16Verifyingkey<'static> {
+        Groth16Verifyingkey {
+            nr_pubinputs: 1,
+            vk_alpha_g1: G1_GEN,
+            vk_beta_g2: G2_GEN,
+            vk_gamme_g2: G2_GEN,
+            vk_delta_g2: G2_GEN,
+            vk_ic: &TEST_IC,
+        }
+    }
+
+    fn one_input() -> [u8; 32] {
+        let mut input = [0u8; 32];
+        input[31] = 1;
+        input
+    }
+
+    #[test]
+    fn test_malformed_proof_point_rejected() {
+        // (1, 1) doesn't satisfy y^2 = x^3 + 3, so proof A isn't a valid G1 point
+        let mut proof_a = [0u8; 64];
+        proof_a[31] = 1;
+        proof_a[63] = 1;
+        
+        let result = verify_with_inputs::<1>(&proof_a, &G2_GEN, &[0u8; 64], &one_input(), &test_vk());
+        assert_eq!(
+            result.unwrap_err(),
+            error!(crate::errors::ErrorCode::MalformedProofComponent)
+        );
+    }
+
+    #[test]
+    fn test_well_formed_but_invalid_proof_fails_pairing() {
+        // Valid curve points that don't satisfy the pairing equation
+        let result = verify_with_inputs::<1>(&G1_GEN, &G2_GEN, &G1_GEN, &one_input(), &test_vk());
+        assert_eq!(
+            result.unwrap_err(),
+            error!(crate::errors::ErrorCode::ProofVerificationFailed)
+        );
+    }
+
+    #[test]
+    fn test_malformed_ic_point_rejected() {
+        let x_only = [[1u8; 32]];
+        assert_eq!(
+            ic_point_bytes(&x_only).unwrap_err(),
+            error!(crate::errors::ErrorCode::VerificationKeyMismatch)
+        );
+        
+        let extra = [[1u8; 32], [2u8; 32], [3u8; 32]];
+        assert!(ic_point_bytes(&extra).is_err());
+        
+        let point = ic_point_bytes(&[[1u8; 32], [2u8; 32]]).unwrap();
+        assert_eq!(&point[..32], &[1u8; 32]);
+        assert_eq!(&point[32..], &[2u8; 32]);
+    }
+
+    #[test]
+    fn test_verification_key_without_ic_rejected() {
+        let vk = VerificationKey { ic: &[], ..AGE_PROOF_VK };
+        assert_eq!(
+            prepare_verification_key(&vk).unwrap_err(),
+            error!(crate::errors::ErrorCode::VerificationKeyMismatch)
+        );
+    }
+
+    #[test]
+    fn test_valid_proof_verifies_with_input_zeroization() {
+        // With alpha = beta = gamma = delta = generators and IC = [G, G], input 1
+        // gives vk_x = 2G, so the pairing holds for A = -3G (pre-negated), B = G2, C = 0
+        let result = verify_with_inputs::<1>(&neg_3g(), &G2_GEN, &[0u8; 64], &one_input(), &test_vk());
+        assert!(result.unwrap());
+    }
+
+    #[test]
+    fn test_zero_public_input_accepted() {
+        // Input 0 gives vk_x = IC[0] = G, so A = -2G satisfies the pairing
+        let zero = [0u8; 32];
+        assert!(verify_with_key(&proof_for_single_input(&zero), &zero, &test_vk()).unwrap());
+        
+        for malformed in [&[][..], &[0u8; 31][..], &[0u8; 33][..]] {
+            assert_eq!(
+                verify_with_key(&proof_for_single_input(&zero), malformed, &test_vk()).unwrap_err(),
+                error!(crate::errors::ErrorCode::InvalidPublicInputs)
+            );
+        }
+    }
+
+    #[test]
+    fn test_compressed_proof_verifies_like_uncompressed() {
+        use solana_bn254::compression::prelude::{alt_bn128_g1_compress, alt_bn128_g2_compress};
+        
+        let zero = [0u8; 32];
+        let proof = proof_for_single_input(&zero);
+        let compressed = [
+            &alt_bn128_g1_compress(&proof[0..64]).unwrap()[..],
+            &alt_bn128_g2_compress(&proof[64..192]).unwrap()[..],
+            &alt_bn128_g1_compress(&proof[192..256]).unwrap()[..],
+        ]
+        .concat();
+        assert_eq!(compressed.len(), COMPRESSED_PROOF_LEN);
+        
+        let expanded = decompress_proof(&compressed).unwrap();
+        assert_eq!(&expanded[..], &proof[..]);
+        assert!(verify_with_key(&expanded, &zero, &test_vk()).unwrap());
+        
+        // An x coordinate above the field modulus has no point
+        let mut invalid = compressed.clone();
+        invalid[..32].copy_from_slice(&[0x3f; 32]);
+        assert_eq!(
+            decompress_proof(&invalid).unwrap_err(),
+            error!(crate::errors::ErrorCode::MalformedProofComponent)
+        );
+        assert_eq!(
+            verify_groth16_proof(&invalid, &zero, 1, true).unwrap_err(),
+            error!(crate::errors::ErrorCode::MalformedProofComponent)
+        );
+        assert_eq!(
+            decompress_proof(&proof).unwrap_err(),
+            error!(crate::errors::ErrorCode::InvalidProof)
+        );
+    }
+
+    fn neg_3g() -> [u8; 64] {
+        let mut point = [0u8; 64];
+        point[..32].copy_from_slice(&[
+            7, 105, 191, 154, 197, 107, 234, 63, 244, 2, 50, 188, 177, 182, 189, 21,
+            147, 21, 216, 71, 21, 184, 230, 121, 242, 211, 85, 150, 25, 21, 171, 240,
+        ]);
+        point[32..].copy_from_slice(&[
+            5, 172, 180, 180, 0, 233, 12, 0, 99, 0, 106, 57, 244, 120, 243, 232,
+            101, 227, 6, 221, 92, 213, 111, 53, 110, 46, 140, 216, 254, 126, 218, 230,
+        ]);
+        point
+    }
+
+    #[test]
+    fn test_proof_verifies_against_archived_key() {
+        let archived = ArchivedVerificationKey {
+            attribute_type: 1,
+            version: 1,
+            alpha_g1: G1_GEN,
+            beta_g2: G2_GEN,
+            gamma_g2: G2_GEN,
+            delta_g2: G2_GEN,
+            ic: TEST_IC.to_vec(),
+            archived_at: 0,
+            bump: 0,
+            namespace: [0u8; 16],
+        };
+        
+        let mut proof = Vec::with_capacity(256);
+        proof.extend_from_slice(&neg_3g());
+        proof.extend_from_slice(&G2_GEN);
+        proof.extend_from_slice(&[0u8; 64]);
+        assert!(verify_groth16_proof_archived(&proof, &one_input(), &archived).unwrap());
+        
+        // A proof for a different statement still fails against the old key
+        let mut two = [0u8; 32];
+        two[31] = 2;
+        assert!(verify_groth16_proof_archived(&proof, &two, &archived).is_err());
+        
+        let empty = ArchivedVerificationKey { ic: Vec::new(), ..archived };
+        assert_eq!(
+            verify_groth16_proof_archived(&proof, &one_input(), &empty).unwrap_err(),
+            error!(crate::errors::ErrorCode::VerificationKeyMismatch)
+        );
+    }
+
+    #[test]
+    fn test_report_names_the_key_version_that_verified() {
+        let mut proof = Vec::with_capacity(256);
+        proof.extend_from_slice(&neg_3g());
+        proof.extend_from_slice(&G2_GEN);
+        proof.extend_from_slice(&[0u8; 64]);
+        let current = test_vk();
+        
+        // A fresh proof verifies under the current key
+        let report = verify_and_report(&proof, &one_input(), &current, 3, &[]).unwrap();
+        assert_eq!(
+            report,
+            VkReport {
+                verified: true,
+                vk_version: 3,
+                fingerprint: verification_key_fingerprint(&current),
+            }
+        );
+        
+        // After a rotation, only the archived key verifies it
+        let archived = ArchivedVerificationKey {
+            attribute_type: 1,
+            version: 2,
+            alpha_g1: G1_GEN,
+            beta_g2: G2_GEN,
+            gamma_g2: G2_GEN,
+            delta_g2: G2_GEN,
+            ic: TEST_IC.to_vec(),
+            archived_at: 0,
+            bump: 0,
+            namespace: [0u8; 16],
+        };
+        let rotated_ic = [G1_GEN, neg_3g()];
+        let rotated = Groth16Verifyingkey { vk_ic: &rotated_ic, ..test_vk() };
+        let malformed = ArchivedVerificationKey { version: 1, ic: Vec::new(), ..archived.clone() };
+        let report = verify_and_report(&proof, &one_input(), &rotated, 3, &[malformed, archived]).unwrap();
+        assert!(report.verified);
+        assert_eq!(report.vk_version, 2);
+        assert_eq!(report.fingerprint, verification_key_fingerprint(&current));
+        assert_ne!(verification_key_fingerprint(&rotated), report.fingerprint);
+        
+        let report = verify_and_report(&proof, &one_input(), &rotated, 3, &[]).unwrap();
+        assert!(!report.verified);
+        assert_eq!((report.vk_version, report.fingerprint), (0, [0u8; 32]));
+    }
+
+    #[test]
+    fn test_compute_unit_estimate_scales_with_inputs() {
+        let one = estimate_verification_compute_units(1);
+        let five = estimate_verification_compute_units(5);
+        
+        // Within the default 200k per-instruction budget, growing per input
+        assert!(one > 80_000 && one < 200_000);
+        assert!(five > one && five < 200_000);
+        assert_eq!(five - one, 4 * PER_INPUT_COMPUTE_UNITS);
+    }
+
+    #[test]
+    fn test_ownership_proof_against_circuit_key() {
+        // IC = [G, G, G, G]: owner 0, root 1 and challenge 0 give vk_x = 2G, as for `one_input`
+        let circuit_vk = CircuitVerificationKey {
+            circuit_id: CircuitVerificationKey::OWNERSHIP,
+            alpha_g1: G1_GEN,
+            beta_g2: G2_GEN,
+            gamma_g2: G2_GEN,
+            delta_g2: G2_GEN,
+            ic: vec![G1_GEN; 4],
+            bump: 0,
+            namespace: [0u8; 16],
+        };
+        let mut proof = neg_3g().to_vec();
+        proof.extend_from_slice(&G2_GEN);
+        proof.extend_from_slice(&[0u8; 64]);
+        
+        let owner = Pubkey::default();
+        let inputs = ownership_public_inputs(&owner, &one_input(), &[0u8; 32]);
+        assert!(verify_groth16_proof_circuit(&proof, &inputs, &circuit_vk).unwrap());
+        
+        // The same proof doesn't answer a different challenge
+        let inputs = ownership_public_inputs(&owner, &one_input(), &one_input());
+        assert!(verify_groth16_proof_circuit(&proof, &inputs, &circuit_vk).is_err());
+        
+        // Nor for another owner's identity
+        let inputs = ownership_public_inputs(&Pubkey::new_unique(), &one_input(), &[0u8; 32]);
+        assert!(!matches!(verify_groth16_proof_circuit(&proof, &inputs, &circuit_vk), Ok(true)));
+    }
+
+    #[test]
+    fn test_simulated_answer_leaves_challenge_open() {
+        let circuit_vk = CircuitVerificationKey {
+            circuit_id: CircuitVerificationKey::OWNERSHIP,
+            alpha_g1: G1_GEN,
+            beta_g2: G2_GEN,
+            gamma_g2: G2_GEN,
+            delta_g2: G2_GEN,
+            ic: vec![G1_GEN; 4],
+            bump: 0,
+            namespace: [0u8; 16],
+        };
+        let mut challenge = crate::state::Challenge {
+            identity: Pubkey::new_unique(),
+            challenge: [0u8; 32],
+            payer: Pubkey::new_unique(),
+            created_at: 0,
+            expires_at: 300,
+            consumed: false,
+            bump: 0,
+        };
+        let proof = [&neg_3g()[..], &G2_GEN[..], &[0u8; 64][..]].concat();
+        
+        // Simulating checks the proof without touching the challenge
+        assert!(challenge.check_answer(&Pubkey::default(), &one_input(), &proof, &circuit_vk, 10).unwrap());
+        assert!(!challenge.consumed);
+        
+        // The real answer consumes it, after which it can't be answered again
+        assert!(challenge.check_answer(&Pubkey::default(), &one_input(), &proof, &circuit_vk, 10).unwrap());
+        challenge.consume(10).unwrap();
+        assert!(challenge.consumed);
+        assert_eq!(
+            challenge.check_answer(&Pubkey::default(), &one_input(), &proof, &circuit_vk, 10).unwrap_err(),
+            error!(crate::errors::ErrorCode::ChallengeConsumed)
+        );
+    }
+
+    /// `a - b` on 32-byte big-endian integers (a >= b)
+    fn sub_be(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        let mut borrow = 0i16;
+        for i in (0..32).rev() {
+            let mut diff = a[i] as i16 - b[i] as i16 - borrow;
+            borrow = (diff < 0) as i16;
+            if diff < 0 {
+                diff += 256;
+            }
+            out[i] = diff as u8;
+        }
+        out
+    }
+    
+    /// Proof A for the generator key with IC = [G, G] and single input `h`:
+    /// -(alpha + vk_x) = -(h + 2)G, as the scalar r - h - 2
+    pub(crate) fn proof_for_single_input(h: &[u8; 32]) -> Vec<u8> {
+        let mut two = [0u8; 32];
+        two[31] = 2;
+        let scalar = sub_be(&sub_be(&crate::compression::BN254_SCALAR_MODULUS_BE, h), &two);
+        let proof_a = solana_bn254::prelude::alt_bn128_multiplication(&[&G1_GEN[..], &scalar[..]].concat()).unwrap();
+        
+        [&proof_a[..], &G2_GEN[..], &[0u8; 64][..]].concat()
+    }
+    
+    #[test]
+    fn test_batch_proof_covers_all_users() {
+        let circuit_vk = CircuitVerificationKey {
+            circuit_id: CircuitVerificationKey::BATCH,
+            alpha_g1: G1_GEN,
+            beta_g2: G2_GEN,
+            gamma_g2: G2_GEN,
+            delta_g2: G2_GEN,
+            ic: TEST_IC.to_vec(),
+            bump: 0,
+            namespace: [0u8; 16],
+        };
+        let user_inputs = vec![vec![1u8; 64], vec![2u8; 64], vec![3u8; 32]];
+        
+        let proof = proof_for_single_input(&batch_public_input(1, &user_inputs).unwrap());
+        assert!(verify_batch_proof(&proof, 1, &user_inputs, &circuit_vk).unwrap());
+        
+        // Changing any one user's inputs, or the attribute, invalidates the whole batch
+        let mut tampered = user_inputs.clone();
+        tampered[1][0] ^= 1;
+        assert!(verify_batch_proof(&proof, 1, &tampered, &circuit_vk).is_err());
+        assert!(verify_batch_proof(&proof, 4, &user_inputs, &circuit_vk).is_err());
+        
+        assert!(batch_public_input(1, &[]).is_err());
+        assert!(batch_public_input(1, &vec![vec![1u8; 32]; MAX_BATCH_PROOF_USERS + 1]).is_err());
+    }
 }
@@ -1,14 +1,16 @@
 use anchor_lang::prelude::*;
+use solana_program::instruction::get_stack_height;
 use crate::state::*;
 
-/// Initialize the identity registry
+/// Initialize the identity registry for a namespace
 #[derive(Accounts)]
+#[instruction(namespace: [u8; 16])]
 pub struct Initialize<'info> {
     #[account(
         init,
         payer = authority,
         space = IdentityRegistry::LEN,
-        seeds = [b"registry"],
+        seeds = [b"registry", namespace.as_ref()],
         bump
     )]
     pub registry: Account<'info, IdentityRegistry>,
@@ -21,41 +23,804 @@ pub struct Initialize<'info> {
 
 /// Register a new identity
 #[derive(Accounts)]
+#[instruction(identity_commitment: [u8; 32])]
 pub struct RegisterIdentity<'info> {
     #[account(
         init,
         payer = user,
         space = Identity::LEN,
-        seeds = [b"identity", user.key().as_ref()],
+        seeds = [b"identity", registry.namespace.as_ref(), user.key().as_ref()],
         bump
     )]
     pub identity: Account<'info, Identity>,
     
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [b"registry", registry.namespace.as_ref()],
+        bump = registry.bump,
+        constraint = !registry.is_paused(IdentityRegistry::PAUSE_REGISTER) @ crate::errors::ErrorCode::InstructionPaused
+    )]
     pub registry: Account<'info, IdentityRegistry>,
     
+    /// Registry-wide audit stream, required once initialized; the registration is appended
+    #[account(
+        mut,
+        seeds = [b"event_log", registry.namespace.as_ref()],
+        bump = event_log.bump
+    )]
+    pub event_log: Option<Box<Account<'info, GlobalEventLog>>>,
+    
+    /// CHECK: Reservation PDA for this commitment; only inspected if initialized
+    #[account(
+        mut,
+        seeds = [b"reservation", registry.namespace.as_ref(), identity_commitment.as_ref()],
+        bump
+    )]
+    pub reservation: UncheckedAccount<'info>,
+    
+    /// CHECK: Uninitialized `DidRecord` PDA, required when registering with a DID;
+    /// address checked and account created in `bind_did`
+    #[account(mut)]
+    pub did_record: Option<UncheckedAccount<'info>>,
+    
     #[account(mut)]
     pub user: Signer<'info>,
     
     pub system_program: Program<'info, System>,
 }
 
+impl<'info> RegisterIdentity<'info> {
+    /// Create the `DidRecord` resolving `did` to the new identity
+    /// 
+    /// Fails with `InvalidDid` when the DID is malformed or another identity in
+    /// the namespace already bound it.
+    pub fn bind_did(&self, did: &[u8; 64], program_id: &Pubkey) -> Result<()> {
+        DidRecord::validate(did)?;
+        let record_info = self
+            .did_record
+            .as_ref()
+            .ok_or(error!(crate::errors::ErrorCode::InvalidDid))?
+            .to_account_info();
+        let namespace = self.registry.namespace;
+        let (expected, bump) = DidRecord::address(&namespace, did, program_id);
+        require_keys_eq!(record_info.key(), expected, crate::errors::ErrorCode::InvalidDid);
+        require!(
+            record_info.owner == &anchor_lang::system_program::ID && record_info.data_is_empty(),
+            crate::errors::ErrorCode::InvalidDid
+        );
+        
+        let did_hash = crate::compression::did_hash(did);
+        create_pda(
+            &self.user,
+            &record_info,
+            &self.system_program,
+            DidRecord::LEN,
+            &[b"did", namespace.as_ref(), &did_hash, &[bump]],
+        )?;
+        
+        let record = DidRecord {
+            namespace,
+            did_hash,
+            did: *did,
+            identity: self.identity.key(),
+            bump,
+        };
+        record.try_serialize(&mut &mut record_info.try_borrow_mut_data()?[..])?;
+        Ok(())
+    }
+}
+
+/// Create the program-owned PDA `account` with `space` bytes, signed by `seeds`
+/// 
+/// Funds, allocates and assigns it in separate steps, as `init` does, so
+/// lamports sent to the address beforehand can't block the creation.
+pub fn create_pda<'info>(
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> Result<()> {
+    let signer = &[seeds];
+    let shortfall = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(account.lamports());
+    if shortfall > 0 {
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                system_program.key(),
+                anchor_lang::system_program::Transfer {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+            ),
+            shortfall,
+        )?;
+    }
+    anchor_lang::system_program::allocate(
+        CpiContext::new_with_signer(
+            system_program.key(),
+            anchor_lang::system_program::Allocate { account_to_allocate: account.clone() },
+            signer,
+        ),
+        space as u64,
+    )?;
+    anchor_lang::system_program::assign(
+        CpiContext::new_with_signer(
+            system_program.key(),
+            anchor_lang::system_program::Assign { account_to_assign: account.clone() },
+            signer,
+        ),
+        &crate::ID,
+    )
+}
+
+/// Claim `nullifier` on `registry`, failing with `NullifierAlreadyUsed` on reuse
+/// 
+/// `nullifier_record` is the nullifier's own `NullifierRecord` address; it must
+/// not exist yet. Once the inline ring is full, `evicted_record` is the address
+/// of the record the evicted nullifier moves to, and is created here.
+pub fn claim_nullifier<'info>(
+    registry: &mut IdentityRegistry,
+    nullifier: [u8; 32],
+    nullifier_record: &AccountInfo<'info>,
+    evicted_record: Option<&AccountInfo<'info>>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    now: i64,
+) -> Result<()> {
+    let namespace = registry.namespace;
+    let (expected, _) = NullifierRecord::address(&namespace, &nullifier, &crate::ID);
+    require_keys_eq!(nullifier_record.key(), expected, crate::errors::ErrorCode::InvalidNullifierRecord);
+    let has_record = nullifier_record.owner == &crate::ID && !nullifier_record.data_is_empty();
+    
+    let Some(evicted) = registry.claim_nullifier(nullifier, has_record)? else {
+        return Ok(());
+    };
+    let record_info = evicted_record.ok_or(error!(crate::errors::ErrorCode::InvalidNullifierRecord))?;
+    let (expected, bump) = NullifierRecord::address(&namespace, &evicted, &crate::ID);
+    require_keys_eq!(record_info.key(), expected, crate::errors::ErrorCode::InvalidNullifierRecord);
+    
+    create_pda(
+        payer,
+        record_info,
+        system_program,
+        NullifierRecord::LEN,
+        &[b"nullifier", namespace.as_ref(), evicted.as_ref(), &[bump]],
+    )?;
+    let record = NullifierRecord { nullifier: evicted, registered_at: now, bump };
+    record.try_serialize(&mut &mut record_info.try_borrow_mut_data()?[..])?;
+    Ok(())
+}
+
+/// Count `verifications` against the registry-wide cap in `slot`'s window
+/// 
+/// Without a cap nothing is written and `rate_window` may be omitted. With one,
+/// it must be the window's `RateWindow` address; the first verification of a
+/// window creates it, paid by `payer`. Beyond the cap, every verification in
+/// the window is `GlobalRateLimited`, whichever identity it is for.
+pub fn record_global_verifications<'info>(
+    registry: &IdentityRegistry,
+    verifications: u32,
+    rate_window: Option<&AccountInfo<'info>>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    slot: u64,
+) -> Result<()> {
+    let Some(window) = registry.rate_window_of(slot) else {
+        return Ok(());
+    };
+    let namespace = registry.namespace;
+    let info = rate_window.ok_or(error!(crate::errors::ErrorCode::InvalidRateWindow))?;
+    let (expected, bump) = RateWindow::address(&namespace, window, &crate::ID);
+    require_keys_eq!(info.key(), expected, crate::errors::ErrorCode::InvalidRateWindow);
+    
+    let mut counter = if info.owner == &crate::ID && !info.data_is_empty() {
+        RateWindow::try_deserialize(&mut &info.try_borrow_data()?[..])?
+    } else {
+        create_pda(
+            payer,
+            info,
+            system_program,
+            RateWindow::LEN,
+            &[b"rate_window", namespace.as_ref(), &window.to_le_bytes(), &[bump]],
+        )?;
+        RateWindow { namespace, window, count: 0, bump }
+    };
+    counter.record(verifications, registry.global_verification_cap)?;
+    counter.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+    Ok(())
+}
+
 /// Verify an identity with ZK proof
 #[derive(Accounts)]
 pub struct VerifyIdentity<'info> {
+    /// Verifying never registers: a missing account fails deserialization and
+    /// one without a registered owner fails its seeds
     #[account(
         mut,
-        seeds = [b"identity", user.key().as_ref()],
+        seeds = [b"identity", registry.namespace.as_ref(), identity.owner.as_ref()],
         bump = identity.bump,
-        constraint = identity.owner == user.key() @ crate::errors::ErrorCode::UnauthorizedAccess
+        constraint = identity.owner == user.key()
+            || (identity.guardian != Pubkey::default() && identity.guardian == user.key())
+            @ crate::errors::ErrorCode::UnauthorizedAccess
     )]
     pub identity: Account<'info, Identity>,
     
+    /// The identity's owner, or its guardian; see `require_owner`
     #[account(mut)]
     pub user: Signer<'info>,
     
     /// CHECK: Verifier authority (could be oracle or multisig)
     pub verifier: AccountInfo<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"registry", registry.namespace.as_ref()],
+        bump = registry.bump,
+        constraint = !registry.is_paused(IdentityRegistry::PAUSE_VERIFY) @ crate::errors::ErrorCode::InstructionPaused
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    /// Verification history, required once initialized; appended to on success
+    #[account(
+        mut,
+        seeds = [b"verification_log", identity.key().as_ref()],
+        bump = verification_log.bump
+    )]
+    pub verification_log: Option<Account<'info, VerificationLog>>,
+    
+    /// Registry-wide audit stream, required once initialized; appended to
+    #[account(
+        mut,
+        seeds = [b"event_log", registry.namespace.as_ref()],
+        bump = event_log.bump
+    )]
+    pub event_log: Option<Box<Account<'info, GlobalEventLog>>>,
+    
+    /// Root checkpoint, required once initialized; the identity's root must be fresh
+    #[account(
+        seeds = [b"root_checkpoint", registry.namespace.as_ref()],
+        bump = root_checkpoint.bump
+    )]
+    pub root_checkpoint: Option<Account<'info, RootCheckpoint>>,
+    
+    /// Optional public-input layout of the attribute's circuit
+    #[account(
+        seeds = [b"input_layout", registry.namespace.as_ref(), &[input_layout.attribute_type]],
+        bump = input_layout.bump
+    )]
+    pub input_layout: Option<Account<'info, PublicInputLayout>>,
+    
+    /// SlotHashes sysvar, required by `verify_identity_slot_bound`
+    /// CHECK: address constrained to the sysvar; parsed by `slot_hash_field_element`
+    #[account(address = solana_program::sysvar::slot_hashes::ID)]
+    pub slot_hashes: Option<UncheckedAccount<'info>>,
+    
+    /// Fee escrow, required by every verification once a fee is set
+    #[account(
+        mut,
+        seeds = [b"fee_escrow", registry.namespace.as_ref()],
+        bump = fee_escrow.bump
+    )]
+    pub fee_escrow: Option<Account<'info, FeeEscrow>>,
+    
+    /// CHECK: The nullifier's `NullifierRecord` address, checked in `claim_nullifier`;
+    /// it must not exist
+    pub nullifier_record: UncheckedAccount<'info>,
+    
+    /// CHECK: `NullifierRecord` address of the ring's oldest nullifier, required
+    /// once the ring is full; checked and created in `claim_nullifier`
+    #[account(mut)]
+    pub evicted_record: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: The current window's `RateWindow` address, required while a global
+    /// cap is set; checked and created in `record_global_verifications`
+    #[account(mut)]
+    pub rate_window: Option<UncheckedAccount<'info>>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> VerifyIdentity<'info> {
+    /// Require `user` to be the owner rather than the guardian, for paths that
+    /// create or close accounts keyed by the owner
+    pub fn require_owner(&self) -> Result<()> {
+        require_keys_eq!(
+            self.identity.owner,
+            self.user.key(),
+            crate::errors::ErrorCode::UnauthorizedAccess
+        );
+        Ok(())
+    }
+    
+    /// Enforce root freshness once the registry has a checkpoint
+    pub fn check_root_freshness(&self, current_slot: u64) -> Result<()> {
+        check_root_freshness(
+            &self.registry,
+            self.root_checkpoint.as_deref(),
+            &self.identity.merkle_root,
+            current_slot,
+        )
+    }
+    
+    /// Check the values a proof is bound to: the verification nonce, plus the
+    /// owner binding when the attribute's layout exposes one
+    pub fn require_bound_inputs(&self, attribute_type: u8, public_inputs: &[u8]) -> Result<()> {
+        require_bound_inputs(
+            &self.identity,
+            &self.registry,
+            self.input_layout.as_deref(),
+            attribute_type,
+            public_inputs,
+        )
+    }
+    
+    /// Checks every attribute verification runs before any pairing work
+    /// 
+    /// The identity must be neither frozen nor expired, its root fresh and the
+    /// attribute's prerequisites verified; the proof must be a Groth16 proof,
+    /// uncompressed (256 bytes) or compressed (`COMPRESSED_PROOF_LEN`), and
+    /// `bound_inputs` must carry the identity's bindings under `layout`.
+    pub fn require_verifiable(
+        &self,
+        layout: Option<&PublicInputLayout>,
+        proof: &[u8],
+        bound_inputs: &[u8],
+        attribute_type: u8,
+        clock: &Clock,
+    ) -> Result<()> {
+        self.identity.require_not_frozen()?;
+        self.identity.require_not_expired(clock.unix_timestamp)?;
+        self.check_root_freshness(clock.slot)?;
+        self.require_attribute_dependencies(attribute_type)?;
+        
+        require!(
+            proof.len() == 256 || proof.len() == crate::groth16_verifier::COMPRESSED_PROOF_LEN,
+            crate::errors::ErrorCode::InvalidProof
+        );
+        require!(!bound_inputs.is_empty(), crate::errors::ErrorCode::InvalidPublicInputs);
+        require_bound_inputs(&self.identity, &self.registry, layout, attribute_type, bound_inputs)
+    }
+    
+    /// `require_verifiable`, then the pairing over `public_inputs`, counting the
+    /// attempt; the inner result is the pairing outcome
+    /// 
+    /// The registry's `verification_fee` is escrowed before the pairing,
+    /// refunded when the proof verifies and forfeited when the pairing rejects
+    /// it. `verify_identity` reports a rejected proof as `false`, so the forfeit
+    /// sticks; paths that fail on one roll it back with the rest of the
+    /// instruction.
+    fn attempt_proof(
+        &mut self,
+        layout: Option<&PublicInputLayout>,
+        proof: &[u8],
+        public_inputs: &[u8],
+        bound_inputs: &[u8],
+        attribute_type: u8,
+        clock: &Clock,
+    ) -> Result<Result<bool>> {
+        self.require_verifiable(layout, proof, bound_inputs, attribute_type, clock)?;
+        let fee = self.escrow_fee()?;
+        
+        let outcome = self.verify_proof(proof, public_inputs, attribute_type);
+        let verified = self.identity.record_verify_attempt(&outcome);
+        self.settle_fee(fee, verified)?;
+        Ok(outcome)
+    }
+    
+    /// Verify `proof` for `attribute_type` with every shared check and claim
+    /// the `nullifier` it emits, failing unless it verifies; the caller records
+    /// the attribute
+    /// 
+    /// `bound_inputs` are the inputs the bindings and nullifier are read from:
+    /// `public_inputs` itself unless the circuit only exposes a hash or
+    /// commitment of them.
+    pub fn verify_attribute(
+        &mut self,
+        proof: &[u8],
+        public_inputs: &[u8],
+        bound_inputs: &[u8],
+        nullifier: [u8; 32],
+        attribute_type: u8,
+        clock: &Clock,
+    ) -> Result<()> {
+        crate::compression::require_nullifier_input(bound_inputs, &nullifier)?;
+        let layout = self.input_layout.as_deref().cloned();
+        self.verify_attribute_with(layout.as_ref(), proof, public_inputs, bound_inputs, attribute_type, clock)?;
+        self.claim_nullifier(nullifier, clock.unix_timestamp)
+    }
+    
+    /// The shared checks and pairing of `verify_attribute` under an explicit
+    /// layout, for batches that prove attributes with different circuits; the
+    /// caller claims the batch's nullifier
+    pub fn verify_attribute_with(
+        &mut self,
+        layout: Option<&PublicInputLayout>,
+        proof: &[u8],
+        public_inputs: &[u8],
+        bound_inputs: &[u8],
+        attribute_type: u8,
+        clock: &Clock,
+    ) -> Result<()> {
+        let outcome = self.attempt_proof(layout, proof, public_inputs, bound_inputs, attribute_type, clock)?;
+        require!(outcome?, crate::errors::ErrorCode::InvalidProof);
+        Ok(())
+    }
+    
+    /// `verify_attribute` followed by `record_verification`, the whole of a
+    /// single-attribute verification
+    pub fn verify_and_record(
+        &mut self,
+        proof: &[u8],
+        public_inputs: &[u8],
+        bound_inputs: &[u8],
+        nullifier: [u8; 32],
+        attribute_type: u8,
+        clock: &Clock,
+    ) -> Result<()> {
+        self.verify_attribute(proof, public_inputs, bound_inputs, nullifier, attribute_type, clock)?;
+        self.record_verification(attribute_type, clock.unix_timestamp)
+    }
+    
+    /// `verify_and_record` that reports a proof the pairing rejects as `false`
+    /// instead of failing, so the counted attempt and forfeited fee persist; the
+    /// nullifier stays unclaimed
+    /// 
+    /// A nullifier already claimed fails before the attempt is counted. Any
+    /// other error fails the instruction with its own code, returning the fee.
+    pub fn verify_and_record_metered(
+        &mut self,
+        proof: &[u8],
+        public_inputs: &[u8],
+        nullifier: [u8; 32],
+        attribute_type: u8,
+        clock: &Clock,
+    ) -> Result<bool> {
+        crate::compression::require_nullifier_input(public_inputs, &nullifier)?;
+        self.require_unclaimed_nullifier(&nullifier)?;
+        let layout = self.input_layout.as_deref().cloned();
+        let outcome = self.attempt_proof(layout.as_ref(), proof, public_inputs, public_inputs, attribute_type, clock)?;
+        if !crate::groth16_verifier::pairing_verdict(outcome)? {
+            return Ok(false);
+        }
+        
+        self.claim_nullifier(nullifier, clock.unix_timestamp)?;
+        self.record_verification(attribute_type, clock.unix_timestamp)?;
+        Ok(true)
+    }
+    
+    /// Register the verified proof's `nullifier`, failing with
+    /// `NullifierAlreadyUsed` on reuse; see `claim_nullifier`
+    pub fn claim_nullifier(&mut self, nullifier: [u8; 32], now: i64) -> Result<()> {
+        crate::compression::validate_field_element(&nullifier)?;
+        claim_nullifier(
+            &mut self.registry,
+            nullifier,
+            &self.nullifier_record,
+            self.evicted_record.as_deref(),
+            &self.user,
+            &self.system_program,
+            now,
+        )
+    }
+    
+    /// Claim the verified proof's `nullifier` and also set its leaf in the
+    /// spent-nullifier tree; see `IdentityRegistry::mark_spent`
+    /// 
+    /// The claim is what every other path checks, so a nullifier spent here
+    /// can't be claimed again without a `spent_root` proof.
+    pub fn spend_nullifier(
+        &mut self,
+        nullifier: [u8; 32],
+        occupied: u64,
+        siblings: &[[u8; 32]],
+        now: i64,
+    ) -> Result<()> {
+        self.claim_nullifier(nullifier, now)?;
+        self.registry.mark_spent(&nullifier, occupied, siblings)
+    }
+    
+    /// Fail with `NullifierAlreadyUsed` if `nullifier` is in the inline ring or
+    /// has a `NullifierRecord`, without claiming it
+    pub fn require_unclaimed_nullifier(&self, nullifier: &[u8; 32]) -> Result<()> {
+        crate::compression::validate_field_element(nullifier)?;
+        let (expected, _) = NullifierRecord::address(&self.registry.namespace, nullifier, &crate::ID);
+        require_keys_eq!(
+            self.nullifier_record.key(),
+            expected,
+            crate::errors::ErrorCode::InvalidNullifierRecord
+        );
+        let has_record = self.nullifier_record.owner == &crate::ID && !self.nullifier_record.data_is_empty();
+        require!(
+            !has_record && !self.registry.contains_nullifier(nullifier),
+            crate::errors::ErrorCode::NullifierAlreadyUsed
+        );
+        Ok(())
+    }
+    
+    /// Load `PublicInputLayout` accounts passed as remaining accounts, checking
+    /// each is the registry's layout PDA for its attribute
+    pub fn input_layouts_from(
+        &self,
+        infos: &'info [AccountInfo<'info>],
+    ) -> Result<Vec<Account<'info, PublicInputLayout>>> {
+        infos
+            .iter()
+            .map(|info| {
+                let layout = Account::<PublicInputLayout>::try_from(info)?;
+                let expected = Pubkey::create_program_address(
+                    &[b"input_layout", self.registry.namespace.as_ref(), &[layout.attribute_type], &[layout.bump]],
+                    &crate::ID,
+                )
+                .map_err(|_| error!(crate::errors::ErrorCode::InvalidPublicInputs))?;
+                require_keys_eq!(info.key(), expected, crate::errors::ErrorCode::InvalidPublicInputs);
+                Ok(layout)
+            })
+            .collect()
+    }
+    
+    /// Require the first public input to be the hash of `slot`, a recent slot
+    pub fn require_recent_slot_hash(
+        &self,
+        public_inputs: &[u8],
+        slot: u64,
+        current_slot: u64,
+    ) -> Result<()> {
+        let slot_hashes = self
+            .slot_hashes
+            .as_ref()
+            .ok_or(error!(crate::errors::ErrorCode::ProofExpired))?;
+        let expected = crate::compression::slot_hash_field_element(
+            &slot_hashes.try_borrow_data()?,
+            slot,
+            current_slot,
+        )?;
+        require!(
+            public_inputs.get(..32) == Some(expected.as_slice()),
+            crate::errors::ErrorCode::ProofExpired
+        );
+        Ok(())
+    }
+    
+    /// Move the registry's verification fee from the user into escrow
+    pub fn escrow_fee(&self) -> Result<u64> {
+        let fee = self.registry.verification_fee;
+        if fee == 0 {
+            return Ok(0);
+        }
+        
+        let escrow = self
+            .fee_escrow
+            .as_ref()
+            .ok_or(error!(crate::errors::ErrorCode::FeeEscrowError))?;
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                anchor_lang::system_program::ID,
+                anchor_lang::system_program::Transfer {
+                    from: self.user.to_account_info(),
+                    to: escrow.to_account_info(),
+                },
+            ),
+            fee,
+        )?;
+        Ok(fee)
+    }
+    
+    /// Refund an escrowed `fee` after a verified proof, or forfeit it
+    pub fn settle_fee(&mut self, fee: u64, verified: bool) -> Result<()> {
+        if fee == 0 {
+            return Ok(());
+        }
+        
+        let escrow = self
+            .fee_escrow
+            .as_mut()
+            .ok_or(error!(crate::errors::ErrorCode::FeeEscrowError))?;
+        let refund = escrow.settle(fee, verified)?;
+        escrow.sub_lamports(refund)?;
+        self.user.add_lamports(refund)?;
+        Ok(())
+    }
+    
+    /// Verify an attribute proof with the registry's proof system for it
+    pub fn verify_proof(&self, proof: &[u8], public_inputs: &[u8], attribute_type: u8) -> Result<bool> {
+        crate::groth16_verifier::verify_attribute_proof(
+            self.registry.proof_system_of(attribute_type)?,
+            proof,
+            public_inputs,
+            attribute_type,
+        )
+    }
+    
+    /// Reject an attribute whose configured prerequisites aren't verified yet
+    pub fn require_attribute_dependencies(&self, attribute_type: u8) -> Result<()> {
+        self.identity.require_dependencies(self.registry.dependencies_of(attribute_type))
+    }
+    
+    /// Record a successful verification on the identity and its log
+    pub fn record_verification(&mut self, attribute_type: u8, timestamp: i64) -> Result<()> {
+        self.record_global_verification()?;
+        self.identity.mark_verified(attribute_type, timestamp)?;
+        self.log_verification(attribute_type, timestamp)
+    }
+    
+    /// `record_verification` with an attribute expiry supplied by the verifier
+    pub fn record_verification_until(
+        &mut self,
+        attribute_type: u8,
+        timestamp: i64,
+        expires_at: i64,
+    ) -> Result<()> {
+        self.record_global_verification()?;
+        self.identity.mark_verified_until(attribute_type, timestamp, expires_at)?;
+        self.log_verification(attribute_type, timestamp)
+    }
+    
+    fn record_global_verification(&self) -> Result<()> {
+        record_global_verifications(
+            &self.registry,
+            1,
+            self.rate_window.as_deref(),
+            &self.user,
+            &self.system_program,
+            crate::current_clock()?.slot,
+        )
+    }
+    
+    fn log_verification(&mut self, attribute_type: u8, timestamp: i64) -> Result<()> {
+        require_verification_log(&self.identity, self.verification_log.as_deref())?;
+        require_event_log(&self.registry, self.event_log.as_deref())?;
+        let verifier = self.credited_verifier();
+        if let Some(log) = self.verification_log.as_mut() {
+            log.append(VerificationLogEntry { attribute_type, timestamp, verifier });
+        }
+        if let Some(log) = self.event_log.as_mut() {
+            log.append(GlobalEventLog::EVENT_VERIFIED, self.identity.key(), timestamp);
+        }
+        Ok(())
+    }
+    
+    /// Require `verifier` to sign and be on the registry's trusted list
+    pub fn require_trusted_verifier(&self) -> Result<()> {
+        require!(self.has_trusted_verifier(), crate::errors::ErrorCode::UnauthorizedAccess);
+        Ok(())
+    }
+    
+    /// Whether `verifier` signed and is on the registry's trusted list
+    pub fn has_trusted_verifier(&self) -> bool {
+        self.verifier.is_signer && self.registry.is_trusted_verifier(self.verifier.key)
+    }
+    
+    /// Who a verification is credited to: `verifier` if it signed and is
+    /// trusted, otherwise the signing `user`
+    pub fn credited_verifier(&self) -> Pubkey {
+        if self.has_trusted_verifier() {
+            self.verifier.key()
+        } else {
+            self.user.key()
+        }
+    }
+    
+    /// Overwrite `record` with the audit of a verified `proof`, credited to
+    /// `credited_verifier` under its registry label
+    pub fn write_audit(
+        &self,
+        record: &mut VerificationProof,
+        proof: &[u8],
+        public_inputs: &[u8],
+        attribute_type: u8,
+        timestamp: i64,
+    ) -> Result<()> {
+        let verifier = self.credited_verifier();
+        record.identity = self.identity.key();
+        record.proof_hash = crate::compression::compute_proof_hash(proof);
+        record.public_inputs_hash = crate::compression::compute_public_inputs_hash(public_inputs)?;
+        record.attribute_type = attribute_type;
+        record.timestamp = timestamp;
+        record.verifier = verifier;
+        record.verifier_label = self.registry.verifier_label(&verifier);
+        Ok(())
+    }
+}
+
+/// Reject a root older than the checkpoint's window
+/// 
+/// Once `initialize_root_checkpoint` ran, the checkpoint can't be skipped by
+/// omitting the account.
+pub fn check_root_freshness(
+    registry: &IdentityRegistry,
+    root_checkpoint: Option<&RootCheckpoint>,
+    merkle_root: &[u8; 32],
+    current_slot: u64,
+) -> Result<()> {
+    let Some(checkpoint) = root_checkpoint else {
+        require!(
+            !registry.root_checkpointed,
+            crate::errors::ErrorCode::MissingRootCheckpoint
+        );
+        return Ok(());
+    };
+    checkpoint.require_fresh(merkle_root, current_slot)
+}
+
+/// Load `identity`'s `VerificationLog` passed as a remaining account, or `None`
+/// for the program id placeholder of an identity without one
+pub fn verification_log_of<'info>(
+    identity: &Account<'info, Identity>,
+    info: &'info AccountInfo<'info>,
+) -> Result<Option<Account<'info, VerificationLog>>> {
+    if info.key() == crate::ID {
+        require_verification_log(identity, None)?;
+        return Ok(None);
+    }
+    require!(info.is_writable, crate::errors::ErrorCode::UnauthorizedAccess);
+    let log = Account::<VerificationLog>::try_from(info)?;
+    let expected = Pubkey::create_program_address(
+        &[b"verification_log", identity.key().as_ref(), &[log.bump]],
+        &crate::ID,
+    )
+    .map_err(|_| error!(crate::errors::ErrorCode::UnauthorizedAccess))?;
+    require_keys_eq!(info.key(), expected, crate::errors::ErrorCode::UnauthorizedAccess);
+    Ok(Some(log))
+}
+
+/// Require the identity's verification log once `initialize_verification_log`
+/// ran, so entries can't be left out by omitting the account
+pub fn require_verification_log(identity: &Identity, verification_log: Option<&VerificationLog>) -> Result<()> {
+    require!(
+        verification_log.is_some() || !identity.verification_logged,
+        crate::errors::ErrorCode::MissingVerificationLog
+    );
+    Ok(())
+}
+
+/// Require the event log once `initialize_event_log` ran, so it can't be
+/// skipped by omitting the account
+pub fn require_event_log(registry: &IdentityRegistry, event_log: Option<&Account<GlobalEventLog>>) -> Result<()> {
+    require!(
+        event_log.is_some() || !registry.event_logged,
+        crate::errors::ErrorCode::MissingEventLog
+    );
+    Ok(())
+}
+
+/// Shared by every verification path; the owner binding is always the identity's
+/// owner, whoever submits the proof
+fn require_bound_inputs(
+    identity: &Identity,
+    registry: &IdentityRegistry,
+    input_layout: Option<&PublicInputLayout>,
+    attribute_type: u8,
+    public_inputs: &[u8],
+) -> Result<()> {
+    // Once configured, the layout can't be skipped by omitting the account
+    let Some(layout) = input_layout else {
+        require!(
+            registry.input_layouts & attribute_type == 0,
+            crate::errors::ErrorCode::InvalidPublicInputs
+        );
+        return identity.require_nonce_input(public_inputs);
+    };
+    require!(
+        layout.attribute_type == attribute_type,
+        crate::errors::ErrorCode::InvalidPublicInputs
+    );
+    
+    layout.require_attribute_tag(public_inputs)?;
+    layout.require_circuit_version(public_inputs)?;
+    layout.require_root_binding(public_inputs, identity)?;
+    require!(
+        layout.nonce(public_inputs)? == identity.nonce_field_element(),
+        crate::errors::ErrorCode::InvalidPublicInputs
+    );
+    if let Some(owner_binding) = layout.owner_binding(public_inputs)? {
+        require!(
+            owner_binding == crate::compression::pubkey_to_field(&identity.owner),
+            crate::errors::ErrorCode::InvalidPublicInputs
+        );
+    }
+    Ok(())
 }
 
 /// Update identity commitment
@@ -63,7 +828,7 @@ pub struct VerifyIdentity<'info> {
 pub struct UpdateIdentity<'info> {
     #[account(
         mut,
-        seeds = [b"identity", user.key().as_ref()],
+        seeds = [b"identity", identity.namespace.as_ref(), user.key().as_ref()],
         bump = identity.bump,
         constraint = identity.owner == user.key() @ crate::errors::ErrorCode::UnauthorizedAccess
     )]
@@ -78,7 +843,7 @@ pub struct UpdateIdentity<'info> {
 pub struct RevokeIdentity<'info> {
     #[account(
         mut,
-        seeds = [b"identity", user.key().as_ref()],
+        seeds = [b"identity", identity.namespace.as_ref(), user.key().as_ref()],
         bump = identity.bump,
         constraint = identity.owner == user.key() @ crate::errors::ErrorCode::UnauthorizedAccess
     )]
@@ -86,6 +851,38 @@ pub struct RevokeIdentity<'info> {
     
     #[account(mut)]
     pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"registry", identity.namespace.as_ref()],
+        bump = registry.bump
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    /// Verification history, required once initialized; the revocation is appended
+    #[account(
+        mut,
+        seeds = [b"verification_log", identity.key().as_ref()],
+        bump = verification_log.bump
+    )]
+    pub verification_log: Option<Account<'info, VerificationLog>>,
+    
+    /// Registry-wide audit stream, required once initialized; the revocation is appended
+    #[account(
+        mut,
+        seeds = [b"event_log", identity.namespace.as_ref()],
+        bump = event_log.bump
+    )]
+    pub event_log: Option<Box<Account<'info, GlobalEventLog>>>,
+    
+    /// The bound DID's record, required when the identity has one; closed to `user`
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"did", did_record.namespace.as_ref(), did_record.did_hash.as_ref()],
+        bump = did_record.bump,
+        constraint = did_record.identity == identity.key() @ crate::errors::ErrorCode::InvalidDid
+    )]
+    pub did_record: Option<Account<'info, DidRecord>>,
 }
 
 /// Create authentication session
@@ -96,18 +893,26 @@ pub struct CreateSession<'info> {
         init,
         payer = user,
         space = Session::LEN,
-        seeds = [b"session", user.key().as_ref(), &session_id],
+        seeds = [b"session", identity.namespace.as_ref(), user.key().as_ref(), &session_id],
         bump
     )]
     pub session: Account<'info, Session>,
     
     #[account(
-        seeds = [b"identity", user.key().as_ref()],
+        mut,
+        seeds = [b"identity", identity.namespace.as_ref(), user.key().as_ref()],
         bump = identity.bump,
         constraint = identity.owner == user.key() @ crate::errors::ErrorCode::UnauthorizedAccess
     )]
     pub identity: Account<'info, Identity>,
     
+    #[account(
+        seeds = [b"registry", identity.namespace.as_ref()],
+        bump = registry.bump,
+        constraint = !registry.is_paused(IdentityRegistry::PAUSE_CREATE_SESSION) @ crate::errors::ErrorCode::InstructionPaused
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
     #[account(mut)]
     pub user: Signer<'info>,
     
@@ -120,12 +925,1113 @@ pub struct CloseSession<'info> {
     #[account(
         mut,
         close = user,
-        seeds = [b"session", user.key().as_ref(), &session.session_id],
+        seeds = [b"session", session.namespace.as_ref(), user.key().as_ref(), &session.session_id],
+        bump = session.bump,
+        has_one = user @ crate::errors::ErrorCode::UnauthorizedAccess
+    )]
+    pub session: Account<'info, Session>,
+    
+    #[account(
+        mut,
+        seeds = [b"identity", session.namespace.as_ref(), user.key().as_ref()],
+        bump = identity.bump
+    )]
+    pub identity: Account<'info, Identity>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
+
+/// Initialize the verification history log for an identity
+#[derive(Accounts)]
+pub struct InitializeVerificationLog<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = VerificationLog::LEN,
+        seeds = [b"verification_log", identity.key().as_ref()],
+        bump
+    )]
+    pub verification_log: Account<'info, VerificationLog>,
+    
+    #[account(
+        mut,
+        seeds = [b"identity", identity.namespace.as_ref(), user.key().as_ref()],
+        bump = identity.bump,
+        constraint = identity.owner == user.key() @ crate::errors::ErrorCode::UnauthorizedAccess
+    )]
+    pub identity: Account<'info, Identity>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Read the verification history log for an identity
+#[derive(Accounts)]
+pub struct ReadVerificationLog<'info> {
+    #[account(
+        seeds = [b"verification_log", identity.key().as_ref()],
+        bump = verification_log.bump
+    )]
+    pub verification_log: Account<'info, VerificationLog>,
+    
+    pub identity: Account<'info, Identity>,
+}
+
+/// Reserve a commitment ahead of registration
+#[derive(Accounts)]
+#[instruction(identity_commitment: [u8; 32])]
+pub struct ReserveCommitment<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = CommitmentReservation::LEN,
+        seeds = [b"reservation", registry.namespace.as_ref(), identity_commitment.as_ref()],
+        bump
+    )]
+    pub reservation: Account<'info, CommitmentReservation>,
+    
+    #[account(
+        seeds = [b"registry", registry.namespace.as_ref()],
+        bump = registry.bump
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Release an expired reservation, refunding rent to the original reserver
+#[derive(Accounts)]
+pub struct ReleaseReservation<'info> {
+    #[account(
+        mut,
+        close = reserver,
+        seeds = [b"reservation", reservation.namespace.as_ref(), reservation.identity_commitment.as_ref()],
+        bump = reservation.bump,
+        has_one = reserver @ crate::errors::ErrorCode::UnauthorizedAccess
+    )]
+    pub reservation: Account<'info, CommitmentReservation>,
+    
+    /// CHECK: Rent recipient, validated against the reservation via has_one
+    #[account(mut)]
+    pub reserver: UncheckedAccount<'info>,
+}
+
+/// Read-only access to a session
+#[derive(Accounts)]
+pub struct ReadSession<'info> {
+    #[account(
+        seeds = [b"session", session.namespace.as_ref(), session.user.as_ref(), &session.session_id],
+        bump = session.bump
+    )]
+    pub session: Account<'info, Session>,
+}
+
+/// Check a session against the current state of its identity
+#[derive(Accounts)]
+pub struct ValidateSession<'info> {
+    #[account(
+        seeds = [b"session", session.namespace.as_ref(), session.user.as_ref(), &session.session_id],
+        bump = session.bump
+    )]
+    pub session: Account<'info, Session>,
+    
+    #[account(
+        seeds = [b"identity", session.namespace.as_ref(), session.user.as_ref()],
+        bump = identity.bump
+    )]
+    pub identity: Account<'info, Identity>,
+    
+    #[account(
+        seeds = [b"registry", session.namespace.as_ref()],
+        bump = registry.bump
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+}
+
+/// Refresh a session's last activity
+#[derive(Accounts)]
+pub struct HeartbeatSession<'info> {
+    #[account(
+        mut,
+        seeds = [b"session", session.namespace.as_ref(), user.key().as_ref(), &session.session_id],
         bump = session.bump,
         has_one = user @ crate::errors::ErrorCode::UnauthorizedAccess
     )]
     pub session: Account<'info, Session>,
     
+    pub user: Signer<'info>,
+}
+
+/// Stateless checks that need no accounts
+#[derive(Accounts)]
+pub struct Stateless {}
+
+/// Initialize the rolling merkle root checkpoint
+#[derive(Accounts)]
+pub struct InitializeRootCheckpoint<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = RootCheckpoint::LEN,
+        seeds = [b"root_checkpoint", registry.namespace.as_ref()],
+        bump
+    )]
+    pub root_checkpoint: Account<'info, RootCheckpoint>,
+    
+    #[account(
+        mut,
+        seeds = [b"registry", registry.namespace.as_ref()],
+        bump = registry.bump,
+        constraint = registry.authorizes(&authority.key(), get_stack_height()) @ crate::errors::ErrorCode::InvalidAuthority
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Commit a new merkle root to the checkpoint (registry authority only)
+#[derive(Accounts)]
+pub struct UpdateRootCheckpoint<'info> {
+    #[account(
+        mut,
+        seeds = [b"root_checkpoint", registry.namespace.as_ref()],
+        bump = root_checkpoint.bump
+    )]
+    pub root_checkpoint: Account<'info, RootCheckpoint>,
+    
+    #[account(
+        seeds = [b"registry", registry.namespace.as_ref()],
+        bump = registry.bump,
+        constraint = registry.authorizes(&authority.key(), get_stack_height()) @ crate::errors::ErrorCode::InvalidAuthority
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    pub authority: Signer<'info>,
+}
+
+/// Create several sessions at once; session PDAs are passed as remaining accounts
+#[derive(Accounts)]
+pub struct CreateSessionsBatch<'info> {
+    #[account(
+        mut,
+        seeds = [b"identity", identity.namespace.as_ref(), user.key().as_ref()],
+        bump = identity.bump,
+        constraint = identity.owner == user.key() @ crate::errors::ErrorCode::UnauthorizedAccess
+    )]
+    pub identity: Account<'info, Identity>,
+    
+    #[account(
+        seeds = [b"registry", identity.namespace.as_ref()],
+        bump = registry.bump,
+        constraint = !registry.is_paused(IdentityRegistry::PAUSE_CREATE_SESSION) @ crate::errors::ErrorCode::InstructionPaused
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
     #[account(mut)]
     pub user: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Read-only access to an identity, e.g. for CPI precondition checks
+#[derive(Accounts)]
+pub struct ReadIdentity<'info> {
+    #[account(
+        seeds = [b"identity", identity.namespace.as_ref(), identity.owner.as_ref()],
+        bump = identity.bump
+    )]
+    pub identity: Account<'info, Identity>,
+}
+
+/// Export an identity, vouched for by its registry's authority
+#[derive(Accounts)]
+pub struct ExportIdentity<'info> {
+    #[account(
+        seeds = [b"identity", identity.namespace.as_ref(), identity.owner.as_ref()],
+        bump = identity.bump
+    )]
+    pub identity: Account<'info, Identity>,
+    
+    #[account(
+        seeds = [b"registry", identity.namespace.as_ref()],
+        bump = registry.bump
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+}
+
+/// List verification status for several identities passed as remaining accounts
+#[derive(Accounts)]
+pub struct BatchIdentityStatus {}
+
+/// Freeze or unfreeze an identity (owner or registry authority; a freeze the
+/// authority placed only it can lift)
+#[derive(Accounts)]
+pub struct FreezeIdentity<'info> {
+    #[account(
+        mut,
+        seeds = [b"identity", identity.namespace.as_ref(), identity.owner.as_ref()],
+        bump = identity.bump,
+        constraint = identity.owner == signer.key()
+            || registry.authorizes(&signer.key(), get_stack_height()) @ crate::errors::ErrorCode::UnauthorizedAccess
+    )]
+    pub identity: Account<'info, Identity>,
+    
+    #[account(
+        seeds = [b"registry", identity.namespace.as_ref()],
+        bump = registry.bump
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    pub signer: Signer<'info>,
+}
+
+/// Recount an identity's sessions, passed as remaining accounts (owner or
+/// registry authority)
+#[derive(Accounts)]
+pub struct ReconcileSessions<'info> {
+    #[account(
+        mut,
+        seeds = [b"identity", identity.namespace.as_ref(), identity.owner.as_ref()],
+        bump = identity.bump,
+        constraint = identity.owner == signer.key()
+            || registry.authorizes(&signer.key(), get_stack_height()) @ crate::errors::ErrorCode::UnauthorizedAccess
+    )]
+    pub identity: Account<'info, Identity>,
+    
+    #[account(
+        seeds = [b"registry", identity.namespace.as_ref()],
+        bump = registry.bump
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    pub signer: Signer<'info>,
+}
+
+/// Move a session to a fresh id, closing the old one
+#[derive(Accounts)]
+#[instruction(new_session_id: [u8; 32])]
+pub struct RotateSessionId<'info> {
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"session", session.namespace.as_ref(), user.key().as_ref(), &session.session_id],
+        bump = session.bump,
+        has_one = user @ crate::errors::ErrorCode::UnauthorizedAccess
+    )]
+    pub session: Account<'info, Session>,
+    
+    #[account(
+        init,
+        payer = user,
+        space = Session::LEN,
+        seeds = [b"session", session.namespace.as_ref(), user.key().as_ref(), &new_session_id],
+        bump
+    )]
+    pub new_session: Account<'info, Session>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Update registry-wide configuration
+#[derive(Accounts)]
+pub struct UpdateRegistry<'info> {
+    #[account(
+        mut,
+        seeds = [b"registry", registry.namespace.as_ref()],
+        bump = registry.bump,
+        constraint = registry.authorizes(&authority.key(), get_stack_height()) @ crate::errors::ErrorCode::InvalidAuthority
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    pub authority: Signer<'info>,
+}
+
+/// Create a session on the owner's behalf as their approved delegate
+#[derive(Accounts)]
+#[instruction(session_id: [u8; 32])]
+pub struct CreateSessionDelegated<'info> {
+    #[account(
+        init,
+        payer = delegate,
+        space = Session::LEN,
+        seeds = [b"session", identity.namespace.as_ref(), identity.owner.as_ref(), &session_id],
+        bump
+    )]
+    pub session: Account<'info, Session>,
+    
+    #[account(
+        mut,
+        seeds = [b"identity", identity.namespace.as_ref(), identity.owner.as_ref()],
+        bump = identity.bump
+    )]
+    pub identity: Account<'info, Identity>,
+    
+    #[account(
+        seeds = [b"registry", identity.namespace.as_ref()],
+        bump = registry.bump,
+        constraint = !registry.is_paused(IdentityRegistry::PAUSE_CREATE_SESSION) @ crate::errors::ErrorCode::InstructionPaused
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    #[account(mut)]
+    pub delegate: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Register a uniqueness nullifier in the registry's inline ring
+#[derive(Accounts)]
+pub struct RegisterNullifier<'info> {
+    #[account(
+        mut,
+        seeds = [b"registry", identity.namespace.as_ref()],
+        bump = registry.bump
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    #[account(
+        seeds = [b"identity", identity.namespace.as_ref(), user.key().as_ref()],
+        bump = identity.bump,
+        constraint = identity.owner == user.key() @ crate::errors::ErrorCode::UnauthorizedAccess
+    )]
+    pub identity: Account<'info, Identity>,
+    
+    /// CHECK: The nullifier's `NullifierRecord` address, checked in `claim_nullifier`;
+    /// it must not exist
+    pub nullifier_record: UncheckedAccount<'info>,
+    
+    /// CHECK: `NullifierRecord` address of the ring's oldest nullifier, required
+    /// once the ring is full; checked and created in `claim_nullifier`
+    #[account(mut)]
+    pub evicted_record: Option<UncheckedAccount<'info>>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Verify an attribute and record it in a `VerificationProof` audit account
+#[derive(Accounts)]
+#[instruction(proof: Vec<u8>, public_inputs: Vec<u8>, attribute_type: u8)]
+pub struct VerifyIdentityWithAudit<'info> {
+    pub verify: VerifyIdentity<'info>,
+    
+    /// Latest audited verification of the attribute, overwritten on re-verification
+    #[account(
+        init_if_needed,
+        payer = verify.user,
+        space = VerificationProof::LEN,
+        seeds = [b"proof", verify.identity.key().as_ref(), &[attribute_type]],
+        bump
+    )]
+    pub verification_proof: Account<'info, VerificationProof>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Check whether a nullifier has been registered
+#[derive(Accounts)]
+#[instruction(nullifier: [u8; 32])]
+pub struct CheckNullifier<'info> {
+    #[account(
+        seeds = [b"registry", registry.namespace.as_ref()],
+        bump = registry.bump
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    /// The nullifier's `NullifierRecord` address, passed whether or not it exists
+    /// CHECK: address constrained by seeds; only read when owned by this program
+    #[account(
+        seeds = [b"nullifier", registry.namespace.as_ref(), nullifier.as_ref()],
+        bump
+    )]
+    pub nullifier_record: UncheckedAccount<'info>,
+}
+
+/// Verify a submitted proof and spend its nullifier in the spent-nullifier tree
+#[derive(Accounts)]
+pub struct MarkSpent<'info> {
+    pub verify: VerifyIdentity<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"pending_verification", verify.identity.key().as_ref()],
+        bump = pending_verification.bump
+    )]
+    pub pending_verification: Account<'info, PendingVerification>,
+}
+
+/// Store a rotated-out verification key under (attribute_type, version)
+#[derive(Accounts)]
+#[instruction(attribute_type: u8, version: u16)]
+pub struct ArchiveVerificationKey<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = ArchivedVerificationKey::LEN,
+        seeds = [b"archived_vk", registry.namespace.as_ref(), &[attribute_type], &version.to_le_bytes()],
+        bump
+    )]
+    pub archived_vk: Account<'info, ArchivedVerificationKey>,
+    
+    #[account(
+        seeds = [b"registry", registry.namespace.as_ref()],
+        bump = registry.bump,
+        constraint = registry.authorizes(&authority.key(), get_stack_height()) @ crate::errors::ErrorCode::InvalidAuthority
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Verify a past proof against an archived verification key
+#[derive(Accounts)]
+#[instruction(attribute_type: u8, vk_version: u16)]
+pub struct VerifyProofHistorical<'info> {
+    #[account(
+        seeds = [b"archived_vk", archived_vk.namespace.as_ref(), &[attribute_type], &vk_version.to_le_bytes()],
+        bump = archived_vk.bump
+    )]
+    pub archived_vk: Account<'info, ArchivedVerificationKey>,
+}
+
+/// Verify a proof and report which key version verified it
+/// 
+/// Archived keys of the same attribute may follow as remaining accounts.
+#[derive(Accounts)]
+#[instruction(attribute_type: u8)]
+pub struct VerifyAndReportVk<'info> {
+    #[account(
+        seeds = [b"attribute", definition.namespace.as_ref(), &[attribute_type]],
+        bump = definition.bump
+    )]
+    pub definition: Account<'info, AttributeDefinition>,
+}
+
+/// Extend an identity's membership by the registry TTL
+#[derive(Accounts)]
+pub struct RenewIdentity<'info> {
+    #[account(
+        mut,
+        seeds = [b"identity", identity.namespace.as_ref(), user.key().as_ref()],
+        bump = identity.bump,
+        constraint = identity.owner == user.key() @ crate::errors::ErrorCode::UnauthorizedAccess
+    )]
+    pub identity: Account<'info, Identity>,
+    
+    #[account(
+        seeds = [b"registry", identity.namespace.as_ref()],
+        bump = registry.bump
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    pub user: Signer<'info>,
+}
+
+/// Upload a proof for later verification
+#[derive(Accounts)]
+pub struct SubmitVerification<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = PendingVerification::LEN,
+        seeds = [b"pending_verification", identity.key().as_ref()],
+        bump
+    )]
+    pub pending_verification: Account<'info, PendingVerification>,
+    
+    #[account(
+        seeds = [b"identity", identity.namespace.as_ref(), user.key().as_ref()],
+        bump = identity.bump,
+        constraint = identity.owner == user.key() @ crate::errors::ErrorCode::UnauthorizedAccess
+    )]
+    pub identity: Account<'info, Identity>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Verify a previously submitted proof and record the result
+#[derive(Accounts)]
+pub struct FinalizeVerification<'info> {
+    pub verify: VerifyIdentity<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"pending_verification", verify.identity.key().as_ref()],
+        bump = pending_verification.bump
+    )]
+    pub pending_verification: Account<'info, PendingVerification>,
+}
+
+/// Discard a submitted proof and reclaim its rent
+#[derive(Accounts)]
+pub struct CancelVerification<'info> {
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"pending_verification", identity.key().as_ref()],
+        bump = pending_verification.bump
+    )]
+    pub pending_verification: Account<'info, PendingVerification>,
+    
+    #[account(
+        seeds = [b"identity", identity.namespace.as_ref(), user.key().as_ref()],
+        bump = identity.bump,
+        constraint = identity.owner == user.key() @ crate::errors::ErrorCode::UnauthorizedAccess
+    )]
+    pub identity: Account<'info, Identity>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
+
+/// Emergency extension of sessions passed as remaining accounts
+#[derive(Accounts)]
+pub struct ExtendSessionsAuthority<'info> {
+    #[account(
+        seeds = [b"registry", registry.namespace.as_ref()],
+        bump = registry.bump,
+        constraint = registry.authorizes(&authority.key(), get_stack_height()) @ crate::errors::ErrorCode::InvalidAuthority
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    pub authority: Signer<'info>,
+}
+
+/// Re-create an identity from an exported snapshot (registry authority only)
+#[derive(Accounts)]
+#[instruction(owner: Pubkey)]
+pub struct ImportIdentity<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = Identity::LEN,
+        seeds = [b"identity", registry.namespace.as_ref(), owner.as_ref()],
+        bump
+    )]
+    pub identity: Account<'info, Identity>,
+    
+    #[account(
+        mut,
+        seeds = [b"registry", registry.namespace.as_ref()],
+        bump = registry.bump,
+        constraint = registry.authorizes(&authority.key(), get_stack_height()) @ crate::errors::ErrorCode::InvalidAuthority
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    /// Authority of the registry the snapshot was exported from; may be `authority`
+    pub source_authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Verify an attribute and issue its attestation in the same instruction
+#[derive(Accounts)]
+#[instruction(proof: Vec<u8>, public_inputs: Vec<u8>, attribute_type: u8)]
+pub struct VerifyAndAttest<'info> {
+    pub verify: VerifyIdentity<'info>,
+    
+    #[account(
+        init_if_needed,
+        payer = verify.user,
+        space = Attestation::LEN,
+        seeds = [b"attestation", verify.identity.key().as_ref(), &[attribute_type]],
+        bump
+    )]
+    pub attestation: Account<'info, Attestation>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Read an attestation together with the identity it was issued for
+#[derive(Accounts)]
+pub struct ReadAttestation<'info> {
+    #[account(
+        seeds = [b"attestation", identity.key().as_ref(), &[attestation.attribute_type]],
+        bump = attestation.bump
+    )]
+    pub attestation: Account<'info, Attestation>,
+    
+    #[account(
+        seeds = [b"identity", identity.namespace.as_ref(), identity.owner.as_ref()],
+        bump = identity.bump
+    )]
+    pub identity: Account<'info, Identity>,
+}
+
+/// Verify an attribute and open a session scoped to it in the same instruction
+#[derive(Accounts)]
+#[instruction(proof: Vec<u8>, public_inputs: Vec<u8>, attribute_type: u8, session_id: [u8; 32])]
+pub struct VerifyAndOpenSession<'info> {
+    pub verify: VerifyIdentity<'info>,
+    
+    #[account(
+        init,
+        payer = verify.user,
+        space = Session::LEN,
+        seeds = [b"session", verify.identity.namespace.as_ref(), verify.user.key().as_ref(), &session_id],
+        bump,
+        constraint = !verify.registry.is_paused(IdentityRegistry::PAUSE_CREATE_SESSION) @ crate::errors::ErrorCode::InstructionPaused
+    )]
+    pub session: Account<'info, Session>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Look up the identity a DID resolves to
+#[derive(Accounts)]
+pub struct ResolveDid<'info> {
+    #[account(
+        seeds = [b"did", did_record.namespace.as_ref(), did_record.did_hash.as_ref()],
+        bump = did_record.bump
+    )]
+    pub did_record: Account<'info, DidRecord>,
+}
+
+/// Check an identity's commitment against the registry revocation root
+#[derive(Accounts)]
+pub struct CheckRevocation<'info> {
+    #[account(
+        seeds = [b"identity", identity.namespace.as_ref(), identity.owner.as_ref()],
+        bump = identity.bump
+    )]
+    pub identity: Account<'info, Identity>,
+    
+    #[account(
+        seeds = [b"registry", identity.namespace.as_ref()],
+        bump = registry.bump
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+}
+
+/// Read an identity alongside its registry's freshness settings
+#[derive(Accounts)]
+pub struct IdentityFreshness<'info> {
+    #[account(
+        seeds = [b"identity", identity.namespace.as_ref(), identity.owner.as_ref()],
+        bump = identity.bump
+    )]
+    pub identity: Account<'info, Identity>,
+    
+    #[account(
+        seeds = [b"registry", identity.namespace.as_ref()],
+        bump = registry.bump
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+}
+
+/// Configure where an attribute circuit's bound values sit in its public inputs
+#[derive(Accounts)]
+#[instruction(attribute_type: u8)]
+pub struct SetPublicInputLayout<'info> {
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = PublicInputLayout::LEN,
+        seeds = [b"input_layout", registry.namespace.as_ref(), &[attribute_type]],
+        bump
+    )]
+    pub input_layout: Account<'info, PublicInputLayout>,
+    
+    #[account(
+        mut,
+        seeds = [b"registry", registry.namespace.as_ref()],
+        bump = registry.bump,
+        constraint = registry.authorizes(&authority.key(), get_stack_height()) @ crate::errors::ErrorCode::InvalidAuthority
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Verify a proof for an identity through its verification cache
+#[derive(Accounts)]
+pub struct VerifyProofCached<'info> {
+    /// One cache per identity, written only with its owner's signature
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = VerificationCache::LEN,
+        seeds = [b"verification_cache", identity.key().as_ref()],
+        bump
+    )]
+    pub cache: Account<'info, VerificationCache>,
+    
+    #[account(
+        seeds = [b"identity", identity.namespace.as_ref(), identity.owner.as_ref()],
+        bump = identity.bump
+    )]
+    pub identity: Account<'info, Identity>,
+    
+    #[account(
+        mut,
+        seeds = [b"registry", identity.namespace.as_ref()],
+        bump = registry.bump,
+        constraint = !registry.is_paused(IdentityRegistry::PAUSE_VERIFY) @ crate::errors::ErrorCode::InstructionPaused
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    /// Root checkpoint, required once initialized; the identity's root must be fresh
+    #[account(
+        seeds = [b"root_checkpoint", registry.namespace.as_ref()],
+        bump = root_checkpoint.bump
+    )]
+    pub root_checkpoint: Option<Account<'info, RootCheckpoint>>,
+    
+    /// CHECK: The nullifier's `NullifierRecord` address, checked in `claim_nullifier`;
+    /// it must not exist when the pairing runs
+    pub nullifier_record: UncheckedAccount<'info>,
+    
+    /// CHECK: `NullifierRecord` address of the ring's oldest nullifier, required
+    /// once the ring is full; checked and created in `claim_nullifier`
+    #[account(mut)]
+    pub evicted_record: Option<UncheckedAccount<'info>>,
+    
+    #[account(
+        mut,
+        constraint = identity.owner == owner.key() @ crate::errors::ErrorCode::UnauthorizedAccess
+    )]
+    pub owner: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Create the registry's verification fee escrow
+#[derive(Accounts)]
+pub struct InitializeFeeEscrow<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = FeeEscrow::LEN,
+        seeds = [b"fee_escrow", registry.namespace.as_ref()],
+        bump
+    )]
+    pub fee_escrow: Account<'info, FeeEscrow>,
+    
+    #[account(
+        seeds = [b"registry", registry.namespace.as_ref()],
+        bump = registry.bump,
+        constraint = registry.authorizes(&authority.key(), get_stack_height()) @ crate::errors::ErrorCode::InvalidAuthority
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Create the registry-wide event log (registry authority only)
+#[derive(Accounts)]
+pub struct InitializeEventLog<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = GlobalEventLog::LEN,
+        seeds = [b"event_log", registry.namespace.as_ref()],
+        bump
+    )]
+    pub event_log: Box<Account<'info, GlobalEventLog>>,
+    
+    #[account(
+        mut,
+        seeds = [b"registry", registry.namespace.as_ref()],
+        bump = registry.bump,
+        constraint = registry.authorizes(&authority.key(), get_stack_height()) @ crate::errors::ErrorCode::InvalidAuthority
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Read a page of the registry-wide event log
+#[derive(Accounts)]
+pub struct ReadEventLog<'info> {
+    #[account(
+        seeds = [b"event_log", event_log.namespace.as_ref()],
+        bump = event_log.bump
+    )]
+    pub event_log: Box<Account<'info, GlobalEventLog>>,
+}
+
+/// Withdraw forfeited verification fees (registry authority only)
+#[derive(Accounts)]
+pub struct WithdrawForfeitedFees<'info> {
+    #[account(
+        mut,
+        seeds = [b"fee_escrow", registry.namespace.as_ref()],
+        bump = fee_escrow.bump
+    )]
+    pub fee_escrow: Account<'info, FeeEscrow>,
+    
+    #[account(
+        seeds = [b"registry", registry.namespace.as_ref()],
+        bump = registry.bump,
+        constraint = registry.authorizes(&authority.key(), get_stack_height()) @ crate::errors::ErrorCode::InvalidAuthority
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    pub authority: Signer<'info>,
+    
+    #[account(mut)]
+    pub recipient: SystemAccount<'info>,
+}
+
+/// Create or update an attribute's display metadata
+#[derive(Accounts)]
+#[instruction(attribute_type: u8)]
+pub struct DefineAttribute<'info> {
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = AttributeDefinition::LEN,
+        seeds = [b"attribute", registry.namespace.as_ref(), &[attribute_type]],
+        bump
+    )]
+    pub definition: Account<'info, AttributeDefinition>,
+    
+    #[account(
+        mut,
+        seeds = [b"registry", registry.namespace.as_ref()],
+        bump = registry.bump,
+        constraint = registry.authorizes(&authority.key(), get_stack_height()) @ crate::errors::ErrorCode::InvalidAuthority
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Read an identity's cached verification result
+#[derive(Accounts)]
+pub struct ReadVerificationCache<'info> {
+    #[account(
+        seeds = [b"verification_cache", cache.identity.as_ref()],
+        bump = cache.bump
+    )]
+    pub cache: Account<'info, VerificationCache>,
+}
+
+/// Pin the circuit version of an attribute's configured layout
+#[derive(Accounts)]
+pub struct SetCircuitVersion<'info> {
+    #[account(
+        mut,
+        seeds = [b"input_layout", registry.namespace.as_ref(), &[input_layout.attribute_type]],
+        bump = input_layout.bump
+    )]
+    pub input_layout: Account<'info, PublicInputLayout>,
+    
+    #[account(
+        seeds = [b"registry", registry.namespace.as_ref()],
+        bump = registry.bump,
+        constraint = registry.authorizes(&authority.key(), get_stack_height()) @ crate::errors::ErrorCode::InvalidAuthority
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    pub authority: Signer<'info>,
+}
+
+/// Configure the verification key of an auxiliary circuit
+#[derive(Accounts)]
+#[instruction(circuit_id: u8)]
+pub struct SetCircuitVerificationKey<'info> {
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = CircuitVerificationKey::LEN,
+        seeds = [b"circuit_vk", registry.namespace.as_ref(), &[circuit_id]],
+        bump
+    )]
+    pub circuit_vk: Account<'info, CircuitVerificationKey>,
+    
+    #[account(
+        seeds = [b"registry", registry.namespace.as_ref()],
+        bump = registry.bump,
+        constraint = registry.authorizes(&authority.key(), get_stack_height()) @ crate::errors::ErrorCode::InvalidAuthority
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Issue a one-time challenge for an identity to answer
+#[derive(Accounts)]
+#[instruction(challenge: [u8; 32])]
+pub struct IssueChallenge<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = Challenge::LEN,
+        seeds = [b"challenge", identity.key().as_ref(), challenge.as_ref()],
+        bump
+    )]
+    pub challenge_account: Account<'info, Challenge>,
+    
+    #[account(
+        seeds = [b"identity", identity.namespace.as_ref(), identity.owner.as_ref()],
+        bump = identity.bump
+    )]
+    pub identity: Account<'info, Identity>,
+    
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Close expired challenges passed as remaining accounts with their payers
+#[derive(Accounts)]
+pub struct ReclaimChallengesBatch {}
+
+/// Answer a challenge with a proof of ownership of the identity's compressed leaf
+/// 
+/// No owner signature: the proof itself is the authorization, so a relayer may submit it.
+#[derive(Accounts)]
+pub struct ProveOwnership<'info> {
+    #[account(
+        mut,
+        seeds = [b"challenge", identity.key().as_ref(), challenge_account.challenge.as_ref()],
+        bump = challenge_account.bump
+    )]
+    pub challenge_account: Account<'info, Challenge>,
+    
+    #[account(
+        seeds = [b"identity", identity.namespace.as_ref(), identity.owner.as_ref()],
+        bump = identity.bump
+    )]
+    pub identity: Account<'info, Identity>,
+    
+    #[account(
+        seeds = [b"circuit_vk", identity.namespace.as_ref(), &[CircuitVerificationKey::OWNERSHIP]],
+        bump = circuit_vk.bump
+    )]
+    pub circuit_vk: Account<'info, CircuitVerificationKey>,
+}
+
+/// Same accounts as `ProveOwnership`, read-only
+#[derive(Accounts)]
+pub struct SimulateOwnership<'info> {
+    #[account(
+        seeds = [b"challenge", identity.key().as_ref(), challenge_account.challenge.as_ref()],
+        bump = challenge_account.bump
+    )]
+    pub challenge_account: Account<'info, Challenge>,
+    
+    #[account(
+        seeds = [b"identity", identity.namespace.as_ref(), identity.owner.as_ref()],
+        bump = identity.bump
+    )]
+    pub identity: Account<'info, Identity>,
+    
+    #[account(
+        seeds = [b"circuit_vk", identity.namespace.as_ref(), &[CircuitVerificationKey::OWNERSHIP]],
+        bump = circuit_vk.bump
+    )]
+    pub circuit_vk: Account<'info, CircuitVerificationKey>,
+}
+
+/// Verify one aggregated proof for the identities passed as remaining accounts
+#[derive(Accounts)]
+pub struct VerifyBatchProof<'info> {
+    #[account(
+        seeds = [b"circuit_vk", registry.namespace.as_ref(), &[CircuitVerificationKey::BATCH]],
+        bump = circuit_vk.bump
+    )]
+    pub circuit_vk: Account<'info, CircuitVerificationKey>,
+    
+    #[account(
+        mut,
+        seeds = [b"registry", registry.namespace.as_ref()],
+        bump = registry.bump,
+        constraint = !registry.is_paused(IdentityRegistry::PAUSE_VERIFY) @ crate::errors::ErrorCode::InstructionPaused
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    
+    /// Root checkpoint, required once initialized; every identity's root must be fresh
+    #[account(
+        seeds = [b"root_checkpoint", registry.namespace.as_ref()],
+        bump = root_checkpoint.bump
+    )]
+    pub root_checkpoint: Option<Account<'info, RootCheckpoint>>,
+    
+    /// Registry-wide audit stream, required once initialized; each identity is appended
+    #[account(
+        mut,
+        seeds = [b"event_log", registry.namespace.as_ref()],
+        bump = event_log.bump
+    )]
+    pub event_log: Option<Box<Account<'info, GlobalEventLog>>>,
+    
+    /// CHECK: The current window's `RateWindow` address, required while a global
+    /// cap is set; the batch counts once per identity
+    #[account(mut)]
+    pub rate_window: Option<UncheckedAccount<'info>>,
+    
+    /// Pays for the records of nullifiers evicted from the inline ring
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+/// Read-only access to the registry settings
+#[derive(Accounts)]
+pub struct ReadRegistry<'info> {
+    #[account(
+        seeds = [b"registry", registry.namespace.as_ref()],
+        bump = registry.bump
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+}
+
+/// Check whether a session id is free for the identity's owner
+#[derive(Accounts)]
+#[instruction(session_id: [u8; 32])]
+pub struct SessionIdAvailability<'info> {
+    #[account(
+        seeds = [b"identity", identity.namespace.as_ref(), identity.owner.as_ref()],
+        bump = identity.bump
+    )]
+    pub identity: Account<'info, Identity>,
+    
+    /// CHECK: Session PDA for `session_id`; only inspected
+    #[account(
+        seeds = [b"session", identity.namespace.as_ref(), identity.owner.as_ref(), &session_id],
+        bump
+    )]
+    pub session: UncheckedAccount<'info>,
 }
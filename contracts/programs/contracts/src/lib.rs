@@ -7,34 +7,134 @@ pub mod instructions;
 pub mod errors;
 pub mod groth16_verifier;
 pub mod compression;
+pub mod merkle;
+pub mod return_data;
 pub mod verification_keys;
 
 use instructions::*;
 use errors::ErrorCode;
 use groth16_verifier::*;
 use compression::*;
+use state::*;
+use solana_program::compute_units::sol_remaining_compute_units;
+use solana_program::instruction::get_stack_height;
+
+/// `Clock::get` with a failure reported as `ClockUnavailable`
+/// 
+/// A missing clock sysvar (seen on some localnet/test setups) otherwise
+/// surfaces as a generic program error indistinguishable from logic failures.
+pub fn current_clock() -> Result<Clock> {
+    Clock::get().map_err(|_| error!(ErrorCode::ClockUnavailable))
+}
 
 #[program]
 pub mod contracts {
     use super::*;
 
-    /// Initialize the identity registry
-    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
+    /// Initialize the identity registry for a namespace
+    /// 
+    /// Each namespace is an independent deployment: its identities, sessions and
+    /// other PDAs are all seeded by it, so registries never see each other's state.
+    /// `verification_ttl` is as in `set_verification_ttl`.
+    pub fn initialize(ctx: Context<Initialize>, namespace: [u8; 16], verification_ttl: i64) -> Result<()> {
+        require!(verification_ttl >= 0, ErrorCode::InvalidConfig);
         let registry = &mut ctx.accounts.registry;
         registry.authority = ctx.accounts.authority.key();
         registry.total_identities = 0;
         registry.bump = ctx.bumps.registry;
+        registry.merkle_arity = 2;
+        registry.recent_nullifiers = [[0u8; 32]; IdentityRegistry::NULLIFIER_CAPACITY];
+        registry.nullifier_count = 0;
+        registry.identity_ttl = 0;
+        registry.namespace = namespace;
+        registry.attribute_dependencies = [0u8; 8];
+        registry.revocation_root = [0u8; 32];
+        registry.input_layouts = 0;
+        registry.trusted_verifiers = [TrustedVerifier::default(); IdentityRegistry::MAX_TRUSTED_VERIFIERS];
+        registry.verifier_count = 0;
+        registry.verification_ttl = verification_ttl;
+        registry.proof_systems = [PROOF_SYSTEM_GROTH16; 8];
+        registry.session_idle_timeout = 0;
+        registry.attribute_weights = [0u16; 8];
+        registry.min_verification_weight = 0;
+        registry.allowlist_root = [0u8; 32];
+        registry.tree_frontier = [[0u8; 32]; IdentityRegistry::TREE_DEPTH];
+        registry.tree_leaf_count = 0;
+        registry.tree_root = merkle::empty_subtree_hashes(IdentityRegistry::TREE_DEPTH)?
+            [IdentityRegistry::TREE_DEPTH];
+        registry.governance_program = Pubkey::default();
+        registry.governed = false;
+        registry.min_tree_depth = 0;
+        registry.verification_fee = 0;
+        registry.defined_attributes = 0;
+        registry.paused_instructions = 0;
+        registry.spent_root = merkle::empty_subtree_hashes(IdentityRegistry::SPENT_TREE_DEPTH)?
+            [IdentityRegistry::SPENT_TREE_DEPTH];
+        registry.global_verification_cap = 0;
+        registry.global_window_slots = 0;
+        registry.nullifier_head = 0;
+        registry.root_checkpointed = false;
+        registry.event_logged = false;
         
         msg!("Identity Registry initialized by: {:?}", ctx.accounts.authority.key());
         Ok(())
     }
 
     /// Register a new identity with compressed commitment using Light Protocol
+    /// 
+    /// With a `did`, the `did_record` PDA is created so `resolve_did` finds the
+    /// identity; a DID can only be bound once per namespace.
     pub fn register_identity(
         ctx: Context<RegisterIdentity>,
         identity_commitment: [u8; 32],
         merkle_root: [u8; 32],
+        root_canary: Option<RootCanary>,
+        allowlist_proof: Option<AllowlistProof>,
+        did: Option<[u8; 64]>,
     ) -> Result<()> {
+        validate_field_element(&identity_commitment)?;
+        validate_field_element(&merkle_root)?;
+        ctx.accounts
+            .registry
+            .require_allowlisted(&ctx.accounts.user.key(), allowlist_proof.as_ref())?;
+        ctx.accounts.registry.require_registration_depth(root_canary.as_ref())?;
+        
+        // Reject garbage roots when the client supplies a canary proof
+        if let Some(canary) = root_canary.as_ref() {
+            verify_root_canary(
+                &merkle_root,
+                ctx.accounts.registry.merkle_arity,
+                canary.tree_height,
+                &canary.siblings,
+            )?;
+        }
+        
+        // Honor any outstanding reservation on this commitment
+        let reservation_info = ctx.accounts.reservation.to_account_info();
+        if reservation_info.owner == ctx.program_id && !reservation_info.data_is_empty() {
+            let reservation = CommitmentReservation::try_deserialize(
+                &mut &reservation_info.try_borrow_data()?[..],
+            )?;
+            let user_key = ctx.accounts.user.key();
+            require!(
+                !reservation.blocks(&user_key, current_clock()?.slot),
+                ErrorCode::CommitmentReserved
+            );
+            
+            // Reservation is finalized by its owner's registration: close it and refund rent
+            if reservation.reserver == user_key {
+                let user_info = ctx.accounts.user.to_account_info();
+                user_info.add_lamports(reservation_info.lamports())?;
+                reservation_info.sub_lamports(reservation_info.lamports())?;
+                reservation_info.assign(&anchor_lang::system_program::ID);
+                reservation_info.resize(0)?;
+            }
+        }
+        
+        if let Some(did) = did.as_ref() {
+            ctx.accounts.bind_did(did, ctx.program_id)?;
+        }
+        
         let identity = &mut ctx.accounts.identity;
         let registry = &mut ctx.accounts.registry;
         
@@ -52,9 +152,33 @@ pub mod contracts {
         identity.verification_timestamp = 0;
         identity.attributes_verified = 0; // Bitmap for verified attributes
         identity.bump = ctx.bumps.identity;
+        identity.discoverable = false; // Private unless the owner opts in
+        identity.frozen = false;
+        identity.frozen_by_authority = false;
+        identity.verify_attempts = 0;
+        identity.session_delegate = Pubkey::default();
+        identity.verification_nonce = 0;
+        identity.expires_at = 0;
+        identity.namespace = registry.namespace;
+        identity.session_epoch = 0;
+        identity.attribute_expiry = [0; 8];
+        identity.attribute_timestamps = [0; 8];
+        identity.recent_roots = [[0u8; 32]; Identity::RECENT_ROOTS];
+        identity.guardian = Pubkey::default();
+        identity.active_sessions = 0;
+        identity.did = did.unwrap_or([0u8; 64]);
+        identity.update_delay_secs = 0;
+        identity.cancel_update();
+        identity.renew(current_clock()?.unix_timestamp, registry.identity_ttl);
         
         registry.total_identities += 1;
         
+        let identity_key = ctx.accounts.identity.key();
+        require_event_log(&ctx.accounts.registry, ctx.accounts.event_log.as_deref())?;
+        if let Some(log) = ctx.accounts.event_log.as_mut() {
+            log.append(GlobalEventLog::EVENT_REGISTERED, identity_key, current_clock()?.unix_timestamp);
+        }
+        
         msg!("Identity registered for user: {:?}", ctx.accounts.user.key());
         msg!("Compressed state hash: {:?}", compressed_state);
         
@@ -65,35 +189,233 @@ pub mod contracts {
         Ok(())
     }
 
+    /// Reserve a commitment so it can't be claimed while proofs are gathered
+    pub fn reserve_commitment(
+        ctx: Context<ReserveCommitment>,
+        identity_commitment: [u8; 32],
+    ) -> Result<()> {
+        let reservation = &mut ctx.accounts.reservation;
+        let slot = current_clock()?.slot;
+        
+        reservation.reserver = ctx.accounts.user.key();
+        reservation.identity_commitment = identity_commitment;
+        reservation.reserved_slot = slot;
+        reservation.expires_slot = slot + CommitmentReservation::DURATION_SLOTS;
+        reservation.bump = ctx.bumps.reservation;
+        reservation.namespace = ctx.accounts.registry.namespace;
+        
+        msg!("Commitment reserved until slot: {}", reservation.expires_slot);
+        Ok(())
+    }
+
+    /// Release an expired commitment reservation (callable by anyone)
+    pub fn release_reservation(ctx: Context<ReleaseReservation>) -> Result<()> {
+        let reservation = &ctx.accounts.reservation;
+        require!(
+            reservation.is_expired(current_clock()?.slot),
+            ErrorCode::ReservationNotExpired
+        );
+        
+        msg!("Commitment reservation released for: {:?}", reservation.reserver);
+        Ok(())
+    }
+
     /// Verify identity with ZK proof using Groth16
+    /// 
+    /// `nullifier` must be one of the proof's public inputs and is registered as
+    /// by `register_nullifier` once the proof verifies, so a reused nullifier
+    /// fails with `NullifierAlreadyUsed`. Every verification path claims one.
+    /// 
+    /// A proof the pairing rejects returns `false` instead of failing, so the
+    /// counted attempt and the forfeited `verification_fee` persist (see
+    /// `VerifyIdentity::verify_attribute`); the nullifier stays unclaimed. A proof
+    /// that never reaches the pairing (e.g. `MalformedProofComponent`) fails
+    /// with its own error and keeps the fee with the user.
     pub fn verify_identity(
         ctx: Context<VerifyIdentity>,
         proof: Vec<u8>,
         public_inputs: Vec<u8>,
         attribute_type: u8, // 1=age, 2=nationality, 4=uniqueness
+        nullifier: [u8; 32],
+    ) -> Result<bool> {
+        let clock = current_clock()?;
+        
+        // Shared checks, Groth16 verification and recording
+        let verified = ctx
+            .accounts
+            .verify_and_record_metered(&proof, &public_inputs, nullifier, attribute_type, &clock)?;
+        if !verified {
+            msg!("Verification attempt {} failed", ctx.accounts.identity.verify_attempts);
+            return Ok(false);
+        }
+        
+        msg!("Identity verified with attribute type: {}", attribute_type);
+        Ok(true)
+    }
+
+    /// Verify identity with an attribute expiry supplied by a trusted verifier
+    /// 
+    /// For oracle-driven verification, where the verifier co-signs and knows the
+    /// credential's real validity (e.g. a passport's expiry date). Past
+    /// `expires_at` the attribute fails `require_verified` until re-verified.
+    pub fn verify_identity_with_expiry(
+        ctx: Context<VerifyIdentity>,
+        proof: Vec<u8>,
+        public_inputs: Vec<u8>,
+        attribute_type: u8,
+        expires_at: i64,
+        nullifier: [u8; 32],
     ) -> Result<()> {
-        let identity = &mut ctx.accounts.identity;
-        let clock = Clock::get()?;
+        let clock = current_clock()?;
+        ctx.accounts.require_trusted_verifier()?;
+        require!(expires_at > clock.unix_timestamp, ErrorCode::AttributeExpired);
+        ctx.accounts
+            .verify_attribute(&proof, &public_inputs, &public_inputs, nullifier, attribute_type, &clock)?;
+        
+        ctx.accounts
+            .record_verification_until(attribute_type, clock.unix_timestamp, expires_at)?;
+        
+        msg!("Identity verified with attribute type {} until {}", attribute_type, expires_at);
+        Ok(())
+    }
+
+    /// Verify identity with a proof bound to a recent slot hash
+    /// 
+    /// The circuit's first public input must be `slot`'s hash from the SlotHashes
+    /// sysvar (see `compression::slot_hash_field_element`), which the prover can
+    /// only have seen within the last `MAX_SLOT_HASH_AGE` slots. Freshness without
+    /// issuing a `Challenge` account first.
+    pub fn verify_identity_slot_bound(
+        ctx: Context<VerifyIdentity>,
+        proof: Vec<u8>,
+        public_inputs: Vec<u8>,
+        attribute_type: u8,
+        slot: u64,
+        nullifier: [u8; 32],
+    ) -> Result<()> {
+        let clock = current_clock()?;
+        ctx.accounts.require_recent_slot_hash(&public_inputs, slot, clock.slot)?;
+        ctx.accounts
+            .verify_and_record(&proof, &public_inputs, &public_inputs, nullifier, attribute_type, &clock)?;
+        
+        msg!("Identity verified at slot {} with attribute type: {}", slot, attribute_type);
+        Ok(())
+    }
+
+    /// Verify identity with ZK proof AND Merkle inclusion of its commitment
+    /// 
+    /// Ties attribute verification to tree membership: the commitment must be
+    /// a leaf of the identity's compressed tree, otherwise nothing is recorded.
+    /// The inclusion path must span all `IdentityRegistry::TREE_DEPTH` levels.
+    pub fn verify_identity_compressed(
+        ctx: Context<VerifyIdentity>,
+        proof: Vec<u8>,
+        public_inputs: Vec<u8>,
+        attribute_type: u8,
+        proof_siblings: Vec<[u8; 32]>,
+        proof_positions: Vec<u8>,
+        nullifier: [u8; 32],
+    ) -> Result<()> {
+        let clock = current_clock()?;
+        let identity = &ctx.accounts.identity;
+        // A full-height path; an empty one would accept the commitment as its own root
+        require!(
+            proof_positions.len() == IdentityRegistry::TREE_DEPTH,
+            ErrorCode::MerkleTreeError
+        );
+        ctx.accounts.registry.require_tree_depth(proof_positions.len())?;
+        
+        // Cheap inclusion check first; no pairing work for non-members
+        verify_commitment_inclusion(
+            &identity.identity_commitment,
+            &identity.merkle_root,
+            ctx.accounts.registry.merkle_arity,
+            &proof_siblings,
+            &proof_positions,
+        )?;
         
-        // Verify proof length
-        require!(proof.len() == 256, ErrorCode::InvalidProof);
-        require!(public_inputs.len() > 0, ErrorCode::InvalidPublicInputs);
+        ctx.accounts
+            .verify_and_record(&proof, &public_inputs, &public_inputs, nullifier, attribute_type, &clock)?;
         
-        // Perform Groth16 verification
-        let is_valid = verify_groth16_proof(
+        msg!("Compressed identity verified with attribute type: {}", attribute_type);
+        Ok(())
+    }
+
+    /// Verify identity with a proof whose only public input is a hash of the full inputs
+    /// 
+    /// `full_public_inputs` must hash to `public_inputs_hash` (see
+    /// `verify_public_inputs_hash`), keeping the on-chain input count at 1
+    pub fn verify_identity_hashed(
+        ctx: Context<VerifyIdentity>,
+        proof: Vec<u8>,
+        public_inputs_hash: [u8; 32],
+        full_public_inputs: Vec<u8>,
+        attribute_type: u8,
+        nullifier: [u8; 32],
+    ) -> Result<()> {
+        let clock = current_clock()?;
+        verify_public_inputs_hash(&full_public_inputs, &public_inputs_hash)?;
+        ctx.accounts.verify_and_record(
             &proof,
-            &public_inputs,
+            &public_inputs_hash,
+            &full_public_inputs,
+            nullifier,
             attribute_type,
+            &clock,
         )?;
         
-        require!(is_valid, ErrorCode::InvalidProof);
+        msg!("Identity verified with hashed public inputs, attribute type: {}", attribute_type);
+        Ok(())
+    }
+
+    /// Verify identity with a proof over committed public inputs, revealing a subset
+    /// 
+    /// The proof's only public input is `committed_inputs_element(inputs_root)`.
+    /// Each revealed input is checked for inclusion in `inputs_root` before the
+    /// pairing; the rest stay hidden. Binding fields (nonce, owner, root, version)
+    /// must be among the revealed ones.
+    pub fn verify_identity_selective(
+        ctx: Context<VerifyIdentity>,
+        proof: Vec<u8>,
+        inputs_root: [u8; 32],
+        input_count: u8,
+        revealed: Vec<RevealedInput>,
+        attribute_type: u8,
+        nullifier: [u8; 32],
+    ) -> Result<()> {
+        let clock = current_clock()?;
+        let disclosed = reveal_public_inputs(&inputs_root, input_count, &revealed)?;
+        ctx.accounts.verify_and_record(
+            &proof,
+            &committed_inputs_element(&inputs_root),
+            &disclosed,
+            nullifier,
+            attribute_type,
+            &clock,
+        )?;
         
-        // Mark attribute as verified (bitmap)
-        identity.attributes_verified |= attribute_type;
-        identity.is_verified = true;
-        identity.verification_timestamp = clock.unix_timestamp;
+        msg!("Identity verified revealing {} of {} inputs, attribute type: {}", revealed.len(), input_count, attribute_type);
+        Ok(())
+    }
+
+    /// Re-prove an attribute the identity already holds, e.g. before it goes stale
+    /// 
+    /// Unlike `update_identity`, the commitment and the other attribute bits are
+    /// left untouched; only the verification timestamp (and nonce) advance. The
+    /// proof is checked as in `verify_identity`, starting with its 256-byte length.
+    pub fn refresh_attribute(
+        ctx: Context<VerifyIdentity>,
+        attribute_type: u8,
+        proof: Vec<u8>,
+        public_inputs: Vec<u8>,
+        nullifier: [u8; 32],
+    ) -> Result<()> {
+        let clock = current_clock()?;
+        ctx.accounts.identity.require_verified(attribute_type)?;
+        ctx.accounts
+            .verify_and_record(&proof, &public_inputs, &public_inputs, nullifier, attribute_type, &clock)?;
         
-        msg!("Identity verified with attribute type: {}", attribute_type);
+        msg!("Attribute {} refreshed", attribute_type);
         Ok(())
     }
 
@@ -103,54 +425,3314 @@ pub mod contracts {
         new_commitment: [u8; 32],
         new_merkle_root: [u8; 32],
     ) -> Result<()> {
+        validate_field_element(&new_commitment)?;
+        validate_field_element(&new_merkle_root)?;
         let identity = &mut ctx.accounts.identity;
+        identity.require_unlocked_updates()?;
         
         identity.identity_commitment = new_commitment;
         identity.merkle_root = new_merkle_root;
-        identity.is_verified = false; // Reset verification status
-        identity.attributes_verified = 0;
+        identity.clear_attributes(); // Reset verification status
+        
+        msg!("Identity updated for user: {:?}", ctx.accounts.user.key());
+        Ok(())
+    }
+
+    /// Advance only the tree root, e.g. after unrelated insertions
+    /// 
+    /// Unlike `update_identity` the commitment is unchanged, so verification is kept.
+    pub fn update_merkle_root(ctx: Context<UpdateIdentity>, new_root: [u8; 32]) -> Result<()> {
+        validate_field_element(&new_root)?;
+        let identity = &mut ctx.accounts.identity;
+        identity.require_not_frozen()?;
+        identity.require_unlocked_updates()?;
+        identity.advance_root(new_root);
+        
+        msg!("Merkle root updated for user: {:?}", ctx.accounts.user.key());
+        Ok(())
+    }
+
+    /// Time-lock commitment and root updates by `update_delay_secs` (can only grow)
+    pub fn set_update_delay(ctx: Context<UpdateIdentity>, update_delay_secs: i64) -> Result<()> {
+        ctx.accounts.identity.set_update_delay(update_delay_secs)?;
+        
+        msg!("Update delay set to: {} seconds", update_delay_secs);
+        Ok(())
+    }
+
+    /// Stage an update of a time-locked identity; see `apply_update`
+    pub fn propose_update(
+        ctx: Context<UpdateIdentity>,
+        new_commitment: [u8; 32],
+        new_merkle_root: [u8; 32],
+    ) -> Result<()> {
+        validate_field_element(&new_commitment)?;
+        validate_field_element(&new_merkle_root)?;
+        let identity = &mut ctx.accounts.identity;
+        identity.require_not_frozen()?;
+        identity.propose_update(new_commitment, new_merkle_root, current_clock()?.unix_timestamp);
+        
+        msg!("Identity update proposed, applicable at: {}", identity.pending_update_at);
+        Ok(())
+    }
+
+    /// Apply the pending update once its delay has elapsed
+    pub fn apply_update(ctx: Context<UpdateIdentity>) -> Result<()> {
+        let identity = &mut ctx.accounts.identity;
+        identity.require_not_frozen()?;
+        identity.apply_update(current_clock()?.unix_timestamp)?;
         
         msg!("Identity updated for user: {:?}", ctx.accounts.user.key());
         Ok(())
     }
 
+    /// Withdraw the pending update before it is applied
+    pub fn cancel_update(ctx: Context<UpdateIdentity>) -> Result<()> {
+        let identity = &mut ctx.accounts.identity;
+        require!(identity.pending_update_at != 0, ErrorCode::NoPendingUpdate);
+        identity.cancel_update();
+        
+        msg!("Pending identity update cancelled");
+        Ok(())
+    }
+
     /// Revoke identity verification
+    /// 
+    /// A bound DID's `DidRecord` is passed and closed, releasing the DID.
     pub fn revoke_identity(ctx: Context<RevokeIdentity>) -> Result<()> {
         let identity = &mut ctx.accounts.identity;
+        require!(
+            !identity.has_did() || ctx.accounts.did_record.is_some(),
+            ErrorCode::InvalidDid
+        );
         
-        identity.is_verified = false;
-        identity.attributes_verified = 0;
+        identity.revoke();
+        // The record closes with the instruction, freeing the DID to be bound again
+        identity.did = [0u8; 64];
+        
+        let now = current_clock()?.unix_timestamp;
+        require_verification_log(identity, ctx.accounts.verification_log.as_deref())?;
+        if let Some(log) = ctx.accounts.verification_log.as_mut() {
+            log.record_revocation(now, ctx.accounts.user.key());
+        }
+        let identity_key = ctx.accounts.identity.key();
+        require_event_log(&ctx.accounts.registry, ctx.accounts.event_log.as_deref())?;
+        if let Some(log) = ctx.accounts.event_log.as_mut() {
+            log.append(GlobalEventLog::EVENT_REVOKED, identity_key, now);
+        }
         
         msg!("Identity revoked for user: {:?}", ctx.accounts.user.key());
         Ok(())
     }
 
     /// Create authentication session
+    /// 
+    /// Once a revocation root is published, `revocation_proof` must show the
+    /// commitment is absent from it (see `IdentityRegistry::require_not_revoked`).
     pub fn create_session(
         ctx: Context<CreateSession>,
         session_id: [u8; 32],
         expiry: i64,
+        scopes: Vec<[u8; 16]>,
+        revocation_proof: Option<Vec<[u8; 32]>>,
     ) -> Result<()> {
         let session = &mut ctx.accounts.session;
-        let identity = &ctx.accounts.identity;
+        let identity = &mut ctx.accounts.identity;
         require!(identity.is_verified, ErrorCode::IdentityNotFound);
+        ctx.accounts
+            .registry
+            .require_not_revoked(&identity.identity_commitment, revocation_proof.as_deref())?;
+        ctx.accounts.registry.require_verification_weight(identity.attributes_verified)?;
+        identity.require_not_frozen()?;
+        identity.require_not_expired(current_clock()?.unix_timestamp)?;
+        Session::require_future_expiry(expiry, current_clock()?.unix_timestamp)?;
         session.user = ctx.accounts.user.key();
         session.session_id = session_id;
-        session.created_at = Clock::get()?.unix_timestamp;
+        session.created_at = current_clock()?.unix_timestamp;
         session.expires_at = expiry;
         session.is_active = true;
         session.bump = ctx.bumps.session;
+        session.set_scopes(&scopes)?;
+        session.disclose_verified(&ctx.accounts.registry, identity, current_clock()?.unix_timestamp)?;
+        session.delegated = false;
+        session.namespace = identity.namespace;
+        session.identity_epoch = identity.session_epoch;
+        session.last_active = session.created_at;
+        identity.open_sessions(1)?;
         
         msg!("Session created for user: {:?}", ctx.accounts.user.key());
         Ok(())
     }
 
+    /// Initialize the append-only verification log for an identity
+    /// 
+    /// From then on every verification of the identity must pass the log.
+    pub fn initialize_verification_log(ctx: Context<InitializeVerificationLog>) -> Result<()> {
+        let log = &mut ctx.accounts.verification_log;
+        log.identity = ctx.accounts.identity.key();
+        log.head = 0;
+        log.count = 0;
+        log.entries = [VerificationLogEntry::default(); VerificationLog::CAPACITY];
+        log.bump = ctx.bumps.verification_log;
+        log.revocations = 0;
+        log.last_revoked_at = 0;
+        ctx.accounts.identity.verification_logged = true;
+        
+        msg!("Verification log initialized for identity: {:?}", log.identity);
+        Ok(())
+    }
+
+    /// Read verification history (oldest first) via return data
+    pub fn read_verification_log(
+        ctx: Context<ReadVerificationLog>,
+    ) -> Result<Vec<VerificationLogEntry>> {
+        Ok(ctx.accounts.verification_log.entries())
+    }
+
+    /// Create the registry-wide event log for compliance export
+    pub fn initialize_event_log(ctx: Context<InitializeEventLog>) -> Result<()> {
+        let log = &mut ctx.accounts.event_log;
+        log.namespace = ctx.accounts.registry.namespace;
+        log.head = 0;
+        log.count = 0;
+        log.sequence = 0;
+        // `init` leaves the entries zeroed; no need to build the array on the stack
+        log.bump = ctx.bumps.event_log;
+        ctx.accounts.registry.event_logged = true;
+        
+        msg!("Event log initialized for namespace: {:?}", log.namespace);
+        Ok(())
+    }
+
+    /// Page of the registry-wide event log from sequence number `from`, via return data
+    /// 
+    /// Follow `next_sequence` until it stops advancing to export every record.
+    pub fn read_event_log(ctx: Context<ReadEventLog>, from: u64) -> Result<EventLogPage> {
+        Ok(ctx.accounts.event_log.page(from))
+    }
+
+    /// Time-weighted reputation score derived from the verification history,
+    /// via return data (see `VerificationLog::reputation_score`)
+    pub fn get_reputation(ctx: Context<ReadVerificationLog>) -> Result<u64> {
+        Ok(ctx.accounts.verification_log.reputation_score(current_clock()?.unix_timestamp))
+    }
+
     /// Close authentication session
     pub fn close_session(ctx: Context<CloseSession>) -> Result<()> {
         let session = &mut ctx.accounts.session;
+        ctx.accounts.identity.close_session(session)?;
         session.is_active = false;
         
         msg!("Session closed for user: {:?}", ctx.accounts.user.key());
         Ok(())
     }
+
+    /// Check whether a session was granted a named scope
+    pub fn session_has_scope(ctx: Context<ReadSession>, tag: [u8; 16]) -> Result<bool> {
+        Ok(ctx.accounts.session.has_scope(&tag))
+    }
+
+    /// Assert two participants are distinct persons via their uniqueness nullifiers
+    /// 
+    /// Only meaningful when both nullifiers were generated for the same app
+    /// namespace. Errors with `SamePerson` if they match, so CPI callers can
+    /// treat success as proof of distinctness.
+    pub fn assert_distinct_persons(
+        _ctx: Context<Stateless>,
+        nullifier_a: [u8; 32],
+        nullifier_b: [u8; 32],
+    ) -> Result<bool> {
+        let distinct = nullifiers_distinct(&nullifier_a, &nullifier_b);
+        require!(distinct, ErrorCode::SamePerson);
+        Ok(distinct)
+    }
+
+    /// Initialize the merkle root checkpoint (registry authority only)
+    /// 
+    /// From then on every verification must pass the checkpoint.
+    pub fn initialize_root_checkpoint(
+        ctx: Context<InitializeRootCheckpoint>,
+        max_root_age_slots: u64,
+    ) -> Result<()> {
+        let checkpoint = &mut ctx.accounts.root_checkpoint;
+        checkpoint.authority = ctx.accounts.authority.key();
+        checkpoint.max_root_age_slots = max_root_age_slots;
+        checkpoint.head = 0;
+        checkpoint.count = 0;
+        checkpoint.roots = [[0u8; 32]; RootCheckpoint::CAPACITY];
+        checkpoint.slots = [0u64; RootCheckpoint::CAPACITY];
+        checkpoint.bump = ctx.bumps.root_checkpoint;
+        checkpoint.namespace = ctx.accounts.registry.namespace;
+        ctx.accounts.registry.root_checkpointed = true;
+        
+        msg!("Root checkpoint initialized, max age: {} slots", max_root_age_slots);
+        Ok(())
+    }
+
+    /// Commit a merkle root to the rolling checkpoint at the current slot
+    pub fn update_root_checkpoint(
+        ctx: Context<UpdateRootCheckpoint>,
+        merkle_root: [u8; 32],
+    ) -> Result<()> {
+        let slot = current_clock()?.slot;
+        ctx.accounts.root_checkpoint.push(merkle_root, slot);
+        
+        msg!("Merkle root checkpointed at slot: {}", slot);
+        Ok(())
+    }
+
+    /// Create multiple sessions for single sign-on across dApps
+    /// 
+    /// `remaining_accounts[i]` must be the uninitialized session PDA for
+    /// `requests[i]`, and every request must name at least one scope. Any
+    /// failure (e.g. an id already in use) reverts the batch.
+    pub fn create_sessions_batch<'info>(
+        ctx: Context<'info, CreateSessionsBatch<'info>>,
+        requests: Vec<SessionRequest>,
+        revocation_proof: Option<Vec<[u8; 32]>>,
+    ) -> Result<()> {
+        require!(ctx.accounts.identity.is_verified, ErrorCode::IdentityNotFound);
+        ctx.accounts.registry.require_not_revoked(
+            &ctx.accounts.identity.identity_commitment,
+            revocation_proof.as_deref(),
+        )?;
+        ctx.accounts
+            .registry
+            .require_verification_weight(ctx.accounts.identity.attributes_verified)?;
+        ctx.accounts.identity.require_not_frozen()?;
+        ctx.accounts.identity.require_not_expired(current_clock()?.unix_timestamp)?;
+        SessionRequest::validate_batch(&requests)?;
+        require!(
+            ctx.remaining_accounts.len() == requests.len(),
+            ErrorCode::InvalidSession
+        );
+        
+        let user = ctx.accounts.user.key();
+        let namespace = ctx.accounts.identity.namespace;
+        let now = current_clock()?.unix_timestamp;
+        
+        for (request, session_info) in requests.iter().zip(ctx.remaining_accounts.iter()) {
+            Session::require_future_expiry(request.expiry, now)?;
+            let (expected, bump) =
+                Session::address(&namespace, &user, &request.session_id, ctx.program_id);
+            require_keys_eq!(session_info.key(), expected, ErrorCode::InvalidSession);
+            // An existing session under this id fails the whole batch
+            require!(
+                session_info.owner == &anchor_lang::system_program::ID
+                    && session_info.data_is_empty(),
+                ErrorCode::InvalidSession
+            );
+            
+            let mut session = Session {
+                user,
+                session_id: request.session_id,
+                created_at: now,
+                expires_at: request.expiry,
+                is_active: true,
+                bump,
+                scopes: [0u8; 128],
+                disclosed_attributes: ctx.accounts.identity.attributes_verified,
+                delegated: false,
+                namespace,
+                identity_epoch: ctx.accounts.identity.session_epoch,
+                last_active: now,
+                disclosed_timestamps: [0; 8],
+            };
+            session.set_scopes(&request.scopes)?;
+            session.disclose_verified(&ctx.accounts.registry, &ctx.accounts.identity, now)?;
+            
+            create_pda(
+                &ctx.accounts.user,
+                session_info,
+                &ctx.accounts.system_program,
+                Session::LEN,
+                &[b"session", namespace.as_ref(), user.as_ref(), &request.session_id, &[bump]],
+            )?;
+            session.try_serialize(&mut &mut session_info.try_borrow_mut_data()?[..])?;
+        }
+        ctx.accounts.identity.open_sessions(requests.len() as u16)?;
+        
+        msg!("Created {} sessions for user: {:?}", requests.len(), user);
+        Ok(())
+    }
+
+    /// Precondition for external programs: succeeds only if the identity is
+    /// unexpired and holds every bit of `attribute_type`
+    /// 
+    /// Designed to be CPI-called before gated logic; a caller whose CPI returns
+    /// successfully knows the user is verified (return data is `true`).
+    pub fn require_verified(ctx: Context<ReadIdentity>, attribute_type: u8) -> Result<bool> {
+        let now = current_clock()?.unix_timestamp;
+        ctx.accounts.identity.require_not_expired(now)?;
+        ctx.accounts.identity.require_verified_at(attribute_type, now)?;
+        Ok(true)
+    }
+
+    /// When a single attribute bit was last verified, via return data (0 if never)
+    /// 
+    /// Lets relying parties apply their own freshness policy, e.g. an age proof
+    /// under 90 days old.
+    pub fn get_attribute_timestamp(ctx: Context<ReadIdentity>, attribute_type: u8) -> Result<i64> {
+        ctx.accounts.identity.attribute_timestamp(attribute_type)
+    }
+
+    /// Opt in or out of public directory listings
+    pub fn set_discoverable(ctx: Context<UpdateIdentity>, discoverable: bool) -> Result<()> {
+        ctx.accounts.identity.discoverable = discoverable;
+        
+        msg!("Identity discoverable: {}", discoverable);
+        Ok(())
+    }
+
+    /// Directory listing of identities passed as remaining accounts
+    /// 
+    /// Identities that haven't opted in via `set_discoverable` are skipped. More
+    /// than `IdentityStatus::MAX_BATCH` accounts fail with `InvalidBatchSize`.
+    pub fn batch_identity_status<'info>(
+        ctx: Context<'info, BatchIdentityStatus>,
+    ) -> Result<Vec<IdentityStatus>> {
+        require!(
+            ctx.remaining_accounts.len() <= IdentityStatus::MAX_BATCH,
+            ErrorCode::InvalidBatchSize
+        );
+        
+        let mut statuses = Vec::with_capacity(ctx.remaining_accounts.len());
+        for info in ctx.remaining_accounts.iter() {
+            let identity = Account::<Identity>::try_from(info)?;
+            if let Some(status) = identity.directory_entry() {
+                statuses.push(status);
+            }
+        }
+        Ok(statuses)
+    }
+
+    /// Temporarily suspend an identity without touching its verified attributes
+    pub fn freeze_identity(ctx: Context<FreezeIdentity>) -> Result<()> {
+        let by_authority = ctx.accounts.registry.authorizes(&ctx.accounts.signer.key(), get_stack_height());
+        ctx.accounts.identity.freeze(by_authority);
+        
+        msg!("Identity frozen: {:?}", ctx.accounts.identity.owner);
+        Ok(())
+    }
+
+    /// Lift a freeze, restoring the identity's previous verification state
+    /// 
+    /// The owner can only lift its own freeze, not a hold the authority placed.
+    pub fn unfreeze_identity(ctx: Context<FreezeIdentity>) -> Result<()> {
+        let by_authority = ctx.accounts.registry.authorizes(&ctx.accounts.signer.key(), get_stack_height());
+        ctx.accounts.identity.unfreeze(by_authority)?;
+        
+        msg!("Identity unfrozen: {:?}", ctx.accounts.identity.owner);
+        Ok(())
+    }
+
+    /// Rotate a leaked session id, keeping the session's claims and expiry
+    pub fn rotate_session_id(ctx: Context<RotateSessionId>, new_session_id: [u8; 32]) -> Result<()> {
+        let rotated = ctx.accounts.session.rotated(new_session_id, ctx.bumps.new_session);
+        ctx.accounts.new_session.set_inner(rotated);
+        
+        msg!("Session rotated for user: {:?}", ctx.accounts.user.key());
+        Ok(())
+    }
+
+    /// Set the fan-out of identity merkle trees (2 or 4)
+    pub fn set_merkle_arity(ctx: Context<UpdateRegistry>, merkle_arity: u8) -> Result<()> {
+        validate_merkle_arity(merkle_arity)?;
+        ctx.accounts.registry.merkle_arity = merkle_arity;
+        
+        msg!("Merkle arity set to: {}", merkle_arity);
+        Ok(())
+    }
+
+    /// Evaluate a relying-party policy over the identity's verified attributes
+    /// 
+    /// Returns the result via return data so calling programs can compose on it.
+    pub fn evaluate_policy(ctx: Context<ReadIdentity>, policy: Vec<PolicyNode>) -> Result<bool> {
+        let identity = &ctx.accounts.identity;
+        let attributes = if identity.is_verified { identity.attributes_verified } else { 0 };
+        
+        PolicyNode::evaluate(&policy, attributes)
+    }
+
+    /// Approve a dApp to create sessions on the owner's behalf (default key revokes)
+    pub fn set_session_delegate(ctx: Context<UpdateIdentity>, delegate: Pubkey) -> Result<()> {
+        ctx.accounts.identity.session_delegate = delegate;
+        
+        msg!("Session delegate set: {:?}", delegate);
+        Ok(())
+    }
+
+    /// Record a guardian who may verify attributes for the owner (default key removes)
+    pub fn set_guardian(ctx: Context<UpdateIdentity>, guardian: Pubkey) -> Result<()> {
+        ctx.accounts.identity.guardian = guardian;
+        
+        msg!("Guardian set: {:?}", guardian);
+        Ok(())
+    }
+
+    /// Verify an attribute of a ward's identity, submitted by its guardian
+    /// 
+    /// Takes the same accounts and checks as `verify_identity`, with the guardian
+    /// as `user`: fees, metering, pauses and logs all apply. Any owner binding
+    /// and the nonce belong to the ward, so the guardian can't reuse its own proofs.
+    pub fn verify_identity_as_guardian(
+        ctx: Context<VerifyIdentity>,
+        proof: Vec<u8>,
+        public_inputs: Vec<u8>,
+        attribute_type: u8,
+        nullifier: [u8; 32],
+    ) -> Result<()> {
+        let clock = current_clock()?;
+        let accounts = &mut *ctx.accounts;
+        accounts.identity.require_guardian(&accounts.user.key())?;
+        
+        accounts.verify_and_record(&proof, &public_inputs, &public_inputs, nullifier, attribute_type, &clock)?;
+        
+        msg!(
+            "Guardian {:?} verified attribute type {} for: {:?}",
+            accounts.user.key(),
+            attribute_type,
+            accounts.identity.owner
+        );
+        Ok(())
+    }
+
+    /// Create a session for the identity owner, signed by their session delegate
+    pub fn create_session_delegated(
+        ctx: Context<CreateSessionDelegated>,
+        session_id: [u8; 32],
+        expiry: i64,
+        scopes: Vec<[u8; 16]>,
+        revocation_proof: Option<Vec<[u8; 32]>>,
+    ) -> Result<()> {
+        let session = &mut ctx.accounts.session;
+        let identity = &mut ctx.accounts.identity;
+        identity.require_session_delegate(&ctx.accounts.delegate.key())?;
+        require!(identity.is_verified, ErrorCode::IdentityNotFound);
+        ctx.accounts
+            .registry
+            .require_not_revoked(&identity.identity_commitment, revocation_proof.as_deref())?;
+        ctx.accounts.registry.require_verification_weight(identity.attributes_verified)?;
+        identity.require_not_frozen()?;
+        identity.require_not_expired(current_clock()?.unix_timestamp)?;
+        Session::require_future_expiry(expiry, current_clock()?.unix_timestamp)?;
+        session.user = identity.owner;
+        session.session_id = session_id;
+        session.created_at = current_clock()?.unix_timestamp;
+        session.expires_at = expiry;
+        session.is_active = true;
+        session.bump = ctx.bumps.session;
+        session.set_scopes(&scopes)?;
+        session.disclose_verified(&ctx.accounts.registry, identity, current_clock()?.unix_timestamp)?;
+        session.delegated = true;
+        session.namespace = identity.namespace;
+        session.identity_epoch = identity.session_epoch;
+        session.last_active = session.created_at;
+        identity.open_sessions(1)?;
+        
+        msg!("Delegated session created for user: {:?}", identity.owner);
+        Ok(())
+    }
+
+    /// Register a uniqueness nullifier, rejecting reuse
+    /// 
+    /// The latest `IdentityRegistry::NULLIFIER_CAPACITY` nullifiers live in a
+    /// ring on the registry. Once it is full each claim evicts the oldest one
+    /// into its `NullifierRecord` PDA, created here and paid by the user, so
+    /// every nullifier stays spent; a claimed nullifier must have no record.
+    pub fn register_nullifier(ctx: Context<RegisterNullifier>, nullifier: [u8; 32]) -> Result<()> {
+        validate_field_element(&nullifier)?;
+        ctx.accounts.identity.require_verified(4)?; // uniqueness
+        
+        let accounts = &mut *ctx.accounts;
+        claim_nullifier(
+            &mut accounts.registry,
+            nullifier,
+            &accounts.nullifier_record,
+            accounts.evicted_record.as_deref(),
+            &accounts.user,
+            &accounts.system_program,
+            current_clock()?.unix_timestamp,
+        )?;
+        
+        msg!(
+            "Nullifier stored inline ({} of {})",
+            accounts.registry.nullifier_count,
+            IdentityRegistry::NULLIFIER_CAPACITY
+        );
+        Ok(())
+    }
+
+    /// Verify up to `AttributeProof::MAX_BATCH` attributes, setting all or none
+    /// 
+    /// The proofs are made together, so each binds the identity's current
+    /// verification nonce; the bits are recorded at once after every proof
+    /// passes and the nonce advances a single time. Layouts of attributes that
+    /// have one are passed as remaining accounts, and dependencies must already
+    /// be verified before the batch. The batch claims one `nullifier`, emitted
+    /// by at least one of its proofs.
+    pub fn verify_identity_batch<'info>(
+        ctx: Context<'info, VerifyIdentity<'info>>,
+        proofs: Vec<AttributeProof>,
+        nullifier: [u8; 32],
+    ) -> Result<()> {
+        let clock = current_clock()?;
+        let attributes = AttributeProof::validate_batch(&proofs)?;
+        let layouts = ctx.accounts.input_layouts_from(ctx.remaining_accounts)?;
+        let verify = &mut *ctx.accounts;
+        require!(
+            proofs
+                .iter()
+                .any(|entry| require_nullifier_input(&entry.public_inputs, &nullifier).is_ok()),
+            ErrorCode::InvalidPublicInputs
+        );
+        
+        for entry in &proofs {
+            let layout = layouts.iter().find(|layout| layout.attribute_type == entry.attribute_type);
+            verify.verify_attribute_with(
+                layout.map(|layout| &**layout),
+                &entry.proof,
+                &entry.public_inputs,
+                &entry.public_inputs,
+                entry.attribute_type,
+                &clock,
+            )?;
+        }
+        verify.claim_nullifier(nullifier, clock.unix_timestamp)?;
+        
+        verify.record_verification(attributes, clock.unix_timestamp)?;
+        
+        msg!("Identity verified with attribute types: {}", attributes);
+        Ok(())
+    }
+
+    /// Verify an attribute, writing a `VerificationProof` audit record
+    /// 
+    /// Same checks as `verify_identity`. The record keeps hashes of the proof
+    /// and public inputs (`compute_proof_hash`, `compute_public_inputs_hash`)
+    /// rather than the bytes, plus who verified it and when: the verifier only
+    /// if it co-signs and is trusted, otherwise the user, with no label.
+    pub fn verify_identity_with_audit(
+        ctx: Context<VerifyIdentityWithAudit>,
+        proof: Vec<u8>,
+        public_inputs: Vec<u8>,
+        attribute_type: u8,
+        nullifier: [u8; 32],
+    ) -> Result<()> {
+        let clock = current_clock()?;
+        let verify = &mut ctx.accounts.verify;
+        verify.verify_and_record(&proof, &public_inputs, &public_inputs, nullifier, attribute_type, &clock)?;
+        
+        let record = &mut ctx.accounts.verification_proof;
+        verify.write_audit(record, &proof, &public_inputs, attribute_type, clock.unix_timestamp)?;
+        
+        msg!("Identity verified with attribute type {}, audit record {:?}", attribute_type, record.key());
+        Ok(())
+    }
+
+    /// Whether `nullifier` has been registered, so clients can pre-check it
+    /// 
+    /// Covers the registry's inline ring and the nullifier's `NullifierRecord`
+    /// PDA, which also hold every nullifier `mark_spent` put in the
+    /// spent-nullifier tree.
+    pub fn is_nullifier_spent(ctx: Context<CheckNullifier>, nullifier: [u8; 32]) -> Result<bool> {
+        let info = ctx.accounts.nullifier_record.to_account_info();
+        let record = if info.owner == &crate::ID && !info.data_is_empty() {
+            Some(NullifierRecord::try_deserialize(&mut &info.try_borrow_data()?[..])?)
+        } else {
+            None
+        };
+        Ok(ctx.accounts.registry.is_nullifier_spent(&nullifier, record.as_ref()))
+    }
+
+    /// `finalize_verification` that also spends the proof's nullifier in the
+    /// registry's spent-nullifier tree
+    /// 
+    /// The proof is uploaded first with `submit_verification`, leaving the
+    /// transaction room for the compressed path (`occupied`, `siblings`; see
+    /// `IdentityRegistry::mark_spent`) proving the nullifier's leaf is empty.
+    /// The nullifier is claimed in the inline ring as well, as every other path
+    /// does, so none of them can claim it again without checking `spent_root`.
+    pub fn mark_spent(
+        ctx: Context<MarkSpent>,
+        nullifier: [u8; 32],
+        occupied: u64,
+        siblings: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let clock = current_clock()?;
+        let verify = &mut ctx.accounts.verify;
+        let pending = &ctx.accounts.pending_verification;
+        verify.require_owner()?;
+        
+        crate::compression::require_nullifier_input(&pending.public_inputs, &nullifier)?;
+        verify.require_unclaimed_nullifier(&nullifier)?;
+        let layout = verify.input_layout.as_deref().cloned();
+        verify.verify_attribute_with(
+            layout.as_ref(),
+            &pending.proof,
+            &pending.public_inputs,
+            &pending.public_inputs,
+            pending.attribute_type,
+            &clock,
+        )?;
+        verify.spend_nullifier(nullifier, occupied, &siblings, clock.unix_timestamp)?;
+        verify.record_verification(pending.attribute_type, clock.unix_timestamp)?;
+        pending.close(verify.user.to_account_info())?;
+        
+        msg!("Nullifier spent at leaf {}", IdentityRegistry::spent_leaf_index(&nullifier));
+        Ok(())
+    }
+
+    /// Archive a verification key before it is rotated out
+    #[allow(clippy::too_many_arguments)]
+    pub fn archive_verification_key(
+        ctx: Context<ArchiveVerificationKey>,
+        attribute_type: u8,
+        version: u16,
+        alpha_g1: [u8; 64],
+        beta_g2: [u8; 128],
+        gamma_g2: [u8; 128],
+        delta_g2: [u8; 128],
+        ic: Vec<[u8; 64]>,
+    ) -> Result<()> {
+        select_verification_key(attribute_type)?;
+        require!(
+            !ic.is_empty() && ic.len() <= ArchivedVerificationKey::MAX_IC,
+            ErrorCode::VerificationKeyMismatch
+        );
+        
+        let archived_vk = &mut ctx.accounts.archived_vk;
+        archived_vk.attribute_type = attribute_type;
+        archived_vk.version = version;
+        archived_vk.alpha_g1 = alpha_g1;
+        archived_vk.beta_g2 = beta_g2;
+        archived_vk.gamma_g2 = gamma_g2;
+        archived_vk.delta_g2 = delta_g2;
+        archived_vk.ic = ic;
+        archived_vk.archived_at = current_clock()?.unix_timestamp;
+        archived_vk.bump = ctx.bumps.archived_vk;
+        archived_vk.namespace = ctx.accounts.registry.namespace;
+        
+        msg!("Archived verification key v{} for attribute type: {}", version, attribute_type);
+        Ok(())
+    }
+
+    /// Audit a past proof against the verification key active at the time
+    /// 
+    /// Read-only: nothing is recorded on any identity.
+    pub fn verify_proof_historical(
+        ctx: Context<VerifyProofHistorical>,
+        _attribute_type: u8,
+        _vk_version: u16,
+        proof: Vec<u8>,
+        public_inputs: Vec<u8>,
+    ) -> Result<bool> {
+        verify_groth16_proof_archived(&proof, &public_inputs, &ctx.accounts.archived_vk)
+    }
+
+    /// Verify a proof and report the key version and fingerprint that verified
+    /// it, via return data
+    /// 
+    /// The attribute's current key is reported at the version compiled into it;
+    /// `ArchivedVerificationKey` accounts of the same attribute passed as
+    /// remaining accounts are tried next, in order, skipping any that don't
+    /// decode. Read-only.
+    pub fn verify_and_report_vk<'info>(
+        ctx: Context<'info, VerifyAndReportVk<'info>>,
+        attribute_type: u8,
+        proof: Vec<u8>,
+        public_inputs: Vec<u8>,
+    ) -> Result<VkReport> {
+        let definition = &ctx.accounts.definition;
+        let archived = ctx
+            .remaining_accounts
+            .iter()
+            .filter_map(|info| Account::<ArchivedVerificationKey>::try_from(info).ok())
+            .map(|key| {
+                require!(
+                    key.namespace == definition.namespace && key.attribute_type == attribute_type,
+                    ErrorCode::VerificationKeyMismatch
+                );
+                Ok(key.into_inner())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        
+        let report = verify_attribute_and_report(&proof, &public_inputs, attribute_type, &archived)?;
+        msg!("Proof verified: {}, key version: {}", report.verified, report.vk_version);
+        Ok(report)
+    }
+
+    /// Append a leaf to the registry's incremental identity tree in O(depth)
+    pub fn append_leaf(ctx: Context<UpdateRegistry>, leaf: [u8; 32]) -> Result<()> {
+        validate_field_element(&leaf)?;
+        let registry = &mut ctx.accounts.registry;
+        let root = registry.append_leaf(leaf)?;
+        
+        msg!("Leaf {} appended, tree root: {:?}", registry.tree_leaf_count - 1, root);
+        Ok(())
+    }
+
+    /// Gate registration on an allowlist of wallets (zero root reopens it)
+    pub fn set_allowlist_root(ctx: Context<UpdateRegistry>, allowlist_root: [u8; 32]) -> Result<()> {
+        ctx.accounts.registry.allowlist_root = allowlist_root;
+        
+        msg!("Allowlist root set: {:?}", allowlist_root);
+        Ok(())
+    }
+
+    /// Set the shallowest identity tree registrations and compressed
+    /// verifications accept (0 = no floor)
+    pub fn set_min_tree_depth(ctx: Context<UpdateRegistry>, min_tree_depth: u8) -> Result<()> {
+        require!(min_tree_depth <= MAX_TREE_HEIGHT, ErrorCode::MerkleTreeError);
+        ctx.accounts.registry.min_tree_depth = min_tree_depth;
+        
+        msg!("Minimum tree depth set to: {}", min_tree_depth);
+        Ok(())
+    }
+
+    /// Cap verifications across the whole registry per window of slots (0 = no cap)
+    /// 
+    /// Each window's count lives in its own `RateWindow` account, which every
+    /// verification in the window writes, so it is meant for incidents rather
+    /// than steady-state use. A changed cap applies to the current window's count.
+    pub fn set_global_rate_limit(
+        ctx: Context<UpdateRegistry>,
+        verification_cap: u32,
+        window_slots: u64,
+    ) -> Result<()> {
+        require!(verification_cap == 0 || window_slots > 0, ErrorCode::GlobalRateLimited);
+        let registry = &mut ctx.accounts.registry;
+        registry.global_verification_cap = verification_cap;
+        registry.global_window_slots = window_slots;
+        
+        msg!("Global rate limit set to {} verifications per {} slots", verification_cap, window_slots);
+        Ok(())
+    }
+
+    /// Pause or resume individual instructions; see `IdentityRegistry::PAUSE_*`
+    pub fn set_paused_instructions(ctx: Context<UpdateRegistry>, paused_instructions: u16) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
+        registry.paused_instructions = paused_instructions & IdentityRegistry::PAUSE_ALL;
+        
+        msg!("Paused instructions set to: {:#06x}", registry.paused_instructions);
+        Ok(())
+    }
+
+    /// Set the lamports escrowed per metered verification (0 = free)
+    pub fn set_verification_fee(ctx: Context<UpdateRegistry>, verification_fee: u64) -> Result<()> {
+        ctx.accounts.registry.verification_fee = verification_fee;
+        
+        msg!("Verification fee set to: {} lamports", verification_fee);
+        Ok(())
+    }
+
+    /// Create the escrow that holds verification fees
+    pub fn initialize_fee_escrow(ctx: Context<InitializeFeeEscrow>) -> Result<()> {
+        let escrow = &mut ctx.accounts.fee_escrow;
+        escrow.namespace = ctx.accounts.registry.namespace;
+        escrow.forfeited = 0;
+        escrow.bump = ctx.bumps.fee_escrow;
+        
+        msg!("Fee escrow initialized for namespace: {:?}", escrow.namespace);
+        Ok(())
+    }
+
+    /// Withdraw `amount` of the forfeited verification fees to `recipient`
+    pub fn withdraw_forfeited_fees(ctx: Context<WithdrawForfeitedFees>, amount: u64) -> Result<()> {
+        let escrow = &mut ctx.accounts.fee_escrow;
+        escrow.withdraw(amount)?;
+        escrow.sub_lamports(amount)?;
+        ctx.accounts.recipient.add_lamports(amount)?;
+        
+        msg!("Withdrew {} lamports of forfeited fees", amount);
+        Ok(())
+    }
+
+    /// Name an attribute bit and record its current verification key version
+    pub fn define_attribute(
+        ctx: Context<DefineAttribute>,
+        attribute_type: u8,
+        name: [u8; 32],
+        vk_version: u16,
+    ) -> Result<()> {
+        // Only attributes with a circuit can be defined
+        select_verification_key(attribute_type)?;
+        
+        let definition = &mut ctx.accounts.definition;
+        definition.namespace = ctx.accounts.registry.namespace;
+        definition.attribute_type = attribute_type;
+        definition.name = name;
+        definition.vk_version = vk_version;
+        definition.bump = ctx.bumps.definition;
+        ctx.accounts.registry.defined_attributes |= attribute_type;
+        
+        msg!("Attribute {} defined at key version {}", attribute_type, vk_version);
+        Ok(())
+    }
+
+    /// Every defined attribute's bit, name, key version and dependencies, via
+    /// return data
+    /// 
+    /// Pass all of the registry's `AttributeDefinition` PDAs as remaining
+    /// accounts in bit order; at most one per bit, so the list stays bounded
+    /// (more fail with `TooManyAttributes`).
+    pub fn list_attributes<'info>(
+        ctx: Context<'info, ReadRegistry<'info>>,
+    ) -> Result<Vec<AttributeMetadata>> {
+        require!(ctx.remaining_accounts.len() <= 8, ErrorCode::TooManyAttributes);
+        let definitions = ctx
+            .remaining_accounts
+            .iter()
+            .map(|info| Account::<AttributeDefinition>::try_from(info).map(Account::into_inner))
+            .collect::<Result<Vec<_>>>()?;
+        ctx.accounts.registry.attribute_metadata(&definitions)
+    }
+
+    /// Set how long new or renewed identities stay valid (0 = never expire)
+    pub fn set_identity_ttl(ctx: Context<UpdateRegistry>, identity_ttl: u64) -> Result<()> {
+        ctx.accounts.registry.identity_ttl = identity_ttl;
+        
+        msg!("Identity TTL set to: {}s", identity_ttl);
+        Ok(())
+    }
+
+    /// Set how long a verified attribute stays fresh (0 = never goes stale)
+    /// 
+    /// This is the authority-only TTL update; `initialize` sets the first value.
+    pub fn set_verification_ttl(ctx: Context<UpdateRegistry>, verification_ttl: i64) -> Result<()> {
+        require!(verification_ttl >= 0, ErrorCode::InvalidConfig);
+        ctx.accounts.registry.verification_ttl = verification_ttl;
+        
+        msg!("Verification TTL set to: {}s", verification_ttl);
+        Ok(())
+    }
+
+    /// Select the proof system an attribute's proofs are verified with
+    pub fn set_proof_system(
+        ctx: Context<UpdateRegistry>,
+        attribute_type: u8,
+        proof_system: u8,
+    ) -> Result<()> {
+        ctx.accounts.registry.set_proof_system(attribute_type, proof_system)?;
+        
+        msg!("Attribute {} proof system set to: {}", attribute_type, proof_system);
+        Ok(())
+    }
+
+    /// Weight each attribute and the total a session's identity must reach
+    /// 
+    /// For "enough verification" gating: e.g. weights (30, 30, 50) with a minimum
+    /// of 60 accept any two attributes.
+    pub fn set_verification_weights(
+        ctx: Context<UpdateRegistry>,
+        weights: [u16; 8],
+        min_verification_weight: u32,
+    ) -> Result<()> {
+        ctx.accounts
+            .registry
+            .set_verification_weights(weights, min_verification_weight)?;
+        
+        msg!("Minimum verification weight set to: {}", min_verification_weight);
+        Ok(())
+    }
+
+    /// Bitmap of verified attributes that need renewal, via return data
+    /// 
+    /// For re-verification prompts: a wallet can show exactly which credentials
+    /// went stale under the registry's `verification_ttl` or passed their expiry.
+    pub fn expired_attributes(ctx: Context<IdentityFreshness>) -> Result<u8> {
+        let now = current_clock()?.unix_timestamp;
+        Ok(ctx
+            .accounts
+            .identity
+            .expired_attributes(now, ctx.accounts.registry.verification_ttl))
+    }
+
+    /// Require `depends_on` attributes to be verified before `attribute_type`
+    /// 
+    /// e.g. age only means something for a proven-unique person: (1, 4).
+    pub fn set_attribute_dependencies(
+        ctx: Context<UpdateRegistry>,
+        attribute_type: u8,
+        depends_on: u8,
+    ) -> Result<()> {
+        ctx.accounts.registry.set_attribute_dependencies(attribute_type, depends_on)?;
+        
+        msg!("Attribute {} now depends on: {}", attribute_type, depends_on);
+        Ok(())
+    }
+
+    /// Renew an identity's membership for another registry TTL
+    pub fn renew_identity(ctx: Context<RenewIdentity>) -> Result<()> {
+        let identity = &mut ctx.accounts.identity;
+        identity.renew(current_clock()?.unix_timestamp, ctx.accounts.registry.identity_ttl);
+        
+        msg!("Identity renewed until: {}", identity.expires_at);
+        Ok(())
+    }
+
+    /// First step of two-step verification: store the proof on-chain
+    /// 
+    /// Splits proof upload from verification so each transaction stays within
+    /// its size and compute limits. The pairing itself runs in one syscall-backed
+    /// `finalize_verification`; splitting the pairing further isn't needed yet.
+    pub fn submit_verification(
+        ctx: Context<SubmitVerification>,
+        proof: Vec<u8>,
+        public_inputs: Vec<u8>,
+        attribute_type: u8,
+    ) -> Result<()> {
+        select_verification_key(attribute_type)?;
+        
+        let pending = &mut ctx.accounts.pending_verification;
+        pending.submit(
+            ctx.accounts.identity.key(),
+            attribute_type,
+            &proof,
+            public_inputs,
+            current_clock()?.unix_timestamp,
+        )?;
+        pending.bump = ctx.bumps.pending_verification;
+        
+        msg!("Verification submitted for attribute type: {}", attribute_type);
+        Ok(())
+    }
+
+    /// Second step: verify the stored proof with the same checks as `verify_identity`
+    pub fn finalize_verification(ctx: Context<FinalizeVerification>, nullifier: [u8; 32]) -> Result<()> {
+        let clock = current_clock()?;
+        let verify = &mut ctx.accounts.verify;
+        let pending = &ctx.accounts.pending_verification;
+        verify.require_owner()?;
+        
+        verify.verify_and_record(
+            &pending.proof,
+            &pending.public_inputs,
+            &pending.public_inputs,
+            nullifier,
+            pending.attribute_type,
+            &clock,
+        )?;
+        pending.close(verify.user.to_account_info())?;
+        
+        msg!("Verification finalized for attribute type: {}", pending.attribute_type);
+        Ok(())
+    }
+
+    /// Abandon a submitted proof, e.g. after a failed finalize
+    pub fn cancel_verification(_ctx: Context<CancelVerification>) -> Result<()> {
+        msg!("Pending verification cancelled");
+        Ok(())
+    }
+
+    /// Root of the padded Merkle tree over `leaves`, via return data
+    /// 
+    /// Authoritative root computation for clients; see `merkle::padded_root`.
+    pub fn compute_tree_root(_ctx: Context<Stateless>, leaves: Vec<[u8; 32]>) -> Result<[u8; 32]> {
+        merkle::padded_root(&leaves)
+    }
+
+    /// Message of the program error with on-chain `code`, via return data
+    /// 
+    /// The `#[msg]` text of the matching `ErrorCode`, so frontends key their
+    /// translations off one catalog instead of copying strings.
+    pub fn describe_error(_ctx: Context<Stateless>, code: u32) -> Result<String> {
+        ErrorCode::describe(code).ok_or(error!(ErrorCode::UnknownErrorCode))
+    }
+
+    /// Zero hash of every level 0..=`depth`, via return data
+    /// 
+    /// Entry `i` is the root of an empty subtree of height `i`, built by hashing
+    /// `merkle::EMPTY_LEAF` with `poseidon_merkle_parent`, exactly as the program
+    /// pads its own trees. Deeper trees continue from the last entry.
+    pub fn empty_subtree_hashes(_ctx: Context<Stateless>, depth: u8) -> Result<Vec<[u8; 32]>> {
+        require!(
+            depth as usize <= merkle::MAX_RETURNED_ZERO_DEPTH,
+            ErrorCode::MerkleTreeError
+        );
+        merkle::empty_subtree_hashes(depth as usize)
+    }
+
+    /// Check a proof for an identity, reusing a cached result within the window
+    /// 
+    /// Returns whether the proof verifies, via return data. Identical calls within
+    /// `VerificationCache::WINDOW_SLOTS` skip the pairing; identity state is
+    /// never changed. Only the identity's owner writes its cache, so nobody else
+    /// can replace the entry; relying parties re-check it with
+    /// `cached_verification_result`. A proof that verifies claims its
+    /// `nullifier` as in `verify_identity`; a hit returns the result of the
+    /// call that claimed it. Only the pairing's verdict is cached: a proof that
+    /// never reaches it fails with its own error.
+    pub fn verify_proof_cached(
+        ctx: Context<VerifyProofCached>,
+        proof: Vec<u8>,
+        public_inputs: Vec<u8>,
+        attribute_type: u8,
+        nullifier: [u8; 32],
+    ) -> Result<bool> {
+        let slot = current_clock()?.slot;
+        check_root_freshness(
+            &ctx.accounts.registry,
+            ctx.accounts.root_checkpoint.as_deref(),
+            &ctx.accounts.identity.merkle_root,
+            slot,
+        )?;
+        let proof_hash = verification_cache_key(attribute_type, &proof, &public_inputs);
+        let cache = &mut ctx.accounts.cache;
+        if let Some(valid) = cache.lookup(&proof_hash, slot) {
+            msg!("Verification cache hit, valid: {}", valid);
+            return Ok(valid);
+        }
+        
+        validate_field_element(&nullifier)?;
+        require_nullifier_input(&public_inputs, &nullifier)?;
+        let outcome = verify_attribute_proof(
+            ctx.accounts.registry.proof_system_of(attribute_type)?,
+            &proof,
+            &public_inputs,
+            attribute_type,
+        );
+        let valid = pairing_verdict(outcome)?;
+        if valid {
+            claim_nullifier(
+                &mut ctx.accounts.registry,
+                nullifier,
+                &ctx.accounts.nullifier_record,
+                ctx.accounts.evicted_record.as_deref(),
+                &ctx.accounts.owner,
+                &ctx.accounts.system_program,
+                current_clock()?.unix_timestamp,
+            )?;
+        }
+        let cache = &mut ctx.accounts.cache;
+        cache.identity = ctx.accounts.identity.key();
+        cache.bump = ctx.bumps.cache;
+        cache.store(proof_hash, valid, slot);
+        
+        msg!("Verification cached until slot {}, valid: {}", cache.cached_until_slot, valid);
+        Ok(valid)
+    }
+
+    /// Cached result for a proof, via return data; `CacheExpired` when none is live
+    pub fn cached_verification_result(
+        ctx: Context<ReadVerificationCache>,
+        proof_hash: [u8; 32],
+    ) -> Result<bool> {
+        ctx.accounts.cache.require_cached(&proof_hash, current_clock()?.slot)
+    }
+
+    /// Lamports of rent saved per identity by compression, via return data
+    pub fn compression_rent_savings(_ctx: Context<Stateless>) -> Result<u64> {
+        let lamports_saved = calculate_rent_savings(&Rent::get()?);
+        
+        msg!("Rent savings: {} lamports per identity", lamports_saved);
+        Ok(lamports_saved)
+    }
+
+    /// Extend sessions during an outage; each extension is bounded and logged
+    pub fn extend_sessions_authority<'info>(
+        ctx: Context<'info, ExtendSessionsAuthority<'info>>,
+        delta: i64,
+    ) -> Result<()> {
+        require!(!ctx.remaining_accounts.is_empty(), ErrorCode::InvalidSession);
+        
+        for info in ctx.remaining_accounts.iter() {
+            require!(info.is_writable, ErrorCode::InvalidSession);
+            let mut session = Account::<Session>::try_from(info)?;
+            require!(
+                session.namespace == ctx.accounts.registry.namespace,
+                ErrorCode::UnauthorizedAccess
+            );
+            session.extend(delta)?;
+            session.exit(ctx.program_id)?;
+            
+            msg!("Session {:?} extended to: {}", info.key(), session.expires_at);
+        }
+        Ok(())
+    }
+
+    /// Measure the compute units a proof verification consumes, via return data
+    /// 
+    /// The proof need not be valid, so clients can size `setComputeUnitLimit`
+    /// with dummy proofs; the outcome is only logged. Any input count up to
+    /// `MAX_PUBLIC_INPUTS` is measured, against the attribute's key resized to
+    /// fit (see `verify_groth16_proof_resized`), so every count reaches the pairing.
+    pub fn benchmark_verification(
+        _ctx: Context<Stateless>,
+        proof: Vec<u8>,
+        public_inputs: Vec<u8>,
+        attribute_type: u8,
+    ) -> Result<u64> {
+        let num_inputs = public_inputs.len() / 32;
+        require!(
+            public_inputs.len().is_multiple_of(32) && (1..=MAX_PUBLIC_INPUTS).contains(&num_inputs),
+            ErrorCode::InvalidPublicInputs
+        );
+        select_verification_key(attribute_type)?;
+        
+        let before = sol_remaining_compute_units();
+        let verified = verify_groth16_proof_resized(&proof, &public_inputs, attribute_type);
+        let consumed = before.saturating_sub(sol_remaining_compute_units());
+        
+        msg!(
+            "Verification with {} inputs consumed {} CU (estimate {}), valid: {}",
+            num_inputs,
+            consumed,
+            estimate_verification_compute_units(num_inputs),
+            matches!(verified, Ok(true))
+        );
+        Ok(consumed)
+    }
+
+    /// Export the identity's verifiable state as a borsh snapshot plus keccak digest
+    /// 
+    /// The snapshot names the registry authority as its exporter, whose
+    /// signature `import_identity` requires.
+    pub fn export_identity(ctx: Context<ExportIdentity>) -> Result<IdentityExport> {
+        let snapshot = ctx
+            .accounts
+            .identity
+            .snapshot(ctx.accounts.registry.authority)
+            .to_bytes()?;
+        let digest = snapshot_digest(&snapshot);
+        
+        Ok(IdentityExport { snapshot, digest })
+    }
+
+    /// Re-create an exported identity, e.g. when migrating between deployments
+    /// 
+    /// The exporting registry's authority must co-sign as `source_authority`;
+    /// the digest alone only shows the bytes weren't corrupted in transit.
+    pub fn import_identity(
+        ctx: Context<ImportIdentity>,
+        owner: Pubkey,
+        snapshot: Vec<u8>,
+        digest: [u8; 32],
+    ) -> Result<()> {
+        verify_snapshot_digest(&snapshot, &digest)?;
+        let snapshot = IdentitySnapshot::try_from_slice(&snapshot)
+            .map_err(|_| error!(ErrorCode::InvalidSnapshot))?;
+        require!(snapshot.owner == owner, ErrorCode::InvalidSnapshot);
+        snapshot.require_exported_by(ctx.accounts.source_authority.key)?;
+        
+        let registry = &mut ctx.accounts.registry;
+        ctx.accounts.identity.set_inner(Identity::from_snapshot(
+            &snapshot,
+            ctx.bumps.identity,
+            registry.namespace,
+        )?);
+        registry.total_identities += 1;
+        
+        msg!("Identity imported for user: {:?}", owner);
+        Ok(())
+    }
+
+    /// Verify an attribute and atomically create or refresh its `Attestation`
+    /// 
+    /// Runs the same checks as `verify_identity`; a failed proof aborts the
+    /// whole instruction, so no attestation is ever issued for it. The issuer
+    /// is the verifier only if it co-signs and is trusted, otherwise the user.
+    pub fn verify_and_attest(
+        ctx: Context<VerifyAndAttest>,
+        proof: Vec<u8>,
+        public_inputs: Vec<u8>,
+        attribute_type: u8,
+        nullifier: [u8; 32],
+    ) -> Result<()> {
+        let clock = current_clock()?;
+        let verify = &mut ctx.accounts.verify;
+        verify.verify_and_record(&proof, &public_inputs, &public_inputs, nullifier, attribute_type, &clock)?;
+        
+        let attestation = Attestation::issue(
+            verify.identity.key(),
+            &verify.identity,
+            attribute_type,
+            verify.credited_verifier(),
+            clock.unix_timestamp,
+            ctx.bumps.attestation,
+        )?;
+        ctx.accounts.attestation.set_inner(attestation);
+        
+        msg!("Attestation issued for attribute type: {}", attribute_type);
+        Ok(())
+    }
+
+    /// Precondition for external programs holding an `Attestation`: succeeds
+    /// only while it still holds for its identity (return data is `true`)
+    /// 
+    /// See `Attestation::require_current`; programs reading both accounts
+    /// directly can run the same check without a CPI.
+    pub fn check_attestation(ctx: Context<ReadAttestation>) -> Result<bool> {
+        ctx.accounts
+            .attestation
+            .require_current(&ctx.accounts.identity, current_clock()?.unix_timestamp)?;
+        Ok(true)
+    }
+
+    /// Identity account a bound DID resolves to, via return data
+    pub fn resolve_did(ctx: Context<ResolveDid>, did: [u8; 64]) -> Result<Pubkey> {
+        ctx.accounts.did_record.resolve(&did)
+    }
+
+    /// Verify an attribute and open a session gated on exactly that attribute
+    /// 
+    /// The session discloses only `attribute_type` and carries its
+    /// `Session::attribute_scope`, so there is no window where the attribute is
+    /// verified without a session, or a session exists for an unproven claim.
+    /// Session checks match `create_session`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn verify_and_open_session(
+        ctx: Context<VerifyAndOpenSession>,
+        proof: Vec<u8>,
+        public_inputs: Vec<u8>,
+        attribute_type: u8,
+        session_id: [u8; 32],
+        expiry: i64,
+        revocation_proof: Option<Vec<[u8; 32]>>,
+        nullifier: [u8; 32],
+    ) -> Result<()> {
+        let clock = current_clock()?;
+        let verify = &mut ctx.accounts.verify;
+        verify.require_owner()?;
+        verify.verify_and_record(&proof, &public_inputs, &public_inputs, nullifier, attribute_type, &clock)?;
+        
+        verify
+            .registry
+            .require_not_revoked(&verify.identity.identity_commitment, revocation_proof.as_deref())?;
+        verify.registry.require_verification_weight(verify.identity.attributes_verified)?;
+        let session = Session::for_attribute(
+            &verify.identity,
+            session_id,
+            attribute_type,
+            expiry,
+            clock.unix_timestamp,
+            ctx.bumps.session,
+        )?;
+        verify.identity.open_sessions(1)?;
+        ctx.accounts.session.set_inner(session);
+        
+        msg!("Verified attribute {} and opened a scoped session", attribute_type);
+        Ok(())
+    }
+
+    /// Publish the merkle root of all revoked commitments, replacing the previous one
+    pub fn set_revocation_root(ctx: Context<UpdateRegistry>, revocation_root: [u8; 32]) -> Result<()> {
+        ctx.accounts.registry.revocation_root = revocation_root;
+        
+        msg!("Revocation root set: {:?}", revocation_root);
+        Ok(())
+    }
+
+    /// Whether the identity's commitment is proven to be in the revocation root,
+    /// via return data
+    pub fn check_revocation(
+        ctx: Context<CheckRevocation>,
+        proof_siblings: Vec<[u8; 32]>,
+        packed_indices: u64,
+    ) -> Result<bool> {
+        ctx.accounts.registry.is_revoked(
+            &ctx.accounts.identity.identity_commitment,
+            &proof_siblings,
+            packed_indices,
+        )
+    }
+
+    /// Describe which public input holds each bound value for an attribute circuit
+    /// 
+    /// Pass `PublicInputLayout::UNUSED` for fields the circuit doesn't expose;
+    /// every other index must be below the circuit's public input count.
+    pub fn set_public_input_layout(
+        ctx: Context<SetPublicInputLayout>,
+        attribute_type: u8,
+        owner_index: u8,
+        threshold_index: u8,
+        nonce_index: u8,
+        root_index: u8,
+        tag_index: u8,
+    ) -> Result<()> {
+        require!(attribute_type != 0, ErrorCode::AttributeTypeRequired);
+        require!(attribute_type.is_power_of_two(), ErrorCode::CompositeAttributeType);
+        
+        let input_count = select_verification_key(attribute_type)?.ic.len() - 1;
+        let layout = &mut ctx.accounts.input_layout;
+        layout.configure(owner_index, threshold_index, nonce_index)?;
+        layout.root_index = root_index;
+        layout.tag_index = tag_index;
+        layout.require_within(input_count)?;
+        layout.attribute_type = attribute_type;
+        layout.bump = ctx.bumps.input_layout;
+        layout.namespace = ctx.accounts.registry.namespace;
+        ctx.accounts.registry.input_layouts |= attribute_type;
+        
+        msg!(
+            "Input layout for attribute {}: owner {}, threshold {}, nonce {}, root {}, tag {}",
+            attribute_type,
+            owner_index,
+            threshold_index,
+            nonce_index,
+            root_index,
+            tag_index
+        );
+        Ok(())
+    }
+
+    /// Require proofs for an attribute to carry `circuit_version` at `version_index`
+    /// 
+    /// Set after a circuit upgrade whose semantics changed, so proofs from the old
+    /// circuit are rejected even where they still verify against the key.
+    pub fn set_circuit_version(
+        ctx: Context<SetCircuitVersion>,
+        version_index: u8,
+        circuit_version: u32,
+    ) -> Result<()> {
+        let layout = &mut ctx.accounts.input_layout;
+        layout.set_circuit_version(version_index, circuit_version)?;
+        
+        msg!(
+            "Circuit version for attribute {}: {} at input {}",
+            layout.attribute_type,
+            circuit_version,
+            version_index
+        );
+        Ok(())
+    }
+
+    /// Configure the verification key of an auxiliary circuit (see `CircuitVerificationKey`)
+    #[allow(clippy::too_many_arguments)]
+    pub fn set_circuit_verification_key(
+        ctx: Context<SetCircuitVerificationKey>,
+        circuit_id: u8,
+        alpha_g1: [u8; 64],
+        beta_g2: [u8; 128],
+        gamma_g2: [u8; 128],
+        delta_g2: [u8; 128],
+        ic: Vec<[u8; 64]>,
+    ) -> Result<()> {
+        require!(
+            !ic.is_empty() && ic.len() <= CircuitVerificationKey::MAX_IC,
+            ErrorCode::VerificationKeyMismatch
+        );
+        
+        let circuit_vk = &mut ctx.accounts.circuit_vk;
+        circuit_vk.circuit_id = circuit_id;
+        circuit_vk.alpha_g1 = alpha_g1;
+        circuit_vk.beta_g2 = beta_g2;
+        circuit_vk.gamma_g2 = gamma_g2;
+        circuit_vk.delta_g2 = delta_g2;
+        circuit_vk.ic = ic;
+        circuit_vk.bump = ctx.bumps.circuit_vk;
+        circuit_vk.namespace = ctx.accounts.registry.namespace;
+        
+        msg!("Verification key set for circuit: {}", circuit_id);
+        Ok(())
+    }
+
+    /// Issue a one-time challenge for `prove_ownership`
+    pub fn issue_challenge(ctx: Context<IssueChallenge>, challenge: [u8; 32]) -> Result<()> {
+        require_canonical_field_element_be(&challenge)?;
+        let now = current_clock()?.unix_timestamp;
+        
+        let challenge_account = &mut ctx.accounts.challenge_account;
+        challenge_account.identity = ctx.accounts.identity.key();
+        challenge_account.challenge = challenge;
+        challenge_account.payer = ctx.accounts.payer.key();
+        challenge_account.created_at = now;
+        challenge_account.expires_at = now + Challenge::DURATION;
+        challenge_account.consumed = false;
+        challenge_account.bump = ctx.bumps.challenge_account;
+        
+        msg!("Challenge issued for identity: {:?}", challenge_account.identity);
+        Ok(())
+    }
+
+    /// Close expired challenges in bulk, refunding each one's rent to its payer
+    /// 
+    /// `remaining_accounts` holds `(challenge, payer)` pairs, both writable.
+    /// Challenges that haven't expired yet are skipped. Callable by anyone.
+    pub fn reclaim_challenges_batch<'info>(
+        ctx: Context<'info, ReclaimChallengesBatch>,
+    ) -> Result<()> {
+        let pairs = ctx.remaining_accounts.chunks_exact(2);
+        require!(
+            pairs.len() > 0 && pairs.remainder().is_empty(),
+            ErrorCode::InvalidPublicInputs
+        );
+        let now = current_clock()?.unix_timestamp;
+        
+        let mut reclaimed = 0;
+        for pair in pairs {
+            let (challenge_info, payer) = (&pair[0], &pair[1]);
+            require!(
+                challenge_info.is_writable && payer.is_writable,
+                ErrorCode::UnauthorizedAccess
+            );
+            let challenge = Account::<Challenge>::try_from(challenge_info)?;
+            if !challenge.reclaimable(payer.key, now)? {
+                continue;
+            }
+            challenge.close(payer.clone())?;
+            reclaimed += 1;
+        }
+        
+        msg!("Reclaimed {} of {} challenges", reclaimed, ctx.remaining_accounts.len() / 2);
+        Ok(())
+    }
+
+    /// Private login from compressed state: prove knowledge of the secret behind
+    /// a leaf of the identity's merkle root without revealing the commitment
+    /// 
+    /// The proof is bound to the identity's owner (see `ownership_public_inputs`).
+    /// A valid proof consumes the challenge; relying parties check `consumed`.
+    pub fn prove_ownership(ctx: Context<ProveOwnership>, proof: Vec<u8>) -> Result<()> {
+        let now = current_clock()?.unix_timestamp;
+        let identity = &ctx.accounts.identity;
+        identity.require_not_frozen()?;
+        identity.require_not_expired(now)?;
+        
+        let is_valid = ctx.accounts.challenge_account.check_answer(
+            &identity.owner,
+            &identity.merkle_root,
+            &proof,
+            &ctx.accounts.circuit_vk,
+            now,
+        )?;
+        require!(is_valid, ErrorCode::InvalidProof);
+        
+        ctx.accounts.challenge_account.consume(now)?;
+        
+        msg!("Ownership proven for identity: {:?}", identity.key());
+        Ok(())
+    }
+
+    /// Dry run of `prove_ownership`, via return data; the challenge stays open
+    /// 
+    /// Any rejection, including a closed challenge, is logged and reported as
+    /// `false`, so clients can debug proofs without burning challenges.
+    pub fn simulate_verify(ctx: Context<SimulateOwnership>, proof: Vec<u8>) -> Result<bool> {
+        let now = current_clock()?.unix_timestamp;
+        let identity = &ctx.accounts.identity;
+        let outcome = identity
+            .require_not_frozen()
+            .and_then(|_| identity.require_not_expired(now))
+            .and_then(|_| {
+                ctx.accounts.challenge_account.check_answer(
+                    &identity.owner,
+                    &identity.merkle_root,
+                    &proof,
+                    &ctx.accounts.circuit_vk,
+                    now,
+                )
+            });
+        
+        match outcome {
+            Ok(is_valid) => Ok(is_valid),
+            Err(err) => {
+                msg!("Simulated verification rejected: {}", err);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Verify one aggregated proof and set `attribute_type` on every identity passed
+    /// as a writable remaining account, in the same order as `user_inputs`
+    /// 
+    /// Each user's inputs start with `pubkey_to_field(owner)`, include the
+    /// user's entry of `nullifiers` and end with the identity's verification
+    /// nonce. The identities are followed by their nullifiers' `NullifierRecord`
+    /// addresses, then their `VerificationLog`s (the program id for an identity
+    /// without one) and, once the inline ring is full, as many evicted record
+    /// addresses. Every identity is recorded as in `verify_identity`, in its
+    /// log and the event log. Any rejected identity or an invalid proof fails
+    /// the instruction, so either every listed identity is verified or none.
+    pub fn verify_batch_proof<'info>(
+        ctx: Context<'info, VerifyBatchProof<'info>>,
+        proof: Vec<u8>,
+        user_inputs: Vec<Vec<u8>>,
+        attribute_type: u8,
+        nullifiers: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        select_verification_key(attribute_type)?;
+        let count = user_inputs.len();
+        require!(nullifiers.len() == count, ErrorCode::BatchLengthMismatch);
+        let remaining = ctx.remaining_accounts.len();
+        require!(
+            remaining == 3 * count || remaining == 4 * count,
+            ErrorCode::BatchLengthMismatch
+        );
+        let (identity_infos, record_infos) = ctx.remaining_accounts.split_at(count);
+        let (record_infos, log_infos) = record_infos.split_at(count);
+        let (log_infos, evicted_infos) = log_infos.split_at(count);
+        let clock = current_clock()?;
+        let now = clock.unix_timestamp;
+        let registry = &ctx.accounts.registry;
+        
+        let mut identities: Vec<Account<Identity>> = Vec::with_capacity(count);
+        for ((info, inputs), nullifier) in identity_infos.iter().zip(user_inputs.iter()).zip(nullifiers.iter()) {
+            require!(info.is_writable, ErrorCode::UnauthorizedAccess);
+            require!(
+                identities.iter().all(|identity| identity.key() != info.key()),
+                ErrorCode::DuplicateAccounts
+            );
+            
+            let identity = Account::<Identity>::try_from(info)?;
+            require!(identity.namespace == registry.namespace, ErrorCode::UnauthorizedAccess);
+            identity.require_not_frozen()?;
+            identity.require_not_expired(now)?;
+            check_root_freshness(registry, ctx.accounts.root_checkpoint.as_deref(), &identity.merkle_root, clock.slot)?;
+            identity.require_dependencies(registry.dependencies_of(attribute_type))?;
+            require!(
+                inputs.get(..32) == Some(&pubkey_to_field(&identity.owner)[..]),
+                ErrorCode::InvalidPublicInputs
+            );
+            identity.require_nonce_input(inputs)?;
+            validate_field_element(nullifier)?;
+            require_nullifier_input(inputs, nullifier)?;
+            identities.push(identity);
+        }
+        let mut logs = identities
+            .iter()
+            .zip(log_infos)
+            .map(|(identity, info)| verification_log_of(identity, info))
+            .collect::<Result<Vec<_>>>()?;
+        require_event_log(registry, ctx.accounts.event_log.as_deref())?;
+        
+        let is_valid = groth16_verifier::verify_batch_proof(
+            &proof,
+            attribute_type,
+            &user_inputs,
+            &ctx.accounts.circuit_vk,
+        )?;
+        require!(is_valid, ErrorCode::InvalidProof);
+        
+        let accounts = &mut *ctx.accounts;
+        record_global_verifications(
+            &accounts.registry,
+            count as u32,
+            accounts.rate_window.as_deref(),
+            &accounts.payer,
+            &accounts.system_program,
+            clock.slot,
+        )?;
+        for (index, nullifier) in nullifiers.iter().enumerate() {
+            claim_nullifier(
+                &mut accounts.registry,
+                *nullifier,
+                &record_infos[index],
+                evicted_infos.get(index),
+                &accounts.payer,
+                &accounts.system_program,
+                now,
+            )?;
+        }
+        let verifier = accounts.payer.key();
+        for (identity, log) in identities.iter_mut().zip(logs.iter_mut()) {
+            identity.mark_verified(attribute_type, now)?;
+            if let Some(log) = log {
+                log.append(VerificationLogEntry { attribute_type, timestamp: now, verifier });
+                log.exit(ctx.program_id)?;
+            }
+            if let Some(event_log) = accounts.event_log.as_mut() {
+                event_log.append(GlobalEventLog::EVENT_VERIFIED, identity.key(), now);
+            }
+            identity.exit(ctx.program_id)?;
+        }
+        
+        msg!("Batch proof verified {} identities for attribute type: {}", identities.len(), attribute_type);
+        Ok(())
+    }
+
+    /// Trust a verifier under a display label, or relabel a trusted one
+    pub fn add_verifier(ctx: Context<UpdateRegistry>, verifier: Pubkey, label: [u8; 32]) -> Result<()> {
+        ctx.accounts.registry.add_verifier(verifier, label)?;
+        
+        msg!("Trusted verifier added: {:?}", verifier);
+        Ok(())
+    }
+
+    /// Stop trusting a verifier
+    pub fn remove_verifier(ctx: Context<UpdateRegistry>, verifier: Pubkey) -> Result<()> {
+        require!(
+            ctx.accounts.registry.remove_verifier(&verifier),
+            ErrorCode::InvalidAuthority
+        );
+        
+        msg!("Trusted verifier removed: {:?}", verifier);
+        Ok(())
+    }
+
+    /// Registry settings, including trusted verifiers and their labels, via return data
+    pub fn get_registry_config(ctx: Context<ReadRegistry>) -> Result<RegistryConfig> {
+        Ok(ctx.accounts.registry.config())
+    }
+
+    /// Whether a session still authenticates its identity, via return data
+    /// 
+    /// Errors with `SessionIdle` once the registry's idle timeout has passed
+    /// without a `heartbeat_session`.
+    pub fn validate_session(ctx: Context<ValidateSession>) -> Result<bool> {
+        let now = current_clock()?.unix_timestamp;
+        let session = &ctx.accounts.session;
+        session.require_not_idle(now, ctx.accounts.registry.session_idle_timeout)?;
+        Ok(session.is_valid_for(&ctx.accounts.identity, now))
+    }
+
+    /// Fail unless a session still authenticates its identity
+    /// 
+    /// The erroring form of `validate_session` for dApps to CPI into before
+    /// granting access: `SessionExpired` past the expiry, `SessionIdle` past the
+    /// idle timeout and `InvalidSession` for a closed or revoked session.
+    pub fn require_valid_session(ctx: Context<ValidateSession>) -> Result<()> {
+        let now = current_clock()?.unix_timestamp;
+        let session = &ctx.accounts.session;
+        session.require_valid_for(&ctx.accounts.identity, now)?;
+        session.require_not_idle(now, ctx.accounts.registry.session_idle_timeout)
+    }
+
+    /// Mark a session as in use, resetting its idle timer
+    pub fn heartbeat_session(ctx: Context<HeartbeatSession>) -> Result<()> {
+        ctx.accounts.session.heartbeat(current_clock()?.unix_timestamp)?;
+        
+        msg!("Session heartbeat for user: {:?}", ctx.accounts.user.key());
+        Ok(())
+    }
+
+    /// Set how long a session may go without a heartbeat (0 = no idle timeout)
+    pub fn set_session_idle_timeout(ctx: Context<UpdateRegistry>, idle_timeout: i64) -> Result<()> {
+        require!(idle_timeout >= 0, ErrorCode::InvalidConfig);
+        ctx.accounts.registry.session_idle_timeout = idle_timeout;
+        
+        msg!("Session idle timeout set to: {}s", idle_timeout);
+        Ok(())
+    }
+
+    /// Recount the identity's active sessions from the session accounts passed as
+    /// remaining accounts, via return data
+    /// 
+    /// Every open session must be passed; the counter is set to how many of them
+    /// are still valid.
+    pub fn reconcile_sessions<'info>(
+        ctx: Context<'info, ReconcileSessions<'info>>,
+    ) -> Result<u16> {
+        let mut sessions = Vec::with_capacity(ctx.remaining_accounts.len());
+        for session_info in ctx.remaining_accounts.iter() {
+            let session = Account::<Session>::try_from(session_info)?;
+            sessions.push((session_info.key(), session.into_inner()));
+        }
+        
+        let identity = &mut ctx.accounts.identity;
+        let active = identity.reconcile_sessions(&sessions, current_clock()?.unix_timestamp)?;
+        
+        msg!("Reconciled {} active sessions for user: {:?}", active, identity.owner);
+        Ok(active)
+    }
+
+    /// Hand registry authority to a governance program's PDA
+    /// 
+    /// From then on every authority-gated instruction must be invoked by
+    /// `governance_program` via CPI, signing with seeds
+    /// `[IdentityRegistry::GOVERNANCE_SEED, registry]`; wallets are refused.
+    pub fn transfer_authority_to_governance(
+        ctx: Context<UpdateRegistry>,
+        governance_program: Pubkey,
+    ) -> Result<()> {
+        let registry_key = ctx.accounts.registry.key();
+        let authority = ctx.accounts.registry.transfer_to_governance(&registry_key, &governance_program)?;
+        
+        msg!("Registry authority transferred to governance PDA: {:?}", authority);
+        Ok(())
+    }
+
+    /// Whether `session_id` is unused for this identity, via return data, so
+    /// clients can pick another id instead of hitting an init failure
+    pub fn is_session_id_available(
+        ctx: Context<SessionIdAvailability>,
+        _session_id: [u8; 32],
+    ) -> Result<bool> {
+        Ok(Session::is_slot_available(&ctx.accounts.session.to_account_info()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::compat::solana_sysvar::program_stubs::{set_syscall_stubs, SyscallStubs};
+    use std::cell::Cell;
+    use std::sync::Once;
+
+    /// Unix time handlers see through `current_clock`
+    const NOW: i64 = 1_700_000_000;
+
+    /// Serves the clock and rent sysvars to handlers run off-chain
+    struct TestSyscalls;
+
+    impl SyscallStubs for TestSyscalls {
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            let clock = Clock { slot: 1_000, unix_timestamp: NOW, ..Clock::default() };
+            unsafe { *(var_addr as *mut Clock) = clock };
+            0
+        }
+
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe { *(var_addr as *mut Rent) = Rent::default() };
+            0
+        }
+    }
+
+    /// Serves the compute meter and stack height, which `solana_program` reads
+    /// through its own stubs rather than anchor's
+    struct TestComputeUnits;
+
+    impl solana_program::program_stubs::SyscallStubs for TestComputeUnits {
+        /// Drops by `CU_PER_READ` on every read, so a measurement spanning two
+        /// reads consumes exactly that
+        fn sol_remaining_compute_units(&self) -> u64 {
+            REMAINING_CU.with(|remaining| {
+                remaining.set(remaining.get().saturating_sub(CU_PER_READ.get()));
+                remaining.get()
+            })
+        }
+
+        fn sol_get_stack_height(&self) -> u64 {
+            STACK_HEIGHT.get() as u64
+        }
+    }
+
+    thread_local! {
+        static REMAINING_CU: Cell<u64> = const { Cell::new(MAX_TRANSACTION_CU) };
+        /// Compute units each `sol_remaining_compute_units` read appears to cost
+        static CU_PER_READ: Cell<u64> = const { Cell::new(0) };
+        /// Stack height the instruction runs at; above the transaction level means via CPI
+        static STACK_HEIGHT: Cell<usize> = const {
+            Cell::new(solana_program::instruction::TRANSACTION_LEVEL_STACK_HEIGHT)
+        };
+    }
+
+    /// Compute budget of a whole transaction
+    const MAX_TRANSACTION_CU: u64 = 1_400_000;
+
+    /// Account info living for the rest of the test, as accounts borrow it for `'info`
+    fn account_info(key: Pubkey, owner: Pubkey, lamports: u64, data: Vec<u8>, is_signer: bool) -> &'static AccountInfo<'static> {
+        Box::leak(Box::new(AccountInfo::new(
+            Box::leak(Box::new(key)),
+            is_signer,
+            true,
+            Box::leak(Box::new(lamports)),
+            Box::leak(data.into_boxed_slice()),
+            Box::leak(Box::new(owner)),
+            key == anchor_lang::system_program::ID,
+        )))
+    }
+
+    /// Funded system account signing as payer
+    fn wallet() -> Signer<'static> {
+        let info = account_info(Pubkey::new_unique(), anchor_lang::system_program::ID, 10_000_000_000, Vec::new(), true);
+        Signer::try_from(info).unwrap()
+    }
+
+    /// Zeroed account at `key` standing in for one the instruction `init`s
+    /// 
+    /// `init` creates accounts through a system program CPI, which only runs
+    /// on-chain, so handlers are called directly on accounts built here.
+    fn new_account<T: AccountSerialize + AccountDeserialize + Owner + Clone>(key: Pubkey, space: usize) -> Account<'static, T> {
+        let info = account_info(key, crate::ID, Rent::default().minimum_balance(space), vec![0u8; space], false);
+        Account::try_from_unchecked(info).unwrap()
+    }
+
+    /// Re-read an account the previous instruction wrote back
+    fn reload<T: AccountSerialize + AccountDeserialize + Owner + Clone>(account: &Account<'static, T>) -> Account<'static, T> {
+        let info: &'static AccountInfo<'static> = Box::leak(Box::new(account.to_account_info()));
+        Account::try_from(info).unwrap()
+    }
+
+    /// Existing program account at `key` holding `value`
+    fn program_account<T: AccountSerialize>(key: Pubkey, value: &T, space: usize) -> &'static AccountInfo<'static> {
+        let mut data = Vec::with_capacity(space);
+        value.try_serialize(&mut data).unwrap();
+        data.resize(space, 0);
+        account_info(key, crate::ID, Rent::default().minimum_balance(space), data, false)
+    }
+
+    /// Uninitialized system account at `key`, e.g. an unused PDA
+    fn empty_account(key: Pubkey) -> UncheckedAccount<'static> {
+        UncheckedAccount::try_from(account_info(key, anchor_lang::system_program::ID, 0, Vec::new(), false))
+    }
+
+    fn system_program() -> Program<'static, System> {
+        Program::try_from(account_info(anchor_lang::system_program::ID, Pubkey::default(), 1, Vec::new(), false)).unwrap()
+    }
+
+    fn pda(seeds: &[&[u8]]) -> (Pubkey, u8) {
+        Pubkey::find_program_address(seeds, &crate::ID)
+    }
+
+    /// Run `handler` on `accounts`, writing them back on success like the entrypoint
+    /// 
+    /// Returns the accounts as the handler left them.
+    fn run<T, R>(
+        accounts: T,
+        bumps: T::Bumps,
+        handler: impl FnOnce(Context<'static, T>) -> Result<R>,
+    ) -> (Result<R>, &'static mut T)
+    where
+        T: anchor_lang::Bumps + Accounts<'static, T::Bumps> + AccountsExit<'static>,
+    {
+        run_with_remaining(accounts, Vec::new(), bumps, handler)
+    }
+
+    /// `run` with `remaining` passed as the instruction's remaining accounts
+    fn run_with_remaining<T, R>(
+        accounts: T,
+        remaining: Vec<AccountInfo<'static>>,
+        bumps: T::Bumps,
+        handler: impl FnOnce(Context<'static, T>) -> Result<R>,
+    ) -> (Result<R>, &'static mut T)
+    where
+        T: anchor_lang::Bumps + Accounts<'static, T::Bumps> + AccountsExit<'static>,
+    {
+        install_syscall_stubs();
+        
+        let accounts = Box::into_raw(Box::new(accounts));
+        let remaining = Box::leak(remaining.into_boxed_slice());
+        let result = handler(Context::new(&crate::ID, unsafe { &mut *accounts }, remaining, bumps));
+        let accounts = unsafe { &mut *accounts };
+        if result.is_ok() {
+            accounts.exit(&crate::ID).unwrap();
+        }
+        (result, accounts)
+    }
+
+    /// Route syscalls handlers and account constraints make to the test stubs
+    fn install_syscall_stubs() {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| {
+            set_syscall_stubs(Box::new(TestSyscalls));
+            solana_program::program_stubs::set_syscall_stubs(Box::new(TestComputeUnits));
+        });
+    }
+
+    /// A fresh registry in its own namespace
+    fn initialize_registry() -> &'static mut Initialize<'static> {
+        let namespace: [u8; 16] = Pubkey::new_unique().to_bytes()[..16].try_into().unwrap();
+        let (key, bump) = pda(&[b"registry", &namespace]);
+        let accounts = Initialize {
+            registry: new_account(key, IdentityRegistry::LEN),
+            authority: wallet(),
+            system_program: system_program(),
+        };
+        let (result, accounts) = run(accounts, InitializeBumps { registry: bump }, |ctx| {
+            contracts::initialize(ctx, namespace, 0)
+        });
+        result.unwrap();
+        accounts
+    }
+
+    /// Register a new wallet in `registry`
+    fn register(
+        registry: &Account<'static, IdentityRegistry>,
+        identity_commitment: [u8; 32],
+        merkle_root: [u8; 32],
+    ) -> (Result<()>, &'static mut RegisterIdentity<'static>) {
+        let user = wallet();
+        let namespace = registry.namespace;
+        let (identity, bump) = pda(&[b"identity", &namespace, user.key.as_ref()]);
+        let (reservation, _) = pda(&[b"reservation", &namespace, &identity_commitment]);
+        let accounts = RegisterIdentity {
+            identity: new_account(identity, Identity::LEN),
+            registry: reload(registry),
+            event_log: None,
+            reservation: empty_account(reservation),
+            did_record: None,
+            user,
+            system_program: system_program(),
+        };
+        run(accounts, RegisterIdentityBumps { identity: bump, reservation: 0 }, |ctx| {
+            contracts::register_identity(ctx, identity_commitment, merkle_root, None, None, None)
+        })
+    }
+
+    /// Register a wallet and mark `attributes` verified on it
+    fn register_verified(attributes: u8) -> &'static mut RegisterIdentity<'static> {
+        let registry = reload(&initialize_registry().registry);
+        let (result, registered) = register(&registry, [1u8; 32], [2u8; 32]);
+        result.unwrap();
+        registered.identity.is_verified = true;
+        registered.identity.attributes_verified = attributes;
+        registered.identity.exit(&crate::ID).unwrap();
+        registered
+    }
+
+    /// Nullifier the verification tests' proofs emit, as their first public input
+    const NULLIFIER: [u8; 32] = [1u8; 32];
+
+    /// `VerifyIdentity` accounts for the identity `register` created, claiming `NULLIFIER`
+    fn verify_accounts(registered: &RegisterIdentity<'static>) -> (VerifyIdentity<'static>, VerifyIdentityBumps) {
+        let namespace = registered.registry.namespace;
+        let accounts = VerifyIdentity {
+            identity: reload(&registered.identity),
+            user: registered.user.clone(),
+            verifier: wallet().to_account_info(),
+            registry: reload(&registered.registry),
+            verification_log: None,
+            event_log: None,
+            root_checkpoint: None,
+            input_layout: None,
+            slot_hashes: None,
+            fee_escrow: None,
+            nullifier_record: empty_account(NullifierRecord::address(&namespace, &NULLIFIER, &crate::ID).0),
+            evicted_record: None,
+            rate_window: None,
+            system_program: system_program(),
+        };
+        (accounts, VerifyIdentityBumps {})
+    }
+
+    /// Checkpoint holding only `root`, committed long enough ago to be stale
+    fn stale_checkpoint(namespace: [u8; 16], root: [u8; 32]) -> Account<'static, RootCheckpoint> {
+        let (key, bump) = pda(&[b"root_checkpoint", &namespace]);
+        let mut checkpoint = RootCheckpoint {
+            authority: Pubkey::default(),
+            max_root_age_slots: 10,
+            head: 0,
+            count: 0,
+            roots: [[0u8; 32]; RootCheckpoint::CAPACITY],
+            slots: [0u64; RootCheckpoint::CAPACITY],
+            bump,
+            namespace,
+        };
+        checkpoint.push(root, 0);
+        Account::try_from(program_account(key, &checkpoint, RootCheckpoint::LEN)).unwrap()
+    }
+
+    #[test]
+    fn test_verification_paths_share_proof_checks() {
+        let registered = register_verified(4);
+        let inputs = vec![1u8; 32];
+        
+        let (accounts, bumps) = verify_accounts(registered);
+        let (result, _) = run(accounts, bumps, |ctx| {
+            contracts::refresh_attribute(ctx, 4, vec![0u8; 255], inputs.clone(), NULLIFIER)
+        });
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::InvalidProof));
+        
+        let (accounts, bumps) = verify_accounts(registered);
+        let (result, _) = run(accounts, bumps, |ctx| {
+            contracts::verify_identity(ctx, vec![0u8; 64], inputs.clone(), 4, NULLIFIER)
+        });
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::InvalidProof));
+        
+        let (mut accounts, bumps) = verify_accounts(registered);
+        accounts.root_checkpoint = Some(stale_checkpoint(accounts.registry.namespace, [2u8; 32]));
+        let (result, _) = run(accounts, bumps, |ctx| {
+            contracts::verify_identity(ctx, vec![0u8; 256], inputs.clone(), 4, NULLIFIER)
+        });
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::StaleMerkleRoot));
+    }
+
+    #[test]
+    fn test_verification_accepts_compressed_proofs() {
+        use solana_bn254::compression::prelude::{alt_bn128_g1_compress, alt_bn128_g2_compress};
+        
+        let registered = register_verified(4);
+        let inputs = [NULLIFIER, registered.identity.nonce_field_element()].concat();
+        // Curve generators decompress, but don't form a valid proof
+        let g1 = alt_bn128_g1_compress(&groth16_verifier::tests::G1_GEN).unwrap();
+        let g2 = alt_bn128_g2_compress(&groth16_verifier::tests::G2_GEN).unwrap();
+        let compressed = [&g1[..], &g2[..], &g1[..]].concat();
+        assert_eq!(compressed.len(), groth16_verifier::COMPRESSED_PROOF_LEN);
+        
+        // Past the length check and decompression, the pairing rejects it
+        let (accounts, bumps) = verify_accounts(registered);
+        let (result, accounts) = run(accounts, bumps, |ctx| {
+            contracts::verify_identity(ctx, compressed.clone(), inputs.clone(), 4, NULLIFIER)
+        });
+        assert!(!result.unwrap());
+        assert_eq!(accounts.identity.verify_attempts, 1);
+        
+        let mut malformed = compressed.clone();
+        malformed[..32].copy_from_slice(&[0x3f; 32]);
+        let (accounts, bumps) = verify_accounts(registered);
+        let (result, _) = run(accounts, bumps, |ctx| {
+            contracts::refresh_attribute(ctx, 4, malformed.clone(), inputs.clone(), NULLIFIER)
+        });
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::MalformedProofComponent));
+    }
+
+    /// Well-formed proof that doesn't verify: the curve generators in every slot
+    fn rejected_proof() -> Vec<u8> {
+        use groth16_verifier::tests::{G1_GEN, G2_GEN};
+        [&G1_GEN[..], &G2_GEN[..], &G1_GEN[..]].concat()
+    }
+
+    #[test]
+    fn test_verify_identity_counts_failed_attempts() {
+        let registered = register_verified(4);
+        let inputs = [NULLIFIER, registered.identity.nonce_field_element()].concat();
+        
+        for attempts in 1..=2 {
+            let (accounts, bumps) = verify_accounts(registered);
+            let (result, accounts) = run(accounts, bumps, |ctx| {
+                contracts::verify_identity(ctx, rejected_proof(), inputs.clone(), 4, NULLIFIER)
+            });
+            assert!(!result.unwrap());
+            registered.identity = reload(&accounts.identity);
+            assert_eq!(registered.identity.verify_attempts, attempts);
+        }
+    }
+
+    #[test]
+    fn test_rejected_verify_identity_commits_its_settlement() {
+        let registered = register_verified(4);
+        let inputs = [NULLIFIER, registered.identity.nonce_field_element()].concat();
+        
+        // The instruction succeeds with `false`, so the settled attempt is
+        // written back rather than rolled back with a failed instruction
+        let (accounts, bumps) = verify_accounts(registered);
+        let (result, accounts) = run(accounts, bumps, |ctx| {
+            contracts::verify_identity(ctx, rejected_proof(), inputs.clone(), 4, NULLIFIER)
+        });
+        assert!(!result.unwrap());
+        assert_eq!(reload(&accounts.identity).verify_attempts, 1);
+        assert!(accounts.nullifier_record.data_is_empty());
+        assert_eq!(reload(&accounts.registry).nullifier_count, registered.registry.nullifier_count);
+        
+        // Escrowing a fee is a system CPI, which only runs on-chain, so the
+        // forfeit that written-back settlement carries is applied by hand
+        let namespace = accounts.registry.namespace;
+        let (key, bump) = pda(&[b"fee_escrow", &namespace]);
+        let escrow = FeeEscrow { namespace, forfeited: 0, bump };
+        let info = program_account(key, &escrow, FeeEscrow::LEN);
+        **info.try_borrow_mut_lamports().unwrap() += 5_000; // the escrowed fee
+        accounts.fee_escrow = Some(Account::try_from(info).unwrap());
+        let user_lamports = accounts.user.lamports();
+        accounts.settle_fee(5_000, false).unwrap();
+        accounts.exit(&crate::ID).unwrap();
+        assert_eq!(accounts.user.lamports(), user_lamports);
+        assert_eq!(Account::<FeeEscrow>::try_from(info).unwrap().forfeited, 5_000);
+    }
+
+    /// Escrowing a fee is a system CPI, which only runs on-chain; a failed
+    /// instruction rolls it back, so the fee stays with the user exactly when
+    /// `verify_identity` fails rather than returning `false`
+    #[test]
+    fn test_malformed_proof_fails_verify_identity_and_keeps_the_fee() {
+        let registered = register_verified(4);
+        let inputs = [NULLIFIER, registered.identity.nonce_field_element()].concat();
+        let verify = |proof: Vec<u8>| {
+            let (accounts, bumps) = verify_accounts(registered);
+            run(accounts, bumps, |ctx| contracts::verify_identity(ctx, proof, inputs.clone(), 4, NULLIFIER))
+        };
+        
+        // (1, 1) isn't on the curve, so the proof never reaches the pairing
+        let mut malformed = rejected_proof();
+        malformed[..64].fill(0);
+        malformed[31] = 1;
+        malformed[63] = 1;
+        let (result, accounts) = verify(malformed);
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::MalformedProofComponent));
+        assert_eq!(reload(&accounts.identity).verify_attempts, 0);
+        
+        // Nor does an attribute type without a single circuit
+        let (accounts, bumps) = verify_accounts(registered);
+        let (result, _) = run(accounts, bumps, |ctx| {
+            contracts::verify_identity(ctx, rejected_proof(), inputs.clone(), 1 | 4, NULLIFIER)
+        });
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::CompositeAttributeType));
+        
+        // Only a proof the pairing rejects settles as `false`, forfeiting the fee
+        let (result, accounts) = verify(rejected_proof());
+        assert!(!result.unwrap());
+        assert_eq!(reload(&accounts.identity).verify_attempts, 1);
+        assert!(!pairing_verdict(Err(error!(ErrorCode::ProofVerificationFailed))).unwrap());
+        assert_eq!(
+            pairing_verdict(Err(error!(ErrorCode::MalformedProofComponent))).unwrap_err(),
+            error!(ErrorCode::MalformedProofComponent)
+        );
+    }
+
+    #[test]
+    fn test_refresh_attribute_rejects_malformed_proofs() {
+        let registered = register_verified(4);
+        let inputs = [NULLIFIER, registered.identity.nonce_field_element()].concat();
+        
+        for length in [0, 64, 127, 255, 257, 512] {
+            let (accounts, bumps) = verify_accounts(registered);
+            let (result, accounts) = run(accounts, bumps, |ctx| {
+                contracts::refresh_attribute(ctx, 4, vec![0u8; length], inputs.clone(), NULLIFIER)
+            });
+            assert_eq!(result.unwrap_err(), error!(ErrorCode::InvalidProof), "{length}-byte proof");
+            assert_eq!(accounts.identity.verify_attempts, 0);
+        }
+        
+        // Only attributes the identity already holds can be refreshed
+        let (accounts, bumps) = verify_accounts(registered);
+        let (result, _) = run(accounts, bumps, |ctx| {
+            contracts::refresh_attribute(ctx, 1, vec![0u8; 256], inputs.clone(), NULLIFIER)
+        });
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::InvalidVerificationStatus));
+    }
+
+    #[test]
+    fn test_check_attestation_reads_the_identitys_state() {
+        let registered = register_verified(4);
+        let identity_key = registered.identity.key();
+        let (key, bump) = pda(&[b"attestation", identity_key.as_ref(), &[4]]);
+        let attestation = Attestation::issue(identity_key, &registered.identity, 4, Pubkey::default(), NOW, bump).unwrap();
+        let attestation = program_account(key, &attestation, Attestation::LEN);
+        let check = |identity: &Account<'static, Identity>| {
+            let accounts = ReadAttestation {
+                attestation: Account::try_from(attestation).unwrap(),
+                identity: reload(identity),
+            };
+            run(accounts, ReadAttestationBumps {}, contracts::check_attestation).0
+        };
+        assert!(check(&registered.identity).unwrap());
+        
+        registered.identity.revoke();
+        registered.identity.mark_verified(4, NOW).unwrap();
+        registered.identity.exit(&crate::ID).unwrap();
+        assert_eq!(
+            check(&registered.identity).unwrap_err(),
+            error!(ErrorCode::CredentialRevoked)
+        );
+    }
+
+    #[test]
+    fn test_revoke_identity_releases_its_did() {
+        let registered = register_verified(4);
+        let mut did = [0u8; 64];
+        did[..12].copy_from_slice(b"did:sol:test");
+        registered.identity.did = did;
+        registered.identity.exit(&crate::ID).unwrap();
+        let namespace = registered.registry.namespace;
+        let (key, bump) = DidRecord::address(&namespace, &did, &crate::ID);
+        let record = DidRecord {
+            namespace,
+            did_hash: compression::did_hash(&did),
+            did,
+            identity: registered.identity.key(),
+            bump,
+        };
+        let record = program_account(key, &record, DidRecord::LEN);
+        let accounts = |did_record: Option<Account<'static, DidRecord>>| RevokeIdentity {
+            identity: reload(&registered.identity),
+            user: registered.user.clone(),
+            registry: reload(&registered.registry),
+            verification_log: None,
+            event_log: None,
+            did_record,
+        };
+        
+        let (result, _) = run(accounts(None), RevokeIdentityBumps {}, contracts::revoke_identity);
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::InvalidDid));
+        
+        // Closing resizes the record, which only works on runtime-serialized
+        // accounts, so the handler runs without the exit that closes it
+        let revoking = Box::into_raw(Box::new(accounts(Some(Account::try_from(record).unwrap()))));
+        contracts::revoke_identity(Context::new(&crate::ID, unsafe { &mut *revoking }, &[], RevokeIdentityBumps {}))
+            .unwrap();
+        let revoking = unsafe { &*revoking };
+        assert!(!revoking.identity.has_did());
+        assert!(!revoking.identity.is_verified);
+    }
+
+    #[test]
+    fn test_require_verified_rejects_expired_identities() {
+        let registered = register_verified(4);
+        registered.identity.mark_verified(4, NOW).unwrap();
+        registered.identity.exit(&crate::ID).unwrap();
+        let check = |identity: &Account<'static, Identity>| {
+            let accounts = ReadIdentity { identity: reload(identity) };
+            run(accounts, ReadIdentityBumps {}, |ctx| contracts::require_verified(ctx, 4)).0
+        };
+        assert!(check(&registered.identity).unwrap());
+        
+        registered.identity.expires_at = NOW;
+        registered.identity.exit(&crate::ID).unwrap();
+        assert_eq!(
+            check(&registered.identity).unwrap_err(),
+            error!(ErrorCode::IdentityExpired)
+        );
+    }
+
+    #[test]
+    fn test_authority_freeze_blocks_sessions_until_it_lifts_it() {
+        let registered = register_verified(4);
+        let namespace = registered.registry.namespace;
+        let authority = account_info(registered.registry.authority, anchor_lang::system_program::ID, 0, Vec::new(), true);
+        let authority = Signer::try_from(authority).unwrap();
+        let hold = |signer: &Signer<'static>, handler: fn(Context<'static, FreezeIdentity<'static>>) -> Result<()>| {
+            let accounts = FreezeIdentity {
+                identity: reload(&registered.identity),
+                registry: reload(&registered.registry),
+                signer: signer.clone(),
+            };
+            run(accounts, FreezeIdentityBumps {}, handler).0
+        };
+        let (key, bump) = Session::address(&namespace, registered.user.key, &[1u8; 32], &crate::ID);
+        let create = || {
+            let accounts = CreateSession {
+                session: new_account(key, Session::LEN),
+                identity: reload(&registered.identity),
+                registry: reload(&registered.registry),
+                user: registered.user.clone(),
+                system_program: system_program(),
+            };
+            run(accounts, CreateSessionBumps { session: bump }, |ctx| {
+                contracts::create_session(ctx, [1u8; 32], NOW + 3_600, Vec::new(), None)
+            })
+            .0
+        };
+        
+        hold(&authority, contracts::freeze_identity).unwrap();
+        assert_eq!(create().unwrap_err(), error!(ErrorCode::IdentityFrozen));
+        let inputs = [NULLIFIER, registered.identity.nonce_field_element()].concat();
+        let (accounts, bumps) = verify_accounts(registered);
+        let (result, _) = run(accounts, bumps, |ctx| {
+            contracts::verify_identity(ctx, vec![0u8; 256], inputs, 4, NULLIFIER)
+        });
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::IdentityFrozen));
+        
+        // The owner can't lift the authority's hold
+        assert_eq!(
+            hold(&registered.user, contracts::unfreeze_identity).unwrap_err(),
+            error!(ErrorCode::FrozenByAuthority)
+        );
+        assert_eq!(create().unwrap_err(), error!(ErrorCode::IdentityFrozen));
+        
+        hold(&authority, contracts::unfreeze_identity).unwrap();
+        assert!(reload(&registered.identity).has_attributes(4));
+        create().unwrap();
+    }
+
+    #[test]
+    fn test_sessions_check_what_they_disclose_for_freshness() {
+        let registered = register_verified(4);
+        registered.registry.verification_ttl = 3_600;
+        registered.registry.exit(&crate::ID).unwrap();
+        registered.identity.mark_verified(4, NOW - 3_601).unwrap();
+        registered.identity.exit(&crate::ID).unwrap();
+        let namespace = registered.registry.namespace;
+        let (key, bump) = Session::address(&namespace, registered.user.key, &[1u8; 32], &crate::ID);
+        let create = |identity: &Account<'static, Identity>| {
+            let accounts = CreateSession {
+                session: new_account(key, Session::LEN),
+                identity: reload(identity),
+                registry: reload(&registered.registry),
+                user: registered.user.clone(),
+                system_program: system_program(),
+            };
+            run(accounts, CreateSessionBumps { session: bump }, |ctx| {
+                contracts::create_session(ctx, [1u8; 32], NOW + 3_600, Vec::new(), None)
+            })
+        };
+        
+        // No scope names the stale attribute, but the session would disclose it
+        let (result, _) = create(&registered.identity);
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::AttributeExpired));
+        let accounts = CreateSessionsBatch {
+            identity: reload(&registered.identity),
+            registry: reload(&registered.registry),
+            user: registered.user.clone(),
+            system_program: system_program(),
+        };
+        let scope = Session::attribute_scope(1).unwrap();
+        let requests = vec![SessionRequest { session_id: [1u8; 32], expiry: NOW + 3_600, scopes: vec![scope] }];
+        let sessions = vec![empty_account(key).to_account_info()];
+        let (result, _) = run_with_remaining(accounts, sessions, CreateSessionsBatchBumps {}, |ctx| {
+            contracts::create_sessions_batch(ctx, requests, None)
+        });
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::AttributeExpired));
+        
+        registered.identity.mark_verified(4, NOW).unwrap();
+        registered.identity.exit(&crate::ID).unwrap();
+        let (result, created) = create(&registered.identity);
+        result.unwrap();
+        assert_eq!(created.session.disclosed_attributes, 4);
+        assert_eq!(created.session.disclosed_timestamps[2], NOW);
+    }
+
+    #[test]
+    fn test_session_batch_rejects_unscoped_and_taken_sessions() {
+        let registered = register_verified(4);
+        let namespace = registered.registry.namespace;
+        let request = |id: u8, scopes: Vec<[u8; 16]>| SessionRequest {
+            session_id: [id; 32],
+            expiry: NOW + 3_600,
+            scopes,
+        };
+        let create = |requests: Vec<SessionRequest>, sessions: Vec<AccountInfo<'static>>| {
+            let accounts = CreateSessionsBatch {
+                identity: reload(&registered.identity),
+                registry: reload(&registered.registry),
+                user: registered.user.clone(),
+                system_program: system_program(),
+            };
+            run_with_remaining(accounts, sessions, CreateSessionsBatchBumps {}, |ctx| {
+                contracts::create_sessions_batch(ctx, requests, None)
+            })
+            .0
+        };
+        let (key, _) = Session::address(&namespace, registered.user.key, &[1u8; 32], &crate::ID);
+        let scope = Session::attribute_scope(4).unwrap();
+        
+        let unscoped = create(vec![request(1, Vec::new())], vec![empty_account(key).to_account_info()]);
+        assert_eq!(unscoped.unwrap_err(), error!(ErrorCode::InvalidSession));
+        
+        let elsewhere = empty_account(Pubkey::new_unique()).to_account_info();
+        let misplaced = create(vec![request(1, vec![scope])], vec![elsewhere]);
+        assert_eq!(misplaced.unwrap_err(), error!(ErrorCode::InvalidSession));
+        
+        // An id already in use fails the batch before anything is created
+        let taken = new_account::<Session>(key, Session::LEN).to_account_info();
+        let reused = create(vec![request(1, vec![scope])], vec![taken]);
+        assert_eq!(reused.unwrap_err(), error!(ErrorCode::InvalidSession));
+    }
+
+    #[test]
+    fn test_revoke_identity_counts_into_the_verification_log() {
+        let registered = register_verified(4);
+        let initialized = initialize_verification_log(registered);
+        let accounts = |verification_log: Option<Account<'static, VerificationLog>>| RevokeIdentity {
+            identity: reload(&initialized.identity),
+            user: registered.user.clone(),
+            registry: reload(&registered.registry),
+            verification_log,
+            event_log: None,
+            did_record: None,
+        };
+        
+        let (result, _) = run(accounts(None), RevokeIdentityBumps {}, contracts::revoke_identity);
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::MissingVerificationLog));
+        
+        let log = reload(&initialized.verification_log);
+        let (result, revoked) = run(accounts(Some(log)), RevokeIdentityBumps {}, contracts::revoke_identity);
+        result.unwrap();
+        let log = revoked.verification_log.as_ref().unwrap();
+        assert_eq!(log.revocations, 1);
+        assert_eq!(log.last_revoked_at, NOW);
+        assert_eq!(log.entries()[0].attribute_type, VerificationLogEntry::REVOKED);
+    }
+
+    /// Resolve `VerifyIdentity` signed by `user` from raw infos through its
+    /// constraints, as the entrypoint does
+    fn try_verify_accounts(
+        identity: &'static AccountInfo<'static>,
+        user: &Signer<'static>,
+        registered: &RegisterIdentity<'static>,
+    ) -> Result<()> {
+        let absent = account_info(crate::ID, Pubkey::default(), 0, Vec::new(), false).clone();
+        let infos = vec![
+            identity.clone(),
+            user.to_account_info(),
+            wallet().to_account_info(),
+            registered.registry.to_account_info(),
+            absent.clone(),
+            absent.clone(),
+            absent.clone(),
+            absent.clone(),
+            absent.clone(),
+            absent.clone(),
+            empty_account(Pubkey::new_unique()).to_account_info(),
+            absent.clone(),
+            absent,
+            system_program().to_account_info(),
+        ];
+        let infos: &'static [AccountInfo<'static>] = Box::leak(infos.into_boxed_slice());
+        let mut bumps = VerifyIdentityBumps {};
+        VerifyIdentity::try_accounts(&crate::ID, &mut &infos[..], &[], &mut bumps, &mut std::collections::BTreeSet::new())
+            .map(|_| ())
+    }
+
+    #[test]
+    fn test_verify_identity_requires_a_registered_identity() {
+        let registered = register_verified(4);
+        let identity: &'static AccountInfo<'static> = Box::leak(Box::new(registered.identity.to_account_info()));
+        assert!(try_verify_accounts(identity, &registered.user, registered).is_ok());
+        
+        // An unregistered identity address is never created by verifying
+        let missing = account_info(registered.identity.key(), anchor_lang::system_program::ID, 0, Vec::new(), false);
+        assert_eq!(
+            try_verify_accounts(missing, &registered.user, registered).unwrap_err(),
+            error!(anchor_lang::error::ErrorCode::AccountNotInitialized)
+        );
+        
+        let mut cleared = (*registered.identity).clone();
+        cleared.owner = Pubkey::default();
+        let cleared = program_account(registered.identity.key(), &cleared, Identity::LEN);
+        assert_eq!(
+            try_verify_accounts(cleared, &registered.user, registered).unwrap_err(),
+            error!(anchor_lang::error::ErrorCode::ConstraintSeeds)
+        );
+    }
+
+    #[test]
+    fn test_guardian_verification_takes_the_shared_path() {
+        let registered = register_verified(4);
+        let guardian = wallet();
+        registered.identity.guardian = guardian.key();
+        registered.identity.exit(&crate::ID).unwrap();
+        let inputs = [NULLIFIER, registered.identity.nonce_field_element()].concat();
+        
+        // Only the owner or the recorded guardian resolves the accounts
+        let identity: &'static AccountInfo<'static> = Box::leak(Box::new(registered.identity.to_account_info()));
+        assert_eq!(
+            try_verify_accounts(identity, &wallet(), registered).unwrap_err(),
+            error!(ErrorCode::UnauthorizedAccess)
+        );
+        assert!(try_verify_accounts(identity, &guardian, registered).is_ok());
+        
+        // The owner can't take the guardian entry point
+        let (accounts, bumps) = verify_accounts(registered);
+        let (result, _) = run(accounts, bumps, |ctx| {
+            contracts::verify_identity_as_guardian(ctx, vec![0u8; 256], inputs.clone(), 4, NULLIFIER)
+        });
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::UnauthorizedAccess));
+        
+        // The guardian gets root freshness like every other verification
+        let (mut accounts, bumps) = verify_accounts(registered);
+        accounts.user = guardian.clone();
+        accounts.root_checkpoint = Some(stale_checkpoint(accounts.registry.namespace, [2u8; 32]));
+        let (result, _) = run(accounts, bumps, |ctx| {
+            contracts::verify_identity_as_guardian(ctx, vec![0u8; 256], inputs.clone(), 4, NULLIFIER)
+        });
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::StaleMerkleRoot));
+        
+        // Paths that create or close accounts keyed by the owner stay owner-only
+        let (mut accounts, _) = verify_accounts(registered);
+        accounts.user = guardian;
+        assert_eq!(accounts.require_owner().unwrap_err(), error!(ErrorCode::UnauthorizedAccess));
+    }
+
+    #[test]
+    fn test_paused_registry_rejects_verification() {
+        let registered = register_verified(4);
+        registered.registry.paused_instructions = IdentityRegistry::PAUSE_VERIFY;
+        registered.registry.exit(&crate::ID).unwrap();
+        
+        let identity: &'static AccountInfo<'static> = Box::leak(Box::new(registered.identity.to_account_info()));
+        assert_eq!(
+            try_verify_accounts(identity, &registered.user, registered).unwrap_err(),
+            error!(ErrorCode::InstructionPaused)
+        );
+    }
+
+    #[test]
+    fn test_event_log_is_required_once_initialized() {
+        let mut registry = reload(&initialize_registry().registry);
+        registry.event_logged = true;
+        registry.exit(&crate::ID).unwrap();
+        let (result, _) = register(&registry, [1u8; 32], [2u8; 32]);
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::MissingEventLog));
+        
+        let registered = register_verified(4);
+        registered.registry.event_logged = true;
+        registered.registry.exit(&crate::ID).unwrap();
+        let (mut accounts, _) = verify_accounts(registered);
+        assert_eq!(
+            accounts.record_verification(4, 0).unwrap_err(),
+            error!(ErrorCode::MissingEventLog)
+        );
+    }
+
+    /// The identity `register` created, with its verification log initialized
+    fn initialize_verification_log(
+        registered: &RegisterIdentity<'static>,
+    ) -> &'static mut InitializeVerificationLog<'static> {
+        let (key, bump) = pda(&[b"verification_log", registered.identity.key().as_ref()]);
+        let accounts = InitializeVerificationLog {
+            verification_log: new_account(key, VerificationLog::LEN),
+            identity: reload(&registered.identity),
+            user: registered.user.clone(),
+            system_program: system_program(),
+        };
+        let (result, initialized) = run(
+            accounts,
+            InitializeVerificationLogBumps { verification_log: bump },
+            contracts::initialize_verification_log,
+        );
+        result.unwrap();
+        initialized
+    }
+
+    #[test]
+    fn test_verification_log_is_required_once_initialized() {
+        let registered = register_verified(4);
+        let initialized = initialize_verification_log(registered);
+        registered.identity = reload(&initialized.identity);
+        assert!(registered.identity.verification_logged);
+        
+        let (mut accounts, _) = verify_accounts(registered);
+        assert_eq!(
+            accounts.record_verification(4, 0).unwrap_err(),
+            error!(ErrorCode::MissingVerificationLog)
+        );
+        // Batches can't leave it out with the program id placeholder either
+        let placeholder = account_info(crate::ID, Pubkey::default(), 0, Vec::new(), false);
+        assert_eq!(
+            verification_log_of(&registered.identity, placeholder).map(|_| ()).unwrap_err(),
+            error!(ErrorCode::MissingVerificationLog)
+        );
+        let log = Box::leak(Box::new(initialized.verification_log.to_account_info()));
+        assert!(verification_log_of(&registered.identity, log).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_global_cap_counts_in_the_windows_account() {
+        let registered = register_verified(4);
+        registered.registry.global_verification_cap = 3;
+        registered.registry.global_window_slots = 100;
+        registered.registry.exit(&crate::ID).unwrap();
+        let namespace = registered.registry.namespace;
+        let window = registered.registry.rate_window_of(1000).unwrap();
+        
+        let (mut accounts, _) = verify_accounts(registered);
+        assert_eq!(
+            accounts.record_verification(4, 0).unwrap_err(),
+            error!(ErrorCode::InvalidRateWindow)
+        );
+        
+        let (key, bump) = RateWindow::address(&namespace, window, &crate::ID);
+        let counter = RateWindow { namespace, window, count: 1, bump };
+        let rate_window = program_account(key, &counter, RateWindow::LEN);
+        let record = |verifications: u32| {
+            record_global_verifications(
+                &registered.registry,
+                verifications,
+                Some(rate_window),
+                &registered.user,
+                registered.system_program.as_ref(),
+                1000,
+            )
+        };
+        record(1).unwrap();
+        assert_eq!(
+            record(2).unwrap_err(),
+            error!(ErrorCode::GlobalRateLimited)
+        );
+        record(1).unwrap();
+        let counter = RateWindow::try_deserialize(&mut &rate_window.try_borrow_data().unwrap()[..]).unwrap();
+        assert_eq!(counter.count, 3);
+        
+        // Another window's account doesn't count for this one
+        let (other, _) = RateWindow::address(&namespace, window + 1, &crate::ID);
+        let other = program_account(other, &counter, RateWindow::LEN);
+        assert_eq!(
+            record_global_verifications(
+                &registered.registry,
+                1,
+                Some(other),
+                &registered.user,
+                registered.system_program.as_ref(),
+                1000,
+            )
+            .unwrap_err(),
+            error!(ErrorCode::InvalidRateWindow)
+        );
+    }
+
+    /// `VerifyBatchProof` accounts under a batch key built from the curve generators,
+    /// which `groth16_verifier::tests::proof_for_single_input` answers
+    fn batch_accounts(registry: &Account<'static, IdentityRegistry>) -> VerifyBatchProof<'static> {
+        use groth16_verifier::tests::{G1_GEN, G2_GEN, TEST_IC};
+        
+        let namespace = registry.namespace;
+        let (vk, vk_bump) = pda(&[b"circuit_vk", &namespace, &[CircuitVerificationKey::BATCH]]);
+        let circuit_vk = CircuitVerificationKey {
+            circuit_id: CircuitVerificationKey::BATCH,
+            alpha_g1: G1_GEN,
+            beta_g2: G2_GEN,
+            gamma_g2: G2_GEN,
+            delta_g2: G2_GEN,
+            ic: TEST_IC.to_vec(),
+            bump: vk_bump,
+            namespace,
+        };
+        VerifyBatchProof {
+            circuit_vk: Account::try_from(program_account(vk, &circuit_vk, CircuitVerificationKey::LEN)).unwrap(),
+            registry: reload(registry),
+            root_checkpoint: None,
+            event_log: None,
+            rate_window: None,
+            payer: wallet(),
+            system_program: system_program(),
+        }
+    }
+
+    /// Empty `VerificationLog` of `identity`
+    fn verification_log(identity: &Account<'static, Identity>) -> &'static AccountInfo<'static> {
+        let (key, bump) = pda(&[b"verification_log", identity.key().as_ref()]);
+        let log = VerificationLog {
+            identity: identity.key(),
+            head: 0,
+            count: 0,
+            entries: [VerificationLogEntry::default(); VerificationLog::CAPACITY],
+            bump,
+            revocations: 0,
+            last_revoked_at: 0,
+        };
+        program_account(key, &log, VerificationLog::LEN)
+    }
+
+    /// Public inputs of `identity`'s entry in a batch proof claiming `nullifier`
+    fn batch_user_inputs(identity: &Identity, nullifier: [u8; 32]) -> Vec<u8> {
+        [
+            pubkey_to_field(&identity.owner).to_vec(),
+            nullifier.to_vec(),
+            identity.nonce_field_element().to_vec(),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_batch_proof_checks_its_account_layout() {
+        let registered = register_verified(4);
+        let namespace = registered.registry.namespace;
+        let batch = |remaining: Vec<AccountInfo<'static>>, nullifiers: Vec<[u8; 32]>| {
+            let accounts = batch_accounts(&registered.registry);
+            let user_inputs = vec![batch_user_inputs(&registered.identity, NULLIFIER); nullifiers.len().max(1)];
+            run_with_remaining(accounts, remaining, VerifyBatchProofBumps {}, |ctx| {
+                contracts::verify_batch_proof(ctx, vec![0u8; 256], user_inputs, 4, nullifiers)
+            })
+            .0
+        };
+        let identity = registered.identity.to_account_info();
+        let record = empty_account(NullifierRecord::address(&namespace, &NULLIFIER, &crate::ID).0).to_account_info();
+        let no_log = account_info(crate::ID, Pubkey::default(), 0, Vec::new(), false).clone();
+        
+        assert_eq!(
+            batch(vec![identity.clone(), record.clone(), no_log.clone()], vec![NULLIFIER, NULLIFIER]).unwrap_err(),
+            error!(ErrorCode::BatchLengthMismatch)
+        );
+        // Each identity needs its log slot after the records
+        assert_eq!(
+            batch(vec![identity.clone(), record.clone()], vec![NULLIFIER]).unwrap_err(),
+            error!(ErrorCode::BatchLengthMismatch)
+        );
+        assert_eq!(
+            batch(
+                vec![identity.clone(), identity.clone(), record.clone(), record, no_log.clone(), no_log],
+                vec![NULLIFIER, NULLIFIER]
+            )
+            .unwrap_err(),
+            error!(ErrorCode::DuplicateAccounts)
+        );
+        
+        // A log slot must be the identity's own log
+        let other = register_verified(4);
+        let log = verification_log(&other.identity);
+        assert!(verification_log_of(&other.identity, log).unwrap().is_some());
+        assert_eq!(
+            verification_log_of(&registered.identity, log).map(|_| ()).unwrap_err(),
+            error!(ErrorCode::UnauthorizedAccess)
+        );
+    }
+
+    /// Identities registered in one registry for a batch proof
+    struct BatchUsers {
+        registry: Account<'static, IdentityRegistry>,
+        identities: Vec<Account<'static, Identity>>,
+        logs: Vec<&'static AccountInfo<'static>>,
+        nullifiers: Vec<[u8; 32]>,
+    }
+
+    /// Two identities, each with its own verification log and nullifier
+    fn batch_users() -> BatchUsers {
+        let mut registry = reload(&initialize_registry().registry);
+        let mut identities = Vec::new();
+        for commitment in [[1u8; 32], [3u8; 32]] {
+            let (result, registered) = register(&registry, commitment, [2u8; 32]);
+            result.unwrap();
+            registry = reload(&registered.registry);
+            identities.push(reload(&registered.identity));
+        }
+        let logs = identities.iter().map(verification_log).collect();
+        BatchUsers { registry, identities, logs, nullifiers: vec![NULLIFIER, [4u8; 32]] }
+    }
+
+    /// Run `verify_batch_proof` for every user with `proof` of their inputs
+    fn verify_batch(
+        users: &BatchUsers,
+        proof: impl FnOnce(&[Vec<u8>]) -> Vec<u8>,
+    ) -> (Result<()>, &'static mut VerifyBatchProof<'static>) {
+        let namespace = users.registry.namespace;
+        let user_inputs: Vec<Vec<u8>> = users
+            .identities
+            .iter()
+            .zip(&users.nullifiers)
+            .map(|(identity, nullifier)| batch_user_inputs(identity, *nullifier))
+            .collect();
+        let records = users
+            .nullifiers
+            .iter()
+            .map(|nullifier| empty_account(NullifierRecord::address(&namespace, nullifier, &crate::ID).0).to_account_info());
+        let remaining = users
+            .identities
+            .iter()
+            .map(|identity| identity.to_account_info())
+            .chain(records)
+            .chain(users.logs.iter().map(|log| (*log).clone()))
+            .collect();
+        let proof = proof(&user_inputs);
+        let nullifiers = users.nullifiers.clone();
+        run_with_remaining(batch_accounts(&users.registry), remaining, VerifyBatchProofBumps {}, |ctx| {
+            contracts::verify_batch_proof(ctx, proof, user_inputs, 4, nullifiers)
+        })
+    }
+
+    #[test]
+    fn test_batch_proof_verifies_every_listed_identity() {
+        let users = batch_users();
+        
+        let (result, accounts) = verify_batch(&users, |user_inputs| {
+            groth16_verifier::tests::proof_for_single_input(&batch_public_input(4, user_inputs).unwrap())
+        });
+        result.unwrap();
+        
+        for ((identity, log), nullifier) in users.identities.iter().zip(&users.logs).zip(&users.nullifiers) {
+            let identity = reload(identity);
+            assert!(identity.is_verified);
+            assert_eq!(identity.attributes_verified & 4, 4);
+            assert_eq!(identity.verification_nonce, 1);
+            assert!(accounts.registry.contains_nullifier(nullifier));
+            
+            let log = Account::<VerificationLog>::try_from(log).unwrap();
+            assert_eq!(log.count, 1);
+            assert_eq!(log.entries[0].attribute_type, 4);
+            assert_eq!(log.entries[0].timestamp, NOW);
+        }
+    }
+
+    #[test]
+    fn test_rejected_batch_proof_verifies_no_identity() {
+        let users = batch_users();
+        
+        let (result, _) = verify_batch(&users, |_| rejected_proof());
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::ProofVerificationFailed));
+        
+        let registry = reload(&users.registry);
+        for ((identity, log), nullifier) in users.identities.iter().zip(&users.logs).zip(&users.nullifiers) {
+            let identity = reload(identity);
+            assert!(!identity.is_verified);
+            assert_eq!(identity.attributes_verified, 0);
+            assert_eq!(identity.verification_nonce, 0);
+            assert!(!registry.contains_nullifier(nullifier));
+            assert_eq!(Account::<VerificationLog>::try_from(log).unwrap().count, 0);
+        }
+    }
+
+    #[test]
+    fn test_root_checkpoint_is_required_once_initialized() {
+        let registered = register_verified(4);
+        registered.registry.root_checkpointed = true;
+        registered.registry.exit(&crate::ID).unwrap();
+        let inputs = [NULLIFIER, registered.identity.nonce_field_element()].concat();
+        
+        let (accounts, bumps) = verify_accounts(registered);
+        let (result, _) = run(accounts, bumps, |ctx| {
+            contracts::verify_identity(ctx, vec![0u8; 256], inputs.clone(), 4, NULLIFIER)
+        });
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::MissingRootCheckpoint));
+    }
+
+    #[test]
+    fn test_governed_registry_commits_roots_only_through_governance() {
+        install_syscall_stubs();
+        let initialized = initialize_registry();
+        let wallet = initialized.authority.key();
+        let registry_key = initialized.registry.key();
+        let namespace = initialized.registry.namespace;
+        
+        // Initialized while the wallet was still the authority
+        let mut checkpoint = stale_checkpoint(namespace, [2u8; 32]);
+        checkpoint.authority = wallet;
+        checkpoint.exit(&crate::ID).unwrap();
+        let mut registry = reload(&initialized.registry);
+        let governance = registry.transfer_to_governance(&registry_key, &Pubkey::new_unique()).unwrap();
+        registry.exit(&crate::ID).unwrap();
+        
+        let commit = |authority: Pubkey, stack_height: usize| {
+            STACK_HEIGHT.set(stack_height);
+            let infos = vec![
+                checkpoint.to_account_info(),
+                registry.to_account_info(),
+                account_info(authority, anchor_lang::system_program::ID, 0, Vec::new(), true).clone(),
+            ];
+            let mut infos: &'static [AccountInfo<'static>] = Box::leak(infos.into_boxed_slice());
+            let mut bumps = UpdateRootCheckpointBumps::default();
+            let accounts = UpdateRootCheckpoint::try_accounts(&crate::ID, &mut infos, &[], &mut bumps, &mut Default::default())?;
+            run(accounts, bumps, |ctx| contracts::update_root_checkpoint(ctx, [3u8; 32])).0
+        };
+        
+        // The wallet the checkpoint recorded is locked out, CPI or not
+        assert_eq!(commit(wallet, 1).unwrap_err(), error!(ErrorCode::InvalidAuthority));
+        assert_eq!(commit(wallet, 2).unwrap_err(), error!(ErrorCode::InvalidAuthority));
+        // The governance PDA only signs through the governance program's CPI
+        assert_eq!(commit(governance, 1).unwrap_err(), error!(ErrorCode::InvalidAuthority));
+        commit(governance, 2).unwrap();
+        assert!(reload(&checkpoint).roots.contains(&[3u8; 32]));
+    }
+
+    /// The escrow transfer is a system program CPI, which only runs on-chain, so
+    /// the charge is checked up to the transfer and settlement from a funded escrow
+    #[test]
+    fn test_verification_fee_is_charged_on_every_path() {
+        let registered = register_verified(4);
+        registered.registry.verification_fee = 5_000;
+        registered.registry.exit(&crate::ID).unwrap();
+        let inputs = [NULLIFIER, registered.identity.nonce_field_element()].concat();
+        
+        let (accounts, bumps) = verify_accounts(registered);
+        let (result, _) = run(accounts, bumps, |ctx| {
+            contracts::verify_identity(ctx, vec![0u8; 256], inputs.clone(), 4, NULLIFIER)
+        });
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::FeeEscrowError));
+        
+        let (accounts, bumps) = verify_accounts(registered);
+        let (result, _) = run(accounts, bumps, |ctx| {
+            contracts::refresh_attribute(ctx, 4, vec![0u8; 256], inputs.clone(), NULLIFIER)
+        });
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::FeeEscrowError));
+        
+        let (mut accounts, _) = verify_accounts(registered);
+        let namespace = accounts.registry.namespace;
+        let (key, bump) = pda(&[b"fee_escrow", &namespace]);
+        let escrow = FeeEscrow { namespace, forfeited: 0, bump };
+        let info = program_account(key, &escrow, FeeEscrow::LEN);
+        **info.try_borrow_mut_lamports().unwrap() += 10_000; // two escrowed fees
+        accounts.fee_escrow = Some(Account::try_from(info).unwrap());
+        let user_lamports = accounts.user.lamports();
+        let escrow_lamports = info.lamports();
+        
+        accounts.settle_fee(5_000, true).unwrap();
+        assert_eq!(accounts.user.lamports(), user_lamports + 5_000);
+        assert_eq!(info.lamports(), escrow_lamports - 5_000);
+        
+        accounts.settle_fee(5_000, false).unwrap();
+        assert_eq!(accounts.user.lamports(), user_lamports + 5_000);
+        assert_eq!(info.lamports(), escrow_lamports - 5_000);
+        assert_eq!(accounts.fee_escrow.as_ref().unwrap().forfeited, 5_000);
+    }
+
+    #[test]
+    fn test_verification_requires_the_proofs_nullifier() {
+        let registered = register_verified(4);
+        let inputs = [[9u8; 32], registered.identity.nonce_field_element()].concat();
+        
+        let (accounts, bumps) = verify_accounts(registered);
+        let (result, _) = run(accounts, bumps, |ctx| {
+            contracts::verify_identity(ctx, vec![0u8; 256], inputs.clone(), 4, NULLIFIER)
+        });
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::InvalidPublicInputs));
+        
+        // Refreshing an attribute claims a nullifier too, bound the same way
+        let (accounts, bumps) = verify_accounts(registered);
+        let (result, _) = run(accounts, bumps, |ctx| {
+            contracts::refresh_attribute(ctx, 4, vec![0u8; 256], inputs.clone(), NULLIFIER)
+        });
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::InvalidPublicInputs));
+    }
+
+    /// Pending uniqueness proof for `identity` whose first public input is `nullifier_input`
+    fn pending_verification(identity: &Account<'static, Identity>, nullifier_input: [u8; 32]) -> &'static AccountInfo<'static> {
+        let (key, bump) = pda(&[b"pending_verification", identity.key().as_ref()]);
+        let pending = PendingVerification {
+            identity: identity.key(),
+            attribute_type: 4,
+            proof: [0u8; 256],
+            public_inputs: [nullifier_input, identity.nonce_field_element()].concat(),
+            submitted_at: NOW,
+            bump,
+        };
+        program_account(key, &pending, PendingVerification::LEN)
+    }
+
+    #[test]
+    fn test_mark_spent_requires_the_pending_proofs_nullifier() {
+        let registered = register_verified(4);
+        let pending = pending_verification(&registered.identity, [9u8; 32]);
+        let (verify, verify_bumps) = verify_accounts(registered);
+        let spent_root = verify.registry.spent_root;
+        let accounts = MarkSpent {
+            verify,
+            pending_verification: Account::try_from(pending).unwrap(),
+        };
+        
+        let (result, accounts) = run(accounts, MarkSpentBumps { verify: verify_bumps }, |ctx| {
+            contracts::mark_spent(ctx, NULLIFIER, 0, Vec::new())
+        });
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::InvalidPublicInputs));
+        assert_eq!(accounts.verify.registry.spent_root, spent_root);
+    }
+
+    #[test]
+    fn test_mark_spent_rejects_nullifiers_spent_in_the_ring() {
+        let registered = register_verified(4);
+        let pending = pending_verification(&registered.identity, NULLIFIER);
+        let spend = || {
+            let (verify, verify_bumps) = verify_accounts(registered);
+            let accounts = MarkSpent { verify, pending_verification: Account::try_from(pending).unwrap() };
+            run(accounts, MarkSpentBumps { verify: verify_bumps }, |ctx| {
+                contracts::mark_spent(ctx, NULLIFIER, 0, Vec::new())
+            })
+        };
+        
+        // The nullifier checks pass and only the verification itself fails
+        let (result, accounts) = spend();
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::ProofVerificationFailed));
+        assert!(!accounts.verify.registry.contains_nullifier(&NULLIFIER));
+        
+        // A nullifier spent through the ring can't be spent again in the tree
+        let mut registry = reload(&registered.registry);
+        registry.claim_nullifier(NULLIFIER, false).unwrap();
+        registry.exit(&crate::ID).unwrap();
+        let (result, _) = spend();
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::NullifierAlreadyUsed));
+    }
+
+    #[test]
+    fn test_nullifier_spent_in_the_tree_fails_verify_identity() {
+        let registered = register_verified(4);
+        let (mut verify, _) = verify_accounts(registered);
+        let spent_root = verify.registry.spent_root;
+        verify.spend_nullifier(NULLIFIER, 0, &[], NOW).unwrap();
+        assert_ne!(verify.registry.spent_root, spent_root);
+        verify.exit(&crate::ID).unwrap();
+        
+        let inputs = [NULLIFIER, registered.identity.nonce_field_element()].concat();
+        let (accounts, bumps) = verify_accounts(registered);
+        let (result, _) = run(accounts, bumps, |ctx| {
+            contracts::verify_identity(ctx, rejected_proof(), inputs.clone(), 4, NULLIFIER)
+        });
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::NullifierAlreadyUsed));
+    }
+
+    #[test]
+    fn test_audit_credits_only_a_signing_trusted_verifier() {
+        let registered = register_verified(4);
+        let trusted = Pubkey::new_unique();
+        let mut registry = reload(&registered.registry);
+        registry.add_verifier(trusted, [7u8; 32]).unwrap();
+        registry.exit(&crate::ID).unwrap();
+        let audit = |is_signer: bool| {
+            let (mut accounts, _) = verify_accounts(registered);
+            accounts.verifier = account_info(trusted, anchor_lang::system_program::ID, 0, Vec::new(), is_signer).clone();
+            let mut record = VerificationProof {
+                identity: Pubkey::default(),
+                proof_hash: [0u8; 32],
+                public_inputs_hash: [0u8; 32],
+                attribute_type: 0,
+                timestamp: 0,
+                verifier: Pubkey::default(),
+                verifier_label: [0u8; 32],
+            };
+            accounts.write_audit(&mut record, &rejected_proof(), &[NULLIFIER; 2].concat(), 4, NOW).unwrap();
+            record
+        };
+        
+        // Anyone can pass a trusted key; unsigned, it's no more than the user's word
+        let record = audit(false);
+        assert_eq!(record.verifier, registered.user.key());
+        assert_eq!(record.verifier_label, [0u8; 32]);
+        
+        let record = audit(true);
+        assert_eq!(record.verifier, trusted);
+        assert_eq!(record.verifier_label, [7u8; 32]);
+    }
+
+    #[test]
+    fn test_audit_record_is_overwritten_on_reverification() {
+        let registered = register_verified(4);
+        let identity_key = registered.identity.key();
+        let (key, bump) = pda(&[b"proof", identity_key.as_ref(), &[4]]);
+        // As `init_if_needed` leaves it on the first verification
+        let record: Account<'static, VerificationProof> = new_account(key, VerificationProof::LEN);
+        let audit = |proof: &[u8], inputs: &[u8], timestamp: i64| {
+            let (accounts, _) = verify_accounts(registered);
+            let mut written = record.clone();
+            accounts.write_audit(&mut written, proof, inputs, 4, timestamp).unwrap();
+            written.exit(&crate::ID).unwrap();
+            reload(&record)
+        };
+        
+        let inputs = [NULLIFIER, registered.identity.nonce_field_element()].concat();
+        let first = audit(&rejected_proof(), &inputs, NOW);
+        assert_eq!(first.identity, identity_key);
+        assert_eq!(first.proof_hash, compute_proof_hash(&rejected_proof()));
+        assert_eq!(first.public_inputs_hash, compute_public_inputs_hash(&inputs).unwrap());
+        assert_eq!((first.attribute_type, first.timestamp), (4, NOW));
+        assert_eq!(first.verifier, registered.user.key());
+        
+        // Re-verifying the attribute replaces the record rather than adding one
+        let proof = [&rejected_proof()[..192], &[0u8; 64][..]].concat();
+        let next_inputs = [[2u8; 32], registered.identity.nonce_field_element()].concat();
+        let second = audit(&proof, &next_inputs, NOW + 60);
+        assert_eq!(second.proof_hash, compute_proof_hash(&proof));
+        assert_eq!(second.public_inputs_hash, compute_public_inputs_hash(&next_inputs).unwrap());
+        assert_eq!(second.timestamp, NOW + 60);
+        
+        // A proof that fails leaves the last audit in place
+        let (verify, verify_bumps) = verify_accounts(registered);
+        let accounts = VerifyIdentityWithAudit {
+            verify,
+            verification_proof: reload(&record),
+            system_program: system_program(),
+        };
+        let bumps = VerifyIdentityWithAuditBumps { verify: verify_bumps, verification_proof: bump };
+        let (result, _) = run(accounts, bumps, |ctx| {
+            contracts::verify_identity_with_audit(ctx, rejected_proof(), inputs.clone(), 4, NULLIFIER)
+        });
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::ProofVerificationFailed));
+        assert_eq!(reload(&record).proof_hash, compute_proof_hash(&proof));
+    }
+
+    #[test]
+    fn test_verification_log_credits_only_a_signing_trusted_verifier() {
+        let registered = register_verified(4);
+        let trusted = Pubkey::new_unique();
+        let mut registry = reload(&registered.registry);
+        registry.add_verifier(trusted, [7u8; 32]).unwrap();
+        registry.exit(&crate::ID).unwrap();
+        let logged = |is_signer: bool| {
+            let (mut accounts, _) = verify_accounts(registered);
+            accounts.verifier = account_info(trusted, anchor_lang::system_program::ID, 0, Vec::new(), is_signer).clone();
+            accounts.verification_log = Some(Account::try_from(verification_log(&accounts.identity)).unwrap());
+            accounts.record_verification(4, NOW).unwrap();
+            accounts.verification_log.unwrap().entries[0].verifier
+        };
+        
+        assert_eq!(logged(false), registered.user.key());
+        assert_eq!(logged(true), trusted);
+    }
+
+    #[test]
+    fn test_verify_proof_cached_checks_root_freshness() {
+        let registered = register_verified(4);
+        let namespace = registered.registry.namespace;
+        let (cache, bump) = pda(&[b"verification_cache", registered.identity.key().as_ref()]);
+        let accounts = VerifyProofCached {
+            cache: new_account(cache, VerificationCache::LEN),
+            identity: reload(&registered.identity),
+            registry: reload(&registered.registry),
+            root_checkpoint: Some(stale_checkpoint(namespace, [2u8; 32])),
+            nullifier_record: empty_account(NullifierRecord::address(&namespace, &NULLIFIER, &crate::ID).0),
+            evicted_record: None,
+            owner: registered.user.clone(),
+            system_program: system_program(),
+        };
+        let (result, _) = run(accounts, VerifyProofCachedBumps { cache: bump }, |ctx| {
+            contracts::verify_proof_cached(ctx, vec![0u8; 256], vec![1u8; 32], 4, NULLIFIER)
+        });
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::StaleMerkleRoot));
+    }
+
+    #[test]
+    fn test_verify_proof_cached_caches_only_the_pairings_verdict() {
+        let registered = register_verified(4);
+        let namespace = registered.registry.namespace;
+        let inputs = [NULLIFIER, registered.identity.nonce_field_element()].concat();
+        let (cache, bump) = pda(&[b"verification_cache", registered.identity.key().as_ref()]);
+        let verify = |proof: Vec<u8>| {
+            let accounts = VerifyProofCached {
+                cache: new_account(cache, VerificationCache::LEN),
+                identity: reload(&registered.identity),
+                registry: reload(&registered.registry),
+                root_checkpoint: None,
+                nullifier_record: empty_account(NullifierRecord::address(&namespace, &NULLIFIER, &crate::ID).0),
+                evicted_record: None,
+                owner: registered.user.clone(),
+                system_program: system_program(),
+            };
+            run(accounts, VerifyProofCachedBumps { cache: bump }, |ctx| {
+                contracts::verify_proof_cached(ctx, proof, inputs.clone(), 4, NULLIFIER)
+            })
+        };
+        
+        let mut malformed = rejected_proof();
+        malformed[..64].fill(0);
+        malformed[31] = 1;
+        malformed[63] = 1;
+        let (result, _) = verify(malformed);
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::MalformedProofComponent));
+        
+        let (result, accounts) = verify(rejected_proof());
+        assert!(!result.unwrap());
+        let cached = reload(&accounts.cache);
+        assert_eq!(cached.proof_hash, verification_cache_key(4, &rejected_proof(), &inputs));
+        assert!(!cached.valid);
+    }
+
+    #[test]
+    fn test_verification_cache_is_written_by_its_owner() {
+        let registered = register_verified(4);
+        let identity_key = registered.identity.key();
+        let (key, bump) = pda(&[b"verification_cache", identity_key.as_ref()]);
+        let cache = VerificationCache {
+            identity: identity_key,
+            proof_hash: [0u8; 32],
+            valid: false,
+            cached_until_slot: 0,
+            bump,
+        };
+        let cache = program_account(key, &cache, VerificationCache::LEN);
+        let absent = account_info(crate::ID, Pubkey::default(), 0, Vec::new(), false);
+        let resolve = |owner: &Signer<'static>| {
+            let infos = vec![
+                cache.clone(),
+                registered.identity.to_account_info(),
+                registered.registry.to_account_info(),
+                absent.clone(),
+                empty_account(Pubkey::new_unique()).to_account_info(),
+                absent.clone(),
+                owner.to_account_info(),
+                system_program().to_account_info(),
+            ];
+            let infos: &'static [AccountInfo<'static>] = Box::leak(infos.into_boxed_slice());
+            let mut bumps = VerifyProofCachedBumps::default();
+            VerifyProofCached::try_accounts(&crate::ID, &mut &infos[..], &[], &mut bumps, &mut std::collections::BTreeSet::new())
+                .map(|_| ())
+        };
+        assert!(resolve(&registered.user).is_ok());
+        assert_eq!(resolve(&wallet()).unwrap_err(), error!(ErrorCode::UnauthorizedAccess));
+    }
+
+    #[test]
+    fn test_benchmark_measures_one_and_five_inputs() {
+        let benchmark = |num_inputs: usize| {
+            // Meter the span as the runtime would charge it for this many inputs
+            let charged = estimate_verification_compute_units(num_inputs);
+            CU_PER_READ.with(|per_read| per_read.set(charged));
+            REMAINING_CU.with(|remaining| remaining.set(MAX_TRANSACTION_CU));
+            let inputs = vec![0u8; 32 * num_inputs];
+            let consumed = run(Stateless {}, StatelessBumps {}, |ctx| {
+                contracts::benchmark_verification(ctx, vec![0u8; 256], inputs, 4)
+            })
+            .0
+            .unwrap();
+            (consumed, charged)
+        };
+        
+        // The uniqueness key takes 2 inputs; both counts are still measured
+        let (one, one_charged) = benchmark(1);
+        let (five, five_charged) = benchmark(5);
+        assert_eq!((one, five), (one_charged, five_charged));
+        for consumed in [one, five] {
+            assert!(consumed > estimate_verification_compute_units(0) && consumed < MAX_TRANSACTION_CU);
+        }
+        assert!(five > one);
+        
+        // Both spans covered the pairing itself, not an early rejection
+        for num_inputs in [1, 5] {
+            assert_eq!(
+                verify_groth16_proof_resized(&[0u8; 256], &vec![0u8; 32 * num_inputs], 4).unwrap_err(),
+                error!(ErrorCode::ProofVerificationFailed)
+            );
+        }
+        
+        // Counts the verifier can't dispatch on aren't measured
+        for num_inputs in [0, MAX_PUBLIC_INPUTS + 1] {
+            let (result, _) = run(Stateless {}, StatelessBumps {}, |ctx| {
+                contracts::benchmark_verification(ctx, vec![0u8; 256], vec![0u8; 32 * num_inputs], 4)
+            });
+            assert_eq!(result.unwrap_err(), error!(ErrorCode::InvalidPublicInputs), "{num_inputs} inputs");
+        }
+    }
+
+    #[test]
+    fn test_register_accepts_computed_tree_root() {
+        let mut registry = reload(&initialize_registry().registry);
+        
+        // Unreduced keccak outputs are above the field modulus most of the time,
+        // so register a spread of trees rather than one lucky root
+        for seed in 1..=16u8 {
+            let leaves: Vec<[u8; 32]> = (0..3).map(|i| [seed.wrapping_mul(7).wrapping_add(i); 32]).collect();
+            let root = contracts::compute_tree_root(
+                Context::new(&crate::ID, Box::leak(Box::new(Stateless {})), &[], StatelessBumps {}),
+                leaves,
+            )
+            .unwrap();
+            
+            let (result, accounts) = register(&registry, [seed; 32], root);
+            assert!(result.is_ok());
+            assert_eq!(accounts.identity.merkle_root, root);
+            registry = reload(&accounts.registry);
+        }
+        assert_eq!(registry.total_identities, 16);
+        
+        let (result, _) = register(&registry, [1u8; 32], [0xffu8; 32]);
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::NonCanonicalInput));
+    }
+
+    #[test]
+    fn test_batch_identity_status_caps_batch_size() {
+        let registry = reload(&initialize_registry().registry);
+        let (result, registered) = register(&registry, [1u8; 32], [2u8; 32]);
+        result.unwrap();
+        registered.identity.discoverable = true;
+        registered.identity.exit(&crate::ID).unwrap();
+        let identity = registered.identity.to_account_info();
+        
+        let status = |count: usize| {
+            run_with_remaining(BatchIdentityStatus {}, vec![identity.clone(); count], BatchIdentityStatusBumps {}, |ctx| {
+                contracts::batch_identity_status(ctx)
+            })
+            .0
+        };
+        assert_eq!(status(IdentityStatus::MAX_BATCH).unwrap().len(), IdentityStatus::MAX_BATCH);
+        assert_eq!(
+            status(IdentityStatus::MAX_BATCH + 1).unwrap_err(),
+            error!(ErrorCode::InvalidBatchSize)
+        );
+    }
+
+    #[test]
+    fn test_list_attributes_caps_account_count() {
+        let registry = reload(&initialize_registry().registry);
+        let wallets: Vec<AccountInfo<'static>> = (0..9).map(|_| wallet().to_account_info()).collect();
+        
+        let (result, _) = run_with_remaining(ReadRegistry { registry }, wallets, ReadRegistryBumps {}, |ctx| {
+            contracts::list_attributes(ctx)
+        });
+        assert_eq!(result.unwrap_err(), error!(ErrorCode::TooManyAttributes));
+    }
+
+    #[test]
+    fn test_register_nullifier_checks_its_record() {
+        let registry = reload(&initialize_registry().registry);
+        let (result, registered) = register(&registry, [1u8; 32], [2u8; 32]);
+        result.unwrap();
+        registered.identity.is_verified = true;
+        registered.identity.attributes_verified = 4; // uniqueness
+        registered.identity.exit(&crate::ID).unwrap();
+        let namespace = registry.namespace;
+        
+        let claim = |nullifier: [u8; 32], nullifier_record: UncheckedAccount<'static>| {
+            let accounts = RegisterNullifier {
+                registry: reload(&registered.registry),
+                identity: reload(&registered.identity),
+                nullifier_record,
+                evicted_record: None,
+                user: registered.user.clone(),
+                system_program: system_program(),
+            };
+            run(accounts, RegisterNullifierBumps {}, |ctx| contracts::register_nullifier(ctx, nullifier)).0
+        };
+        let no_record = |nullifier: &[u8; 32]| empty_account(NullifierRecord::address(&namespace, nullifier, &crate::ID).0);
+        let used = error!(ErrorCode::NullifierAlreadyUsed);
+        
+        claim([5u8; 32], no_record(&[5u8; 32])).unwrap();
+        assert_eq!(reload(&registered.registry).nullifier_count, 1);
+        assert_eq!(claim([5u8; 32], no_record(&[5u8; 32])).unwrap_err(), used);
+        
+        // A nullifier evicted from the ring is still spent through its record
+        let (key, bump) = NullifierRecord::address(&namespace, &[6u8; 32], &crate::ID);
+        let record = NullifierRecord { nullifier: [6u8; 32], registered_at: NOW, bump };
+        let existing = UncheckedAccount::try_from(program_account(key, &record, NullifierRecord::LEN));
+        assert_eq!(claim([6u8; 32], existing).unwrap_err(), used);
+        
+        assert_eq!(
+            claim([7u8; 32], no_record(&[8u8; 32])).unwrap_err(),
+            error!(ErrorCode::InvalidNullifierRecord)
+        );
+        assert_eq!(reload(&registered.registry).nullifier_count, 1);
+    }
 }
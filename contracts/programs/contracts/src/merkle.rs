@@ -0,0 +1,275 @@
+//! Pure binary Merkle tree API over the compression hash
+//! 
+//! Nothing here reads `Clock`, sysvars or accounts, so property-based harnesses
+//! (proptest, quickcheck, cargo-fuzz) can drive tree construction and proof
+//! verification directly. The on-chain instructions keep using `compression`.
+
+use anchor_lang::prelude::*;
+
+use crate::compression::{poseidon_merkle_parent, verify_poseidon_merkle_proof};
+
+/// Leaf value filling the unused positions of a padded tree
+pub const EMPTY_LEAF: [u8; 32] = [0u8; 32];
+
+/// Most leaves `padded_root` accepts, keeping the hashing within compute limits
+pub const MAX_PADDED_LEAVES: usize = 64;
+
+/// Hash two children into their parent; see `poseidon_merkle_parent`
+pub fn parent(left: &[u8; 32], right: &[u8; 32]) -> Result<[u8; 32]> {
+    poseidon_merkle_parent(left, right)
+}
+
+/// Check an inclusion proof; see `verify_poseidon_merkle_proof`
+pub fn verify(
+    leaf: &[u8; 32],
+    siblings: &[[u8; 32]],
+    indices: &[bool],
+    root: &[u8; 32],
+) -> Result<bool> {
+    verify_poseidon_merkle_proof(leaf, siblings, indices, root)
+}
+
+/// Root reached by hashing `leaf` up the path given by `siblings` and `indices`
+/// 
+/// Same walk as `verify`, returning the root instead of comparing it; used to
+/// recompute a root after replacing a leaf whose old value was just proven.
+pub fn path_root(leaf: &[u8; 32], siblings: &[[u8; 32]], indices: &[bool]) -> Result<[u8; 32]> {
+    require!(
+        siblings.len() == indices.len(),
+        crate::errors::ErrorCode::InvalidProof
+    );
+    
+    let mut current = *leaf;
+    for (sibling, &is_left) in siblings.iter().zip(indices) {
+        current = if is_left {
+            parent(&current, sibling)?
+        } else {
+            parent(sibling, &current)?
+        };
+    }
+    Ok(current)
+}
+
+/// Every level of the tree over `leaves`, leaves first and the root last
+/// 
+/// The leaf count must be a non-zero power of two.
+pub fn build_levels(leaves: &[[u8; 32]]) -> Result<Vec<Vec<[u8; 32]>>> {
+    require!(
+        leaves.len().is_power_of_two(),
+        crate::errors::ErrorCode::MerkleTreeError
+    );
+    
+    let mut levels = vec![leaves.to_vec()];
+    while levels[levels.len() - 1].len() > 1 {
+        let next = levels[levels.len() - 1]
+            .chunks_exact(2)
+            .map(|pair| parent(&pair[0], &pair[1]))
+            .collect::<Result<Vec<_>>>()?;
+        levels.push(next);
+    }
+    Ok(levels)
+}
+
+/// Root of the tree over `leaves`
+pub fn root(leaves: &[[u8; 32]]) -> Result<[u8; 32]> {
+    let levels = build_levels(leaves)?;
+    Ok(levels[levels.len() - 1][0])
+}
+
+/// Root of the balanced tree over `leaves`, padded with `EMPTY_LEAF` up to the
+/// next power of two as the circuits build it
+pub fn padded_root(leaves: &[[u8; 32]]) -> Result<[u8; 32]> {
+    require!(
+        !leaves.is_empty() && leaves.len() <= MAX_PADDED_LEAVES,
+        crate::errors::ErrorCode::MerkleTreeError
+    );
+    
+    let mut padded = leaves.to_vec();
+    padded.resize(leaves.len().next_power_of_two(), EMPTY_LEAF);
+    root(&padded)
+}
+
+/// Deepest `empty_subtree_hashes` list that fits in return data: 31 hashes of
+/// 32 bytes plus the 4-byte length prefix stay under `MAX_RETURN_DATA`
+pub const MAX_RETURNED_ZERO_DEPTH: usize = 30;
+
+/// Root of each empty subtree, from a single `EMPTY_LEAF` up to height `depth`
+pub fn empty_subtree_hashes(depth: usize) -> Result<Vec<[u8; 32]>> {
+    let mut hashes = Vec::with_capacity(depth + 1);
+    hashes.push(EMPTY_LEAF);
+    for level in 0..depth {
+        hashes.push(parent(&hashes[level], &hashes[level])?);
+    }
+    Ok(hashes)
+}
+
+/// Append `leaf` at position `leaf_count` of a fixed-depth incremental tree
+/// 
+/// `frontier[i]` holds the latest left node at level `i`, which is all that's
+/// needed to hash the new leaf up to the root in `frontier.len()` steps.
+/// Returns the new root, equal to `padded_root` over every leaf padded to
+/// `2^depth`.
+pub fn append(frontier: &mut [[u8; 32]], leaf_count: u64, leaf: [u8; 32]) -> Result<[u8; 32]> {
+    let depth = frontier.len();
+    require!(
+        depth < 64 && leaf_count < 1u64 << depth,
+        crate::errors::ErrorCode::MerkleTreeError
+    );
+    
+    let zeros = empty_subtree_hashes(depth)?;
+    let mut index = leaf_count;
+    let mut current = leaf;
+    for (level, node) in frontier.iter_mut().enumerate() {
+        current = if index & 1 == 0 {
+            *node = current;
+            parent(&current, &zeros[level])?
+        } else {
+            parent(node, &current)?
+        };
+        index >>= 1;
+    }
+    Ok(current)
+}
+
+/// Siblings and indices proving the leaf at `index`, in `verify`'s format
+/// 
+/// `indices[i]` is set when the node at level `i` is the left child.
+pub fn proof(levels: &[Vec<[u8; 32]>], index: usize) -> Result<(Vec<[u8; 32]>, Vec<bool>)> {
+    require!(
+        !levels.is_empty() && index < levels[0].len(),
+        crate::errors::ErrorCode::MerkleTreeError
+    );
+    
+    let mut siblings = Vec::with_capacity(levels.len() - 1);
+    let mut indices = Vec::with_capacity(levels.len() - 1);
+    let mut position = index;
+    for level in &levels[..levels.len() - 1] {
+        siblings.push(level[position ^ 1]);
+        indices.push(position & 1 == 0);
+        position >>= 1;
+    }
+    Ok((siblings, indices))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Deterministic splitmix64 stream, so failures reproduce from the seed
+    struct Rng(u64);
+    
+    impl Rng {
+        fn next_u64(&mut self) -> u64 {
+            self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        }
+        
+        fn node(&mut self) -> [u8; 32] {
+            let mut node = [0u8; 32];
+            for chunk in node.chunks_exact_mut(8) {
+                chunk.copy_from_slice(&self.next_u64().to_le_bytes());
+            }
+            node
+        }
+    }
+    
+    #[test]
+    fn test_random_trees_every_leaf_proof_verifies() {
+        let mut rng = Rng(0x5017_5ce0);
+        
+        for _ in 0..32 {
+            let depth = (rng.next_u64() % 5) as u32;
+            let leaves: Vec<[u8; 32]> = (0..1usize << depth).map(|_| rng.node()).collect();
+            let levels = build_levels(&leaves).unwrap();
+            let root = root(&leaves).unwrap();
+            
+            for (index, leaf) in leaves.iter().enumerate() {
+                let (siblings, indices) = proof(&levels, index).unwrap();
+                assert_eq!(siblings.len(), depth as usize);
+                assert!(verify(leaf, &siblings, &indices, &root).unwrap(), "seeded leaf {}", index);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_random_trees_tampered_sibling_fails() {
+        let mut rng = Rng(0x7a3b_e4ed);
+        
+        for _ in 0..32 {
+            let depth = 1 + (rng.next_u64() % 4) as u32;
+            let leaves: Vec<[u8; 32]> = (0..1usize << depth).map(|_| rng.node()).collect();
+            let levels = build_levels(&leaves).unwrap();
+            let root = root(&leaves).unwrap();
+            
+            let index = (rng.next_u64() % leaves.len() as u64) as usize;
+            let (siblings, indices) = proof(&levels, index).unwrap();
+            for level in 0..siblings.len() {
+                let mut tampered = siblings.clone();
+                tampered[level][(rng.next_u64() % 32) as usize] ^= 1 << (rng.next_u64() % 8);
+                assert!(!verify(&leaves[index], &tampered, &indices, &root).unwrap());
+            }
+        }
+    }
+    
+    #[test]
+    fn test_padded_root_matches_known_root() {
+        let hash = |left: &[u8; 32], right: &[u8; 32]| {
+            let mut node = solana_program::keccak::hashv(&[left, right]).to_bytes();
+            node[31] &= 0x1f;
+            node
+        };
+        let leaves = [[1u8; 32], [2u8; 32], [3u8; 32]];
+        
+        let known = hash(&hash(&leaves[0], &leaves[1]), &hash(&leaves[2], &EMPTY_LEAF));
+        assert_eq!(padded_root(&leaves).unwrap(), known);
+        assert_eq!(padded_root(&leaves[..2]).unwrap(), root(&leaves[..2]).unwrap());
+        
+        assert!(padded_root(&[]).is_err());
+        assert!(padded_root(&[[1u8; 32]; MAX_PADDED_LEAVES + 1]).is_err());
+    }
+
+    #[test]
+    fn test_incremental_append_matches_rebuild() {
+        let mut rng = Rng(0x1ec2_3a5e);
+        let depth = 3;
+        let mut frontier = [[0u8; 32]; 3];
+        let mut leaves = Vec::new();
+        
+        for count in 0..1u64 << depth {
+            let leaf = rng.node();
+            let appended = append(&mut frontier, count, leaf).unwrap();
+            leaves.push(leaf);
+            
+            let mut padded = leaves.clone();
+            padded.resize(1 << depth, EMPTY_LEAF);
+            assert_eq!(appended, root(&padded).unwrap(), "after {} leaves", count + 1);
+        }
+        
+        // The tree is full
+        assert!(append(&mut frontier, 1 << depth, rng.node()).is_err());
+        assert_eq!(empty_subtree_hashes(depth).unwrap()[depth], root(&[EMPTY_LEAF; 8]).unwrap());
+    }
+
+    #[test]
+    fn test_empty_subtree_hashes_chain_from_zero_leaf() {
+        let zeros = empty_subtree_hashes(MAX_RETURNED_ZERO_DEPTH).unwrap();
+        assert_eq!(zeros.len(), MAX_RETURNED_ZERO_DEPTH + 1);
+        assert_eq!(zeros[0], EMPTY_LEAF);
+        assert_eq!(zeros[1], poseidon_merkle_parent(&EMPTY_LEAF, &EMPTY_LEAF).unwrap());
+        assert_eq!(zeros[2], parent(&zeros[1], &zeros[1]).unwrap());
+        
+        // The longest list still fits in return data
+        assert!(crate::return_data::encode_return_data(&zeros).is_ok());
+        assert!(crate::return_data::encode_return_data(&empty_subtree_hashes(MAX_RETURNED_ZERO_DEPTH + 1).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_build_levels_rejects_non_power_of_two() {
+        assert!(build_levels(&[]).is_err());
+        assert!(build_levels(&[[1u8; 32]; 3]).is_err());
+        assert_eq!(root(&[[1u8; 32]]).unwrap(), [1u8; 32]);
+    }
+}
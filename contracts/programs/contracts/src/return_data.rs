@@ -0,0 +1,85 @@
+//! Shared encoding for the return data of view instructions
+//! 
+//! Anchor borsh-serializes a handler's `Ok` value into return data, so typed
+//! queries (`Result<bool>`, `Result<u64>`, `Result<RegistryConfig>`, ...) all
+//! decode the same way. `emit_return_data` applies that encoding to values set
+//! outside a handler's return, and `decode_return_data` is the client-side
+//! inverse for every query instruction.
+
+use anchor_lang::prelude::*;
+use solana_program::program::{set_return_data, MAX_RETURN_DATA};
+
+/// Borsh encoding of `value`, as placed in return data
+pub fn encode_return_data<T: AnchorSerialize>(value: &T) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    value.serialize(&mut data)?;
+    require!(
+        data.len() <= MAX_RETURN_DATA,
+        crate::errors::ErrorCode::InvalidReturnData
+    );
+    Ok(data)
+}
+
+/// Set the instruction's return data to `value` in the shared encoding
+pub fn emit_return_data<T: AnchorSerialize>(value: &T) -> Result<()> {
+    set_return_data(&encode_return_data(value)?);
+    Ok(())
+}
+
+/// Decode return data produced by `emit_return_data` or a typed handler return
+pub fn decode_return_data<T: AnchorDeserialize>(data: &[u8]) -> Result<T> {
+    T::try_from_slice(data).map_err(|_| error!(crate::errors::ErrorCode::InvalidReturnData))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{IdentityExport, RegistryConfig};
+    
+    #[test]
+    fn test_query_results_decode_through_shared_helper() {
+        // validate_session / session_has_scope
+        let valid = encode_return_data(&true).unwrap();
+        assert!(decode_return_data::<bool>(&valid).unwrap());
+        
+        // get_reputation / compression_rent_savings
+        let reputation = encode_return_data(&42u64).unwrap();
+        assert_eq!(decode_return_data::<u64>(&reputation).unwrap(), 42);
+        
+        // expired_attributes
+        assert_eq!(decode_return_data::<u8>(&encode_return_data(&0b101u8).unwrap()).unwrap(), 0b101);
+        
+        // get_registry_config
+        let config = RegistryConfig {
+            authority: Pubkey::new_unique(),
+            total_identities: 7,
+            merkle_arity: 2,
+            identity_ttl: 3600,
+            namespace: [1u8; 16],
+            attribute_dependencies: [0u8; 8],
+            revocation_root: [0u8; 32],
+            trusted_verifiers: Vec::new(),
+            verification_ttl: 0,
+        };
+        let encoded = encode_return_data(&config).unwrap();
+        assert_eq!(decode_return_data::<RegistryConfig>(&encoded).unwrap(), config);
+        
+        // export_identity
+        let export = IdentityExport { snapshot: vec![1, 2, 3], digest: [9u8; 32] };
+        let encoded = encode_return_data(&export).unwrap();
+        assert_eq!(decode_return_data::<IdentityExport>(&encoded).unwrap(), export);
+        
+        // Truncated data doesn't decode
+        assert_eq!(
+            decode_return_data::<IdentityExport>(&encoded[..4]).unwrap_err(),
+            error!(crate::errors::ErrorCode::InvalidReturnData)
+        );
+    }
+    
+    #[test]
+    fn test_oversized_return_data_rejected() {
+        let large = vec![0u8; MAX_RETURN_DATA];
+        assert!(encode_return_data(&large).is_err());
+        assert!(emit_return_data(&vec![0u8; 16]).is_ok());
+    }
+}
@@ -6,13 +6,583 @@ pub struct IdentityRegistry {
     pub authority: Pubkey,
     pub total_identities: u64,
     pub bump: u8,
+    pub merkle_arity: u8, // Fan-out of identity merkle trees (2 or 4)
+    pub recent_nullifiers: [[u8; 32]; 32], // Inline ring of the latest nullifiers
+    pub nullifier_count: u8,
+    pub identity_ttl: u64, // Seconds an identity stays valid before renewal; 0 = never
+    pub namespace: [u8; 16], // Tenant id; seeds every PDA under this registry
+    pub attribute_dependencies: [u8; 8], // Per attribute bit: bitmap that must be verified first
+    pub revocation_root: [u8; 32], // Binary Poseidon root of revoked commitments; zero = none
+    pub input_layouts: u8, // Attribute bits with a `PublicInputLayout`; verifying them requires it
+    pub trusted_verifiers: [TrustedVerifier; 8], // First `verifier_count` entries are set
+    pub verifier_count: u8,
+    pub verification_ttl: i64, // Seconds a verified attribute stays fresh; 0 = never stale
+    pub proof_systems: [u8; 8], // Per attribute bit; see `groth16_verifier::PROOF_SYSTEM_GROTH16`
+    pub session_idle_timeout: i64, // Seconds without a heartbeat before a session is idle; 0 = never
+    pub attribute_weights: [u16; 8], // Per attribute bit, summed against `min_verification_weight`
+    pub min_verification_weight: u32, // Weight a session's identity must reach; 0 = no minimum
+    pub allowlist_root: [u8; 32], // Binary root of allowed wallets; zero = open registration
+    pub tree_frontier: [[u8; 32]; IdentityRegistry::TREE_DEPTH], // See `merkle::append`
+    pub tree_leaf_count: u64,
+    pub tree_root: [u8; 32], // Root of the incremental identity tree
+    pub governance_program: Pubkey, // Owner of the authority PDA once governed
+    pub governed: bool, // Authority only signs via CPI from `governance_program`
+    pub min_tree_depth: u8, // Shallowest identity tree accepted; 0 = no floor
+    pub verification_fee: u64, // Lamports escrowed per metered verification; 0 = free
+    pub defined_attributes: u8, // Attribute bits with an `AttributeDefinition`
+    pub paused_instructions: u16, // `PAUSE_*` bits of instructions halted during an incident
+    pub spent_root: [u8; 32], // Root of the sparse spent-nullifier tree; see `mark_spent`
+    pub global_verification_cap: u32, // Verifications per window across the registry; 0 = unlimited
+    pub global_window_slots: u64, // Length of the throttling window; 1 = per slot
+    pub nullifier_head: u8, // Oldest entry of the full ring, evicted by the next claim
+    pub root_checkpointed: bool, // A `RootCheckpoint` exists; verifying requires it
+    pub event_logged: bool, // A `GlobalEventLog` exists; logged operations require it
+}
+
+/// Verifier the registry vouches for, with the name relying parties display
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, Debug, PartialEq)]
+pub struct TrustedVerifier {
+    pub key: Pubkey,
+    pub label: [u8; 32], // UTF-8, zero-padded, e.g. "Acme KYC"
+}
+
+impl TrustedVerifier {
+    pub const LEN: usize = 32 + // key
+        32; // label
+}
+
+/// Registry settings returned by `get_registry_config`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct RegistryConfig {
+    pub authority: Pubkey,
+    pub total_identities: u64,
+    pub merkle_arity: u8,
+    pub identity_ttl: u64,
+    pub namespace: [u8; 16],
+    pub attribute_dependencies: [u8; 8],
+    pub revocation_root: [u8; 32],
+    pub trusted_verifiers: Vec<TrustedVerifier>,
+    pub verification_ttl: i64,
 }
 
 impl IdentityRegistry {
+    /// Nullifiers held in the inline ring before falling back to `NullifierRecord` PDAs
+    /// 
+    /// Once full, each new nullifier overwrites the oldest entry at
+    /// `nullifier_head`, which the claiming instruction moves into its own
+    /// `NullifierRecord`. Only the most recent nullifiers stay inline; small
+    /// deployments never pay for a record account.
+    pub const NULLIFIER_CAPACITY: usize = 32;
+
+    pub const MAX_TRUSTED_VERIFIERS: usize = 8;
+
+    /// Depth of the incremental identity tree: about a million leaves
+    pub const TREE_DEPTH: usize = 20;
+
     pub const LEN: usize = 8 + // discriminator
         32 + // authority
         8 + // total_identities
-        1; // bump
+        1 + // bump
+        1 + // merkle_arity
+        32 * Self::NULLIFIER_CAPACITY + // recent_nullifiers
+        1 + // nullifier_count
+        8 + // identity_ttl
+        16 + // namespace
+        8 + // attribute_dependencies
+        32 + // revocation_root
+        1 + // input_layouts
+        TrustedVerifier::LEN * Self::MAX_TRUSTED_VERIFIERS + // trusted_verifiers
+        1 + // verifier_count
+        8 + // verification_ttl
+        8 + // proof_systems
+        8 + // session_idle_timeout
+        2 * 8 + // attribute_weights
+        4 + // min_verification_weight
+        32 + // allowlist_root
+        32 * Self::TREE_DEPTH + // tree_frontier
+        8 + // tree_leaf_count
+        32 + // tree_root
+        32 + // governance_program
+        1 + // governed
+        1 + // min_tree_depth
+        8 + // verification_fee
+        1 + // defined_attributes
+        2 + // paused_instructions
+        32 + // spent_root
+        4 + // global_verification_cap
+        8 + // global_window_slots
+        1 + // nullifier_head
+        1 + // root_checkpointed
+        1; // event_logged
+
+    /// `paused_instructions` bits; each halts one instruction path
+    pub const PAUSE_REGISTER: u16 = 1 << 0; // register_identity
+    pub const PAUSE_VERIFY: u16 = 1 << 1; // verify_identity and its variants, cached and batch proofs
+    pub const PAUSE_CREATE_SESSION: u16 = 1 << 2; // create_session, batched and delegated
+    pub const PAUSE_ALL: u16 = Self::PAUSE_REGISTER | Self::PAUSE_VERIFY | Self::PAUSE_CREATE_SESSION;
+
+    /// Depth of the sparse spent-nullifier tree, one leaf per 64-bit key
+    pub const SPENT_TREE_DEPTH: usize = 64;
+    
+    /// Most non-empty siblings a `mark_spent` path may carry
+    /// 
+    /// Empty subtrees are left out of the path, so a tree of `n` spent
+    /// nullifiers needs about `log2(n)` siblings: 24 of them cover millions of
+    /// spends in 768 bytes, leaving room for the accounts in one transaction.
+    pub const MAX_SPENT_PATH_SIBLINGS: usize = 24;
+
+    /// Seed of the governance program's PDA that holds a governed registry
+    pub const GOVERNANCE_SEED: &'static [u8] = b"governance";
+
+    pub fn is_authority(&self, key: &Pubkey) -> bool {
+        self.authority == *key
+    }
+
+    /// Whether any of the `PAUSE_*` bits in `instructions` is set
+    pub fn is_paused(&self, instructions: u16) -> bool {
+        self.paused_instructions & instructions != 0
+    }
+
+    /// Whether `key`, signing at `stack_height`, may run authority-gated instructions
+    /// 
+    /// A governed registry's authority is a PDA only `governance_program` can sign
+    /// for, so it must arrive through a CPI; a top-level signature is refused.
+    pub fn authorizes(&self, key: &Pubkey, stack_height: usize) -> bool {
+        self.is_authority(key)
+            && (!self.governed
+                || stack_height > solana_program::instruction::TRANSACTION_LEVEL_STACK_HEIGHT)
+    }
+
+    /// Hand the authority to `governance_program`'s PDA for this registry
+    /// 
+    /// Returns the PDA, seeded by `GOVERNANCE_SEED` and the registry address.
+    /// Authority-gated instructions then only succeed when the governance program
+    /// invokes them with `invoke_signed`.
+    pub fn transfer_to_governance(
+        &mut self,
+        registry: &Pubkey,
+        governance_program: &Pubkey,
+    ) -> Result<Pubkey> {
+        require!(
+            *governance_program != Pubkey::default() && *governance_program != crate::ID,
+            crate::errors::ErrorCode::InvalidAuthority
+        );
+        
+        let (authority, _) = Pubkey::find_program_address(
+            &[Self::GOVERNANCE_SEED, registry.as_ref()],
+            governance_program,
+        );
+        self.authority = authority;
+        self.governance_program = *governance_program;
+        self.governed = true;
+        Ok(authority)
+    }
+
+    pub fn contains_nullifier(&self, nullifier: &[u8; 32]) -> bool {
+        self.recent_nullifiers[..self.nullifier_count as usize].contains(nullifier)
+    }
+
+    pub fn trusted_verifiers(&self) -> &[TrustedVerifier] {
+        &self.trusted_verifiers[..self.verifier_count as usize]
+    }
+
+    /// Trust `key`, or relabel it if it is already trusted
+    pub fn add_verifier(&mut self, key: Pubkey, label: [u8; 32]) -> Result<()> {
+        let count = self.verifier_count as usize;
+        if let Some(existing) = self.trusted_verifiers[..count].iter_mut().find(|v| v.key == key) {
+            existing.label = label;
+            return Ok(());
+        }
+        
+        require!(
+            count < Self::MAX_TRUSTED_VERIFIERS,
+            crate::errors::ErrorCode::VerifierLimitReached
+        );
+        self.trusted_verifiers[count] = TrustedVerifier { key, label };
+        self.verifier_count += 1;
+        Ok(())
+    }
+
+    /// Stop trusting `key`; returns false if it wasn't trusted
+    pub fn remove_verifier(&mut self, key: &Pubkey) -> bool {
+        let count = self.verifier_count as usize;
+        let Some(index) = self.trusted_verifiers[..count].iter().position(|v| v.key == *key) else {
+            return false;
+        };
+        
+        self.trusted_verifiers.copy_within(index + 1..count, index);
+        self.trusted_verifiers[count - 1] = TrustedVerifier::default();
+        self.verifier_count -= 1;
+        true
+    }
+
+    /// Display label of a trusted verifier; all zeros if it isn't trusted
+    pub fn verifier_label(&self, key: &Pubkey) -> [u8; 32] {
+        self.trusted_verifiers()
+            .iter()
+            .find(|v| v.key == *key)
+            .map_or([0u8; 32], |v| v.label)
+    }
+
+    /// Whether `key` is on the trusted verifier list
+    pub fn is_trusted_verifier(&self, key: &Pubkey) -> bool {
+        self.trusted_verifiers().iter().any(|v| v.key == *key)
+    }
+
+    pub fn config(&self) -> RegistryConfig {
+        RegistryConfig {
+            authority: self.authority,
+            total_identities: self.total_identities,
+            merkle_arity: self.merkle_arity,
+            identity_ttl: self.identity_ttl,
+            namespace: self.namespace,
+            attribute_dependencies: self.attribute_dependencies,
+            revocation_root: self.revocation_root,
+            trusted_verifiers: self.trusted_verifiers().to_vec(),
+            verification_ttl: self.verification_ttl,
+        }
+    }
+
+    /// Whether `identity_commitment` is a leaf of the published revocation tree
+    /// 
+    /// `packed_indices` follows `verify_poseidon_merkle_proof_packed`. A proof
+    /// that doesn't hash to the root only shows the commitment isn't at that
+    /// position, so callers treat `false` as "no revocation proven".
+    pub fn is_revoked(
+        &self,
+        identity_commitment: &[u8; 32],
+        proof_siblings: &[[u8; 32]],
+        packed_indices: u64,
+    ) -> Result<bool> {
+        if self.revocation_root == [0u8; 32] {
+            return Ok(false);
+        }
+        
+        let depth = u8::try_from(proof_siblings.len())
+            .map_err(|_| error!(crate::errors::ErrorCode::MerkleTreeError))?;
+        crate::compression::verify_poseidon_merkle_proof_packed(
+            identity_commitment,
+            proof_siblings,
+            packed_indices,
+            depth,
+            &self.revocation_root,
+        )
+    }
+
+    /// Require a proof that `identity_commitment` is absent from the revocation tree
+    /// 
+    /// The tree is sparse and keyed by commitment: the leaf at the position given
+    /// by the commitment's low `siblings.len()` bits (little-endian) is the
+    /// commitment once revoked and `merkle::EMPTY_LEAF` otherwise. With no
+    /// revocation root published nothing needs proving.
+    pub fn require_not_revoked(
+        &self,
+        identity_commitment: &[u8; 32],
+        siblings: Option<&[[u8; 32]]>,
+    ) -> Result<()> {
+        if self.revocation_root == [0u8; 32] {
+            return Ok(());
+        }
+        
+        let siblings = siblings.ok_or(error!(crate::errors::ErrorCode::MerkleTreeError))?;
+        let depth = u8::try_from(siblings.len())
+            .ok()
+            .filter(|&depth| depth <= 64)
+            .ok_or(error!(crate::errors::ErrorCode::MerkleTreeError))?;
+        let mask = if depth == 64 { u64::MAX } else { (1u64 << depth) - 1 };
+        let mut key = [0u8; 8];
+        key.copy_from_slice(&identity_commitment[..8]);
+        // Packed indices mark left children, i.e. clear position bits
+        let packed_indices = !u64::from_le_bytes(key) & mask;
+        
+        let verify = |leaf: &[u8; 32]| {
+            crate::compression::verify_poseidon_merkle_proof_packed(
+                leaf,
+                siblings,
+                packed_indices,
+                depth,
+                &self.revocation_root,
+            )
+        };
+        if verify(&crate::merkle::EMPTY_LEAF)? {
+            return Ok(());
+        }
+        require!(!verify(identity_commitment)?, crate::errors::ErrorCode::CredentialRevoked);
+        err!(crate::errors::ErrorCode::MerkleTreeError)
+    }
+
+    /// Index of the throttling window holding `slot`, or `None` without a cap
+    /// 
+    /// Windows are aligned to multiples of `global_window_slots`; each one
+    /// counts its verifications in its own `RateWindow` account.
+    pub fn rate_window_of(&self, slot: u64) -> Option<u64> {
+        (self.global_verification_cap > 0).then(|| slot / self.global_window_slots.max(1))
+    }
+
+    /// Leaf position of `nullifier` in the spent-nullifier tree
+    /// 
+    /// The nullifier's low 8 bytes, little-endian, as `require_not_revoked` keys
+    /// the revocation tree. Two nullifiers sharing a position can't both be spent.
+    pub fn spent_leaf_index(nullifier: &[u8; 32]) -> u64 {
+        let mut key = [0u8; 8];
+        key.copy_from_slice(&nullifier[..8]);
+        u64::from_le_bytes(key)
+    }
+
+    /// Spend `nullifier` by setting its leaf in the spent-nullifier tree
+    /// 
+    /// The leaf's path is compressed: bit `i` of `occupied` says whether the
+    /// sibling at level `i` is non-empty, and `siblings` holds only those,
+    /// leaf level first; the others are `merkle::empty_subtree_hashes`. The leaf
+    /// must currently be `merkle::EMPTY_LEAF`; it becomes the nullifier and
+    /// `spent_root` moves to the new root.
+    pub fn mark_spent(&mut self, nullifier: &[u8; 32], occupied: u64, siblings: &[[u8; 32]]) -> Result<()> {
+        require!(*nullifier != crate::merkle::EMPTY_LEAF, crate::errors::ErrorCode::EmptyLeafNullifier);
+        require!(
+            siblings.len() == occupied.count_ones() as usize
+                && siblings.len() <= Self::MAX_SPENT_PATH_SIBLINGS,
+            crate::errors::ErrorCode::MerkleTreeError
+        );
+        
+        let zeros = crate::merkle::empty_subtree_hashes(Self::SPENT_TREE_DEPTH)?;
+        let mut compressed = siblings.iter();
+        let siblings: Vec<[u8; 32]> = (0..Self::SPENT_TREE_DEPTH)
+            .map(|level| match (occupied >> level) & 1 {
+                1 => compressed.next().copied().unwrap_or_default(),
+                _ => zeros[level],
+            })
+            .collect();
+        
+        let index = Self::spent_leaf_index(nullifier);
+        let indices: Vec<bool> = (0..Self::SPENT_TREE_DEPTH).map(|level| (index >> level) & 1 == 0).collect();
+        let current = |leaf: &[u8; 32]| crate::merkle::path_root(leaf, &siblings, &indices);
+        
+        if current(&crate::merkle::EMPTY_LEAF)? != self.spent_root {
+            require!(current(nullifier)? != self.spent_root, crate::errors::ErrorCode::NullifierAlreadyUsed);
+            return err!(crate::errors::ErrorCode::MerkleTreeError);
+        }
+        self.spent_root = current(nullifier)?;
+        Ok(())
+    }
+
+    /// Require `user` to be a leaf of the allowlist when registration is gated
+    /// 
+    /// Leaves are the wallet's 32 key bytes; the proof is checked with
+    /// `verify_poseidon_merkle_proof`, like any other tree in the program.
+    pub fn require_allowlisted(&self, user: &Pubkey, proof: Option<&AllowlistProof>) -> Result<()> {
+        if self.allowlist_root == [0u8; 32] {
+            return Ok(());
+        }
+        
+        let proof = proof.ok_or(error!(crate::errors::ErrorCode::NotAllowlisted))?;
+        let included = crate::compression::verify_poseidon_merkle_proof(
+            &user.to_bytes(),
+            &proof.siblings,
+            &proof.indices,
+            &self.allowlist_root,
+        )?;
+        require!(included, crate::errors::ErrorCode::NotAllowlisted);
+        Ok(())
+    }
+
+    /// Reject a tree shallower than `min_tree_depth`
+    /// 
+    /// Forging an inclusion proof gets cheaper as the tree gets shallower, so a
+    /// depth-1 tree would make membership nearly free to fake.
+    pub fn require_tree_depth(&self, depth: usize) -> Result<()> {
+        require!(
+            depth >= self.min_tree_depth as usize,
+            crate::errors::ErrorCode::MerkleTreeError
+        );
+        Ok(())
+    }
+
+    /// Require a registration's declared tree height to meet the floor
+    /// 
+    /// With a floor set, the root canary becomes mandatory since it's the only
+    /// place a registration declares its tree's height.
+    pub fn require_registration_depth(&self, root_canary: Option<&RootCanary>) -> Result<()> {
+        if self.min_tree_depth == 0 {
+            return Ok(());
+        }
+        
+        let canary = root_canary.ok_or(error!(crate::errors::ErrorCode::MerkleTreeError))?;
+        self.require_tree_depth(canary.tree_height as usize)
+    }
+
+    /// Append a leaf to the incremental identity tree, updating `tree_root`
+    pub fn append_leaf(&mut self, leaf: [u8; 32]) -> Result<[u8; 32]> {
+        self.tree_root = crate::merkle::append(&mut self.tree_frontier, self.tree_leaf_count, leaf)?;
+        self.tree_leaf_count += 1;
+        Ok(self.tree_root)
+    }
+
+    /// Sum of the weights of every attribute in `attributes_verified`
+    pub fn verification_weight(&self, attributes_verified: u8) -> u32 {
+        (0..8)
+            .filter(|bit| attributes_verified & (1 << bit) != 0)
+            .map(|bit| self.attribute_weights[bit] as u32)
+            .sum()
+    }
+
+    /// Fail with `InsufficientVerification` below `min_verification_weight`
+    pub fn require_verification_weight(&self, attributes_verified: u8) -> Result<()> {
+        require!(
+            self.verification_weight(attributes_verified) >= self.min_verification_weight,
+            crate::errors::ErrorCode::InsufficientVerification
+        );
+        Ok(())
+    }
+
+    /// Whether `identity` holds every bit of `attribute_type`, none of them stale
+    /// under `verification_ttl` or past a verifier-supplied expiry at `now`
+    pub fn is_attribute_fresh(&self, identity: &Identity, attribute_type: u8, now: i64) -> bool {
+        identity.has_attributes(attribute_type)
+            && identity.expired_attributes(now, self.verification_ttl) & attribute_type == 0
+    }
+
+    /// Fail with `AttributeExpired` if any verified bit of `attribute_type` went
+    /// stale; unlike `is_attribute_fresh` it doesn't require holding the bits
+    pub fn require_fresh_attributes(&self, identity: &Identity, attribute_type: u8, now: i64) -> Result<()> {
+        require!(
+            identity.expired_attributes(now, self.verification_ttl) & attribute_type == 0,
+            crate::errors::ErrorCode::AttributeExpired
+        );
+        Ok(())
+    }
+
+    /// Configure attribute weights; only defined attributes may carry weight
+    pub fn set_verification_weights(&mut self, weights: [u16; 8], min_weight: u32) -> Result<()> {
+        let weighted = (0..8)
+            .filter(|&bit| weights[bit] != 0)
+            .fold(0u8, |bits, bit| bits | (1 << bit));
+        Identity::require_defined_attributes(weighted)?;
+        
+        self.attribute_weights = weights;
+        self.min_verification_weight = min_weight;
+        Ok(())
+    }
+
+    /// Attributes that must already be verified before `attribute_type` can be
+    pub fn dependencies_of(&self, attribute_type: u8) -> u8 {
+        (0..8)
+            .filter(|bit| attribute_type & (1 << bit) != 0)
+            .fold(0, |deps, bit| deps | self.attribute_dependencies[bit])
+    }
+
+    /// Configure the prerequisites of a single attribute bit
+    pub fn set_attribute_dependencies(&mut self, attribute_type: u8, depends_on: u8) -> Result<()> {
+        require!(attribute_type != 0, crate::errors::ErrorCode::AttributeTypeRequired);
+        require!(
+            attribute_type.is_power_of_two(),
+            crate::errors::ErrorCode::CompositeAttributeType
+        );
+        // An attribute can't be its own prerequisite
+        require!(depends_on & attribute_type == 0, crate::errors::ErrorCode::MissingDependencyAttribute);
+        Identity::require_defined_attributes(attribute_type | depends_on)?;
+        
+        self.attribute_dependencies[attribute_type.trailing_zeros() as usize] = depends_on;
+        Ok(())
+    }
+
+    /// Display metadata of every defined attribute, in bit order
+    /// 
+    /// `definitions` must be exactly the registry's `AttributeDefinition`
+    /// accounts, ordered by bit, so the list is complete; otherwise this fails
+    /// with `AttributeDefinitionMismatch`.
+    pub fn attribute_metadata(&self, definitions: &[AttributeDefinition]) -> Result<Vec<AttributeMetadata>> {
+        let mut remaining = self.defined_attributes;
+        let mut metadata = Vec::with_capacity(definitions.len());
+        for definition in definitions {
+            // Lowest outstanding bit, so definitions arrive in bit order
+            let expected = remaining & remaining.wrapping_neg();
+            require!(
+                expected != 0
+                    && definition.attribute_type == expected
+                    && definition.namespace == self.namespace,
+                crate::errors::ErrorCode::AttributeDefinitionMismatch
+            );
+            remaining &= !expected;
+            
+            metadata.push(AttributeMetadata {
+                attribute_type: definition.attribute_type,
+                name: definition.name,
+                vk_version: definition.vk_version,
+                depends_on: self.dependencies_of(definition.attribute_type),
+            });
+        }
+        require!(remaining == 0, crate::errors::ErrorCode::AttributeDefinitionMismatch);
+        Ok(metadata)
+    }
+
+    /// Proof system every bit of `attribute_type` is verified with
+    /// 
+    /// Bits configured with different systems can't share one proof.
+    pub fn proof_system_of(&self, attribute_type: u8) -> Result<u8> {
+        let mut systems = (0..8)
+            .filter(|bit| attribute_type & (1 << bit) != 0)
+            .map(|bit| self.proof_systems[bit]);
+        let first = systems.next().unwrap_or(crate::groth16_verifier::PROOF_SYSTEM_GROTH16);
+        require!(
+            systems.all(|system| system == first),
+            crate::errors::ErrorCode::UnsupportedProofSystem
+        );
+        Ok(first)
+    }
+
+    /// Select the proof system of a single attribute bit
+    pub fn set_proof_system(&mut self, attribute_type: u8, proof_system: u8) -> Result<()> {
+        require!(attribute_type != 0, crate::errors::ErrorCode::AttributeTypeRequired);
+        require!(
+            attribute_type.is_power_of_two(),
+            crate::errors::ErrorCode::CompositeAttributeType
+        );
+        Identity::require_defined_attributes(attribute_type)?;
+        
+        self.proof_systems[attribute_type.trailing_zeros() as usize] = proof_system;
+        Ok(())
+    }
+
+    /// Store a nullifier in the inline ring
+    /// 
+    /// Once the ring holds `NULLIFIER_CAPACITY` entries each push overwrites the
+    /// oldest one and returns it; the caller must keep it spent in its
+    /// `NullifierRecord`, or it could be claimed again.
+    pub fn push_nullifier(&mut self, nullifier: [u8; 32]) -> Option<[u8; 32]> {
+        let count = self.nullifier_count as usize;
+        if count < Self::NULLIFIER_CAPACITY {
+            self.recent_nullifiers[count] = nullifier;
+            self.nullifier_count += 1;
+            return None;
+        }
+        
+        let head = self.nullifier_head as usize;
+        let evicted = std::mem::replace(&mut self.recent_nullifiers[head], nullifier);
+        self.nullifier_head = ((head + 1) % Self::NULLIFIER_CAPACITY) as u8;
+        Some(evicted)
+    }
+
+    /// Whether `nullifier` was registered, inline or in `record`
+    /// 
+    /// `record` is the account at the nullifier's `NullifierRecord` address, if
+    /// it exists. Nullifiers in the spent-nullifier tree were claimed here too.
+    pub fn is_nullifier_spent(&self, nullifier: &[u8; 32], record: Option<&NullifierRecord>) -> bool {
+        self.contains_nullifier(nullifier) || record.is_some_and(|r| r.nullifier == *nullifier)
+    }
+
+    /// Register `nullifier` in the inline ring, failing with `NullifierAlreadyUsed`
+    /// on reuse
+    /// 
+    /// `has_record` is whether the nullifier's `NullifierRecord` exists, i.e. it
+    /// was spent and later evicted. Returns the nullifier this claim evicts, if
+    /// any, for the caller to store in its own record.
+    pub fn claim_nullifier(&mut self, nullifier: [u8; 32], has_record: bool) -> Result<Option<[u8; 32]>> {
+        require!(
+            !has_record && !self.contains_nullifier(&nullifier),
+            crate::errors::ErrorCode::NullifierAlreadyUsed
+        );
+        Ok(self.push_nullifier(nullifier))
+    }
 }
 
 /// Individual identity account with compressed commitment
@@ -25,9 +595,32 @@ pub struct Identity {
     pub verification_timestamp: i64,
     pub attributes_verified: u8, // Bitmap: 1=age, 2=nationality, 4=uniqueness, etc.
     pub bump: u8,
+    pub discoverable: bool, // Opt-in to public directory listings
+    pub frozen: bool, // Temporary hold; blocks sessions and verifications
+    pub verify_attempts: u32, // Proof verifications attempted, for metering
+    pub session_delegate: Pubkey, // dApp allowed to open sessions for the owner; default = none
+    pub verification_nonce: u64, // Expected as the last public input of the next proof
+    pub expires_at: i64, // Must renew before this time; 0 = never expires
+    pub namespace: [u8; 16], // Registry namespace this identity belongs to
+    pub session_epoch: u32, // Bumped on revocation; sessions from older epochs are invalid
+    pub attribute_expiry: [i64; 8], // Verifier-supplied expiry per attribute bit; 0 = none
+    pub attribute_timestamps: [i64; 8], // Last verification time per attribute bit
+    pub recent_roots: [[u8; 32]; Identity::RECENT_ROOTS], // Previous merkle roots, newest first
+    pub guardian: Pubkey, // May verify attributes on the owner's behalf; default = none
+    pub active_sessions: u16, // Open sessions of the current `session_epoch`
+    pub did: [u8; 64], // Bound `did:sol:` DID, zero-padded; all zeros = none
+    pub update_delay_secs: i64, // Time lock on commitment/root updates; 0 = immediate
+    pub pending_commitment: [u8; 32], // Proposed by `propose_update`
+    pub pending_merkle_root: [u8; 32],
+    pub pending_update_at: i64, // When the pending update may be applied; 0 = none pending
+    pub verification_logged: bool, // Set by `initialize_verification_log`; the log is then required
+    pub frozen_by_authority: bool, // The hold was placed by the registry authority, which alone may lift it
 }
 
 impl Identity {
+    /// Previous merkle roots kept after `advance_root`
+    pub const RECENT_ROOTS: usize = 4;
+
     pub const LEN: usize = 8 + // discriminator
         32 + // owner
         32 + // identity_commitment
@@ -35,7 +628,511 @@ impl Identity {
         1 + // is_verified
         8 + // verification_timestamp
         1 + // attributes_verified
-        1; // bump
+        1 + // bump
+        1 + // discoverable
+        1 + // frozen
+        4 + // verify_attempts
+        32 + // session_delegate
+        8 + // verification_nonce
+        8 + // expires_at
+        16 + // namespace
+        4 + // session_epoch
+        64 + // attribute_expiry
+        64 + // attribute_timestamps
+        32 * Self::RECENT_ROOTS + // recent_roots
+        32 + // guardian
+        2 + // active_sessions
+        64 + // did
+        8 + // update_delay_secs
+        32 + // pending_commitment
+        32 + // pending_merkle_root
+        8 + // pending_update_at
+        1 + // verification_logged
+        1; // frozen_by_authority
+
+    /// Reject new sessions and verifications once the membership has lapsed
+    pub fn require_not_expired(&self, now: i64) -> Result<()> {
+        require!(
+            self.expires_at == 0 || now < self.expires_at,
+            crate::errors::ErrorCode::IdentityExpired
+        );
+        Ok(())
+    }
+
+    /// Extend the membership by `ttl` seconds from now or the current expiry,
+    /// whichever is later; a zero TTL makes the identity non-expiring
+    pub fn renew(&mut self, now: i64, ttl: u64) {
+        self.expires_at = if ttl == 0 {
+            0
+        } else {
+            self.expires_at.max(now).saturating_add_unsigned(ttl)
+        };
+    }
+
+    /// Attribute bits that have a circuit: 1=age, 2=nationality, 4=uniqueness
+    pub const DEFINED_ATTRIBUTES: u8 = 1 | 2 | 4;
+
+    /// Reject an attribute bitmap with bits outside `DEFINED_ATTRIBUTES`
+    /// 
+    /// Every write of `attributes_verified` goes through here or `clear_attributes`,
+    /// so meaningless bits can never be set.
+    pub fn require_defined_attributes(attribute_type: u8) -> Result<u8> {
+        require!(
+            attribute_type & !Self::DEFINED_ATTRIBUTES == 0,
+            crate::errors::ErrorCode::InvalidPublicInputs
+        );
+        Ok(attribute_type)
+    }
+
+    /// Set an attribute bit after a successful proof and advance the nonce
+    pub fn mark_verified(&mut self, attribute_type: u8, timestamp: i64) -> Result<()> {
+        // Mark attribute as verified (bitmap)
+        self.attributes_verified |= Self::require_defined_attributes(attribute_type)?;
+        self.is_verified = true;
+        self.verification_timestamp = timestamp;
+        self.verification_nonce += 1;
+        self.set_attribute_expiry(attribute_type, 0);
+        for (bit, verified_at) in self.attribute_timestamps.iter_mut().enumerate() {
+            if attribute_type & (1 << bit) != 0 {
+                *verified_at = timestamp;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_attribute_expiry(&mut self, attribute_type: u8, expires_at: i64) {
+        for (bit, expiry) in self.attribute_expiry.iter_mut().enumerate() {
+            if attribute_type & (1 << bit) != 0 {
+                *expiry = expires_at;
+            }
+        }
+    }
+
+    /// Mark `attribute_type` verified until the real-world `expires_at` of the credential
+    /// 
+    /// Overrides the identity-wide TTL for these bits; the expiry must be in the future.
+    pub fn mark_verified_until(&mut self, attribute_type: u8, timestamp: i64, expires_at: i64) -> Result<()> {
+        require!(expires_at > timestamp, crate::errors::ErrorCode::AttributeExpired);
+        self.mark_verified(attribute_type, timestamp)?;
+        self.set_attribute_expiry(attribute_type, expires_at);
+        Ok(())
+    }
+
+    /// Whether any bit of `attribute_type` carries an expiry at or before `now`
+    pub fn attribute_expired(&self, attribute_type: u8, now: i64) -> bool {
+        self.attribute_expiry
+            .iter()
+            .enumerate()
+            .any(|(bit, &expiry)| attribute_type & (1 << bit) != 0 && expiry != 0 && now >= expiry)
+    }
+
+    /// Longest `update_delay_secs` an owner may set
+    pub const MAX_UPDATE_DELAY: i64 = 30 * 24 * 60 * 60;
+
+    /// Require updates to apply immediately, i.e. no time lock is configured
+    pub fn require_unlocked_updates(&self) -> Result<()> {
+        require!(self.update_delay_secs == 0, crate::errors::ErrorCode::UpdateTimeLocked);
+        Ok(())
+    }
+
+    /// Configure the update time lock
+    /// 
+    /// The delay can only grow: lowering it would let a briefly compromised key
+    /// skip the very lock meant to stop it.
+    pub fn set_update_delay(&mut self, update_delay_secs: i64) -> Result<()> {
+        require!(
+            update_delay_secs >= self.update_delay_secs && update_delay_secs <= Self::MAX_UPDATE_DELAY,
+            crate::errors::ErrorCode::UpdateTimeLocked
+        );
+        self.update_delay_secs = update_delay_secs;
+        Ok(())
+    }
+
+    /// Stage a commitment and root change that `apply_update` performs after
+    /// `update_delay_secs`; replaces any earlier proposal and restarts the delay
+    pub fn propose_update(&mut self, new_commitment: [u8; 32], new_merkle_root: [u8; 32], now: i64) {
+        self.pending_commitment = new_commitment;
+        self.pending_merkle_root = new_merkle_root;
+        self.pending_update_at = now.saturating_add(self.update_delay_secs).max(1);
+    }
+
+    /// Perform the pending update once its delay has elapsed
+    /// 
+    /// Same effect as an immediate `update_identity`: verification status resets.
+    pub fn apply_update(&mut self, now: i64) -> Result<()> {
+        require!(self.pending_update_at != 0, crate::errors::ErrorCode::NoPendingUpdate);
+        require!(now >= self.pending_update_at, crate::errors::ErrorCode::UpdateTimeLocked);
+        
+        self.identity_commitment = self.pending_commitment;
+        self.merkle_root = self.pending_merkle_root;
+        self.clear_attributes();
+        self.cancel_update();
+        Ok(())
+    }
+
+    /// Drop the pending update, if any
+    pub fn cancel_update(&mut self) {
+        self.pending_commitment = [0u8; 32];
+        self.pending_merkle_root = [0u8; 32];
+        self.pending_update_at = 0;
+    }
+
+    /// Move to a new tree root, keeping the commitment and verified attributes
+    /// 
+    /// The old root becomes the newest entry of `recent_roots`.
+    pub fn advance_root(&mut self, new_root: [u8; 32]) {
+        self.recent_roots.rotate_right(1);
+        self.recent_roots[0] = self.merkle_root;
+        self.merkle_root = new_root;
+    }
+
+    /// Whether `root` is the current root or one of `recent_roots`
+    /// 
+    /// Unused `recent_roots` entries are zero and match nothing.
+    pub fn is_recent_root(&self, root: &[u8; 32]) -> bool {
+        self.merkle_root == *root || (*root != [0u8; 32] && self.recent_roots.contains(root))
+    }
+
+    /// Drop every verified attribute
+    pub fn clear_attributes(&mut self) {
+        self.is_verified = false;
+        self.attributes_verified = 0;
+        self.attribute_expiry = [0; 8];
+        self.attribute_timestamps = [0; 8];
+    }
+
+    /// Whether a DID was bound at registration
+    pub fn has_did(&self) -> bool {
+        self.did != [0u8; 64]
+    }
+
+    /// Clear attributes and invalidate every session opened so far
+    pub fn revoke(&mut self) {
+        self.clear_attributes();
+        self.session_epoch = self.session_epoch.wrapping_add(1);
+        // Every open session belongs to the old epoch now
+        self.active_sessions = 0;
+    }
+
+    /// Count `count` newly opened sessions
+    pub fn open_sessions(&mut self, count: u16) -> Result<()> {
+        self.active_sessions = self
+            .active_sessions
+            .checked_add(count)
+            .ok_or(crate::errors::ErrorCode::SessionCountOutOfRange)?;
+        Ok(())
+    }
+
+    /// Uncount a session being closed
+    /// 
+    /// Sessions from an earlier epoch were already dropped from the count by
+    /// `revoke`, so closing them leaves it alone.
+    pub fn close_session(&mut self, session: &Session) -> Result<()> {
+        if session.identity_epoch == self.session_epoch && session.is_active {
+            self.active_sessions = self
+                .active_sessions
+                .checked_sub(1)
+                .ok_or(crate::errors::ErrorCode::SessionCountOutOfRange)?;
+        }
+        Ok(())
+    }
+
+    /// Reset `active_sessions` to the number of `sessions` still valid at `now`
+    /// 
+    /// Every entry must be a distinct session of this identity; the counter can
+    /// drift when a session account is closed without `close_session`.
+    pub fn reconcile_sessions(&mut self, sessions: &[(Pubkey, Session)], now: i64) -> Result<u16> {
+        let mut active: u16 = 0;
+        for (index, (key, session)) in sessions.iter().enumerate() {
+            require!(
+                session.user == self.owner
+                    && session.namespace == self.namespace
+                    && !sessions[..index].iter().any(|(seen, _)| seen == key),
+                crate::errors::ErrorCode::InvalidSession
+            );
+            if session.is_valid_for(self, now) {
+                active = active
+                    .checked_add(1)
+                    .ok_or(crate::errors::ErrorCode::SessionCountOutOfRange)?;
+            }
+        }
+        self.active_sessions = active;
+        Ok(active)
+    }
+
+    /// Require every attribute in `depends_on` to be verified already
+    pub fn require_dependencies(&self, depends_on: u8) -> Result<()> {
+        require!(
+            self.attributes_verified & depends_on == depends_on,
+            crate::errors::ErrorCode::MissingDependencyAttribute
+        );
+        Ok(())
+    }
+
+    /// Whether `register_identity` has populated this account
+    pub fn is_registered(&self) -> bool {
+        self.owner != Pubkey::default()
+    }
+
+    /// Require the proof's last public input to be the current verification nonce
+    /// 
+    /// The nonce is encoded as a 32-byte big-endian field element and advances
+    /// on every successful verification, so each proof is bound to one position
+    /// in the identity's verification sequence.
+    pub fn require_nonce_input(&self, public_inputs: &[u8]) -> Result<()> {
+        let expected = self.nonce_field_element();
+        
+        let inputs = public_inputs.chunks_exact(32);
+        let bound = inputs.remainder().is_empty() && inputs.last() == Some(&expected[..]);
+        require!(bound, crate::errors::ErrorCode::InvalidPublicInputs);
+        Ok(())
+    }
+
+    /// The verification nonce as the proof's 32-byte big-endian public input
+    pub fn nonce_field_element(&self) -> [u8; 32] {
+        let mut element = [0u8; 32];
+        element[24..].copy_from_slice(&self.verification_nonce.to_be_bytes());
+        element
+    }
+
+    /// Require `delegate` to be the owner-approved session delegate
+    pub fn require_session_delegate(&self, delegate: &Pubkey) -> Result<()> {
+        require!(
+            self.session_delegate != Pubkey::default() && self.session_delegate == *delegate,
+            crate::errors::ErrorCode::UnauthorizedAccess
+        );
+        Ok(())
+    }
+
+    /// Require `guardian` to be the owner-recorded guardian
+    pub fn require_guardian(&self, guardian: &Pubkey) -> Result<()> {
+        require!(
+            self.guardian != Pubkey::default() && self.guardian == *guardian,
+            crate::errors::ErrorCode::UnauthorizedAccess
+        );
+        Ok(())
+    }
+
+    /// Count one proof verification attempt and report whether it succeeded
+    pub fn record_verify_attempt(&mut self, outcome: &Result<bool>) -> bool {
+        self.verify_attempts = self.verify_attempts.saturating_add(1);
+        matches!(outcome, Ok(true))
+    }
+
+    /// Reject new sessions and verifications while the identity is on hold
+    /// 
+    /// Freezing leaves `attributes_verified` untouched, so unfreezing restores
+    /// the identity exactly as it was.
+    pub fn require_not_frozen(&self) -> Result<()> {
+        require!(!self.frozen, crate::errors::ErrorCode::IdentityFrozen);
+        Ok(())
+    }
+
+    /// Place a hold, remembering whether the registry authority placed it
+    /// 
+    /// An owner freezing an identity the authority already froze leaves it the
+    /// authority's hold.
+    pub fn freeze(&mut self, by_authority: bool) {
+        self.frozen = true;
+        self.frozen_by_authority |= by_authority;
+    }
+
+    /// Lift a hold; one the registry authority placed only it may lift
+    pub fn unfreeze(&mut self, by_authority: bool) -> Result<()> {
+        require!(
+            by_authority || !self.frozen_by_authority,
+            crate::errors::ErrorCode::FrozenByAuthority
+        );
+        self.frozen = false;
+        self.frozen_by_authority = false;
+        Ok(())
+    }
+
+    /// Directory listing entry, or `None` if the owner hasn't opted in
+    pub fn directory_entry(&self) -> Option<IdentityStatus> {
+        self.discoverable.then_some(IdentityStatus {
+            owner: self.owner,
+            is_verified: self.is_verified,
+            attributes_verified: self.attributes_verified,
+        })
+    }
+
+    /// Snapshot of the identity, vouched for by the registry authority `exported_by`
+    pub fn snapshot(&self, exported_by: Pubkey) -> IdentitySnapshot {
+        IdentitySnapshot {
+            version: IdentitySnapshot::VERSION,
+            exported_by,
+            owner: self.owner,
+            identity_commitment: self.identity_commitment,
+            merkle_root: self.merkle_root,
+            is_verified: self.is_verified,
+            verification_timestamp: self.verification_timestamp,
+            attributes_verified: self.attributes_verified,
+            verification_nonce: self.verification_nonce,
+            expires_at: self.expires_at,
+            attribute_expiry: self.attribute_expiry,
+            attribute_timestamps: self.attribute_timestamps,
+            update_delay_secs: self.update_delay_secs,
+        }
+    }
+
+    /// Re-create an identity from an exported snapshot
+    pub fn from_snapshot(snapshot: &IdentitySnapshot, bump: u8, namespace: [u8; 16]) -> Result<Self> {
+        require!(
+            snapshot.version == IdentitySnapshot::VERSION,
+            crate::errors::ErrorCode::InvalidSnapshot
+        );
+        require!(
+            (0..=Self::MAX_UPDATE_DELAY).contains(&snapshot.update_delay_secs),
+            crate::errors::ErrorCode::InvalidSnapshot
+        );
+        
+        Ok(Identity {
+            owner: snapshot.owner,
+            identity_commitment: snapshot.identity_commitment,
+            merkle_root: snapshot.merkle_root,
+            is_verified: snapshot.is_verified,
+            verification_timestamp: snapshot.verification_timestamp,
+            attributes_verified: Self::require_defined_attributes(snapshot.attributes_verified)?,
+            bump,
+            discoverable: false,
+            frozen: false,
+            verify_attempts: 0,
+            session_delegate: Pubkey::default(),
+            verification_nonce: snapshot.verification_nonce,
+            expires_at: snapshot.expires_at,
+            namespace,
+            session_epoch: 0,
+            attribute_expiry: snapshot.attribute_expiry,
+            attribute_timestamps: snapshot.attribute_timestamps,
+            recent_roots: [[0u8; 32]; Self::RECENT_ROOTS],
+            guardian: Pubkey::default(),
+            active_sessions: 0,
+            did: [0u8; 64],
+            update_delay_secs: snapshot.update_delay_secs,
+            pending_commitment: [0u8; 32],
+            pending_merkle_root: [0u8; 32],
+            pending_update_at: 0,
+            verification_logged: false,
+            frozen_by_authority: false,
+        })
+    }
+
+    /// Whether every bit of `attribute_type` is currently verified
+    pub fn has_attributes(&self, attribute_type: u8) -> bool {
+        attribute_type != 0
+            && self.is_verified
+            && self.attributes_verified & attribute_type == attribute_type
+    }
+
+    /// Error with `InvalidVerificationStatus` unless `attribute_type` is verified
+    pub fn require_verified(&self, attribute_type: u8) -> Result<()> {
+        require!(
+            self.has_attributes(attribute_type),
+            crate::errors::ErrorCode::InvalidVerificationStatus
+        );
+        Ok(())
+    }
+
+    /// When a single attribute bit was last verified; 0 if it never was
+    pub fn attribute_timestamp(&self, attribute_type: u8) -> Result<i64> {
+        require!(attribute_type != 0, crate::errors::ErrorCode::AttributeTypeRequired);
+        require!(
+            attribute_type.is_power_of_two(),
+            crate::errors::ErrorCode::CompositeAttributeType
+        );
+        Self::require_defined_attributes(attribute_type)?;
+        
+        Ok(self.attribute_timestamps[attribute_type.trailing_zeros() as usize])
+    }
+
+    /// Verified attribute bits that need renewal at `now`
+    /// 
+    /// A bit is expired once its last verification is more than `ttl` seconds old
+    /// (`ttl` 0 disables this) or it passed a verifier-supplied expiry.
+    pub fn expired_attributes(&self, now: i64, ttl: i64) -> u8 {
+        (0..8)
+            .map(|bit| 1u8 << bit)
+            .filter(|&attribute| self.attributes_verified & attribute != 0)
+            .filter(|&attribute| {
+                let verified_at = self.attribute_timestamps[attribute.trailing_zeros() as usize];
+                (ttl > 0 && now.saturating_sub(verified_at) > ttl)
+                    || self.attribute_expired(attribute, now)
+            })
+            .fold(0, |expired, attribute| expired | attribute)
+    }
+
+    /// `require_verified`, also failing with `AttributeExpired` past a verifier-supplied expiry
+    pub fn require_verified_at(&self, attribute_type: u8, now: i64) -> Result<()> {
+        self.require_verified(attribute_type)?;
+        require!(
+            !self.attribute_expired(attribute_type, now),
+            crate::errors::ErrorCode::AttributeExpired
+        );
+        Ok(())
+    }
+}
+
+/// Public verification status of a discoverable identity
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub struct IdentityStatus {
+    pub owner: Pubkey,
+    pub is_verified: bool,
+    pub attributes_verified: u8,
+}
+
+impl IdentityStatus {
+    /// Entries per listing, sized to fit the 1024-byte return data limit
+    pub const MAX_BATCH: usize = 16;
+}
+
+/// Portable copy of an identity's verifiable state, for backups and migrations
+/// 
+/// Local settings (discoverability, freeze, delegate, metering) are not part of
+/// the snapshot; an imported identity starts with their defaults.
+/// 
+/// The keccak digest only detects corruption; anyone can recompute it. What
+/// vouches for the contents is `exported_by` co-signing the import.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct IdentitySnapshot {
+    pub version: u8, // Snapshot format version
+    pub exported_by: Pubkey, // Authority of the exporting registry
+    pub owner: Pubkey,
+    pub identity_commitment: [u8; 32],
+    pub merkle_root: [u8; 32],
+    pub is_verified: bool,
+    pub verification_timestamp: i64,
+    pub attributes_verified: u8,
+    pub verification_nonce: u64, // Carried over so pre-export proofs can't be replayed
+    pub expires_at: i64,
+    pub attribute_expiry: [i64; 8], // Verifier-supplied expiries, so lapsed credentials stay lapsed
+    pub attribute_timestamps: [i64; 8], // Per-attribute verification times, so freshness carries over
+    pub update_delay_secs: i64, // Update time lock, which migrating must not lift
+}
+
+impl IdentitySnapshot {
+    pub const VERSION: u8 = 5;
+
+    /// Fail with `InvalidSnapshot` unless `signer`, a signer of the import, is
+    /// the authority that exported the snapshot
+    pub fn require_exported_by(&self, signer: &Pubkey) -> Result<()> {
+        require_keys_eq!(self.exported_by, *signer, crate::errors::ErrorCode::InvalidSnapshot);
+        Ok(())
+    }
+
+    /// Borsh encoding, as digested and returned by `export_identity`
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.serialize(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// Return data of `export_identity`: the borsh snapshot and its keccak digest
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct IdentityExport {
+    pub snapshot: Vec<u8>,
+    pub digest: [u8; 32],
 }
 
 /// Authentication session for dApp access
@@ -47,16 +1144,223 @@ pub struct Session {
     pub expires_at: i64,
     pub is_active: bool,
     pub bump: u8,
+    pub scopes: [u8; 128], // Borsh-encoded Vec<[u8; 16]> of scope tags, e.g. "read:profile"
+    pub disclosed_attributes: u8, // Attribute bitmap disclosed to the dApp at creation
+    pub delegated: bool, // Created by the identity's session delegate, not the user
+    pub namespace: [u8; 16], // Registry namespace of the owning identity
+    pub identity_epoch: u32, // Identity `session_epoch` when the session was opened
+    pub last_active: i64, // Creation or latest heartbeat
+    pub disclosed_timestamps: [i64; 8], // Per disclosed attribute bit, its verification time at creation
 }
 
 impl Session {
+    /// Scope tags that fit in the buffer after the 4-byte length prefix
+    pub const MAX_SCOPES: usize = (128 - 4) / 16;
+
     pub const LEN: usize = 8 + // discriminator
         32 + // user
         32 + // session_id
         8 + // created_at
         8 + // expires_at
         1 + // is_active
-        1; // bump
+        1 + // bump
+        128 + // scopes
+        1 + // disclosed_attributes
+        1 + // delegated
+        16 + // namespace
+        4 + // identity_epoch
+        8 + // last_active
+        8 * 8; // disclosed_timestamps
+
+    /// Session PDA for `session_id` under a registry namespace
+    pub fn address(
+        namespace: &[u8; 16],
+        user: &Pubkey,
+        session_id: &[u8; 32],
+        program_id: &Pubkey,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[b"session", namespace.as_ref(), user.as_ref(), session_id.as_ref()],
+            program_id,
+        )
+    }
+
+    /// Encode scope tags into the fixed scopes buffer
+    pub fn set_scopes(&mut self, scopes: &[[u8; 16]]) -> Result<()> {
+        require!(
+            scopes.len() <= Self::MAX_SCOPES,
+            crate::errors::ErrorCode::TooManyScopes
+        );
+        
+        let mut buffer = [0u8; 128];
+        scopes
+            .to_vec()
+            .serialize(&mut &mut buffer[..])
+            .map_err(|_| error!(crate::errors::ErrorCode::TooManyScopes))?;
+        self.scopes = buffer;
+        Ok(())
+    }
+
+    /// Decode the scope tags granted to this session
+    pub fn scopes(&self) -> Vec<[u8; 16]> {
+        Vec::<[u8; 16]>::deserialize(&mut &self.scopes[..]).unwrap_or_default()
+    }
+
+    pub fn has_scope(&self, tag: &[u8; 16]) -> bool {
+        self.scopes().contains(tag)
+    }
+
+    /// Longest emergency extension the registry authority may apply at once
+    pub const MAX_AUTHORITY_EXTENSION: i64 = 24 * 60 * 60;
+
+    /// Push back the expiry by a bounded, positive number of seconds
+    pub fn extend(&mut self, delta: i64) -> Result<()> {
+        require!(
+            delta > 0 && delta <= Self::MAX_AUTHORITY_EXTENSION,
+            crate::errors::ErrorCode::InvalidSession
+        );
+        self.expires_at = self.expires_at.saturating_add(delta);
+        Ok(())
+    }
+
+    /// Whether the session still authenticates `identity` at `now`
+    /// 
+    /// Fails once closed or expired, and for every session opened before the
+    /// identity was last revoked.
+    pub fn is_valid_for(&self, identity: &Identity, now: i64) -> bool {
+        self.is_active
+            && now < self.expires_at
+            && self.user == identity.owner
+            && self.namespace == identity.namespace
+            && self.identity_epoch == identity.session_epoch
+            && !identity.frozen
+    }
+
+    /// Fail with `SessionExpired` unless a new session's expiry is still ahead
+    pub fn require_future_expiry(expires_at: i64, now: i64) -> Result<()> {
+        require!(expires_at > now, crate::errors::ErrorCode::SessionExpired);
+        Ok(())
+    }
+
+    /// `is_valid_for` as an error: `SessionExpired` past the expiry, otherwise
+    /// `InvalidSession` for a closed session or one no longer bound to `identity`
+    pub fn require_valid_for(&self, identity: &Identity, now: i64) -> Result<()> {
+        require!(self.is_active, crate::errors::ErrorCode::InvalidSession);
+        require!(now < self.expires_at, crate::errors::ErrorCode::SessionExpired);
+        require!(self.is_valid_for(identity, now), crate::errors::ErrorCode::InvalidSession);
+        Ok(())
+    }
+
+    /// Record use of the session; closed or expired sessions can't be revived
+    pub fn heartbeat(&mut self, now: i64) -> Result<()> {
+        require!(
+            self.is_active && now < self.expires_at,
+            crate::errors::ErrorCode::InvalidSession
+        );
+        self.last_active = now;
+        Ok(())
+    }
+
+    /// Fail with `SessionIdle` once `idle_timeout` seconds pass without a heartbeat
+    pub fn require_not_idle(&self, now: i64, idle_timeout: i64) -> Result<()> {
+        require!(
+            idle_timeout == 0 || now.saturating_sub(self.last_active) <= idle_timeout,
+            crate::errors::ErrorCode::SessionIdle
+        );
+        Ok(())
+    }
+
+    /// Whether a session PDA is still free for `create_session` to initialize
+    pub fn is_slot_available(info: &AccountInfo) -> bool {
+        info.data_is_empty() && *info.owner == anchor_lang::system_program::ID
+    }
+
+    /// Scope tag granting content gated on a single attribute, e.g. "attr:age"
+    pub fn attribute_scope(attribute_type: u8) -> Result<[u8; 16]> {
+        let name: &[u8] = match attribute_type {
+            1 => b"attr:age",
+            2 => b"attr:nationality",
+            4 => b"attr:uniqueness",
+            _ => return err!(crate::errors::ErrorCode::InvalidPublicInputs),
+        };
+        let mut tag = [0u8; 16];
+        tag[..name.len()].copy_from_slice(name);
+        Ok(tag)
+    }
+
+    /// Session disclosing only `attribute_type`, scoped to its `attribute_scope`
+    /// 
+    /// For `verify_and_open_session`: the session's claims come from the attribute
+    /// just verified, not whatever else the identity holds.
+    pub fn for_attribute(
+        identity: &Identity,
+        session_id: [u8; 32],
+        attribute_type: u8,
+        expires_at: i64,
+        now: i64,
+        bump: u8,
+    ) -> Result<Session> {
+        require!(
+            identity.has_attributes(attribute_type),
+            crate::errors::ErrorCode::IdentityNotFound
+        );
+        Self::require_future_expiry(expires_at, now)?;
+        
+        let mut session = Session {
+            user: identity.owner,
+            session_id,
+            created_at: now,
+            expires_at,
+            is_active: true,
+            bump,
+            scopes: [0u8; 128],
+            disclosed_attributes: attribute_type,
+            delegated: false,
+            namespace: identity.namespace,
+            identity_epoch: identity.session_epoch,
+            last_active: now,
+            disclosed_timestamps: [0; 8],
+        };
+        session.record_disclosure(identity, attribute_type);
+        session.set_scopes(&[Self::attribute_scope(attribute_type)?])?;
+        Ok(session)
+    }
+
+    /// Snapshot which of `identity`'s attributes the session discloses and when
+    /// each was verified
+    /// 
+    /// Written once at creation; later revocations or re-verifications of the
+    /// identity leave the session's record of what was presented untouched.
+    pub fn record_disclosure(&mut self, identity: &Identity, attributes: u8) {
+        self.disclosed_attributes = attributes;
+        for (bit, disclosed_at) in self.disclosed_timestamps.iter_mut().enumerate() {
+            *disclosed_at = if attributes & (1 << bit) != 0 {
+                identity.attribute_timestamps[bit]
+            } else {
+                0
+            };
+        }
+    }
+
+    /// Disclose every attribute `identity` holds, failing with `AttributeExpired`
+    /// if any of them went stale under `registry`
+    /// 
+    /// Shared by every session-opening path so what a session discloses is
+    /// exactly what was checked for freshness.
+    pub fn disclose_verified(&mut self, registry: &IdentityRegistry, identity: &Identity, now: i64) -> Result<()> {
+        registry.require_fresh_attributes(identity, identity.attributes_verified, now)?;
+        self.record_disclosure(identity, identity.attributes_verified);
+        Ok(())
+    }
+
+    /// Copy of this session under a new id; claims, scopes and expiry carry over
+    pub fn rotated(&self, session_id: [u8; 32], bump: u8) -> Session {
+        Session {
+            session_id,
+            bump,
+            ..self.clone()
+        }
+    }
 }
 
 /// Verification proof record for audit trail
@@ -64,10 +1368,11 @@ impl Session {
 pub struct VerificationProof {
     pub identity: Pubkey,
     pub proof_hash: [u8; 32],
-    pub public_inputs_hash: [u8; 32],
+    pub public_inputs_hash: [u8; 32], // keccak256 of the concatenated inputs, see compute_public_inputs_hash
     pub attribute_type: u8,
     pub timestamp: i64,
     pub verifier: Pubkey,
+    pub verifier_label: [u8; 32], // Registry label of `verifier` at verification time
 }
 
 impl VerificationProof {
@@ -76,6 +1381,2848 @@ impl VerificationProof {
         32 + // proof_hash
         32 + // public_inputs_hash
         1 + // attribute_type
+        8 + // timestamp
+        32 + // verifier
+        32; // verifier_label
+}
+
+/// Single entry in an identity's verification history
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, Debug, PartialEq)]
+pub struct VerificationLogEntry {
+    pub attribute_type: u8,
+    pub timestamp: i64,
+    pub verifier: Pubkey,
+}
+
+impl VerificationLogEntry {
+    /// `attribute_type` marking a revocation rather than a verification
+    pub const REVOKED: u8 = 0;
+
+    pub const LEN: usize = 1 + // attribute_type
         8 + // timestamp
         32; // verifier
 }
+
+/// Append-only verification history for compliance audit
+/// Bounded ring buffer: once full, new entries overwrite the oldest ones
+#[account]
+pub struct VerificationLog {
+    pub identity: Pubkey,
+    pub head: u16, // Index of the next write
+    pub count: u16, // Number of valid entries (<= CAPACITY)
+    pub entries: [VerificationLogEntry; VerificationLog::CAPACITY],
+    pub bump: u8,
+    pub revocations: u32, // Every revocation ever logged; the ring can't overwrite it
+    pub last_revoked_at: i64, // Time of the latest revocation; 0 = never revoked
+}
+
+impl VerificationLog {
+    /// Kept small so the full log fits in a single return data buffer (1024 bytes)
+    pub const CAPACITY: usize = 16;
+
+    pub const LEN: usize = 8 + // discriminator
+        32 + // identity
+        2 + // head
+        2 + // count
+        VerificationLogEntry::LEN * Self::CAPACITY + // entries
+        1 + // bump
+        4 + // revocations
+        8; // last_revoked_at
+
+    /// Append an entry, overwriting the oldest one when the log is full
+    pub fn append(&mut self, entry: VerificationLogEntry) {
+        self.entries[self.head as usize] = entry;
+        self.head = ((self.head as usize + 1) % Self::CAPACITY) as u16;
+        if (self.count as usize) < Self::CAPACITY {
+            self.count += 1;
+        }
+    }
+
+    /// Append a `REVOKED` entry and count the revocation outside the ring
+    pub fn record_revocation(&mut self, timestamp: i64, verifier: Pubkey) {
+        self.append(VerificationLogEntry {
+            attribute_type: VerificationLogEntry::REVOKED,
+            timestamp,
+            verifier,
+        });
+        self.revocations = self.revocations.saturating_add(1);
+        self.last_revoked_at = timestamp;
+    }
+
+    /// Points a verification is worth when fresh; revocations cost twice that
+    pub const VERIFICATION_POINTS: u64 = 100;
+
+    /// Age at which an entry stops counting toward the score
+    pub const REPUTATION_WINDOW: i64 = 365 * 24 * 60 * 60;
+
+    /// Time-weighted reputation over the stored history
+    /// 
+    /// Each verification adds `VERIFICATION_POINTS` scaled by how much of the
+    /// `REPUTATION_WINDOW` is left since it happened (linear decay to zero).
+    /// Each of the `revocations` subtracts twice that, weighted by the latest
+    /// one, so revocations the ring has since overwritten still count. The
+    /// score never goes below zero.
+    pub fn reputation_score(&self, now: i64) -> u64 {
+        let points = |timestamp: i64| {
+            let remaining = Self::REPUTATION_WINDOW - now.saturating_sub(timestamp).max(0);
+            Self::VERIFICATION_POINTS * remaining.max(0) as u64 / Self::REPUTATION_WINDOW as u64
+        };
+        let earned: u64 = self
+            .entries()
+            .iter()
+            .filter(|entry| entry.attribute_type != VerificationLogEntry::REVOKED)
+            .map(|entry| points(entry.timestamp))
+            .sum();
+        let lost = 2 * self.revocations as u64 * points(self.last_revoked_at);
+        earned.saturating_sub(lost)
+    }
+
+    /// Entries in chronological order (oldest first)
+    pub fn entries(&self) -> Vec<VerificationLogEntry> {
+        let count = self.count as usize;
+        let start = (self.head as usize + Self::CAPACITY - count) % Self::CAPACITY;
+        (0..count)
+            .map(|i| self.entries[(start + i) % Self::CAPACITY])
+            .collect()
+    }
+}
+
+/// Compact record in the registry-wide event log
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, Debug, PartialEq)]
+pub struct EventLogEntry {
+    pub event_type: u8, // One of the `GlobalEventLog::EVENT_*` constants
+    pub identity: Pubkey, // Identity account the event is about
+    pub timestamp: i64,
+}
+
+impl EventLogEntry {
+    pub const LEN: usize = 1 + // event_type
+        32 + // identity
+        8; // timestamp
+}
+
+/// Page of `GlobalEventLog` records returned by `read_event_log`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct EventLogPage {
+    pub first_sequence: u64, // Sequence number of `entries[0]`
+    pub next_sequence: u64, // Pass to `read_event_log` for the following page
+    pub entries: Vec<EventLogEntry>,
+}
+
+/// Registry-wide audit stream of registrations, verifications and revocations
+/// 
+/// Bounded ring buffer like `VerificationLog`: once `CAPACITY` records are held,
+/// each new one overwrites the oldest. Every record gets the next `sequence`
+/// number, so an exporter that falls more than `CAPACITY` records behind sees
+/// the gap as a `first_sequence` past the one it asked for.
+#[account]
+pub struct GlobalEventLog {
+    pub namespace: [u8; 16],
+    pub head: u16, // Index of the next write
+    pub count: u16, // Number of valid entries (<= CAPACITY)
+    pub sequence: u64, // Records ever appended; the next record's sequence number
+    pub entries: [EventLogEntry; GlobalEventLog::CAPACITY],
+    pub bump: u8,
+}
+
+impl GlobalEventLog {
+    /// Kept at a few kilobytes so the boxed account stays clear of stack limits
+    pub const CAPACITY: usize = 64;
+
+    /// Records per `read_event_log` page, sized to fit in return data
+    pub const PAGE_SIZE: usize = 24;
+
+    pub const EVENT_REGISTERED: u8 = 1;
+    pub const EVENT_VERIFIED: u8 = 2;
+    pub const EVENT_REVOKED: u8 = 3;
+
+    pub const LEN: usize = 8 + // discriminator
+        16 + // namespace
+        2 + // head
+        2 + // count
+        8 + // sequence
+        EventLogEntry::LEN * Self::CAPACITY + // entries
+        1; // bump
+
+    /// Append a record, overwriting the oldest one when the log is full
+    pub fn append(&mut self, event_type: u8, identity: Pubkey, timestamp: i64) {
+        self.entries[self.head as usize] = EventLogEntry {
+            event_type,
+            identity,
+            timestamp,
+        };
+        self.head = ((self.head as usize + 1) % Self::CAPACITY) as u16;
+        if (self.count as usize) < Self::CAPACITY {
+            self.count += 1;
+        }
+        self.sequence += 1;
+    }
+
+    /// Up to `PAGE_SIZE` records from sequence number `from`, oldest first
+    /// 
+    /// Starts at the oldest record still held if `from` has been overwritten.
+    pub fn page(&self, from: u64) -> EventLogPage {
+        let oldest = self.sequence - self.count as u64;
+        let first = from.clamp(oldest, self.sequence);
+        let len = (self.sequence - first).min(Self::PAGE_SIZE as u64);
+        // The oldest held record sits `count` writes behind `head`
+        let start = (self.head as usize + Self::CAPACITY - self.count as usize) % Self::CAPACITY;
+        let offset = (first - oldest) as usize;
+        
+        EventLogPage {
+            first_sequence: first,
+            next_sequence: first + len,
+            entries: (0..len as usize)
+                .map(|i| self.entries[(start + offset + i) % Self::CAPACITY])
+                .collect(),
+        }
+    }
+}
+
+/// Short-lived claim on a commitment during multi-step onboarding
+/// Blocks other users from registering the same commitment until it expires
+#[account]
+pub struct CommitmentReservation {
+    pub reserver: Pubkey,
+    pub identity_commitment: [u8; 32],
+    pub reserved_slot: u64,
+    pub expires_slot: u64,
+    pub bump: u8,
+    pub namespace: [u8; 16],
+}
+
+impl CommitmentReservation {
+    /// ~10 minutes at 400ms slots
+    pub const DURATION_SLOTS: u64 = 1_500;
+
+    pub const LEN: usize = 8 + // discriminator
+        32 + // reserver
+        32 + // identity_commitment
+        8 + // reserved_slot
+        8 + // expires_slot
+        1 + // bump
+        16; // namespace
+
+    pub fn is_expired(&self, slot: u64) -> bool {
+        slot >= self.expires_slot
+    }
+
+    /// Whether this reservation prevents `user` from registering the commitment
+    pub fn blocks(&self, user: &Pubkey, slot: u64) -> bool {
+        self.reserver != *user && !self.is_expired(slot)
+    }
+}
+
+/// Rolling window of recently committed merkle roots and the slots they landed in
+/// Lets verification reject proofs generated against long-stale roots
+#[account]
+pub struct RootCheckpoint {
+    pub authority: Pubkey, // Authority at initialization; updates check the registry's current one
+    pub max_root_age_slots: u64,
+    pub head: u8, // Index of the next write
+    pub count: u8,
+    pub roots: [[u8; 32]; RootCheckpoint::CAPACITY],
+    pub slots: [u64; RootCheckpoint::CAPACITY],
+    pub bump: u8,
+    pub namespace: [u8; 16],
+}
+
+impl RootCheckpoint {
+    pub const CAPACITY: usize = 16;
+
+    pub const LEN: usize = 8 + // discriminator
+        32 + // authority
+        8 + // max_root_age_slots
+        1 + // head
+        1 + // count
+        32 * Self::CAPACITY + // roots
+        8 * Self::CAPACITY + // slots
+        1 + // bump
+        16; // namespace
+
+    /// Record a newly committed root, evicting the oldest when full
+    pub fn push(&mut self, root: [u8; 32], slot: u64) {
+        let head = self.head as usize;
+        self.roots[head] = root;
+        self.slots[head] = slot;
+        self.head = ((head + 1) % Self::CAPACITY) as u8;
+        if (self.count as usize) < Self::CAPACITY {
+            self.count += 1;
+        }
+    }
+
+    /// Slot at which `root` was most recently checkpointed
+    pub fn root_slot(&self, root: &[u8; 32]) -> Option<u64> {
+        (0..self.count as usize)
+            .filter(|&i| self.roots[i] == *root)
+            .map(|i| self.slots[i])
+            .max()
+    }
+
+    /// Require `root` to be checkpointed within `max_root_age_slots` of `current_slot`
+    pub fn require_fresh(&self, root: &[u8; 32], current_slot: u64) -> Result<()> {
+        let root_slot = self
+            .root_slot(root)
+            .ok_or(error!(crate::errors::ErrorCode::MerkleTreeError))?;
+        require!(
+            current_slot.saturating_sub(root_slot) <= self.max_root_age_slots,
+            crate::errors::ErrorCode::StaleMerkleRoot
+        );
+        Ok(())
+    }
+}
+
+/// One entry of a batched session creation request
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct SessionRequest {
+    pub session_id: [u8; 32],
+    pub expiry: i64,
+    pub scopes: Vec<[u8; 16]>,
+}
+
+impl SessionRequest {
+    /// Bounded so a batch's account creations fit in the compute budget
+    pub const MAX_BATCH: usize = 8;
+
+    /// Reject oversized batches, unscoped requests and duplicate ids before any
+    /// account is created
+    pub fn validate_batch(requests: &[SessionRequest]) -> Result<()> {
+        require!(
+            !requests.is_empty() && requests.len() <= Self::MAX_BATCH,
+            crate::errors::ErrorCode::InvalidSession
+        );
+        for (i, request) in requests.iter().enumerate() {
+            require!(
+                !request.scopes.is_empty(),
+                crate::errors::ErrorCode::InvalidSession
+            );
+            require!(
+                requests[..i].iter().all(|other| other.session_id != request.session_id),
+                crate::errors::ErrorCode::InvalidSession
+            );
+        }
+        Ok(())
+    }
+}
+
+/// One attribute proof of a `verify_identity_batch` call
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct AttributeProof {
+    pub attribute_type: u8,
+    pub proof: Vec<u8>,
+    pub public_inputs: Vec<u8>,
+}
+
+impl AttributeProof {
+    /// Bounded so a batch's pairing checks fit in the compute budget
+    pub const MAX_BATCH: usize = 5;
+
+    /// Reject empty or oversized batches and attributes proven twice, returning
+    /// every attribute bit the batch proves
+    pub fn validate_batch(proofs: &[AttributeProof]) -> Result<u8> {
+        require!(
+            !proofs.is_empty() && proofs.len() <= Self::MAX_BATCH,
+            crate::errors::ErrorCode::InvalidBatchSize
+        );
+        let mut attributes = 0u8;
+        for entry in proofs {
+            require!(entry.attribute_type != 0, crate::errors::ErrorCode::AttributeTypeRequired);
+            require!(
+                attributes & entry.attribute_type == 0,
+                crate::errors::ErrorCode::DuplicateAttribute
+            );
+            attributes |= entry.attribute_type;
+        }
+        Identity::require_defined_attributes(attributes)
+    }
+}
+
+/// One step of a relying-party policy in postfix order
+/// 
+/// `(age AND uniqueness) OR nationality` is
+/// `[Attribute(1), Attribute(4), And, Attribute(2), Or]`. A flat postfix list
+/// keeps deserialization non-recursive while still encoding the expression tree.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum PolicyNode {
+    /// True if every bit in the mask is verified
+    Attribute(u8),
+    And,
+    Or,
+    Not,
+}
+
+impl PolicyNode {
+    /// Bounded so evaluation cost stays small and predictable
+    pub const MAX_NODES: usize = 32;
+    /// Maximum number of pending sub-expressions during evaluation
+    pub const MAX_DEPTH: usize = 8;
+
+    /// Evaluate a postfix policy against a verified attribute bitmap
+    pub fn evaluate(policy: &[PolicyNode], attributes: u8) -> Result<bool> {
+        require!(
+            !policy.is_empty() && policy.len() <= Self::MAX_NODES,
+            crate::errors::ErrorCode::InvalidPolicy
+        );
+        
+        let mut stack: Vec<bool> = Vec::with_capacity(Self::MAX_DEPTH);
+        for node in policy {
+            let value = match *node {
+                PolicyNode::Attribute(mask) => {
+                    require!(mask != 0, crate::errors::ErrorCode::InvalidPolicy);
+                    attributes & mask == mask
+                }
+                PolicyNode::Not => !Self::pop(&mut stack)?,
+                PolicyNode::And => {
+                    let (right, left) = (Self::pop(&mut stack)?, Self::pop(&mut stack)?);
+                    left && right
+                }
+                PolicyNode::Or => {
+                    let (right, left) = (Self::pop(&mut stack)?, Self::pop(&mut stack)?);
+                    left || right
+                }
+            };
+            require!(stack.len() < Self::MAX_DEPTH, crate::errors::ErrorCode::InvalidPolicy);
+            stack.push(value);
+        }
+        
+        require!(stack.len() == 1, crate::errors::ErrorCode::InvalidPolicy);
+        Ok(stack[0])
+    }
+
+    fn pop(stack: &mut Vec<bool>) -> Result<bool> {
+        stack.pop().ok_or_else(|| error!(crate::errors::ErrorCode::InvalidPolicy))
+    }
+}
+
+/// Spent nullifier evicted from the registry's inline ring
+#[account]
+pub struct NullifierRecord {
+    pub nullifier: [u8; 32],
+    pub registered_at: i64,
+    pub bump: u8,
+}
+
+impl NullifierRecord {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // nullifier
+        8 + // registered_at
+        1; // bump
+
+    /// Nullifier record PDA for `nullifier` under a registry namespace
+    pub fn address(namespace: &[u8; 16], nullifier: &[u8; 32], program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"nullifier", namespace.as_ref(), nullifier.as_ref()], program_id)
+    }
+}
+
+/// Verifications counted against the registry-wide cap in one throttling window
+/// 
+/// Kept out of the registry so the counter is only written while a cap is set.
+#[account]
+pub struct RateWindow {
+    pub namespace: [u8; 16],
+    pub window: u64, // `IdentityRegistry::rate_window_of` the counted slots
+    pub count: u32,
+    pub bump: u8,
+}
+
+impl RateWindow {
+    pub const LEN: usize = 8 + // discriminator
+        16 + // namespace
+        8 + // window
+        4 + // count
+        1; // bump
+
+    /// Rate window PDA for `window` under a registry namespace
+    pub fn address(namespace: &[u8; 16], window: u64, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"rate_window", namespace.as_ref(), &window.to_le_bytes()], program_id)
+    }
+
+    /// Count `verifications` more, failing with `GlobalRateLimited` past `cap`
+    pub fn record(&mut self, verifications: u32, cap: u32) -> Result<()> {
+        let count = self.count.saturating_add(verifications);
+        require!(count <= cap, crate::errors::ErrorCode::GlobalRateLimited);
+        self.count = count;
+        Ok(())
+    }
+}
+
+/// Index from a DID to the identity that bound it at registration
+#[account]
+pub struct DidRecord {
+    pub namespace: [u8; 16],
+    pub did_hash: [u8; 32], // See `compression::did_hash`; the DID is too long for a seed
+    pub did: [u8; 64],
+    pub identity: Pubkey,
+    pub bump: u8,
+}
+
+impl DidRecord {
+    /// DID method every bound DID uses
+    pub const PREFIX: &'static [u8] = b"did:sol:";
+
+    pub const LEN: usize = 8 + // discriminator
+        16 + // namespace
+        32 + // did_hash
+        64 + // did
+        32 + // identity
+        1; // bump
+
+    /// Require `did:sol:` plus a non-empty id of printable ASCII, zero-padded
+    pub fn validate(did: &[u8; 64]) -> Result<()> {
+        let len = did.iter().position(|&byte| byte == 0).unwrap_or(did.len());
+        require!(
+            len > Self::PREFIX.len()
+                && did.starts_with(Self::PREFIX)
+                && did[..len].iter().all(u8::is_ascii_graphic)
+                && did[len..].iter().all(|&byte| byte == 0),
+            crate::errors::ErrorCode::InvalidDid
+        );
+        Ok(())
+    }
+
+    /// DID record PDA for `did` under a registry namespace
+    pub fn address(namespace: &[u8; 16], did: &[u8; 64], program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[b"did", namespace.as_ref(), &crate::compression::did_hash(did)],
+            program_id,
+        )
+    }
+
+    /// The identity `did` resolves to
+    pub fn resolve(&self, did: &[u8; 64]) -> Result<Pubkey> {
+        require!(self.did == *did, crate::errors::ErrorCode::InvalidDid);
+        Ok(self.identity)
+    }
+}
+
+/// Public input disclosed from a committed input tree, with its inclusion proof
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct RevealedInput {
+    pub index: u8, // Position among the circuit's public inputs
+    pub value: [u8; 32],
+    pub siblings: Vec<[u8; 32]>, // Leaf level first; path follows `index`'s bits
+}
+
+/// Optional registration proof that a merkle root comes from a real tree
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct RootCanary {
+    pub tree_height: u8,
+    /// Siblings of the all-zero leaf at index 0, `arity - 1` per level
+    pub siblings: Vec<[u8; 32]>,
+}
+
+/// Inclusion proof of the registering wallet in the registry allowlist
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct AllowlistProof {
+    pub siblings: Vec<[u8; 32]>,
+    pub indices: Vec<bool>, // As in `verify_poseidon_merkle_proof`: set = current node is left
+}
+
+/// Display metadata of an attribute bit, set by the registry authority
+#[account]
+pub struct AttributeDefinition {
+    pub namespace: [u8; 16],
+    pub attribute_type: u8, // Single bit within `Identity::DEFINED_ATTRIBUTES`
+    pub name: [u8; 32], // UTF-8, zero-padded, e.g. "Over 18"
+    pub vk_version: u16, // Current key version, as in `ArchivedVerificationKey`
+    pub bump: u8,
+}
+
+impl AttributeDefinition {
+    pub const LEN: usize = 8 + // discriminator
+        16 + // namespace
+        1 + // attribute_type
+        32 + // name
+        2 + // vk_version
+        1; // bump
+}
+
+/// Outcome of `verify_and_report_vk`: whether a proof verified, and under which key
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct VkReport {
+    pub verified: bool,
+    pub vk_version: u16, // Version of the key that verified; 0 when none did
+    pub fingerprint: [u8; 32], // `groth16_verifier::verification_key_fingerprint`; zero when none did
+}
+
+/// Entry returned by `list_attributes`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct AttributeMetadata {
+    pub attribute_type: u8,
+    pub name: [u8; 32],
+    pub vk_version: u16,
+    pub depends_on: u8, // Registry dependency bitmap, see `dependencies_of`
+}
+
+/// Verification key kept after rotation so past proofs stay auditable
+#[account]
+pub struct ArchivedVerificationKey {
+    pub attribute_type: u8,
+    pub version: u16,
+    pub alpha_g1: [u8; 64],
+    pub beta_g2: [u8; 128],
+    pub gamma_g2: [u8; 128],
+    pub delta_g2: [u8; 128],
+    pub ic: Vec<[u8; 64]>, // One point per public input, plus the constant term
+    pub archived_at: i64,
+    pub bump: u8,
+    pub namespace: [u8; 16],
+}
+
+impl ArchivedVerificationKey {
+    /// Enough IC points for the verifier's five-input maximum
+    pub const MAX_IC: usize = 6;
+
+    pub const LEN: usize = 8 + // discriminator
+        1 + // attribute_type
+        2 + // version
+        64 + // alpha_g1
+        128 + // beta_g2
+        128 + // gamma_g2
+        128 + // delta_g2
+        4 + 64 * Self::MAX_IC + // ic
+        8 + // archived_at
+        1 + // bump
+        16; // namespace
+}
+
+/// Proof uploaded by `submit_verification`, awaiting `finalize_verification`
+/// 
+/// Lifecycle: `submit_verification` creates it; `finalize_verification` verifies
+/// and closes it on success, or `cancel_verification` closes it unverified. A
+/// failed finalize rolls back, leaving the submission in place to cancel.
+#[account]
+pub struct PendingVerification {
+    pub identity: Pubkey,
+    pub attribute_type: u8,
+    pub proof: [u8; 256],
+    pub public_inputs: Vec<u8>, // 32-byte field elements, verifier maximum of five
+    pub submitted_at: i64,
+    pub bump: u8,
+}
+
+impl PendingVerification {
+    pub const MAX_PUBLIC_INPUTS_LEN: usize = 5 * 32;
+
+    pub const LEN: usize = 8 + // discriminator
+        32 + // identity
+        1 + // attribute_type
+        256 + // proof
+        4 + Self::MAX_PUBLIC_INPUTS_LEN + // public_inputs
+        8 + // submitted_at
+        1; // bump
+
+    /// Validate and store a submission so finalize only has to verify
+    pub fn submit(
+        &mut self,
+        identity: Pubkey,
+        attribute_type: u8,
+        proof: &[u8],
+        public_inputs: Vec<u8>,
+        submitted_at: i64,
+    ) -> Result<()> {
+        self.proof = proof
+            .try_into()
+            .map_err(|_| error!(crate::errors::ErrorCode::InvalidProof))?;
+        
+        let inputs = public_inputs.chunks_exact(32);
+        require!(
+            !public_inputs.is_empty()
+                && inputs.remainder().is_empty()
+                && public_inputs.len() <= Self::MAX_PUBLIC_INPUTS_LEN,
+            crate::errors::ErrorCode::InvalidPublicInputs
+        );
+        
+        self.identity = identity;
+        self.attribute_type = attribute_type;
+        self.public_inputs = public_inputs;
+        self.submitted_at = submitted_at;
+        Ok(())
+    }
+}
+
+/// Portable proof that an identity verified one attribute
+/// 
+/// Seeded by identity and attribute type, so other programs can find it by
+/// deriving the address. Revoking, freezing or expiring the identity doesn't
+/// touch the attestation, so readers check it against the identity with
+/// `require_current` (or CPI `check_attestation`). Re-verifying refreshes `issued_at`.
+#[account]
+pub struct Attestation {
+    pub identity: Pubkey,
+    pub owner: Pubkey,
+    pub attribute_type: u8,
+    pub issued_at: i64,
+    pub verifier: Pubkey,
+    pub bump: u8,
+    pub namespace: [u8; 16],
+    pub session_epoch: u32, // Identity's epoch at issuance; a revocation bumps it
+}
+
+impl Attestation {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // identity
+        32 + // owner
+        1 + // attribute_type
+        8 + // issued_at
+        32 + // verifier
+        1 + // bump
+        16 + // namespace
+        4; // session_epoch
+
+    /// Build the attestation for an attribute the identity has actually verified
+    pub fn issue(
+        identity_key: Pubkey,
+        identity: &Identity,
+        attribute_type: u8,
+        verifier: Pubkey,
+        issued_at: i64,
+        bump: u8,
+    ) -> Result<Self> {
+        identity.require_verified(attribute_type)?;
+        
+        Ok(Attestation {
+            identity: identity_key,
+            owner: identity.owner,
+            attribute_type,
+            issued_at,
+            verifier,
+            bump,
+            namespace: identity.namespace,
+            session_epoch: identity.session_epoch,
+        })
+    }
+
+    /// Require the attestation to still hold for `identity` at `now`
+    /// 
+    /// Fails with `CredentialRevoked` once the identity was revoked after
+    /// issuance, even if the attribute was verified again since, and like
+    /// `require_verified` for a frozen, expired or no longer verified identity.
+    pub fn require_current(&self, identity: &Identity, now: i64) -> Result<()> {
+        require!(
+            self.owner == identity.owner && self.namespace == identity.namespace,
+            crate::errors::ErrorCode::UnauthorizedAccess
+        );
+        require!(
+            self.session_epoch == identity.session_epoch,
+            crate::errors::ErrorCode::CredentialRevoked
+        );
+        identity.require_not_frozen()?;
+        identity.require_not_expired(now)?;
+        identity.require_verified_at(self.attribute_type, now)
+    }
+}
+
+/// Where each semantic value sits in an attribute circuit's public inputs
+/// 
+/// Lets circuits evolve without the program hardcoding input positions. When
+/// no layout is configured the nonce is the last input and nothing else is read.
+#[account]
+pub struct PublicInputLayout {
+    pub attribute_type: u8,
+    pub owner_index: u8, // Owner binding, see `compression::pubkey_to_field`
+    pub threshold_index: u8, // e.g. the minimum age an age proof was made against
+    pub nonce_index: u8, // Verification nonce; always present
+    pub bump: u8,
+    pub namespace: [u8; 16],
+    pub version_index: u8, // Circuit version input; read only when `circuit_version` is set
+    pub circuit_version: u32, // Version proofs must be made with; 0 = not enforced
+    pub root_index: u8, // Merkle root the proof was generated against
+    pub tag_index: u8, // Attribute tag the circuit commits to, see `require_attribute_tag`
+}
+
+impl PublicInputLayout {
+    /// Index value for a field the circuit doesn't expose
+    pub const UNUSED: u8 = u8::MAX;
+
+    pub const LEN: usize = 8 + // discriminator
+        1 + // attribute_type
+        1 + // owner_index
+        1 + // threshold_index
+        1 + // nonce_index
+        1 + // bump
+        16 + // namespace
+        1 + // version_index
+        4 + // circuit_version
+        1 + // root_index
+        1; // tag_index
+
+    /// Set the field positions; the nonce can't be left out
+    pub fn configure(&mut self, owner_index: u8, threshold_index: u8, nonce_index: u8) -> Result<()> {
+        require!(nonce_index != Self::UNUSED, crate::errors::ErrorCode::InvalidPublicInputs);
+        
+        self.owner_index = owner_index;
+        self.threshold_index = threshold_index;
+        self.nonce_index = nonce_index;
+        Ok(())
+    }
+
+    /// Fail with `InvalidConfig` if a used field points past the circuit's
+    /// `input_count` public inputs, which would reject every proof
+    pub fn require_within(&self, input_count: usize) -> Result<()> {
+        let indices = [
+            self.owner_index,
+            self.threshold_index,
+            self.nonce_index,
+            self.root_index,
+            self.tag_index,
+        ];
+        require!(
+            indices
+                .iter()
+                .all(|&index| index == Self::UNUSED || (index as usize) < input_count),
+            crate::errors::ErrorCode::InvalidConfig
+        );
+        Ok(())
+    }
+
+    /// The 32-byte input at `index`, or `None` for an unused field
+    fn field(public_inputs: &[u8], index: u8) -> Result<Option<&[u8]>> {
+        if index == Self::UNUSED {
+            return Ok(None);
+        }
+        
+        let mut inputs = public_inputs.chunks_exact(32);
+        require!(inputs.remainder().is_empty(), crate::errors::ErrorCode::InvalidPublicInputs);
+        inputs
+            .nth(index as usize)
+            .map(Some)
+            .ok_or(error!(crate::errors::ErrorCode::InvalidPublicInputs))
+    }
+
+    pub fn owner_binding<'a>(&self, public_inputs: &'a [u8]) -> Result<Option<&'a [u8]>> {
+        Self::field(public_inputs, self.owner_index)
+    }
+
+    pub fn threshold<'a>(&self, public_inputs: &'a [u8]) -> Result<Option<&'a [u8]>> {
+        Self::field(public_inputs, self.threshold_index)
+    }
+
+    pub fn nonce<'a>(&self, public_inputs: &'a [u8]) -> Result<&'a [u8]> {
+        Self::field(public_inputs, self.nonce_index)?
+            .ok_or(error!(crate::errors::ErrorCode::InvalidPublicInputs))
+    }
+
+    /// Reject a proof generated against a tree other than the identity's own
+    /// 
+    /// Without this, a proof over another identity's `merkle_root` could pass the
+    /// pairing check and be replayed for this identity. The root may also be one
+    /// of `Identity::recent_roots`, so a proof made just before `update_merkle_root`
+    /// still lands. Roots are stored little-endian and inputs are big-endian, as
+    /// in `require_nullifier_input`.
+    pub fn require_root_binding(&self, public_inputs: &[u8], identity: &Identity) -> Result<()> {
+        if let Some(input) = Self::field(public_inputs, self.root_index)? {
+            let mut root = [0u8; 32];
+            root.copy_from_slice(input);
+            root.reverse();
+            require!(identity.is_recent_root(&root), crate::errors::ErrorCode::InvalidPublicInputs);
+        }
+        Ok(())
+    }
+
+    /// Reject a valid proof of another attribute, e.g. nationality submitted as age
+    /// 
+    /// The tag input is the big-endian field element of the attribute bit the
+    /// circuit proves; it must equal this layout's `attribute_type`. Catches a
+    /// client wiring a circuit to the wrong attribute slot, on top of key selection.
+    pub fn require_attribute_tag(&self, public_inputs: &[u8]) -> Result<()> {
+        if let Some(tag) = Self::field(public_inputs, self.tag_index)? {
+            let mut expected = [0u8; 32];
+            expected[31] = self.attribute_type;
+            require!(tag == expected, crate::errors::ErrorCode::AttributeTypeMismatch);
+        }
+        Ok(())
+    }
+
+    /// Pin the circuit version proofs must carry at `version_index`; 0 stops enforcing
+    pub fn set_circuit_version(&mut self, version_index: u8, circuit_version: u32) -> Result<()> {
+        require!(
+            circuit_version == 0 || version_index != Self::UNUSED,
+            crate::errors::ErrorCode::InvalidPublicInputs
+        );
+        
+        self.version_index = version_index;
+        self.circuit_version = circuit_version;
+        Ok(())
+    }
+
+    /// Reject proofs from another circuit version, even if the pairing passes
+    /// 
+    /// The version input is the big-endian field element of `circuit_version`.
+    pub fn require_circuit_version(&self, public_inputs: &[u8]) -> Result<()> {
+        if self.circuit_version == 0 {
+            return Ok(());
+        }
+        
+        let mut expected = [0u8; 32];
+        expected[28..].copy_from_slice(&self.circuit_version.to_be_bytes());
+        require!(
+            Self::field(public_inputs, self.version_index)? == Some(&expected[..]),
+            crate::errors::ErrorCode::InvalidPublicInputs
+        );
+        Ok(())
+    }
+}
+
+/// Verification key for an auxiliary circuit outside the per-attribute set
+/// 
+/// The attribute keys are compiled into `verification_keys`; these circuits
+/// (e.g. compressed-identity ownership) are configured by the registry authority.
+#[account]
+pub struct CircuitVerificationKey {
+    pub circuit_id: u8,
+    pub alpha_g1: [u8; 64],
+    pub beta_g2: [u8; 128],
+    pub gamma_g2: [u8; 128],
+    pub delta_g2: [u8; 128],
+    pub ic: Vec<[u8; 64]>, // One point per public input, plus the constant term
+    pub bump: u8,
+    pub namespace: [u8; 16],
+}
+
+impl CircuitVerificationKey {
+    /// Knowledge of the secret behind a compressed leaf, bound to the owner and a challenge
+    pub const OWNERSHIP: u8 = 1;
+
+    /// One aggregated attribute proof covering several identities
+    pub const BATCH: u8 = 2;
+
+    pub const MAX_IC: usize = ArchivedVerificationKey::MAX_IC;
+
+    pub const LEN: usize = 8 + // discriminator
+        1 + // circuit_id
+        64 + // alpha_g1
+        128 + // beta_g2
+        128 + // gamma_g2
+        128 + // delta_g2
+        4 + 64 * Self::MAX_IC + // ic
+        1 + // bump
+        16; // namespace
+}
+
+/// One-time challenge an identity answers with a zero-knowledge proof
+/// 
+/// Issued by a relying party (the payer); consumed by the first valid answer,
+/// so a proof can't be replayed for another login.
+#[account]
+pub struct Challenge {
+    pub identity: Pubkey,
+    pub challenge: [u8; 32], // Big-endian BN254 field element, fed to the circuit as-is
+    pub payer: Pubkey,
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub consumed: bool,
+    pub bump: u8,
+}
+
+impl Challenge {
+    /// Five minutes to answer
+    pub const DURATION: i64 = 5 * 60;
+
+    pub const LEN: usize = 8 + // discriminator
+        32 + // identity
+        32 + // challenge
+        32 + // payer
+        8 + // created_at
+        8 + // expires_at
+        1 + // consumed
+        1; // bump
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Require the challenge to still be answerable
+    pub fn require_open(&self, now: i64) -> Result<()> {
+        require!(!self.consumed, crate::errors::ErrorCode::ChallengeConsumed);
+        require!(!self.is_expired(now), crate::errors::ErrorCode::ChallengeExpired);
+        Ok(())
+    }
+
+    /// Verify an ownership proof by `owner` for `merkle_root` against this challenge
+    /// 
+    /// Leaves the challenge open; `prove_ownership` consumes it afterwards.
+    pub fn check_answer(
+        &self,
+        owner: &Pubkey,
+        merkle_root: &[u8; 32],
+        proof: &[u8],
+        circuit_vk: &CircuitVerificationKey,
+        now: i64,
+    ) -> Result<bool> {
+        self.require_open(now)?;
+        
+        let public_inputs = crate::groth16_verifier::ownership_public_inputs(owner, merkle_root, &self.challenge);
+        crate::groth16_verifier::verify_groth16_proof_circuit(proof, &public_inputs, circuit_vk)
+    }
+
+    /// Mark the challenge answered; it can't authorize anything again
+    pub fn consume(&mut self, now: i64) -> Result<()> {
+        self.require_open(now)?;
+        self.consumed = true;
+        Ok(())
+    }
+
+    /// Whether the challenge can be closed with its rent refunded to `payer`
+    /// 
+    /// Only expired challenges are reclaimable, answered or not; a refund to
+    /// anyone but the issuing payer is `UnauthorizedAccess`.
+    pub fn reclaimable(&self, payer: &Pubkey, now: i64) -> Result<bool> {
+        require!(self.payer == *payer, crate::errors::ErrorCode::UnauthorizedAccess);
+        Ok(self.is_expired(now))
+    }
+}
+
+/// Last verification outcome of an identity's proof, reused within a slot window
+#[account]
+pub struct VerificationCache {
+    pub identity: Pubkey,
+    pub proof_hash: [u8; 32], // See `compression::verification_cache_key`
+    pub valid: bool,
+    pub cached_until_slot: u64,
+    pub bump: u8,
+}
+
+impl VerificationCache {
+    /// About a minute of slots
+    pub const WINDOW_SLOTS: u64 = 150;
+
+    pub const LEN: usize = 8 + // discriminator
+        32 + // identity
+        32 + // proof_hash
+        1 + // valid
+        8 + // cached_until_slot
+        1; // bump
+
+    /// Cached outcome for `proof_hash`, or `None` on a miss or once the window passed
+    pub fn lookup(&self, proof_hash: &[u8; 32], slot: u64) -> Option<bool> {
+        (self.proof_hash == *proof_hash && slot <= self.cached_until_slot).then_some(self.valid)
+    }
+
+    /// `lookup`, failing with `CacheExpired` when there is no live entry
+    pub fn require_cached(&self, proof_hash: &[u8; 32], slot: u64) -> Result<bool> {
+        self.lookup(proof_hash, slot)
+            .ok_or(error!(crate::errors::ErrorCode::CacheExpired))
+    }
+
+    pub fn store(&mut self, proof_hash: [u8; 32], valid: bool, slot: u64) {
+        self.proof_hash = proof_hash;
+        self.valid = valid;
+        self.cached_until_slot = slot.saturating_add(Self::WINDOW_SLOTS);
+    }
+}
+
+/// Escrow for verification fees; keeps those forfeited by failed proofs
+#[account]
+pub struct FeeEscrow {
+    pub namespace: [u8; 16],
+    pub forfeited: u64, // Lamports withdrawable by the registry authority
+    pub bump: u8,
+}
+
+impl FeeEscrow {
+    pub const LEN: usize = 8 + // discriminator
+        16 + // namespace
+        8 + // forfeited
+        1; // bump
+
+    /// Settle a `fee` escrowed for one attempt; returns the lamports to refund
+    /// 
+    /// A verified proof gets its fee back; a rejected one forfeits it.
+    pub fn settle(&mut self, fee: u64, verified: bool) -> Result<u64> {
+        if verified {
+            return Ok(fee);
+        }
+        self.forfeited = self
+            .forfeited
+            .checked_add(fee)
+            .ok_or(crate::errors::ErrorCode::FeeEscrowError)?;
+        Ok(0)
+    }
+
+    /// Release `amount` of the forfeited fees
+    pub fn withdraw(&mut self, amount: u64) -> Result<()> {
+        self.forfeited = self
+            .forfeited
+            .checked_sub(amount)
+            .ok_or(crate::errors::ErrorCode::FeeEscrowError)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fee_refunded_on_success_and_forfeited_on_failure() {
+        let mut escrow = FeeEscrow { namespace: [0u8; 16], forfeited: 0, bump: 0 };
+        
+        assert_eq!(escrow.settle(5_000, true).unwrap(), 5_000);
+        assert_eq!(escrow.forfeited, 0);
+        
+        assert_eq!(escrow.settle(5_000, false).unwrap(), 0);
+        assert_eq!(escrow.forfeited, 5_000);
+        
+        // Only forfeited fees can leave the escrow
+        assert_eq!(
+            escrow.withdraw(5_001).unwrap_err(),
+            error!(crate::errors::ErrorCode::FeeEscrowError)
+        );
+        escrow.withdraw(5_000).unwrap();
+        assert_eq!(escrow.forfeited, 0);
+    }
+
+    fn empty_log() -> VerificationLog {
+        VerificationLog {
+            identity: Pubkey::new_unique(),
+            head: 0,
+            count: 0,
+            entries: [VerificationLogEntry::default(); VerificationLog::CAPACITY],
+            bump: 0,
+            revocations: 0,
+            last_revoked_at: 0,
+        }
+    }
+
+    fn entry(attribute_type: u8, timestamp: i64) -> VerificationLogEntry {
+        VerificationLogEntry {
+            attribute_type,
+            timestamp,
+            verifier: Pubkey::default(),
+        }
+    }
+
+    #[test]
+    fn test_verification_log_appends_in_order() {
+        let mut log = empty_log();
+        log.append(entry(1, 100));
+        log.append(entry(2, 200));
+        log.append(entry(4, 300));
+
+        let entries = log.entries();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0], entry(1, 100));
+        assert_eq!(entries[1], entry(2, 200));
+        assert_eq!(entries[2], entry(4, 300));
+    }
+
+    #[test]
+    fn test_verification_log_wraps_when_full() {
+        let mut log = empty_log();
+        let total = VerificationLog::CAPACITY as i64 + 3;
+        for ts in 0..total {
+            log.append(entry(1, ts));
+        }
+
+        let entries = log.entries();
+        assert_eq!(entries.len(), VerificationLog::CAPACITY);
+        // The three oldest entries were overwritten
+        assert_eq!(entries[0].timestamp, 3);
+        assert_eq!(entries[VerificationLog::CAPACITY - 1].timestamp, total - 1);
+    }
+
+    #[test]
+    fn test_event_log_records_operations_in_order() {
+        let mut log = GlobalEventLog {
+            namespace: [0u8; 16],
+            head: 0,
+            count: 0,
+            sequence: 0,
+            entries: [EventLogEntry::default(); GlobalEventLog::CAPACITY],
+            bump: 0,
+        };
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        log.append(GlobalEventLog::EVENT_REGISTERED, alice, 100);
+        log.append(GlobalEventLog::EVENT_REGISTERED, bob, 110);
+        log.append(GlobalEventLog::EVENT_VERIFIED, alice, 120);
+        log.append(GlobalEventLog::EVENT_REVOKED, alice, 130);
+        
+        let page = log.page(0);
+        let events: Vec<(u8, Pubkey, i64)> = page.entries.iter().map(|e| (e.event_type, e.identity, e.timestamp)).collect();
+        assert_eq!(
+            events,
+            vec![
+                (GlobalEventLog::EVENT_REGISTERED, alice, 100),
+                (GlobalEventLog::EVENT_REGISTERED, bob, 110),
+                (GlobalEventLog::EVENT_VERIFIED, alice, 120),
+                (GlobalEventLog::EVENT_REVOKED, alice, 130),
+            ]
+        );
+        assert_eq!((page.first_sequence, page.next_sequence), (0, 4));
+        assert_eq!(log.page(3).entries.len(), 1);
+        assert!(log.page(4).entries.is_empty());
+        
+        // After wrapping, overwritten records are skipped and paging resumes in order
+        for ts in 0..GlobalEventLog::CAPACITY as i64 {
+            log.append(GlobalEventLog::EVENT_VERIFIED, bob, 1_000 + ts);
+        }
+        let page = log.page(0);
+        assert_eq!(page.first_sequence, 4);
+        assert_eq!(page.entries.len(), GlobalEventLog::PAGE_SIZE);
+        assert_eq!(page.entries[0].timestamp, 1_000);
+        let next = log.page(page.next_sequence);
+        assert_eq!(next.entries[0].timestamp, 1_000 + GlobalEventLog::PAGE_SIZE as i64);
+        assert!(crate::return_data::encode_return_data(&page).is_ok());
+    }
+
+    fn reservation(reserver: Pubkey, reserved_slot: u64) -> CommitmentReservation {
+        CommitmentReservation {
+            reserver,
+            identity_commitment: [1u8; 32],
+            reserved_slot,
+            expires_slot: reserved_slot + CommitmentReservation::DURATION_SLOTS,
+            bump: 0,
+            namespace: [0u8; 16],
+        }
+    }
+
+    #[test]
+    fn test_reservation_blocks_competing_registration_until_expiry() {
+        let reserver = Pubkey::new_unique();
+        let competitor = Pubkey::new_unique();
+        let reservation = reservation(reserver, 100);
+        let expiry = 100 + CommitmentReservation::DURATION_SLOTS;
+
+        // The reserver can always finalize
+        assert!(!reservation.blocks(&reserver, 101));
+        // Others are blocked while active
+        assert!(reservation.blocks(&competitor, 101));
+        assert!(reservation.blocks(&competitor, expiry - 1));
+        // And free to register once it lapses
+        assert!(!reservation.blocks(&competitor, expiry));
+    }
+
+    fn scope(name: &str) -> [u8; 16] {
+        let mut tag = [0u8; 16];
+        tag[..name.len()].copy_from_slice(name.as_bytes());
+        tag
+    }
+
+    fn empty_session() -> Session {
+        Session {
+            user: Pubkey::new_unique(),
+            session_id: [0u8; 32],
+            created_at: 0,
+            expires_at: 0,
+            is_active: true,
+            bump: 0,
+            scopes: [0u8; 128],
+            disclosed_attributes: 0,
+            delegated: false,
+            namespace: [0u8; 16],
+            identity_epoch: 0,
+            last_active: 0,
+            disclosed_timestamps: [0; 8],
+        }
+    }
+
+    fn test_did(text: &str) -> [u8; 64] {
+        let mut did = [0u8; 64];
+        did[..text.len()].copy_from_slice(text.as_bytes());
+        did
+    }
+
+    #[test]
+    fn test_registered_did_resolves_to_identity() {
+        let did = test_did("did:sol:8jrTVUyvHrL5WTWyDoa6PTJRhh3MwbvLZXeGT81YjJjz");
+        DidRecord::validate(&did).unwrap();
+        
+        let namespace = [3u8; 16];
+        let identity = Pubkey::new_unique();
+        let (address, bump) = DidRecord::address(&namespace, &did, &crate::ID);
+        let (expected, _) = Pubkey::find_program_address(
+            &[b"did", namespace.as_ref(), &crate::compression::did_hash(&did)],
+            &crate::ID,
+        );
+        assert_eq!(address, expected);
+        
+        let record = DidRecord {
+            namespace,
+            did_hash: crate::compression::did_hash(&did),
+            did,
+            identity,
+            bump,
+        };
+        assert_eq!(record.resolve(&did).unwrap(), identity);
+        assert!(record.resolve(&test_did("did:sol:other")).is_err());
+    }
+
+    #[test]
+    fn test_did_prefix_validated() {
+        for malformed in ["", "did:sol:", "did:web:example.com", "did:sol:has space"] {
+            assert_eq!(
+                DidRecord::validate(&test_did(malformed)).unwrap_err(),
+                error!(crate::errors::ErrorCode::InvalidDid),
+                "{:?}",
+                malformed
+            );
+        }
+        // Bytes after the zero padding starts
+        let mut trailing = test_did("did:sol:abc");
+        trailing[40] = b'x';
+        assert!(DidRecord::validate(&trailing).is_err());
+    }
+
+    #[test]
+    fn test_attribute_session_discloses_only_that_attribute() {
+        let mut identity = test_identity(0);
+        assert!(Session::for_attribute(&identity, [1u8; 32], 1, 1_000, 0, 0).is_err());
+        
+        identity.mark_verified(2, 0).unwrap();
+        identity.mark_verified(1, 10).unwrap();
+        let session = Session::for_attribute(&identity, [1u8; 32], 1, 1_000, 10, 0).unwrap();
+        
+        assert_eq!(session.disclosed_attributes, 1);
+        assert_eq!(session.scopes(), vec![Session::attribute_scope(1).unwrap()]);
+        assert!(session.has_scope(&Session::attribute_scope(1).unwrap()));
+        assert!(!session.has_scope(&Session::attribute_scope(2).unwrap()));
+        assert!(session.is_valid_for(&identity, 500));
+        assert_eq!(&Session::attribute_scope(2).unwrap(), b"attr:nationality");
+    }
+
+    #[test]
+    fn test_session_expiry_enforced() {
+        let mut identity = test_identity(0);
+        identity.mark_verified(1, 0).unwrap();
+        
+        assert_eq!(
+            Session::require_future_expiry(100, 100).unwrap_err(),
+            error!(crate::errors::ErrorCode::SessionExpired)
+        );
+        assert!(Session::require_future_expiry(101, 100).is_ok());
+        assert!(Session::for_attribute(&identity, [1u8; 32], 1, 50, 100, 0).is_err());
+        
+        let mut session = Session::for_attribute(&identity, [1u8; 32], 1, 200, 100, 0).unwrap();
+        assert!(session.require_valid_for(&identity, 199).is_ok());
+        assert_eq!(
+            session.require_valid_for(&identity, 200).unwrap_err(),
+            error!(crate::errors::ErrorCode::SessionExpired)
+        );
+        
+        session.is_active = false;
+        assert_eq!(
+            session.require_valid_for(&identity, 150).unwrap_err(),
+            error!(crate::errors::ErrorCode::InvalidSession)
+        );
+        session.is_active = true;
+        identity.session_epoch += 1;
+        assert_eq!(
+            session.require_valid_for(&identity, 150).unwrap_err(),
+            error!(crate::errors::ErrorCode::InvalidSession)
+        );
+    }
+
+    #[test]
+    fn test_session_disclosure_survives_revocation() {
+        let mut identity = test_identity(0);
+        identity.mark_verified(1, 100).unwrap();
+        identity.mark_verified(4, 200).unwrap();
+        
+        let mut session = empty_session();
+        session.record_disclosure(&identity, identity.attributes_verified);
+        assert_eq!(session.disclosed_attributes, 1 | 4);
+        assert_eq!(session.disclosed_timestamps, [100, 0, 200, 0, 0, 0, 0, 0]);
+        
+        // Revoking and re-verifying later doesn't rewrite what was presented
+        identity.revoke();
+        identity.mark_verified(1, 900).unwrap();
+        assert_eq!(session.disclosed_attributes, 1 | 4);
+        assert_eq!(session.disclosed_timestamps, [100, 0, 200, 0, 0, 0, 0, 0]);
+        assert!(!session.is_valid_for(&identity, 500));
+        
+        // Single-attribute sessions only snapshot their own bit
+        let session = Session::for_attribute(&identity, [2u8; 32], 1, 1_000, 900, 0).unwrap();
+        assert_eq!(session.disclosed_timestamps[0], 900);
+        assert_eq!(session.disclosed_timestamps[2], 0);
+    }
+
+    #[test]
+    fn test_reconcile_fixes_desynced_session_counter() {
+        let mut identity = test_identity(1);
+        let mut session = empty_session();
+        session.user = identity.owner;
+        session.expires_at = 1_000;
+        
+        identity.open_sessions(3).unwrap();
+        identity.close_session(&session).unwrap();
+        assert_eq!(identity.active_sessions, 2);
+        
+        // One session account vanished without `close_session`
+        let mut expired = session.clone();
+        expired.expires_at = 100;
+        let sessions = [(Pubkey::new_unique(), session.clone()), (Pubkey::new_unique(), expired)];
+        assert_eq!(identity.reconcile_sessions(&sessions, 500).unwrap(), 1);
+        assert_eq!(identity.active_sessions, 1);
+        
+        // Passing the same account twice or another user's session is refused
+        let duplicated = [sessions[0].clone(), sessions[0].clone()];
+        assert!(identity.reconcile_sessions(&duplicated, 500).is_err());
+        let foreign = [(Pubkey::new_unique(), empty_session())];
+        assert!(identity.reconcile_sessions(&foreign, 500).is_err());
+        
+        // A desynced counter can't wrap around
+        identity.active_sessions = 0;
+        assert_eq!(
+            identity.close_session(&session).unwrap_err(),
+            error!(crate::errors::ErrorCode::SessionCountOutOfRange)
+        );
+        identity.active_sessions = u16::MAX;
+        assert!(identity.open_sessions(1).is_err());
+        
+        identity.revoke();
+        assert_eq!(identity.active_sessions, 0);
+        assert!(identity.close_session(&session).is_ok());
+    }
+
+    #[test]
+    fn test_revocation_invalidates_sessions() {
+        let mut identity = test_identity(1);
+        let mut session = empty_session();
+        session.user = identity.owner;
+        session.expires_at = 1_000;
+        session.identity_epoch = identity.session_epoch;
+        assert!(session.is_valid_for(&identity, 500));
+        assert!(!session.is_valid_for(&identity, 1_000));
+        
+        identity.revoke();
+        assert!(!identity.is_verified);
+        assert!(!session.is_valid_for(&identity, 500));
+        
+        // Sessions opened after revocation pick up the new epoch
+        let mut fresh = empty_session();
+        fresh.user = identity.owner;
+        fresh.expires_at = 1_000;
+        fresh.identity_epoch = identity.session_epoch;
+        assert!(fresh.is_valid_for(&identity, 500));
+    }
+
+    #[test]
+    fn test_idle_session_needs_heartbeat() {
+        let mut session = empty_session();
+        session.expires_at = 10_000;
+        session.last_active = 1_000;
+        let idle_timeout = 600;
+        
+        assert!(session.require_not_idle(1_600, idle_timeout).is_ok());
+        assert_eq!(
+            session.require_not_idle(1_601, idle_timeout).unwrap_err(),
+            error!(crate::errors::ErrorCode::SessionIdle)
+        );
+        assert!(session.require_not_idle(5_000, 0).is_ok());
+        
+        session.heartbeat(1_601).unwrap();
+        assert!(session.require_not_idle(1_601, idle_timeout).is_ok());
+        
+        // Heartbeats don't revive expired sessions
+        assert!(session.heartbeat(10_000).is_err());
+    }
+
+    #[test]
+    fn test_session_scopes_membership() {
+        let mut session = empty_session();
+        assert!(!session.has_scope(&scope("trade")));
+
+        session.set_scopes(&[scope("read:profile"), scope("trade")]).unwrap();
+        assert!(session.has_scope(&scope("read:profile")));
+        assert!(session.has_scope(&scope("trade")));
+        assert!(!session.has_scope(&scope("withdraw")));
+        assert_eq!(session.scopes().len(), 2);
+    }
+
+    #[test]
+    fn test_session_scopes_bounded() {
+        let mut session = empty_session();
+        let scopes = vec![scope("trade"); Session::MAX_SCOPES];
+        assert!(session.set_scopes(&scopes).is_ok());
+
+        let too_many = vec![scope("trade"); Session::MAX_SCOPES + 1];
+        assert!(session.set_scopes(&too_many).is_err());
+    }
+
+    #[test]
+    fn test_root_checkpoint_rejects_stale_root() {
+        let mut checkpoint = RootCheckpoint {
+            authority: Pubkey::new_unique(),
+            max_root_age_slots: 100,
+            head: 0,
+            count: 0,
+            roots: [[0u8; 32]; RootCheckpoint::CAPACITY],
+            slots: [0u64; RootCheckpoint::CAPACITY],
+            bump: 0,
+            namespace: [0u8; 16],
+        };
+        checkpoint.push([1u8; 32], 1_000);
+        checkpoint.push([2u8; 32], 1_050);
+
+        assert!(checkpoint.require_fresh(&[2u8; 32], 1_100).is_ok());
+        assert!(checkpoint.require_fresh(&[1u8; 32], 1_100).is_ok());
+        assert_eq!(
+            checkpoint.require_fresh(&[1u8; 32], 1_101).unwrap_err(),
+            error!(crate::errors::ErrorCode::StaleMerkleRoot)
+        );
+        // Roots never checkpointed are rejected outright
+        assert_eq!(
+            checkpoint.require_fresh(&[3u8; 32], 1_050).unwrap_err(),
+            error!(crate::errors::ErrorCode::MerkleTreeError)
+        );
+    }
+
+    fn session_request(id: u8) -> SessionRequest {
+        SessionRequest {
+            session_id: [id; 32],
+            expiry: 1_000,
+            scopes: vec![[id; 16]],
+        }
+    }
+
+    #[test]
+    fn test_session_batch_validation() {
+        let batch = [session_request(1), session_request(2), session_request(3)];
+        assert!(SessionRequest::validate_batch(&batch).is_ok());
+
+        // A duplicate id anywhere rejects the whole batch
+        let duplicate = [session_request(1), session_request(2), session_request(1)];
+        assert!(SessionRequest::validate_batch(&duplicate).is_err());
+
+        let oversized = vec![session_request(1); SessionRequest::MAX_BATCH + 1];
+        assert!(SessionRequest::validate_batch(&oversized).is_err());
+        assert!(SessionRequest::validate_batch(&[]).is_err());
+
+        // Every session must be granted at least one scope
+        let mut unscoped = session_request(4);
+        unscoped.scopes.clear();
+        assert_eq!(
+            SessionRequest::validate_batch(&[session_request(1), unscoped]).unwrap_err(),
+            error!(crate::errors::ErrorCode::InvalidSession)
+        );
+    }
+
+    #[test]
+    fn test_attribute_proof_batch_validation() {
+        let entry = |attribute_type: u8| AttributeProof {
+            attribute_type,
+            proof: vec![0u8; 256],
+            public_inputs: vec![0u8; 32],
+        };
+        assert_eq!(AttributeProof::validate_batch(&[entry(1), entry(2), entry(4)]).unwrap(), 1 | 2 | 4);
+        
+        // Overlapping bits would verify an attribute twice
+        assert_eq!(
+            AttributeProof::validate_batch(&[entry(1), entry(1 | 2)]).unwrap_err(),
+            error!(crate::errors::ErrorCode::DuplicateAttribute)
+        );
+        assert!(AttributeProof::validate_batch(&[entry(1), entry(0)]).is_err());
+        assert!(AttributeProof::validate_batch(&[entry(8)]).is_err());
+        assert_eq!(
+            AttributeProof::validate_batch(&[]).unwrap_err(),
+            error!(crate::errors::ErrorCode::InvalidBatchSize)
+        );
+        let oversized: Vec<_> = (0..=AttributeProof::MAX_BATCH).map(|_| entry(1)).collect();
+        assert_eq!(
+            AttributeProof::validate_batch(&oversized).unwrap_err(),
+            error!(crate::errors::ErrorCode::InvalidBatchSize)
+        );
+    }
+
+    fn test_identity(attributes_verified: u8) -> Identity {
+        Identity {
+            owner: Pubkey::new_unique(),
+            identity_commitment: [1u8; 32],
+            merkle_root: [2u8; 32],
+            is_verified: attributes_verified != 0,
+            verification_timestamp: 0,
+            attributes_verified,
+            bump: 0,
+            discoverable: false,
+            frozen: false,
+            verify_attempts: 0,
+            session_delegate: Pubkey::default(),
+            verification_nonce: 0,
+            expires_at: 0,
+            namespace: [0u8; 16],
+            session_epoch: 0,
+            attribute_expiry: [0; 8],
+            attribute_timestamps: [0; 8],
+            recent_roots: [[0u8; 32]; Identity::RECENT_ROOTS],
+            guardian: Pubkey::default(),
+            active_sessions: 0,
+            did: [0u8; 64],
+            update_delay_secs: 0,
+            pending_commitment: [0u8; 32],
+            pending_merkle_root: [0u8; 32],
+            pending_update_at: 0,
+            verification_logged: false,
+            frozen_by_authority: false,
+        }
+    }
+
+    #[test]
+    fn test_time_locked_update_applies_after_delay() {
+        let mut identity = test_identity(1);
+        identity.set_update_delay(3_600).unwrap();
+        assert_eq!(
+            identity.require_unlocked_updates().unwrap_err(),
+            error!(crate::errors::ErrorCode::UpdateTimeLocked)
+        );
+        assert!(identity.set_update_delay(60).is_err());
+        assert_eq!(
+            identity.apply_update(0).unwrap_err(),
+            error!(crate::errors::ErrorCode::NoPendingUpdate)
+        );
+        
+        identity.propose_update([7u8; 32], [8u8; 32], 1_000);
+        assert_eq!(
+            identity.apply_update(1_000 + 3_599).unwrap_err(),
+            error!(crate::errors::ErrorCode::UpdateTimeLocked)
+        );
+        assert_ne!(identity.identity_commitment, [7u8; 32]);
+        
+        identity.apply_update(1_000 + 3_600).unwrap();
+        assert_eq!(identity.identity_commitment, [7u8; 32]);
+        assert_eq!(identity.merkle_root, [8u8; 32]);
+        assert_eq!(identity.attributes_verified, 0);
+        assert_eq!(identity.pending_update_at, 0);
+        
+        // A cancelled proposal can't be applied later
+        identity.propose_update([9u8; 32], [9u8; 32], 5_000);
+        identity.cancel_update();
+        assert!(identity.apply_update(10_000).is_err());
+        assert_eq!(identity.identity_commitment, [7u8; 32]);
+    }
+
+    #[test]
+    fn test_verifier_supplied_attribute_expiry() {
+        let mut identity = test_identity(0);
+        let now = 1_700_000_000;
+        
+        assert_eq!(
+            identity.mark_verified_until(2, now, now).unwrap_err(),
+            error!(crate::errors::ErrorCode::AttributeExpired)
+        );
+        
+        identity.mark_verified_until(2, now, now + 100).unwrap();
+        identity.mark_verified(1, now).unwrap();
+        assert!(identity.require_verified_at(1 | 2, now + 99).is_ok());
+        assert_eq!(
+            identity.require_verified_at(2, now + 100).unwrap_err(),
+            error!(crate::errors::ErrorCode::AttributeExpired)
+        );
+        // Attributes without a supplied expiry are unaffected
+        assert!(identity.require_verified_at(1, now + 100).is_ok());
+        
+        // A plain re-verification drops the override
+        identity.mark_verified(2, now + 200).unwrap();
+        assert!(identity.require_verified_at(2, now + 200).is_ok());
+    }
+
+    #[test]
+    fn test_expired_attributes_reports_stale_bits() {
+        let mut identity = test_identity(0);
+        let ttl = 90 * 24 * 60 * 60;
+        let now = 1_700_000_000;
+        
+        identity.mark_verified(2, now - ttl - 1).unwrap(); // stale nationality
+        identity.mark_verified(1, now - 60).unwrap(); // fresh age
+        assert_eq!(identity.expired_attributes(now, ttl), 2);
+        
+        // No TTL means nothing goes stale
+        assert_eq!(identity.expired_attributes(now, 0), 0);
+        
+        // Re-verifying refreshes the bit
+        identity.mark_verified(2, now).unwrap();
+        assert_eq!(identity.expired_attributes(now, ttl), 0);
+    }
+
+    #[test]
+    fn test_attribute_freshness_under_registry_ttl() {
+        let mut registry = test_registry();
+        let mut identity = test_identity(0);
+        let now = 1_700_000_000;
+        registry.verification_ttl = 90 * 24 * 60 * 60;
+        
+        identity.mark_verified(1, now - registry.verification_ttl).unwrap();
+        identity.mark_verified(2, now - registry.verification_ttl - 1).unwrap();
+        assert!(registry.is_attribute_fresh(&identity, 1, now));
+        assert!(!registry.is_attribute_fresh(&identity, 2, now));
+        assert!(!registry.is_attribute_fresh(&identity, 1 | 2, now));
+        assert!(!registry.is_attribute_fresh(&identity, 4, now)); // never verified
+        assert_eq!(
+            registry.require_fresh_attributes(&identity, identity.attributes_verified, now).unwrap_err(),
+            error!(crate::errors::ErrorCode::AttributeExpired)
+        );
+        
+        assert!(registry.require_fresh_attributes(&identity, 1 | 4, now).is_ok());
+        
+        registry.verification_ttl = 0;
+        assert!(registry.require_fresh_attributes(&identity, identity.attributes_verified, now).is_ok());
+    }
+
+    #[test]
+    fn test_attribute_timestamp_per_bit() {
+        let mut identity = test_identity(0);
+        identity.mark_verified(1, 100).unwrap();
+        identity.mark_verified(2, 200).unwrap();
+        identity.mark_verified(1, 300).unwrap();
+        
+        assert_eq!(identity.attribute_timestamp(1).unwrap(), 300);
+        assert_eq!(identity.attribute_timestamp(2).unwrap(), 200);
+        assert_eq!(identity.attribute_timestamp(4).unwrap(), 0);
+        assert_eq!(
+            identity.attribute_timestamp(1 | 2).unwrap_err(),
+            error!(crate::errors::ErrorCode::CompositeAttributeType)
+        );
+        assert!(identity.attribute_timestamp(0).is_err());
+    }
+
+    #[test]
+    fn test_root_update_preserves_verification() {
+        let mut identity = test_identity(1 | 4);
+        let old_root = identity.merkle_root;
+        
+        identity.advance_root([9u8; 32]);
+        assert_eq!(identity.merkle_root, [9u8; 32]);
+        assert_eq!(identity.recent_roots[0], old_root);
+        assert!(identity.is_recent_root(&old_root));
+        assert!(identity.has_attributes(1 | 4));
+        assert_eq!(identity.identity_commitment, [1u8; 32]);
+        
+        // Only the newest RECENT_ROOTS old roots are kept
+        for i in 0..Identity::RECENT_ROOTS as u8 {
+            identity.advance_root([10 + i; 32]);
+        }
+        assert!(!identity.is_recent_root(&old_root));
+        assert!(identity.is_recent_root(&[9u8; 32]));
+    }
+
+    #[test]
+    fn test_require_verified_guard() {
+        let identity = test_identity(1 | 4);
+        assert!(identity.require_verified(1).is_ok());
+        assert!(identity.require_verified(1 | 4).is_ok());
+        assert_eq!(
+            identity.require_verified(2).unwrap_err(),
+            error!(crate::errors::ErrorCode::InvalidVerificationStatus)
+        );
+        
+        // Unverified identities and empty requirements never pass
+        assert!(test_identity(0).require_verified(1).is_err());
+        assert!(identity.require_verified(0).is_err());
+    }
+
+    #[test]
+    fn test_directory_skips_non_discoverable() {
+        let hidden = test_identity(1);
+        assert_eq!(hidden.directory_entry(), None);
+
+        let mut listed = test_identity(1 | 2);
+        listed.discoverable = true;
+        let entry = listed.directory_entry().unwrap();
+        assert_eq!(entry.owner, listed.owner);
+        assert_eq!(entry.attributes_verified, 1 | 2);
+    }
+
+    #[test]
+    fn test_freeze_preserves_attributes() {
+        let mut identity = test_identity(1 | 4);
+        identity.freeze(false);
+        assert_eq!(
+            identity.require_not_frozen().unwrap_err(),
+            error!(crate::errors::ErrorCode::IdentityFrozen)
+        );
+        
+        identity.unfreeze(false).unwrap();
+        assert!(identity.require_not_frozen().is_ok());
+        assert!(identity.has_attributes(1 | 4));
+    }
+
+    #[test]
+    fn test_only_the_authority_lifts_its_freeze() {
+        let mut identity = test_identity(1);
+        identity.freeze(true);
+        // The owner freezing again doesn't make it the owner's hold
+        identity.freeze(false);
+        assert_eq!(
+            identity.unfreeze(false).unwrap_err(),
+            error!(crate::errors::ErrorCode::FrozenByAuthority)
+        );
+        assert!(identity.frozen);
+        
+        identity.unfreeze(true).unwrap();
+        assert!(!identity.frozen && !identity.frozen_by_authority);
+    }
+
+    #[test]
+    fn test_verify_attempts_count_every_outcome() {
+        let mut identity = test_identity(0);
+        
+        assert!(identity.record_verify_attempt(&Ok(true)));
+        assert!(!identity.record_verify_attempt(&Err(error!(crate::errors::ErrorCode::ProofVerificationFailed))));
+        assert!(!identity.record_verify_attempt(&Ok(false)));
+        assert_eq!(identity.verify_attempts, 3);
+        
+        identity.verify_attempts = u32::MAX;
+        identity.record_verify_attempt(&Ok(true));
+        assert_eq!(identity.verify_attempts, u32::MAX);
+    }
+
+    #[test]
+    fn test_rotated_session_preserves_claims() {
+        let mut session = empty_session();
+        session.expires_at = 1_700_000_000;
+        session.disclosed_attributes = 1 | 2;
+        session.set_scopes(&[scope("read:profile")]).unwrap();
+        
+        let rotated = session.rotated([9u8; 32], 254);
+        assert_eq!(rotated.session_id, [9u8; 32]);
+        assert_eq!(rotated.bump, 254);
+        assert_eq!(rotated.user, session.user);
+        assert_eq!(rotated.expires_at, 1_700_000_000);
+        assert_eq!(rotated.disclosed_attributes, 1 | 2);
+        assert!(rotated.has_scope(&scope("read:profile")));
+    }
+
+    #[test]
+    fn test_policy_evaluation() {
+        // (age AND uniqueness) OR nationality
+        let policy = [
+            PolicyNode::Attribute(1),
+            PolicyNode::Attribute(4),
+            PolicyNode::And,
+            PolicyNode::Attribute(2),
+            PolicyNode::Or,
+        ];
+        assert!(PolicyNode::evaluate(&policy, 1 | 4).unwrap());
+        assert!(PolicyNode::evaluate(&policy, 2).unwrap());
+        assert!(!PolicyNode::evaluate(&policy, 1).unwrap());
+        
+        let not_age = [PolicyNode::Attribute(1), PolicyNode::Not];
+        assert!(PolicyNode::evaluate(&not_age, 2).unwrap());
+    }
+
+    #[test]
+    fn test_malformed_policy_rejected() {
+        let invalid = error!(crate::errors::ErrorCode::InvalidPolicy);
+        assert_eq!(PolicyNode::evaluate(&[], 1).unwrap_err(), invalid);
+        assert_eq!(PolicyNode::evaluate(&[PolicyNode::And], 1).unwrap_err(), invalid);
+        assert_eq!(
+            PolicyNode::evaluate(&[PolicyNode::Attribute(1), PolicyNode::Attribute(2)], 1).unwrap_err(),
+            invalid
+        );
+        
+        // Nine pending operands exceed the depth bound
+        let deep = [PolicyNode::Attribute(1); PolicyNode::MAX_DEPTH + 1];
+        assert_eq!(PolicyNode::evaluate(&deep, 1).unwrap_err(), invalid);
+    }
+
+    #[test]
+    fn test_session_delegate_authorization() {
+        let mut identity = test_identity(1);
+        let delegate = Pubkey::new_unique();
+        
+        // No delegate configured: nobody, not even the default key, may act
+        assert!(identity.require_session_delegate(&delegate).is_err());
+        assert!(identity.require_session_delegate(&Pubkey::default()).is_err());
+        
+        identity.session_delegate = delegate;
+        assert!(identity.require_session_delegate(&delegate).is_ok());
+        assert_eq!(
+            identity.require_session_delegate(&Pubkey::new_unique()).unwrap_err(),
+            error!(crate::errors::ErrorCode::UnauthorizedAccess)
+        );
+    }
+
+    #[test]
+    fn test_guardian_verifies_ward() {
+        let mut ward = test_identity(0);
+        let guardian = Pubkey::new_unique();
+        assert!(ward.require_guardian(&guardian).is_err());
+        assert!(ward.require_guardian(&Pubkey::default()).is_err());
+        
+        ward.guardian = guardian;
+        assert!(ward.require_guardian(&guardian).is_ok());
+        ward.mark_verified(1, 0).unwrap();
+        assert!(ward.has_attributes(1));
+        
+        assert_eq!(
+            ward.require_guardian(&Pubkey::new_unique()).unwrap_err(),
+            error!(crate::errors::ErrorCode::UnauthorizedAccess)
+        );
+        // The guardian is not the owner: owner-bound inputs still name the ward
+        assert_ne!(
+            crate::compression::pubkey_to_field(&guardian),
+            crate::compression::pubkey_to_field(&ward.owner)
+        );
+    }
+
+    fn test_registry() -> IdentityRegistry {
+        IdentityRegistry {
+            authority: Pubkey::new_unique(),
+            total_identities: 0,
+            bump: 0,
+            merkle_arity: 2,
+            recent_nullifiers: [[0u8; 32]; IdentityRegistry::NULLIFIER_CAPACITY],
+            nullifier_count: 0,
+            identity_ttl: 0,
+            namespace: [0u8; 16],
+            attribute_dependencies: [0u8; 8],
+            revocation_root: [0u8; 32],
+            input_layouts: 0,
+            trusted_verifiers: [TrustedVerifier::default(); IdentityRegistry::MAX_TRUSTED_VERIFIERS],
+            verifier_count: 0,
+            verification_ttl: 0,
+            proof_systems: [0u8; 8],
+            session_idle_timeout: 0,
+            attribute_weights: [0u16; 8],
+            min_verification_weight: 0,
+            allowlist_root: [0u8; 32],
+            tree_frontier: [[0u8; 32]; IdentityRegistry::TREE_DEPTH],
+            tree_leaf_count: 0,
+            tree_root: [0u8; 32],
+            governance_program: Pubkey::default(),
+            governed: false,
+            min_tree_depth: 0,
+            verification_fee: 0,
+            defined_attributes: 0,
+            paused_instructions: 0,
+            spent_root: crate::merkle::empty_subtree_hashes(IdentityRegistry::SPENT_TREE_DEPTH).unwrap()
+                [IdentityRegistry::SPENT_TREE_DEPTH],
+            global_verification_cap: 0,
+            global_window_slots: 0,
+            nullifier_head: 0,
+            root_checkpointed: false,
+            event_logged: false,
+        }
+    }
+
+    #[test]
+    fn test_list_attributes_returns_every_definition() {
+        let mut registry = test_registry();
+        registry.set_attribute_dependencies(4, 1).unwrap();
+        
+        let mut definitions = Vec::new();
+        for (attribute_type, label) in [(1u8, "Over 18"), (2, "Nationality"), (4, "Unique human")] {
+            let mut name = [0u8; 32];
+            name[..label.len()].copy_from_slice(label.as_bytes());
+            definitions.push(AttributeDefinition {
+                namespace: registry.namespace,
+                attribute_type,
+                name,
+                vk_version: attribute_type as u16,
+                bump: 0,
+            });
+            registry.defined_attributes |= attribute_type;
+        }
+        
+        let metadata = registry.attribute_metadata(&definitions).unwrap();
+        assert_eq!(metadata.len(), 3);
+        assert_eq!(metadata[0].name, definitions[0].name);
+        assert_eq!(metadata[1].vk_version, 2);
+        assert_eq!(metadata[2].attribute_type, 4);
+        assert_eq!(metadata[2].depends_on, 1);
+        
+        // A missing, repeated or out-of-order definition makes the list incomplete
+        let mismatch = error!(crate::errors::ErrorCode::AttributeDefinitionMismatch);
+        assert_eq!(registry.attribute_metadata(&definitions[..2]).unwrap_err(), mismatch);
+        let repeated = [definitions[0].clone(), definitions[0].clone(), definitions[2].clone()];
+        assert_eq!(registry.attribute_metadata(&repeated).unwrap_err(), mismatch);
+        let reordered = [definitions[1].clone(), definitions[0].clone(), definitions[2].clone()];
+        assert_eq!(registry.attribute_metadata(&reordered).unwrap_err(), mismatch);
+    }
+
+    #[test]
+    fn test_registration_below_min_tree_depth_rejected() {
+        let mut registry = test_registry();
+        let shallow = RootCanary { tree_height: 1, siblings: vec![[0u8; 32]] };
+        assert!(registry.require_registration_depth(None).is_ok());
+        assert!(registry.require_registration_depth(Some(&shallow)).is_ok());
+        
+        registry.min_tree_depth = 4;
+        let at_minimum = RootCanary { tree_height: 4, siblings: vec![[0u8; 32]; 4] };
+        assert!(registry.require_registration_depth(Some(&at_minimum)).is_ok());
+        assert_eq!(
+            registry.require_registration_depth(Some(&shallow)).unwrap_err(),
+            error!(crate::errors::ErrorCode::MerkleTreeError)
+        );
+        // The height must be declared once a floor is set
+        assert!(registry.require_registration_depth(None).is_err());
+        
+        assert!(registry.require_tree_depth(3).is_err());
+        assert!(registry.require_tree_depth(4).is_ok());
+    }
+
+    #[test]
+    fn test_pausing_verify_leaves_session_creation_open() {
+        let mut registry = test_registry();
+        assert!(!registry.is_paused(IdentityRegistry::PAUSE_ALL));
+        
+        registry.paused_instructions = IdentityRegistry::PAUSE_VERIFY;
+        assert!(registry.is_paused(IdentityRegistry::PAUSE_VERIFY));
+        assert!(!registry.is_paused(IdentityRegistry::PAUSE_CREATE_SESSION));
+        assert!(!registry.is_paused(IdentityRegistry::PAUSE_REGISTER));
+        
+        registry.paused_instructions |= IdentityRegistry::PAUSE_CREATE_SESSION;
+        assert!(registry.is_paused(IdentityRegistry::PAUSE_CREATE_SESSION));
+    }
+
+    #[test]
+    fn test_governed_registry_requires_governance_cpi() {
+        let mut registry = test_registry();
+        let wallet = registry.authority;
+        let registry_key = Pubkey::new_unique();
+        let governance_program = Pubkey::new_unique();
+        assert!(registry.authorizes(&wallet, 1));
+        
+        let pda = registry.transfer_to_governance(&registry_key, &governance_program).unwrap();
+        let (expected, _) = Pubkey::find_program_address(
+            &[IdentityRegistry::GOVERNANCE_SEED, registry_key.as_ref()],
+            &governance_program,
+        );
+        assert_eq!(pda, expected);
+        
+        // Invoked by the governance program, signing for its PDA
+        assert!(registry.authorizes(&pda, 2));
+        // The old wallet is locked out, and the PDA can't sign at the top level
+        assert!(!registry.authorizes(&wallet, 1));
+        assert!(!registry.authorizes(&wallet, 2));
+        assert!(!registry.authorizes(&pda, 1));
+        
+        assert!(registry.transfer_to_governance(&registry_key, &Pubkey::default()).is_err());
+        assert!(registry.transfer_to_governance(&registry_key, &crate::ID).is_err());
+    }
+
+    #[test]
+    fn test_revoked_commitment_cannot_open_session() {
+        let revoked = [5u8; 32]; // low bits 0b01: position 1
+        let active = [6u8; 32]; // low bits 0b10: position 2
+        let empty = crate::merkle::EMPTY_LEAF;
+        let leaves = [empty, revoked, empty, empty];
+        let levels = crate::merkle::build_levels(&leaves).unwrap();
+        
+        let mut registry = test_registry();
+        assert!(registry.require_not_revoked(&revoked, None).is_ok());
+        registry.revocation_root = crate::merkle::root(&leaves).unwrap();
+        
+        let (siblings, _) = crate::merkle::proof(&levels, 1).unwrap();
+        assert_eq!(
+            registry.require_not_revoked(&revoked, Some(&siblings)).unwrap_err(),
+            error!(crate::errors::ErrorCode::CredentialRevoked)
+        );
+        
+        let (siblings, _) = crate::merkle::proof(&levels, 2).unwrap();
+        assert!(registry.require_not_revoked(&active, Some(&siblings)).is_ok());
+        
+        // Once a root is published the proof is mandatory
+        assert!(registry.require_not_revoked(&active, None).is_err());
+        // A wrong proof proves neither membership nor absence
+        let (siblings, _) = crate::merkle::proof(&levels, 1).unwrap();
+        assert_eq!(
+            registry.require_not_revoked(&active, Some(&siblings)).unwrap_err(),
+            error!(crate::errors::ErrorCode::MerkleTreeError)
+        );
+    }
+
+    #[test]
+    fn test_global_cap_blocks_until_window_rolls() {
+        let mut registry = test_registry();
+        assert_eq!(registry.rate_window_of(5), None);
+        
+        registry.global_verification_cap = 2;
+        registry.global_window_slots = 10;
+        assert_eq!(registry.rate_window_of(100), Some(10));
+        assert_eq!(registry.rate_window_of(109), Some(10));
+        assert_eq!(registry.rate_window_of(110), Some(11));
+        
+        let mut window = RateWindow { namespace: registry.namespace, window: 10, count: 0, bump: 0 };
+        window.record(1, registry.global_verification_cap).unwrap();
+        window.record(1, registry.global_verification_cap).unwrap();
+        assert_eq!(
+            window.record(1, registry.global_verification_cap).unwrap_err(),
+            error!(crate::errors::ErrorCode::GlobalRateLimited)
+        );
+        assert_eq!(window.count, 2);
+        
+        // A batch counts every identity it verifies, all or nothing
+        let mut window = RateWindow { namespace: registry.namespace, window: 11, count: 1, bump: 0 };
+        assert!(window.record(2, registry.global_verification_cap).is_err());
+        assert_eq!(window.count, 1);
+        window.record(1, registry.global_verification_cap).unwrap();
+        
+        // One-slot windows roll every slot
+        registry.global_window_slots = 1;
+        assert_eq!(registry.rate_window_of(111), Some(111));
+        assert_eq!(registry.rate_window_of(112), Some(112));
+    }
+
+    #[test]
+    fn test_mark_spent_updates_root_once() {
+        let mut registry = test_registry();
+        let empty_root = registry.spent_root;
+        // Every sibling of a leaf in the empty tree is an empty subtree
+        let nullifier = [7u8; 32];
+        
+        registry.mark_spent(&nullifier, 0, &[]).unwrap();
+        assert_ne!(registry.spent_root, empty_root);
+        let spent_root = registry.spent_root;
+        
+        assert_eq!(
+            registry.mark_spent(&nullifier, 0, &[]).unwrap_err(),
+            error!(crate::errors::ErrorCode::NullifierAlreadyUsed)
+        );
+        assert_eq!(registry.spent_root, spent_root);
+        
+        // A path from before the first spend no longer matches the root
+        assert_eq!(
+            registry.mark_spent(&[9u8; 32], 0, &[]).unwrap_err(),
+            error!(crate::errors::ErrorCode::MerkleTreeError)
+        );
+        assert_eq!(
+            registry.mark_spent(&crate::merkle::EMPTY_LEAF, 0, &[]).unwrap_err(),
+            error!(crate::errors::ErrorCode::EmptyLeafNullifier)
+        );
+        assert!(registry.mark_spent(&[9u8; 32], 1, &[]).is_err());
+        
+        // The leaf indices of [7; 32] and [6; 32] differ in bit 56 at most, so
+        // the second leaf's only non-empty sibling is the subtree of the first
+        let level = 56;
+        let zeros = crate::merkle::empty_subtree_hashes(level).unwrap();
+        let index = IdentityRegistry::spent_leaf_index(&nullifier);
+        let indices: Vec<bool> = (0..level).map(|i| (index >> i) & 1 == 0).collect();
+        let subtree = crate::merkle::path_root(&nullifier, &zeros[..level], &indices).unwrap();
+        assert_eq!(
+            registry.mark_spent(&[6u8; 32], 0, &[]).unwrap_err(),
+            error!(crate::errors::ErrorCode::MerkleTreeError)
+        );
+        registry.mark_spent(&[6u8; 32], 1 << level, &[subtree]).unwrap();
+        assert_ne!(registry.spent_root, spent_root);
+        
+        let too_long = [[0u8; 32]; IdentityRegistry::MAX_SPENT_PATH_SIBLINGS + 1];
+        assert_eq!(
+            registry.mark_spent(&[9u8; 32], (1 << too_long.len()) - 1, &too_long).unwrap_err(),
+            error!(crate::errors::ErrorCode::MerkleTreeError)
+        );
+    }
+
+    #[test]
+    fn test_verification_weight_threshold() {
+        let mut registry = test_registry();
+        assert!(registry.require_verification_weight(0).is_ok());
+        
+        // age 30, nationality 30, uniqueness 50; need 60
+        registry.set_verification_weights([30, 30, 50, 0, 0, 0, 0, 0], 60).unwrap();
+        assert!(registry.require_verification_weight(1 | 2).is_ok());
+        assert!(registry.require_verification_weight(1 | 4).is_ok());
+        assert!(registry.require_verification_weight(2 | 4).is_ok());
+        assert_eq!(
+            registry.require_verification_weight(4).unwrap_err(),
+            error!(crate::errors::ErrorCode::InsufficientVerification)
+        );
+        
+        // Undefined attribute bits can't be weighted
+        assert!(registry.set_verification_weights([0, 0, 0, 10, 0, 0, 0, 0], 0).is_err());
+    }
+
+    #[test]
+    fn test_allowlisted_registration() {
+        let allowed = Pubkey::new_unique();
+        let leaves = [allowed.to_bytes(), [7u8; 32]];
+        let levels = crate::merkle::build_levels(&leaves).unwrap();
+        let (siblings, indices) = crate::merkle::proof(&levels, 0).unwrap();
+        let proof = AllowlistProof { siblings, indices };
+        
+        let mut registry = test_registry();
+        assert!(registry.require_allowlisted(&Pubkey::new_unique(), None).is_ok());
+        
+        registry.allowlist_root = crate::merkle::root(&leaves).unwrap();
+        assert!(registry.require_allowlisted(&allowed, Some(&proof)).is_ok());
+        assert_eq!(
+            registry.require_allowlisted(&Pubkey::new_unique(), Some(&proof)).unwrap_err(),
+            error!(crate::errors::ErrorCode::NotAllowlisted)
+        );
+        assert_eq!(
+            registry.require_allowlisted(&allowed, None).unwrap_err(),
+            error!(crate::errors::ErrorCode::NotAllowlisted)
+        );
+    }
+
+    #[test]
+    fn test_verification_cache_window() {
+        let mut cache = VerificationCache {
+            identity: Pubkey::new_unique(),
+            proof_hash: [0u8; 32],
+            valid: false,
+            cached_until_slot: 0,
+            bump: 0,
+        };
+        let key = crate::compression::verification_cache_key(1, &[1u8; 256], &[2u8; 32]);
+        assert_eq!(cache.lookup(&key, 0), None);
+        
+        cache.store(key, true, 100);
+        assert_eq!(cache.lookup(&key, 100 + VerificationCache::WINDOW_SLOTS), Some(true));
+        assert!(cache.require_cached(&key, 120).unwrap());
+        
+        // A different proof misses, and the entry expires after the window
+        let other = crate::compression::verification_cache_key(2, &[1u8; 256], &[2u8; 32]);
+        assert_eq!(cache.lookup(&other, 120), None);
+        assert_eq!(cache.lookup(&key, 101 + VerificationCache::WINDOW_SLOTS), None);
+        assert_eq!(
+            cache.require_cached(&key, 101 + VerificationCache::WINDOW_SLOTS).unwrap_err(),
+            error!(crate::errors::ErrorCode::CacheExpired)
+        );
+    }
+
+    #[test]
+    fn test_proof_system_selection() {
+        let mut registry = test_registry();
+        assert_eq!(registry.proof_system_of(1 | 2).unwrap(), 0);
+        
+        registry.set_proof_system(2, 1).unwrap();
+        assert_eq!(registry.proof_system_of(2).unwrap(), 1);
+        assert_eq!(
+            registry.proof_system_of(1 | 2).unwrap_err(),
+            error!(crate::errors::ErrorCode::UnsupportedProofSystem)
+        );
+        assert!(registry.set_proof_system(1 | 2, 1).is_err());
+        assert!(registry.set_proof_system(8, 1).is_err());
+    }
+
+    #[test]
+    fn test_inline_nullifier_set() {
+        let mut registry = test_registry();
+        
+        // Unused zeroed slots don't count as members
+        assert!(!registry.contains_nullifier(&[0u8; 32]));
+        
+        assert_eq!(registry.push_nullifier([1u8; 32]), None);
+        assert!(registry.contains_nullifier(&[1u8; 32]));
+        assert!(!registry.contains_nullifier(&[2u8; 32]));
+        
+        for i in 2..=IdentityRegistry::NULLIFIER_CAPACITY as u8 {
+            assert_eq!(registry.push_nullifier([i; 32]), None);
+        }
+        assert!(registry.contains_nullifier(&[32u8; 32]));
+        
+        // Full: each push evicts the oldest entry, in insertion order
+        assert_eq!(registry.push_nullifier([33u8; 32]), Some([1u8; 32]));
+        assert_eq!(registry.push_nullifier([34u8; 32]), Some([2u8; 32]));
+        assert!(registry.contains_nullifier(&[33u8; 32]));
+        assert!(!registry.contains_nullifier(&[1u8; 32]));
+        assert!(registry.contains_nullifier(&[3u8; 32]));
+        
+        // The head wraps around after a full turn
+        for i in 35..=64u8 {
+            registry.push_nullifier([i; 32]);
+        }
+        assert_eq!(registry.nullifier_head, 0);
+        assert_eq!(registry.push_nullifier([65u8; 32]), Some([33u8; 32]));
+    }
+
+    #[test]
+    fn test_claim_nullifier_rejects_reuse() {
+        let mut registry = test_registry();
+        let used = error!(crate::errors::ErrorCode::NullifierAlreadyUsed);
+        
+        assert_eq!(registry.claim_nullifier([1u8; 32], false).unwrap(), None);
+        assert!(registry.is_nullifier_spent(&[1u8; 32], None));
+        assert_eq!(registry.claim_nullifier([1u8; 32], false).unwrap_err(), used);
+        
+        // A nullifier with a record was spent before and evicted
+        assert_eq!(registry.claim_nullifier([2u8; 32], true).unwrap_err(), used);
+        assert!(!registry.is_nullifier_spent(&[2u8; 32], None));
+        
+        for i in 2..=IdentityRegistry::NULLIFIER_CAPACITY as u8 {
+            registry.claim_nullifier([i; 32], false).unwrap();
+        }
+        assert_eq!(registry.claim_nullifier([33u8; 32], false).unwrap(), Some([1u8; 32]));
+        
+        // The evicted nullifier stays spent through its record
+        let record = NullifierRecord { nullifier: [1u8; 32], registered_at: 200, bump: 254 };
+        assert!(registry.is_nullifier_spent(&[1u8; 32], Some(&record)));
+        assert!(!registry.is_nullifier_spent(&[34u8; 32], Some(&record)));
+        assert_eq!(registry.claim_nullifier([1u8; 32], true).unwrap_err(), used);
+    }
+
+    fn inputs_with_nonce(nonce: u64) -> Vec<u8> {
+        let mut public_inputs = vec![0u8; 64];
+        public_inputs[31] = 18;
+        public_inputs[56..].copy_from_slice(&nonce.to_be_bytes());
+        public_inputs
+    }
+
+    #[test]
+    fn test_verification_nonce_sequence() {
+        let mut identity = test_identity(0);
+        identity.verification_nonce = 5;
+        assert!(identity.require_nonce_input(&inputs_with_nonce(5)).is_ok());
+        
+        // Stale and skipped-ahead nonces are both out of sequence
+        let invalid = error!(crate::errors::ErrorCode::InvalidPublicInputs);
+        assert_eq!(identity.require_nonce_input(&inputs_with_nonce(4)).unwrap_err(), invalid);
+        assert_eq!(identity.require_nonce_input(&inputs_with_nonce(6)).unwrap_err(), invalid);
+        assert_eq!(identity.require_nonce_input(&[]).unwrap_err(), invalid);
+    }
+
+    #[test]
+    fn test_unregistered_identity_detected() {
+        assert!(test_identity(0).is_registered());
+        
+        // A zeroed account, as created by init_if_needed, has no owner yet
+        let mut unregistered = test_identity(0);
+        unregistered.owner = Pubkey::default();
+        assert!(!unregistered.is_registered());
+    }
+
+    #[test]
+    fn test_expired_identity_blocked_until_renewed() {
+        let mut identity = test_identity(1);
+        assert!(identity.require_not_expired(i64::MAX).is_ok());
+        
+        identity.renew(1_000, 100);
+        assert_eq!(identity.expires_at, 1_100);
+        assert!(identity.require_not_expired(1_099).is_ok());
+        assert_eq!(
+            identity.require_not_expired(1_100).unwrap_err(),
+            error!(crate::errors::ErrorCode::IdentityExpired)
+        );
+        
+        // Renewing after lapse counts from now; attributes are untouched
+        identity.renew(5_000, 100);
+        assert_eq!(identity.expires_at, 5_100);
+        assert!(identity.require_not_expired(5_000).is_ok());
+        assert!(identity.has_attributes(1));
+        
+        // Early renewal extends from the existing expiry
+        identity.renew(5_050, 100);
+        assert_eq!(identity.expires_at, 5_200);
+    }
+
+    fn empty_pending() -> PendingVerification {
+        PendingVerification {
+            identity: Pubkey::default(),
+            attribute_type: 0,
+            proof: [0u8; 256],
+            public_inputs: Vec::new(),
+            submitted_at: 0,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn test_submit_then_finalize_sets_attribute() {
+        let mut identity = test_identity(0);
+        let mut pending = empty_pending();
+        pending
+            .submit(identity.owner, 4, &[7u8; 256], inputs_with_nonce(0), 100)
+            .unwrap();
+        assert_eq!(pending.proof, [7u8; 256]);
+        
+        // Finalize: the stored inputs pass the same checks as a direct verification
+        identity.require_nonce_input(&pending.public_inputs).unwrap();
+        identity.mark_verified(pending.attribute_type, 200).unwrap();
+        assert!(identity.has_attributes(4));
+        assert_eq!(identity.verification_nonce, 1);
+    }
+
+    #[test]
+    fn test_submission_validated_up_front() {
+        let mut pending = empty_pending();
+        let owner = Pubkey::new_unique();
+        assert!(pending.submit(owner, 1, &[0u8; 255], vec![0u8; 32], 0).is_err());
+        assert!(pending.submit(owner, 1, &[0u8; 256], vec![], 0).is_err());
+        assert!(pending.submit(owner, 1, &[0u8; 256], vec![0u8; 33], 0).is_err());
+        assert!(pending.submit(owner, 1, &[0u8; 256], vec![0u8; 6 * 32], 0).is_err());
+    }
+
+    #[test]
+    fn test_authority_session_extension() {
+        let registry = test_registry();
+        assert!(registry.is_authority(&registry.authority));
+        assert!(!registry.is_authority(&Pubkey::new_unique()));
+        
+        let mut session = empty_session();
+        session.expires_at = 1_000;
+        session.extend(3_600).unwrap();
+        assert_eq!(session.expires_at, 4_600);
+        
+        // Extensions are bounded and can't shorten a session
+        assert!(session.extend(Session::MAX_AUTHORITY_EXTENSION + 1).is_err());
+        assert!(session.extend(-1).is_err());
+        assert_eq!(session.expires_at, 4_600);
+    }
+
+    #[test]
+    fn test_namespaced_registries_isolated() {
+        let program_id = crate::ID;
+        let user = Pubkey::new_unique();
+        let (org_a, org_b) = ([1u8; 16], [2u8; 16]);
+        
+        let registry = |namespace: &[u8; 16]| {
+            Pubkey::find_program_address(&[b"registry", namespace.as_ref()], &program_id).0
+        };
+        let identity = |namespace: &[u8; 16]| {
+            Pubkey::find_program_address(
+                &[b"identity", namespace.as_ref(), user.as_ref()],
+                &program_id,
+            ).0
+        };
+        
+        // The same user and session id resolve to distinct accounts per namespace
+        assert_ne!(registry(&org_a), registry(&org_b));
+        assert_ne!(identity(&org_a), identity(&org_b));
+        assert_ne!(
+            Session::address(&org_a, &user, &[7u8; 32], &program_id),
+            Session::address(&org_b, &user, &[7u8; 32], &program_id)
+        );
+    }
+
+    #[test]
+    fn test_attribute_dependencies() {
+        let mut registry = test_registry();
+        let mut identity = test_identity(0);
+        
+        // Unconfigured attributes have no prerequisites
+        assert!(identity.require_dependencies(registry.dependencies_of(1)).is_ok());
+        
+        // age requires uniqueness
+        registry.set_attribute_dependencies(1, 4).unwrap();
+        assert_eq!(registry.dependencies_of(1), 4);
+        assert_eq!(
+            identity.require_dependencies(registry.dependencies_of(1)).unwrap_err(),
+            error!(crate::errors::ErrorCode::MissingDependencyAttribute)
+        );
+        
+        identity.mark_verified(4, 1_000).unwrap();
+        assert!(identity.require_dependencies(registry.dependencies_of(1)).is_ok());
+        
+        // Only single, non-self-referencing bits can be configured
+        assert!(registry.set_attribute_dependencies(5, 2).is_err());
+        assert!(registry.set_attribute_dependencies(4, 4).is_err());
+    }
+
+    #[test]
+    fn test_identity_snapshot_round_trip() {
+        let mut identity = test_identity(5);
+        identity.verification_timestamp = 1_700_000_000;
+        identity.verification_nonce = 3;
+        identity.expires_at = 1_800_000_000;
+        identity.mark_verified_until(4, 1_700_000_000, 1_750_000_000).unwrap();
+        identity.set_update_delay(24 * 60 * 60).unwrap();
+        
+        let authority = Pubkey::new_unique();
+        let bytes = identity.snapshot(authority).to_bytes().unwrap();
+        let digest = crate::compression::snapshot_digest(&bytes);
+        assert!(crate::compression::verify_snapshot_digest(&bytes, &digest).is_ok());
+        
+        let snapshot = IdentitySnapshot::try_from_slice(&bytes).unwrap();
+        assert!(snapshot.require_exported_by(&authority).is_ok());
+        assert_eq!(
+            snapshot.require_exported_by(&Pubkey::new_unique()).unwrap_err(),
+            error!(crate::errors::ErrorCode::InvalidSnapshot)
+        );
+        let imported = Identity::from_snapshot(&snapshot, identity.bump, identity.namespace).unwrap();
+        assert_eq!(imported.snapshot(authority), identity.snapshot(authority));
+        assert!(!imported.frozen);
+        // The verifier-supplied expiry still lapses the attribute after import
+        assert_eq!(imported.attribute_expiry, identity.attribute_expiry);
+        assert!(imported.attribute_expired(4, 1_750_000_000));
+        // Imported attributes are as fresh as they were at export
+        assert_eq!(imported.attribute_timestamps, identity.attribute_timestamps);
+        assert_eq!(imported.expired_attributes(1_700_000_000, 3_600), 1);
+        // A time-locked identity stays time-locked
+        assert_eq!(
+            imported.require_unlocked_updates().unwrap_err(),
+            error!(crate::errors::ErrorCode::UpdateTimeLocked)
+        );
+        let mut unbounded = snapshot.clone();
+        unbounded.update_delay_secs = Identity::MAX_UPDATE_DELAY + 1;
+        assert!(Identity::from_snapshot(&unbounded, identity.bump, identity.namespace).is_err());
+        
+        // Tampered bytes no longer match the digest
+        let mut tampered = bytes.clone();
+        tampered[1] ^= 1;
+        assert!(crate::compression::verify_snapshot_digest(&tampered, &digest).is_err());
+    }
+
+    #[test]
+    fn test_reputation_score() {
+        let mut log = empty_log();
+        let now = 1_700_000_000;
+        let entry = |attribute_type, timestamp| VerificationLogEntry {
+            attribute_type,
+            timestamp,
+            verifier: Pubkey::default(),
+        };
+        assert_eq!(log.reputation_score(now), 0);
+        
+        // A fresh verification counts fully, an old one only partially
+        log.append(entry(1, now - VerificationLog::REPUTATION_WINDOW / 2));
+        let stale = log.reputation_score(now);
+        assert_eq!(stale, VerificationLog::VERIFICATION_POINTS / 2);
+        
+        log.append(entry(4, now));
+        let fresh = log.reputation_score(now);
+        assert!(fresh > stale);
+        
+        log.record_revocation(now, Pubkey::default());
+        assert!(log.reputation_score(now) < fresh);
+        assert_eq!(log.reputation_score(now), 0);
+        
+        // Verifications pushing the revocation out of the ring don't erase it
+        for _ in 0..VerificationLog::CAPACITY {
+            log.append(entry(4, now - VerificationLog::REPUTATION_WINDOW / 2));
+        }
+        assert!(log.entries().iter().all(|entry| entry.attribute_type != VerificationLogEntry::REVOKED));
+        assert_eq!(log.revocations, 1);
+        let earned = VerificationLog::CAPACITY as u64 * (VerificationLog::VERIFICATION_POINTS / 2);
+        assert_eq!(log.reputation_score(now), earned - 2 * VerificationLog::VERIFICATION_POINTS);
+        
+        // Everything has decayed after the window
+        assert_eq!(log.reputation_score(now + VerificationLog::REPUTATION_WINDOW), 0);
+    }
+
+    #[test]
+    fn test_attestation_requires_verified_attribute() {
+        let mut identity = test_identity(0);
+        let identity_key = Pubkey::new_unique();
+        let verifier = Pubkey::new_unique();
+        
+        // Nothing verified yet: no attestation can be built
+        assert_eq!(
+            Attestation::issue(identity_key, &identity, 1, verifier, 1_000, 255).err(),
+            Some(error!(crate::errors::ErrorCode::InvalidVerificationStatus))
+        );
+        
+        identity.mark_verified(1, 1_000).unwrap();
+        let attestation = Attestation::issue(identity_key, &identity, 1, verifier, 1_000, 255).unwrap();
+        assert_eq!(attestation.identity, identity_key);
+        assert_eq!(attestation.owner, identity.owner);
+        assert_eq!(attestation.attribute_type, 1);
+        
+        // Scoped to the attribute: a different one is still refused
+        assert!(Attestation::issue(identity_key, &identity, 4, verifier, 1_000, 255).is_err());
+    }
+
+    #[test]
+    fn test_attestation_follows_identity_state() {
+        let mut identity = test_identity(0);
+        identity.mark_verified(1, 1_000).unwrap();
+        let attestation = Attestation::issue(Pubkey::new_unique(), &identity, 1, Pubkey::new_unique(), 1_000, 255).unwrap();
+        attestation.require_current(&identity, 2_000).unwrap();
+        
+        let mut frozen = identity.clone();
+        frozen.frozen = true;
+        assert_eq!(
+            attestation.require_current(&frozen, 2_000).unwrap_err(),
+            error!(crate::errors::ErrorCode::IdentityFrozen)
+        );
+        
+        let mut lapsed = identity.clone();
+        lapsed.expires_at = 1_500;
+        assert_eq!(
+            attestation.require_current(&lapsed, 2_000).unwrap_err(),
+            error!(crate::errors::ErrorCode::IdentityExpired)
+        );
+        
+        // A revocation outlives re-verifying the attribute
+        identity.revoke();
+        assert_eq!(
+            attestation.require_current(&identity, 2_000).unwrap_err(),
+            error!(crate::errors::ErrorCode::CredentialRevoked)
+        );
+        identity.mark_verified(1, 3_000).unwrap();
+        assert_eq!(
+            attestation.require_current(&identity, 4_000).unwrap_err(),
+            error!(crate::errors::ErrorCode::CredentialRevoked)
+        );
+        let reissued = Attestation::issue(Pubkey::new_unique(), &identity, 1, Pubkey::new_unique(), 3_000, 255).unwrap();
+        reissued.require_current(&identity, 4_000).unwrap();
+        
+        let stranger = test_identity(1);
+        assert_eq!(
+            reissued.require_current(&stranger, 4_000).unwrap_err(),
+            error!(crate::errors::ErrorCode::UnauthorizedAccess)
+        );
+    }
+
+    #[test]
+    fn test_revocation_root_membership() {
+        let mut registry = test_registry();
+        let revoked = [1u8; 32];
+        let other = [2u8; 32];
+        
+        // No root published: nothing is revoked
+        assert!(!registry.is_revoked(&revoked, &[other], 0b1).unwrap());
+        
+        registry.revocation_root = crate::compression::poseidon_merkle_parent(&revoked, &other).unwrap();
+        assert!(registry.is_revoked(&revoked, &[other], 0b1).unwrap());
+        
+        // A commitment outside the tree can't produce a matching proof
+        assert!(!registry.is_revoked(&[3u8; 32], &[other], 0b1).unwrap());
+    }
+
+    fn test_layout(nonce_index: u8) -> PublicInputLayout {
+        PublicInputLayout {
+            attribute_type: 1,
+            owner_index: PublicInputLayout::UNUSED,
+            threshold_index: PublicInputLayout::UNUSED,
+            nonce_index,
+            bump: 255,
+            namespace: [0u8; 16],
+            version_index: PublicInputLayout::UNUSED,
+            circuit_version: 0,
+            root_index: PublicInputLayout::UNUSED,
+            tag_index: PublicInputLayout::UNUSED,
+        }
+    }
+
+    #[test]
+    fn test_public_input_layout_selects_owner_index() {
+        let inputs: Vec<u8> = [[1u8; 32], [2u8; 32], [3u8; 32]].concat();
+        let mut layout = PublicInputLayout { owner_index: 0, ..test_layout(2) };
+        assert_eq!(layout.owner_binding(&inputs).unwrap(), Some(&[1u8; 32][..]));
+        assert_eq!(layout.threshold(&inputs).unwrap(), None);
+        assert_eq!(layout.nonce(&inputs).unwrap(), &[3u8; 32][..]);
+        
+        // Moving the owner binding moves the input that is read
+        layout.configure(1, 0, 2).unwrap();
+        assert_eq!(layout.owner_binding(&inputs).unwrap(), Some(&[2u8; 32][..]));
+        assert_eq!(layout.threshold(&inputs).unwrap(), Some(&[1u8; 32][..]));
+        
+        // Out of range indices and a missing nonce are rejected
+        layout.configure(7, 0, 2).unwrap();
+        assert!(layout.owner_binding(&inputs).is_err());
+        assert!(layout.configure(0, 1, PublicInputLayout::UNUSED).is_err());
+    }
+
+    #[test]
+    fn test_proof_bound_to_other_identity_root_rejected() {
+        let mut identity_a = test_identity(0);
+        let mut identity_b = test_identity(0);
+        identity_b.merkle_root = [8u8; 32];
+        
+        let layout = PublicInputLayout { root_index: 0, ..test_layout(1) };
+        let mut root_input = identity_a.merkle_root;
+        root_input[0] = 1;
+        identity_a.merkle_root = root_input;
+        root_input.reverse();
+        let inputs: Vec<u8> = [root_input, [0u8; 32]].concat();
+        
+        // The input is the big-endian form of the stored little-endian root
+        assert!(layout.require_root_binding(&inputs, &identity_a).is_ok());
+        assert_eq!(
+            layout.require_root_binding(&inputs, &identity_b).unwrap_err(),
+            error!(crate::errors::ErrorCode::InvalidPublicInputs)
+        );
+        let mut reversed = identity_a.clone();
+        reversed.merkle_root = root_input;
+        assert!(layout.require_root_binding(&inputs, &reversed).is_err());
+        
+        // A root the identity just moved off still binds, until it rotates out
+        identity_a.advance_root([9u8; 32]);
+        assert!(layout.require_root_binding(&inputs, &identity_a).is_ok());
+        for _ in 0..Identity::RECENT_ROOTS {
+            identity_a.advance_root([9u8; 32]);
+        }
+        assert!(layout.require_root_binding(&inputs, &identity_a).is_err());
+        
+        // Unused recent entries don't bind a zero root
+        let zero_inputs = [[0u8; 32], [0u8; 32]].concat();
+        assert!(layout.require_root_binding(&zero_inputs, &identity_b).is_err());
+        
+        // A root index past the circuit's inputs is rejected when configured
+        assert!(layout.require_within(2).is_ok());
+        assert_eq!(
+            layout.require_within(1).unwrap_err(),
+            error!(crate::errors::ErrorCode::InvalidConfig)
+        );
+    }
+
+    #[test]
+    fn test_nationality_proof_submitted_as_age_rejected() {
+        let tag = |attribute: u8| {
+            let mut element = [0u8; 32];
+            element[31] = attribute;
+            element
+        };
+        let mut age = PublicInputLayout { tag_index: 0, ..test_layout(1) };
+        let nationality_inputs: Vec<u8> = [tag(2), [0u8; 32]].concat();
+        let age_inputs: Vec<u8> = [tag(1), [0u8; 32]].concat();
+        
+        assert!(age.require_attribute_tag(&age_inputs).is_ok());
+        assert_eq!(
+            age.require_attribute_tag(&nationality_inputs).unwrap_err(),
+            error!(crate::errors::ErrorCode::AttributeTypeMismatch)
+        );
+        
+        // Circuits without a tag input aren't checked
+        age.tag_index = PublicInputLayout::UNUSED;
+        assert!(age.require_attribute_tag(&nationality_inputs).is_ok());
+    }
+
+    #[test]
+    fn test_stale_circuit_version_rejected() {
+        let version = |v: u32| {
+            let mut element = [0u8; 32];
+            element[28..].copy_from_slice(&v.to_be_bytes());
+            element
+        };
+        let mut layout = test_layout(1);
+        let stale: Vec<u8> = [version(1), [0u8; 32]].concat();
+        let current: Vec<u8> = [version(2), [0u8; 32]].concat();
+        
+        // Unversioned layouts accept either
+        assert!(layout.require_circuit_version(&stale).is_ok());
+        
+        layout.set_circuit_version(0, 2).unwrap();
+        assert!(layout.require_circuit_version(&current).is_ok());
+        assert_eq!(
+            layout.require_circuit_version(&stale).unwrap_err(),
+            error!(crate::errors::ErrorCode::InvalidPublicInputs)
+        );
+        assert!(layout.set_circuit_version(PublicInputLayout::UNUSED, 2).is_err());
+    }
+
+    #[test]
+    fn test_challenge_consumed_once() {
+        let mut challenge = Challenge {
+            identity: Pubkey::new_unique(),
+            challenge: [1u8; 32],
+            payer: Pubkey::new_unique(),
+            created_at: 1_000,
+            expires_at: 1_000 + Challenge::DURATION,
+            consumed: false,
+            bump: 255,
+        };
+        
+        assert_eq!(
+            challenge.clone().consume(1_000 + Challenge::DURATION).unwrap_err(),
+            error!(crate::errors::ErrorCode::ChallengeExpired)
+        );
+        
+        challenge.consume(1_100).unwrap();
+        assert!(challenge.consumed);
+        assert_eq!(
+            challenge.consume(1_100).unwrap_err(),
+            error!(crate::errors::ErrorCode::ChallengeConsumed)
+        );
+    }
+
+    #[test]
+    fn test_challenge_batch_reclaims_only_expired() {
+        let payer = Pubkey::new_unique();
+        let challenge = |expires_at: i64, consumed: bool| Challenge {
+            identity: Pubkey::new_unique(),
+            challenge: [1u8; 32],
+            payer,
+            created_at: expires_at - Challenge::DURATION,
+            expires_at,
+            consumed,
+            bump: 255,
+        };
+        let now = 2_000;
+        let batch = [
+            challenge(now - 1, false),
+            challenge(now + 60, false),
+            challenge(now, true),
+            challenge(now + 1, true),
+        ];
+        
+        let reclaimed: Vec<bool> = batch.iter().map(|c| c.reclaimable(&payer, now).unwrap()).collect();
+        assert_eq!(reclaimed, [true, false, true, false]);
+        
+        // The rent only goes back to the account that paid it
+        assert_eq!(
+            batch[0].reclaimable(&Pubkey::new_unique(), now).unwrap_err(),
+            error!(crate::errors::ErrorCode::UnauthorizedAccess)
+        );
+    }
+
+    #[test]
+    fn test_trusted_verifier_labels() {
+        let mut registry = test_registry();
+        let acme = Pubkey::new_unique();
+        let mut label = [0u8; 32];
+        label[..8].copy_from_slice(b"Acme KYC");
+        
+        registry.add_verifier(acme, label).unwrap();
+        assert_eq!(registry.verifier_label(&acme), label);
+        assert_eq!(registry.config().trusted_verifiers, vec![TrustedVerifier { key: acme, label }]);
+        assert_eq!(registry.verifier_label(&Pubkey::new_unique()), [0u8; 32]);
+        
+        // The label is what a verification record by this verifier carries
+        let record = VerificationProof {
+            identity: Pubkey::new_unique(),
+            proof_hash: [0u8; 32],
+            public_inputs_hash: [0u8; 32],
+            attribute_type: 1,
+            timestamp: 1_000,
+            verifier: acme,
+            verifier_label: registry.verifier_label(&acme),
+        };
+        assert_eq!(&record.verifier_label[..8], b"Acme KYC");
+        
+        // Relabeling doesn't add an entry; the list is bounded
+        registry.add_verifier(acme, [1u8; 32]).unwrap();
+        assert_eq!(registry.trusted_verifiers().len(), 1);
+        for _ in 1..IdentityRegistry::MAX_TRUSTED_VERIFIERS {
+            registry.add_verifier(Pubkey::new_unique(), [0u8; 32]).unwrap();
+        }
+        assert_eq!(
+            registry.add_verifier(Pubkey::new_unique(), [0u8; 32]).unwrap_err(),
+            error!(crate::errors::ErrorCode::VerifierLimitReached)
+        );
+        
+        assert!(registry.remove_verifier(&acme));
+        assert!(!registry.remove_verifier(&acme));
+        assert_eq!(registry.verifier_label(&acme), [0u8; 32]);
+    }
+
+    #[test]
+    fn test_refresh_keeps_commitment_and_other_attributes() {
+        let mut identity = test_identity(0);
+        identity.mark_verified(1 | 4, 1_000).unwrap();
+        let commitment = identity.identity_commitment;
+        
+        // Refreshing requires the attribute to be held already
+        assert!(identity.require_verified(2).is_err());
+        identity.require_verified(1).unwrap();
+        identity.mark_verified(1, 2_000).unwrap();
+        
+        assert_eq!(identity.verification_timestamp, 2_000);
+        assert_eq!(identity.attributes_verified, 1 | 4);
+        assert_eq!(identity.identity_commitment, commitment);
+    }
+
+    #[test]
+    fn test_undefined_attribute_bits_rejected() {
+        let mut identity = test_identity(0);
+        let invalid = error!(crate::errors::ErrorCode::InvalidPublicInputs);
+        
+        assert_eq!(identity.mark_verified(8, 1_000).unwrap_err(), invalid);
+        assert_eq!(identity.mark_verified(255, 1_000).unwrap_err(), invalid);
+        assert_eq!(identity.attributes_verified, 0);
+        assert_eq!(identity.verification_nonce, 0);
+        
+        for bit in [1, 2, 4] {
+            identity.mark_verified(bit, 1_000).unwrap();
+        }
+        assert_eq!(identity.attributes_verified, Identity::DEFINED_ATTRIBUTES);
+    }
+
+    #[test]
+    fn test_session_slot_availability() {
+        let key = Pubkey::new_unique();
+        let system = anchor_lang::system_program::ID;
+        let program = crate::ID;
+        
+        let mut lamports = 0;
+        let mut empty: [u8; 0] = [];
+        let free = AccountInfo::new(&key, false, false, &mut lamports, &mut empty, &system, false);
+        assert!(Session::is_slot_available(&free));
+        
+        let mut rent = 1_000_000;
+        let mut data = vec![0u8; Session::LEN];
+        let taken = AccountInfo::new(&key, false, false, &mut rent, &mut data, &program, false);
+        assert!(!Session::is_slot_available(&taken));
+    }
+}
@@ -9,6 +9,7 @@ pub struct VerificationKey {
     pub beta_g2: [[u8; 32]; 4],
     pub gamma_g2: [[u8; 32]; 4],
     pub delta_g2: [[u8; 32]; 4],
+    pub version: u16, // Key version, as in `ArchivedVerificationKey`
     pub ic: &'static [[[u8; 32]; 2]],
 }
 
@@ -36,6 +37,7 @@ pub struct VerificationKey {
             [0x21, 0x4c, 0x04, 0x44, 0xf9, 0xaa, 0x1e, 0xdc, 0x05, 0x27, 0x9a, 0x90, 0xba, 0xb0, 0xf8, 0xa4, 0x8c, 0x1d, 0x79, 0x8f, 0x22, 0xfb, 0xae, 0x03, 0x24, 0x19, 0xd7, 0xbe, 0x21, 0x1d, 0x0b, 0x12],
             [0x62, 0xd3, 0xfc, 0xbe, 0x46, 0x57, 0xfc, 0xf2, 0x43, 0xc8, 0xc9, 0x67, 0x1b, 0x9a, 0xd9, 0xa2, 0x8d, 0x0f, 0x93, 0x97, 0xb1, 0x49, 0x94, 0xcb, 0x2c, 0x0a, 0x59, 0x38, 0x41, 0x13, 0x53, 0x07]
         ],
+        version: 1,
         ic: &[
             [
                 [0xc7, 0x8c, 0xeb, 0xbf, 0xbb, 0x7c, 0x4c, 0xf6, 0x39, 0x21, 0x7e, 0x52, 0x2a, 0x7d, 0x58, 0xdb, 0x98, 0x42, 0xc6, 0xe6, 0x0f, 0x28, 0x98, 0x39, 0x6e, 0x92, 0xee, 0x8b, 0x0f, 0x77, 0x83, 0x05],
@@ -80,6 +82,7 @@ pub struct VerificationKey {
             [0xbf, 0x29, 0x05, 0x6e, 0x38, 0xb1, 0x0b, 0xab, 0xc7, 0x9c, 0x7a, 0x1b, 0x86, 0x9e, 0xa9, 0x2d, 0x6d, 0xad, 0x9f, 0x45, 0x6e, 0x0b, 0x9c, 0x41, 0x6e, 0xa4, 0x11, 0x68, 0xc7, 0x0f, 0x46, 0x23],
             [0x34, 0xd6, 0x5f, 0x8c, 0xb5, 0xa6, 0x2d, 0xcc, 0xe4, 0xd3, 0xd6, 0x07, 0x2e, 0x02, 0x45, 0x9b, 0x21, 0x5d, 0x20, 0x9a, 0x5d, 0x85, 0xb2, 0x3a, 0xcf, 0x4d, 0x38, 0x94, 0x0e, 0xb5, 0xc4, 0x22]
         ],
+        version: 1,
         ic: &[
             [
                 [0x16, 0x57, 0xd1, 0x7e, 0xf6, 0x1b, 0x51, 0x32, 0x95, 0x7a, 0x6e, 0x8b, 0x9d, 0xc2, 0x53, 0x02, 0xf9, 0x5c, 0x17, 0x5d, 0x01, 0x01, 0x3b, 0xb9, 0x0b, 0x20, 0xb8, 0x57, 0x99, 0xef, 0xf2, 0x13],
@@ -124,6 +127,7 @@ pub struct VerificationKey {
             [0x6a, 0x46, 0xa8, 0x54, 0xef, 0x3a, 0x3b, 0x9f, 0x5d, 0xc1, 0x5a, 0xc8, 0x83, 0x4f, 0x45, 0x31, 0xb8, 0x1e, 0xd3, 0x72, 0x4c, 0xab, 0x87, 0x1e, 0xaa, 0xca, 0x76, 0x13, 0xa9, 0xb9, 0x59, 0x2a],
             [0xc4, 0x27, 0x95, 0x27, 0xf4, 0xad, 0x7c, 0x9e, 0x36, 0xf7, 0x05, 0x2b, 0xe8, 0x19, 0xfd, 0xc8, 0x57, 0x51, 0xde, 0xf4, 0xde, 0xf4, 0xc4, 0x02, 0xac, 0x1e, 0x8d, 0x0e, 0xec, 0x39, 0xe0, 0x11]
         ],
+        version: 1,
         ic: &[
             [
                 [0x51, 0x30, 0xe8, 0x17, 0x3f, 0x0b, 0x59, 0x4d, 0x1d, 0xd1, 0x88, 0x5c, 0xf1, 0x87, 0x46, 0x7f, 0x29, 0xfd, 0x84, 0xca, 0x14, 0x83, 0x42, 0x3b, 0x47, 0x90, 0x57, 0xc2, 0x18, 0x37, 0xe3, 0x15],
@@ -11,6 +11,9 @@ describe("Solstice Protocol - ZK Identity Verification", () => {
   const program = anchor.workspace.contracts as Program<Contracts>;
   const provider = anchor.AnchorProvider.env();
 
+  // Registry namespace (16 bytes); each namespace is an isolated deployment
+  const namespace = Buffer.alloc(16, 0);
+
   // PDAs
   let registryPda: PublicKey;
   let identityPda: PublicKey;
@@ -18,20 +21,20 @@ describe("Solstice Protocol - ZK Identity Verification", () => {
   before(async () => {
     // Derive registry PDA
     [registryPda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("registry")],
+      [Buffer.from("registry"), namespace],
       program.programId
     );
 
     // Derive identity PDA for the provider's wallet
     [identityPda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("identity"), provider.wallet.publicKey.toBuffer()],
+      [Buffer.from("identity"), namespace, provider.wallet.publicKey.toBuffer()],
       program.programId
     );
   });
 
   it("Initialize registry", async () => {
     const tx = await program.methods
-      .initialize()
+      .initialize(Array.from(namespace), new anchor.BN(0))
       .accounts({
         registry: registryPda,
         authority: provider.wallet.publicKey,
@@ -82,7 +85,8 @@ describe("Solstice Protocol - ZK Identity Verification", () => {
         .verifyIdentity(
           Array.from(dummyProof),
           Array.from(dummyPublicInputs),
-          attributeType
+          attributeType,
+          Array.from(Buffer.alloc(32, 1)) // nullifier, one of the public inputs
         )
         .accounts({
           identity: identityPda,